@@ -1,12 +1,20 @@
-use std::collections::VecDeque;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, VecDeque};
 use std::env;
 use std::path::Path;
 use std::path::PathBuf;
+use std::rc::Rc;
 
-use bindgen::callbacks::{MacroParsingBehavior, ParseCallbacks, TypeKind};
+use bindgen::callbacks::{EnumVariantValue, MacroParsingBehavior, ParseCallbacks, TypeKind};
 
-#[derive(Debug)]
-struct NMCallbacks {}
+/// The variants of every enum by enum name, in declaration order, with their values.
+type EnumVariants = BTreeMap<String, Vec<(String, EnumVariantValue)>>;
+
+#[derive(Debug, Default)]
+struct NMCallbacks {
+    /// Filled in by `enum_variant_name`, for `write_enum_names`.
+    variants: Rc<RefCell<EnumVariants>>,
+}
 
 impl ParseCallbacks for NMCallbacks {
     fn add_derives(&self, info: &bindgen::callbacks::DeriveInfo<'_>) -> Vec<String> {
@@ -29,10 +37,11 @@ impl ParseCallbacks for NMCallbacks {
         &self,
         enum_name: Option<&str>,
         original_variant_name: &str,
-        _variant_value: bindgen::callbacks::EnumVariantValue,
+        variant_value: EnumVariantValue,
     ) -> Option<String> {
         // Try to give more sensible enum variant names by stripping common prefixes
-        let mut enum_name = enum_name.unwrap();
+        let full_enum_name = enum_name.unwrap();
+        let mut enum_name = full_enum_name;
         let name_prefix = match enum_name {
             "NMConnectivityState" => "NM_CONNECTIVITY_".to_owned(),
             "NMDeviceCapabilities" => "NM_DEVICE_CAP_".to_owned(),
@@ -78,7 +87,7 @@ impl ParseCallbacks for NMCallbacks {
             }
         };
 
-        if let Some(varient_name) = original_variant_name.strip_prefix(&name_prefix) {
+        let renamed = if let Some(varient_name) = original_variant_name.strip_prefix(&name_prefix) {
             if varient_name.chars().next().unwrap().is_numeric() {
                 Some("NM_".to_owned() + varient_name)
             } else {
@@ -86,13 +95,47 @@ impl ParseCallbacks for NMCallbacks {
             }
         } else {
             None
-        }
+        };
+
+        record_variant(
+            &mut self.variants.borrow_mut(),
+            full_enum_name,
+            renamed.as_deref().unwrap_or(original_variant_name),
+            variant_value,
+        );
+        renamed
     }
     fn read_env_var(&self, key: &str) {
         println!("cargo:rerun-if-env-changed={key}");
     }
 }
 
+/// Remembers a variant of a generated enum for `write_enum_names`. Variants with the value of
+/// an earlier one become associated constants rather than variants, so they are left out, as
+/// are enums that don't become Rust enums.
+fn record_variant(
+    variants: &mut EnumVariants,
+    enum_name: &str,
+    variant: &str,
+    value: EnumVariantValue,
+) {
+    let is_rust_enum = enum_name.starts_with("NM")
+        && enum_name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !is_rust_enum {
+        return;
+    }
+    let enum_variants = variants.entry(enum_name.to_owned()).or_default();
+    // The callback can run more than once for the same enum
+    if !enum_variants
+        .iter()
+        .any(|(known, known_value)| known == variant || *known_value == value)
+    {
+        enum_variants.push((variant.to_owned(), value));
+    }
+}
+
 fn main() {
     const INTERFACE_HEADER_NAME: &str = "nm-dbus-interface.h";
     println!("cargo:rerun-if-changed={INTERFACE_HEADER_NAME}");
@@ -105,9 +148,11 @@ fn main() {
         &processed_output_path,
     );
 
+    let callbacks = NMCallbacks::default();
+    let variants = Rc::clone(&callbacks.variants);
     let bindings = bindgen::Builder::default()
         .header(processed_output_path.to_str().unwrap())
-        .parse_callbacks(Box::new(NMCallbacks {}))
+        .parse_callbacks(Box::new(callbacks))
         .rustified_enum("NM.*")
         .prepend_enum_name(false)
         // Finish the builder and generate the bindings.
@@ -120,6 +165,28 @@ fn main() {
     bindings
         .write_to_file(out_path.join("bindings.rs"))
         .expect("Couldn't write bindings!");
+
+    write_enum_names(&variants.borrow(), &out_path.join("enum_names.rs"));
+}
+
+fn write_enum_names(variants: &EnumVariants, output: &Path) {
+    // Emits an `enum_names!` invocation for every generated enum, which implements
+    // Display/FromStr (and serde) using the lower case variant names. Variants that were
+    // prefixed with NM_ because they start with a digit get their original name back.
+    let mut invocations = String::new();
+    for (enum_name, enum_variants) in variants {
+        invocations.push_str(&format!("enum_names!({enum_name} {{\n"));
+        for (variant, _) in enum_variants {
+            let name = variant
+                .strip_prefix("NM_")
+                .filter(|name| name.starts_with(|c: char| c.is_ascii_digit()))
+                .unwrap_or(variant);
+            invocations.push_str(&format!("    {variant} => \"{}\",\n", name.to_lowercase()));
+        }
+        invocations.push_str("});\n");
+    }
+
+    std::fs::write(output, invocations).unwrap();
 }
 
 struct VariantComment {
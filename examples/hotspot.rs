@@ -0,0 +1,76 @@
+//! # hotspot Example
+//!
+//! Rust example that starts a WPA2 Wi-Fi hotspot and reports the number of connected clients
+//! until interrupted with Ctrl-C.
+//! Usage: ./hotspot <ifname> <ssid> <password>
+//!
+//! Example: ./hotspot wlan0 MyHotspot MyPassword
+//!
+//! DISCLAIMER:
+//! The example code provided here is for illustrative purposes only. It is provided "AS IS",
+//! without warranty of any kind, express or implied, including but not limited to the warranties of
+//! merchantability, fitness for a particular purpose, and non-infringement. In no event shall the authors
+//! or copyright holders be liable for any claim, damages, or other liability, whether in an action of
+//! contract, tort, or otherwise, arising from, out of, or in connection with the example code or
+//! the use or other dealings in the example code.
+
+use rusty_network_manager::{
+    Band, DeviceProxy, HotspotSecurity, NetworkManagerProxy, create_hotspot,
+};
+use std::env;
+use std::time::Duration;
+use zbus::Connection;
+
+#[tokio::main]
+async fn main() {
+    let args: Vec<String> = env::args().collect();
+
+    if args.len() != 4 {
+        eprintln!("Usage: ./hotspot <ifname> <ssid> <password>");
+        eprintln!("Example: ./hotspot wlan0 MyHotspot MyPassword");
+        std::process::exit(1);
+    }
+
+    let connection = Connection::system()
+        .await
+        .expect("Could not get a connection.");
+
+    let nm = NetworkManagerProxy::new(&connection)
+        .await
+        .expect("Could not get NetworkManager");
+
+    let device_path = nm
+        .get_device_by_ip_iface(&args[1])
+        .await
+        .expect("Could not find interface");
+
+    let device = DeviceProxy::new_from_path(device_path, &connection)
+        .await
+        .expect("Could not get device");
+
+    let hotspot = create_hotspot(
+        &device,
+        &args[2],
+        HotspotSecurity::Wpa2(args[3].clone()),
+        Some(Band::Bg),
+        None,
+    )
+    .await
+    .expect("Could not create hotspot");
+
+    println!("Hotspot '{}' running on {}", args[2], hotspot.interface());
+
+    loop {
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => break,
+            _ = tokio::time::sleep(Duration::from_secs(5)) => {
+                match hotspot.connected_clients() {
+                    Ok(clients) => println!("{clients} client(s) connected"),
+                    Err(err) => eprintln!("Could not count clients: {err}"),
+                }
+            }
+        }
+    }
+
+    hotspot.stop().await.expect("Could not stop hotspot");
+}
@@ -0,0 +1,113 @@
+//! # nm-exporter
+//!
+//! Serves NetworkManager metrics for Prometheus on `/metrics`, see
+//! [`MetricsCollector`](rusty_network_manager::MetricsCollector) for what is exported:
+//!
+//! ```text
+//! nm-exporter [--listen 0.0.0.0:9850] [--refresh-rate-ms 1000]
+//! ```
+//!
+//! Requests are answered one at a time, which is plenty for a few scrapers.
+
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::process::ExitCode;
+use std::time::Duration;
+
+use clap::Parser;
+use rusty_network_manager::{CONTENT_TYPE, Metrics, MetricsCollector};
+use zbus::Connection;
+
+/// How long a client gets to send its request.
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+/// Longest request head accepted.
+const MAX_REQUEST_SIZE: u64 = 8192;
+
+const INDEX: &str = "<html><head><title>NetworkManager exporter</title></head>\
+    <body><h1>NetworkManager exporter</h1><p><a href=\"/metrics\">Metrics</a></p></body></html>\n";
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "nm-exporter",
+    version,
+    about = "Prometheus exporter for NetworkManager"
+)]
+struct Cli {
+    /// Address to serve the metrics on
+    #[arg(short, long, default_value = "0.0.0.0:9850")]
+    listen: String,
+
+    /// How often NetworkManager should update the traffic counters, 0 to leave it as is
+    #[arg(short, long, default_value_t = 1000)]
+    refresh_rate_ms: u64,
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let connection = match async_io::block_on(Connection::system()) {
+        Ok(connection) => connection,
+        Err(err) => {
+            eprintln!("Error: failed to connect to the system bus: {err}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let collector = MetricsCollector::new(&connection, Duration::from_millis(cli.refresh_rate_ms));
+    let listener = match TcpListener::bind(&cli.listen) {
+        Ok(listener) => listener,
+        Err(err) => {
+            eprintln!("Error: failed to listen on {}: {err}", cli.listen);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    for stream in listener.incoming() {
+        let result = stream.and_then(|stream| serve(stream, &collector));
+        if let Err(err) = result {
+            eprintln!("Warning: {err}");
+        }
+    }
+    ExitCode::SUCCESS
+}
+
+/// Answers one request and closes the connection.
+fn serve(mut stream: TcpStream, collector: &MetricsCollector) -> io::Result<()> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let mut request = BufReader::new(stream.try_clone()?.take(MAX_REQUEST_SIZE));
+    let mut request_line = String::new();
+    request.read_line(&mut request_line)?;
+    // Skip the headers, nothing in them changes the response
+    let mut header = String::new();
+    while request.read_line(&mut header)? > 0 && !header.trim_end().is_empty() {
+        header.clear();
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let (method, target) = (
+        parts.next().unwrap_or_default(),
+        parts.next().unwrap_or_default(),
+    );
+    let path = target.split('?').next().unwrap_or_default();
+    let (status, content_type, body) = match (method, path) {
+        ("GET", "/metrics") => {
+            let metrics = async_io::block_on(collector.collect()).unwrap_or_else(|err| {
+                eprintln!("Warning: failed to collect metrics: {err}");
+                Metrics::unavailable()
+            });
+            ("200 OK", CONTENT_TYPE, metrics.to_string())
+        }
+        ("GET", "/") => ("200 OK", "text/html; charset=utf-8", INDEX.to_owned()),
+        ("GET", _) => ("404 Not Found", "text/plain", "Not found\n".to_owned()),
+        _ => (
+            "405 Method Not Allowed",
+            "text/plain",
+            "Method not allowed\n".to_owned(),
+        ),
+    };
+
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+    stream.flush()
+}
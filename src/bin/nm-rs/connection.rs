@@ -0,0 +1,612 @@
+//! `connection` subcommands.
+
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+
+use rusty_network_manager::dbus_interface_types::NMActiveConnectionState;
+use rusty_network_manager::{
+    ActiveProxy, ConnectionSettings, DeviceProxy, ImportReport, SettingsConnectionProxy,
+    SettingsProxy, WireGuardConfig, apply_property_text, connection_type_alias,
+    get_connection_secrets, import_ovpn, import_wg_quick, property_text_entries, setting_name,
+};
+use zbus::Result;
+use zbus::zvariant::{ObjectPath, OwnedObjectPath};
+
+use crate::device::{ip_entries, optional};
+use crate::output::Fields;
+use crate::{Context, failure, find_device, keywords, wait_activated};
+
+const LIST_FIELDS: Fields = Fields {
+    all: &[
+        "NAME",
+        "UUID",
+        "TYPE",
+        "DEVICE",
+        "ACTIVE",
+        "STATE",
+        "AUTOCONNECT",
+        "TIMESTAMP",
+        "FILENAME",
+        "DBUS-PATH",
+        "ACTIVE-PATH",
+    ],
+    common: &["NAME", "UUID", "TYPE", "DEVICE"],
+};
+
+/// nmcli's shorthands for common properties of `connection add` and `connection modify`.
+const PROPERTY_ALIASES: &[(&str, &str)] = &[
+    ("type", "connection.type"),
+    ("con-name", "connection.id"),
+    ("ifname", "connection.interface-name"),
+    ("autoconnect", "connection.autoconnect"),
+    ("ssid", "802-11-wireless.ssid"),
+    ("ip4", "ipv4.addresses"),
+    ("gw4", "ipv4.gateway"),
+    ("ip6", "ipv6.addresses"),
+    ("gw6", "ipv6.gateway"),
+    ("dev", "vlan.parent"),
+    ("id", "vlan.id"),
+];
+
+/// A profile reference, `[id|uuid|path] ID` on the command line.
+#[derive(Debug, Clone, Copy)]
+enum Selector<'a> {
+    /// Matched against the UUID, the name and the D-Bus path, in that order.
+    Any(&'a str),
+    Id(&'a str),
+    Uuid(&'a str),
+    Path(&'a str),
+}
+
+impl<'a> Selector<'a> {
+    /// Takes a selector off the front of `args`, returning the remaining arguments.
+    fn parse(args: &'a [String]) -> Result<(Selector<'a>, &'a [String])> {
+        match args {
+            [kind, value, rest @ ..] if matches!(kind.as_str(), "id" | "uuid" | "path") => {
+                let selector = match kind.as_str() {
+                    "id" => Selector::Id(value),
+                    "uuid" => Selector::Uuid(value),
+                    _ => Selector::Path(value),
+                };
+                Ok((selector, rest))
+            }
+            [value, rest @ ..] => Ok((Selector::Any(value), rest)),
+            [] => Err(failure("missing connection ID")),
+        }
+    }
+
+    /// Parses a list of selectors.
+    fn parse_all(mut args: &'a [String]) -> Result<Vec<Selector<'a>>> {
+        let mut selectors = Vec::new();
+        while !args.is_empty() {
+            let (selector, rest) = Selector::parse(args)?;
+            selectors.push(selector);
+            args = rest;
+        }
+        Ok(selectors)
+    }
+
+    fn matches(&self, id: &str, uuid: &str, paths: &[&str]) -> bool {
+        let path_matches = |wanted: &str| {
+            paths.iter().any(|path| {
+                *path == wanted || path.rsplit('/').next().is_some_and(|idx| idx == wanted)
+            })
+        };
+        match *self {
+            Selector::Any(value) => uuid == value || id == value || path_matches(value),
+            Selector::Id(value) => id == value,
+            Selector::Uuid(value) => uuid == value,
+            Selector::Path(value) => path_matches(value),
+        }
+    }
+
+    fn value(&self) -> &str {
+        match *self {
+            Selector::Any(value)
+            | Selector::Id(value)
+            | Selector::Uuid(value)
+            | Selector::Path(value) => value,
+        }
+    }
+}
+
+/// A saved profile and its settings, without secrets.
+struct Profile<'a> {
+    path: OwnedObjectPath,
+    proxy: SettingsConnectionProxy<'a>,
+    settings: ConnectionSettings,
+}
+
+impl Profile<'_> {
+    fn id(&self) -> &str {
+        self.settings.id().unwrap_or_default()
+    }
+
+    fn uuid(&self) -> &str {
+        self.settings.uuid().unwrap_or_default()
+    }
+
+    /// Adds the secrets of the profile to its settings. Settings without secrets, or with
+    /// secrets owned by an agent, are skipped; not being allowed to read them is an error.
+    async fn load_secrets(&mut self) -> Result<()> {
+        let secrets = get_connection_secrets(&self.proxy, &self.settings).await?;
+        self.settings.merge_secrets(secrets);
+        Ok(())
+    }
+}
+
+/// An active connection and the profile it was activated from.
+struct Active<'a> {
+    path: OwnedObjectPath,
+    proxy: ActiveProxy<'a>,
+    profile_path: OwnedObjectPath,
+}
+
+pub async fn show(
+    context: &Context,
+    active_only: bool,
+    show_secrets: bool,
+    ids: &[String],
+) -> Result<()> {
+    let profiles = profiles(context).await?;
+    let active = active_connections(context).await?;
+
+    if ids.is_empty() {
+        let mut rows = Vec::new();
+        for profile in &profiles {
+            let active = active
+                .iter()
+                .find(|active| active.profile_path == profile.path);
+            if active_only && active.is_none() {
+                continue;
+            }
+            let (devices, state, active_path) = match active {
+                Some(active) => (
+                    device_names(context, &active.proxy).await?,
+                    active_state_name(active.proxy.state().await?),
+                    active.path.to_string(),
+                ),
+                None => Default::default(),
+            };
+            rows.push(vec![
+                profile.id().to_owned(),
+                profile.uuid().to_owned(),
+                connection_type_alias(profile.settings.connection_type().unwrap_or_default())
+                    .to_owned(),
+                devices,
+                if active.is_some() { "yes" } else { "no" }.to_owned(),
+                state,
+                if profile
+                    .settings
+                    .get_bool("connection", "autoconnect")
+                    .unwrap_or(true)
+                {
+                    "yes"
+                } else {
+                    "no"
+                }
+                .to_owned(),
+                profile
+                    .settings
+                    .get_u64("connection", "timestamp")
+                    .unwrap_or_default()
+                    .to_string(),
+                profile.proxy.filename().await?,
+                profile.path.to_string(),
+                active_path,
+            ]);
+        }
+        // Active profiles first, like nmcli
+        rows.sort_by(|a, b| b[4].cmp(&a[4]).then_with(|| a[0].cmp(&b[0])));
+        return context.output.table(&LIST_FIELDS, &rows);
+    }
+
+    let selectors = Selector::parse_all(ids)?;
+    let mut first = true;
+    for selector in selectors {
+        let mut profile = find_profile(&profiles, selector)?;
+        if active_only
+            && !active
+                .iter()
+                .any(|active| active.profile_path == profile.path)
+        {
+            continue;
+        }
+        if !first {
+            println!();
+        }
+        first = false;
+
+        if show_secrets {
+            profile.load_secrets().await?;
+        }
+        let mut sections: Vec<String> =
+            profile.settings.settings().map(ToOwned::to_owned).collect();
+        sections.extend(["GENERAL", "IP4", "IP6"].map(ToOwned::to_owned));
+        let mut entries = property_text_entries(&profile.settings);
+
+        if let Some(active) = active
+            .iter()
+            .find(|active| active.profile_path == profile.path)
+        {
+            let proxy = &active.proxy;
+            let yes_no = |value: bool| if value { "yes" } else { "no" }.to_owned();
+            entries.extend([
+                ("GENERAL.NAME".to_owned(), proxy.id().await?),
+                ("GENERAL.UUID".to_owned(), proxy.uuid().await?),
+                (
+                    "GENERAL.DEVICES".to_owned(),
+                    device_names(context, proxy).await?,
+                ),
+                (
+                    "GENERAL.STATE".to_owned(),
+                    active_state_name(proxy.state().await?),
+                ),
+                ("GENERAL.DEFAULT".to_owned(), yes_no(proxy.default().await?)),
+                (
+                    "GENERAL.DEFAULT6".to_owned(),
+                    yes_no(proxy.default6().await?),
+                ),
+                ("GENERAL.VPN".to_owned(), yes_no(proxy.vpn().await?)),
+                ("GENERAL.DBUS-PATH".to_owned(), active.path.to_string()),
+                ("GENERAL.CON-PATH".to_owned(), profile.path.to_string()),
+            ]);
+            entries.extend(
+                ip_entries(
+                    context,
+                    proxy.ip4_config().await?,
+                    proxy.ip6_config().await?,
+                )
+                .await?,
+            );
+        }
+
+        let sections: Vec<&str> = sections.iter().map(String::as_str).collect();
+        context.output.list(&sections, &entries)?;
+    }
+    Ok(())
+}
+
+pub async fn up(context: &Context, args: &[String]) -> Result<()> {
+    let (selector, rest) = Selector::parse(args)?;
+    let options = keywords(rest, &["ifname"])?;
+    let profiles = profiles(context).await?;
+    let profile = find_profile(&profiles, selector)?;
+
+    let root = ObjectPath::try_from("/")?;
+    let device = match options.get("ifname") {
+        Some(ifname) => Some(find_device(context, ifname).await?),
+        None => None,
+    };
+    let device_path = device
+        .as_ref()
+        .map_or(root.clone(), |(path, _)| path.as_ref());
+    let active_path = context
+        .nm
+        .activate_connection(&profile.path, &device_path, &root)
+        .await?;
+
+    // Without an interface name, report the device NetworkManager picked
+    let device = match device {
+        Some((_, device)) => Some(device),
+        None => {
+            let active =
+                ActiveProxy::new_from_path(active_path.clone(), &context.connection).await?;
+            match active.devices().await?.into_iter().next() {
+                Some(path) => Some(DeviceProxy::new_from_path(path, &context.connection).await?),
+                None => None,
+            }
+        }
+    };
+    wait_activated(context, active_path.clone(), device.as_ref()).await?;
+
+    println!("Connection successfully activated (D-Bus active path: {active_path})");
+    Ok(())
+}
+
+pub async fn down(context: &Context, ids: &[String]) -> Result<()> {
+    let active = active_connections(context).await?;
+    for selector in Selector::parse_all(ids)? {
+        let mut found = None;
+        for active in &active {
+            let id = active.proxy.id().await?;
+            let uuid = active.proxy.uuid().await?;
+            let paths = [active.path.as_str(), active.profile_path.as_str()];
+            if selector.matches(&id, &uuid, &paths) {
+                found = Some((id, active));
+                break;
+            }
+        }
+        let Some((id, active)) = found else {
+            return Err(failure(format!(
+                "'{}' is not an active connection.",
+                selector.value()
+            )));
+        };
+
+        context.nm.deactivate_connection(&active.path).await?;
+        println!(
+            "Connection '{id}' successfully deactivated (D-Bus active path: {})",
+            active.path
+        );
+    }
+    Ok(())
+}
+
+pub async fn add(context: &Context, args: &[String]) -> Result<()> {
+    if !args.len().is_multiple_of(2) {
+        return Err(failure(format!(
+            "missing value for '{}'",
+            args[args.len() - 1]
+        )));
+    }
+    let mut settings = ConnectionSettings::new();
+    for pair in args.chunks(2) {
+        apply(&mut settings, &pair[0], &pair[1])?;
+    }
+
+    let connection_type = settings
+        .connection_type()
+        .ok_or_else(|| failure("'type' is required"))?
+        .to_owned();
+    settings.add_setting(&connection_type);
+    if settings.id().is_none() {
+        let alias = connection_type_alias(&connection_type);
+        let id = match settings.get_str("connection", "interface-name") {
+            Some(ifname) => format!("{alias}-{ifname}"),
+            None => alias.to_owned(),
+        };
+        settings.set("connection", "id", id);
+    }
+    if settings.uuid().is_none() {
+        settings.set("connection", "uuid", new_uuid()?);
+    }
+    if connection_type == "802-11-wireless" && settings.get("802-11-wireless", "mode").is_none() {
+        settings.set("802-11-wireless", "mode", "infrastructure");
+    }
+    // Ports are configured through their controller
+    let is_port = settings.get("connection", "master").is_some()
+        || settings.get("connection", "controller").is_some();
+    if !is_port {
+        for family in ["ipv4", "ipv6"] {
+            if settings.get(family, "method").is_some() {
+                continue;
+            }
+            let method = if settings.get(family, "address-data").is_some() {
+                "manual"
+            } else if connection_type == "wireguard" {
+                "disabled"
+            } else {
+                "auto"
+            };
+            settings.set(family, "method", method);
+        }
+    }
+
+    let settings_proxy = SettingsProxy::new(&context.connection).await?;
+    settings_proxy.add_connection(settings.to_dbus()).await?;
+    println!(
+        "Connection '{}' ({}) successfully added.",
+        settings.id().unwrap_or_default(),
+        settings.uuid().unwrap_or_default()
+    );
+    Ok(())
+}
+
+pub async fn modify(context: &Context, args: &[String]) -> Result<()> {
+    let (selector, rest) = Selector::parse(args)?;
+    if rest.is_empty() || !rest.len().is_multiple_of(2) {
+        return Err(failure("expected [+|-]setting.property VALUE pairs"));
+    }
+    let profiles = profiles(context).await?;
+    let mut profile = find_profile(&profiles, selector)?;
+
+    // Update replaces the whole profile, so the secrets have to be sent back as well
+    profile.load_secrets().await?;
+    for pair in rest.chunks(2) {
+        apply(&mut profile.settings, &pair[0], &pair[1])?;
+    }
+    profile.proxy.update(profile.settings.to_dbus()).await
+}
+
+pub async fn delete(context: &Context, ids: &[String]) -> Result<()> {
+    let profiles = profiles(context).await?;
+    for selector in Selector::parse_all(ids)? {
+        let profile = find_profile(&profiles, selector)?;
+        profile.proxy.delete().await?;
+        println!(
+            "Connection '{}' ({}) successfully deleted.",
+            profile.id(),
+            profile.uuid()
+        );
+    }
+    Ok(())
+}
+
+pub async fn import(context: &Context, args: &[String]) -> Result<()> {
+    let options = keywords(args, &["type", "file"])?;
+    let (Some(kind), Some(file)) = (options.get("type"), options.get("file")) else {
+        return Err(failure("'type' and 'file' are required"));
+    };
+    let file = Path::new(file);
+    let input = fs::read_to_string(file)
+        .map_err(|err| failure(format!("failed to read {}: {err}", file.display())))?;
+    let name = file
+        .file_stem()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let invalid = |err: rusty_network_manager::ParseError| {
+        failure(format!("failed to import {}: {err}", file.display()))
+    };
+
+    let report: ImportReport = match kind.as_str() {
+        "openvpn" => {
+            // Where nmcli's OpenVPN plugin keeps certificates of imported profiles
+            let cert_dir = std::env::var_os("HOME")
+                .map(PathBuf::from)
+                .unwrap_or_default()
+                .join(".cert/nm-openvpn");
+            let import = import_ovpn(&name, &input, &cert_dir).map_err(invalid)?;
+            import
+                .write_files()
+                .map_err(|err| failure(format!("failed to write certificates: {err}")))?;
+            import.report
+        }
+        "wireguard" => import_wg_quick(&name, &input).map_err(invalid)?,
+        other => {
+            return Err(failure(format!(
+                "unsupported type '{other}'; expected openvpn or wireguard"
+            )));
+        }
+    };
+
+    for untranslated in &report.untranslated {
+        eprintln!("Warning: {untranslated}");
+    }
+    let settings_proxy = SettingsProxy::new(&context.connection).await?;
+    for settings in &report.connections {
+        settings_proxy.add_connection(settings.to_dbus()).await?;
+        println!(
+            "Connection '{}' ({}) successfully added.",
+            settings.id().unwrap_or_default(),
+            settings.uuid().unwrap_or_default()
+        );
+    }
+    Ok(())
+}
+
+pub async fn export(context: &Context, args: &[String]) -> Result<()> {
+    let (selector, rest) = Selector::parse(args)?;
+    let file = match rest {
+        [] => None,
+        [file] => Some(Path::new(file)),
+        [_, extra, ..] => return Err(failure(format!("unexpected argument '{extra}'"))),
+    };
+    let profiles = profiles(context).await?;
+    let profile = find_profile(&profiles, selector)?;
+    let connection_type = profile.settings.connection_type().unwrap_or_default();
+    if connection_type != "wireguard" {
+        return Err(failure(format!(
+            "exporting '{connection_type}' profiles is not supported, only wireguard"
+        )));
+    }
+
+    let config = WireGuardConfig::from_connection(&profile.proxy).await?;
+    match file {
+        // The configuration contains the private key
+        Some(file) => OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(file)
+            .and_then(|mut output| output.write_all(config.to_string().as_bytes()))
+            .map_err(|err| failure(format!("failed to write {}: {err}", file.display()))),
+        None => {
+            print!("{config}");
+            Ok(())
+        }
+    }
+}
+
+/// Applies a `setting.property VALUE` pair or one of nmcli's shorthands.
+fn apply(settings: &mut ConnectionSettings, name: &str, value: &str) -> Result<()> {
+    let name = PROPERTY_ALIASES
+        .iter()
+        .find(|(alias, _)| *alias == name)
+        .map_or(name, |(_, property)| property);
+    match name {
+        "connection.type" => {
+            settings.set("connection", "type", setting_name(value).to_owned());
+            Ok(())
+        }
+        // nmcli's way of not binding a profile to an interface
+        "connection.interface-name" if value == "*" => {
+            settings.remove("connection", "interface-name");
+            Ok(())
+        }
+        name if !name.contains('.') => Err(failure(format!("invalid property '{name}'"))),
+        name => apply_property_text(settings, name, value),
+    }
+}
+
+async fn profiles(context: &Context) -> Result<Vec<Profile<'_>>> {
+    let settings = SettingsProxy::new(&context.connection).await?;
+    let mut profiles = Vec::new();
+    for path in settings.list_connections().await? {
+        let proxy =
+            SettingsConnectionProxy::new_from_path(path.clone(), &context.connection).await?;
+        let settings = ConnectionSettings::from(proxy.get_settings().await?);
+        profiles.push(Profile {
+            path,
+            proxy,
+            settings,
+        });
+    }
+    Ok(profiles)
+}
+
+fn find_profile<'a>(profiles: &[Profile<'a>], selector: Selector<'_>) -> Result<Profile<'a>> {
+    // Prefer a UUID match so a profile named like another's UUID can't shadow it
+    let found = match selector {
+        Selector::Any(value) => profiles
+            .iter()
+            .find(|profile| profile.uuid() == value)
+            .or_else(|| {
+                profiles
+                    .iter()
+                    .find(|profile| selector.matches(profile.id(), "", &[profile.path.as_str()]))
+            }),
+        selector => profiles.iter().find(|profile| {
+            selector.matches(profile.id(), profile.uuid(), &[profile.path.as_str()])
+        }),
+    };
+    found
+        .map(|profile| Profile {
+            path: profile.path.clone(),
+            proxy: profile.proxy.clone(),
+            settings: profile.settings.clone(),
+        })
+        .ok_or_else(|| failure(format!("unknown connection '{}'.", selector.value())))
+}
+
+async fn active_connections(context: &Context) -> Result<Vec<Active<'_>>> {
+    let mut active_connections = Vec::new();
+    for path in context.nm.active_connections().await? {
+        let proxy = ActiveProxy::new_from_path(path.clone(), &context.connection).await?;
+        let profile_path = proxy.connection().await?;
+        active_connections.push(Active {
+            path,
+            proxy,
+            profile_path,
+        });
+    }
+    Ok(active_connections)
+}
+
+/// The interface names of the devices of an active connection, comma separated.
+async fn device_names(context: &Context, active: &ActiveProxy<'_>) -> Result<String> {
+    let mut names = Vec::new();
+    for path in active.devices().await? {
+        if let Some(path) = optional(path) {
+            let device = DeviceProxy::new_from_path(path, &context.connection).await?;
+            names.push(device.interface().await?);
+        }
+    }
+    Ok(names.join(","))
+}
+
+fn active_state_name(state: u32) -> String {
+    NMActiveConnectionState::try_from(state)
+        .unwrap_or(NMActiveConnectionState::UNKNOWN)
+        .to_string()
+}
+
+/// A random UUID for `connection.uuid`, generated by the kernel.
+fn new_uuid() -> Result<String> {
+    fs::read_to_string("/proc/sys/kernel/random/uuid")
+        .map(|uuid| uuid.trim().to_owned())
+        .map_err(|err| failure(format!("failed to generate a UUID: {err}")))
+}
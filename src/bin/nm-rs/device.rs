@@ -0,0 +1,588 @@
+//! `device` subcommands.
+
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::time::Duration;
+
+use async_io::Timer;
+use futures_lite::{StreamExt, future};
+use rusty_network_manager::dbus_interface_types::{
+    NM80211Mode, NMConnectivityState, NMDeviceState, NMDeviceType,
+};
+use rusty_network_manager::{
+    AccessPointProxy, ActiveProxy, Channel, ConnectionSettings, DeviceProxy, IP4ConfigProxy,
+    IP6ConfigProxy, NM80211ApFlags, NM80211ApSecurityFlags, WirelessProxy, connect_hidden,
+};
+use zbus::Result;
+use zbus::zvariant::{ObjectPath, OwnedObjectPath, OwnedValue};
+
+use crate::output::Fields;
+use crate::{Context, Rescan, failure, find_device, keywords, wait_activated};
+
+const STATUS_FIELDS: Fields = Fields {
+    all: &[
+        "DEVICE",
+        "TYPE",
+        "STATE",
+        "IP4-CONNECTIVITY",
+        "IP6-CONNECTIVITY",
+        "DBUS-PATH",
+        "CONNECTION",
+        "CON-UUID",
+        "CON-PATH",
+    ],
+    common: &["DEVICE", "TYPE", "STATE", "CONNECTION"],
+};
+
+const SHOW_SECTIONS: &[&str] = &["GENERAL", "IP4", "IP6"];
+
+const WIFI_FIELDS: Fields = Fields {
+    all: &[
+        "IN-USE",
+        "BSSID",
+        "SSID",
+        "MODE",
+        "CHAN",
+        "FREQ",
+        "RATE",
+        "SIGNAL",
+        "BARS",
+        "SECURITY",
+        "DEVICE",
+        "DBUS-PATH",
+    ],
+    common: &[
+        "IN-USE", "BSSID", "SSID", "MODE", "CHAN", "RATE", "SIGNAL", "BARS", "SECURITY",
+    ],
+};
+
+/// How long `wifi list` waits for a requested scan.
+const SCAN_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Scan results older than this are refreshed by `wifi list --rescan auto`.
+const SCAN_MAX_AGE: Duration = Duration::from_secs(30);
+
+pub async fn status(context: &Context) -> Result<()> {
+    let mut devices = Vec::new();
+    for path in context.nm.get_devices().await? {
+        let device = DeviceProxy::new_from_path(path.clone(), &context.connection).await?;
+        let state = device.state().await?;
+        let (connection, uuid, connection_path) = match optional(device.active_connection().await?)
+        {
+            Some(active_path) => {
+                let active = ActiveProxy::new_from_path(active_path, &context.connection).await?;
+                (
+                    active.id().await?,
+                    active.uuid().await?,
+                    active.connection().await?.to_string(),
+                )
+            }
+            None => Default::default(),
+        };
+        let row = vec![
+            device.interface().await?,
+            type_name(device.device_type().await?),
+            state_name(state),
+            connectivity_name(device.ip4_connectivity().await?),
+            connectivity_name(device.ip6_connectivity().await?),
+            path.to_string(),
+            connection,
+            uuid,
+            connection_path,
+        ];
+        devices.push((state, row));
+    }
+    // Connected devices first, like nmcli
+    devices
+        .sort_by(|(a_state, a), (b_state, b)| b_state.cmp(a_state).then_with(|| a[0].cmp(&b[0])));
+
+    let rows: Vec<Vec<String>> = devices.into_iter().map(|(_, row)| row).collect();
+    context.output.table(&STATUS_FIELDS, &rows)
+}
+
+pub async fn show(context: &Context, ifname: Option<&str>) -> Result<()> {
+    let paths = match ifname {
+        Some(ifname) => vec![find_device(context, ifname).await?.0],
+        None => context.nm.get_devices().await?,
+    };
+
+    for (idx, path) in paths.into_iter().enumerate() {
+        if idx > 0 {
+            println!();
+        }
+        let device = DeviceProxy::new_from_path(path.clone(), &context.connection).await?;
+        let state = device.state().await?;
+        let active_path = optional(device.active_connection().await?);
+        let (connection, connection_path) = match &active_path {
+            Some(active_path) => {
+                let active =
+                    ActiveProxy::new_from_path(active_path.clone(), &context.connection).await?;
+                (active.id().await?, active.connection().await?.to_string())
+            }
+            None => Default::default(),
+        };
+
+        let mut entries = vec![
+            ("GENERAL.DEVICE".to_owned(), device.interface().await?),
+            (
+                "GENERAL.TYPE".to_owned(),
+                type_name(device.device_type().await?),
+            ),
+            ("GENERAL.HWADDR".to_owned(), device.hw_address().await?),
+            ("GENERAL.MTU".to_owned(), device.mtu().await?.to_string()),
+            (
+                "GENERAL.STATE".to_owned(),
+                format!("{state} ({})", state_name(state)),
+            ),
+            ("GENERAL.CONNECTION".to_owned(), connection),
+            ("GENERAL.CON-PATH".to_owned(), connection_path),
+            ("GENERAL.DRIVER".to_owned(), device.driver().await?),
+        ];
+        entries.extend(
+            ip_entries(
+                context,
+                device.ip4_config().await?,
+                device.ip6_config().await?,
+            )
+            .await?,
+        );
+        context.output.list(SHOW_SECTIONS, &entries)?;
+    }
+    Ok(())
+}
+
+pub async fn connect(context: &Context, ifname: &str) -> Result<()> {
+    let (path, device) = find_device(context, ifname).await?;
+    let root = ObjectPath::try_from("/")?;
+    let active_path = context.nm.activate_connection(&root, &path, &root).await?;
+    let active = ActiveProxy::new_from_path(active_path.clone(), &context.connection).await?;
+    let uuid = active.uuid().await?;
+    wait_activated(context, active_path, Some(&device)).await?;
+
+    println!("Device '{ifname}' successfully activated with '{uuid}'.");
+    Ok(())
+}
+
+pub async fn disconnect(context: &Context, ifnames: &[String]) -> Result<()> {
+    for ifname in ifnames {
+        let (_, device) = find_device(context, ifname).await?;
+        device.disconnect().await?;
+        println!("Device '{ifname}' successfully disconnected.");
+    }
+    Ok(())
+}
+
+pub async fn wifi_list(context: &Context, rescan: Rescan, args: &[String]) -> Result<()> {
+    let options = keywords(args, &["ifname", "bssid"])?;
+    let devices = wifi_devices(context, options.get("ifname").map(String::as_str)).await?;
+
+    let mut access_points = Vec::new();
+    for (interface, wireless) in devices {
+        if rescan == Rescan::Yes
+            || (rescan == Rescan::Auto && scan_age(wireless.last_scan().await?) > SCAN_MAX_AGE)
+        {
+            scan(&wireless).await?;
+        }
+
+        let active_access_point = wireless.active_access_point().await?;
+        for path in wireless.get_access_points().await? {
+            let access_point =
+                AccessPointProxy::new_from_path(path.clone(), &context.connection).await?;
+            let bssid = access_point.hw_address().await?;
+            if options
+                .get("bssid")
+                .is_some_and(|wanted| !wanted.eq_ignore_ascii_case(&bssid))
+            {
+                continue;
+            }
+            let frequency = access_point.frequency().await?;
+            let strength = access_point.strength().await?;
+            let in_use = path == active_access_point;
+            let row = vec![
+                if in_use { "*" } else { " " }.to_owned(),
+                bssid,
+                String::from_utf8_lossy(&access_point.ssid().await?).into_owned(),
+                mode_name(access_point.mode().await?),
+                Channel::from_frequency(frequency)
+                    .map(|channel| channel.channel.to_string())
+                    .unwrap_or_default(),
+                format!("{frequency} MHz"),
+                format!("{} Mbit/s", access_point.max_bitrate().await? / 1000),
+                strength.to_string(),
+                bars(strength).to_owned(),
+                security(&access_point).await?,
+                interface.clone(),
+                path.to_string(),
+            ];
+            access_points.push((in_use, strength, row));
+        }
+    }
+    access_points.sort_by(|(a_in_use, a_strength, _), (b_in_use, b_strength, _)| {
+        b_in_use.cmp(a_in_use).then(b_strength.cmp(a_strength))
+    });
+
+    let rows: Vec<Vec<String>> = access_points.into_iter().map(|(_, _, row)| row).collect();
+    context.output.table(&WIFI_FIELDS, &rows)
+}
+
+pub async fn wifi_connect(context: &Context, ssid: &str, args: &[String]) -> Result<()> {
+    let options = keywords(args, &["password", "ifname", "bssid", "name", "hidden"])?;
+    let password = options.get("password").map(String::as_str);
+    let hidden = match options.get("hidden").map(String::as_str) {
+        None | Some("no") => false,
+        Some("yes") => true,
+        Some(other) => return Err(failure(format!("invalid value '{other}' for 'hidden'"))),
+    };
+
+    let devices = wifi_devices(context, options.get("ifname").map(String::as_str)).await?;
+
+    if hidden {
+        let (interface, wireless) = devices.into_iter().next().unwrap();
+        let path = OwnedObjectPath::from(wireless.inner().path().clone());
+        let device = DeviceProxy::new_from_path(path, &context.connection).await?;
+        let timeout = context.timeout.unwrap_or(Duration::from_secs(90));
+        let hidden = connect_hidden(&device, ssid.as_bytes(), password, timeout).await?;
+        wait_activated(context, hidden.active_path, Some(&device)).await?;
+        println!("Device '{interface}' successfully activated.");
+        return Ok(());
+    }
+
+    // The strongest matching access point over all candidate devices
+    let mut best: Option<(u8, OwnedObjectPath, OwnedObjectPath, String)> = None;
+    for (interface, wireless) in &devices {
+        for path in wireless.get_access_points().await? {
+            let access_point =
+                AccessPointProxy::new_from_path(path.clone(), &context.connection).await?;
+            if access_point.ssid().await? != ssid.as_bytes() {
+                continue;
+            }
+            if let Some(bssid) = options.get("bssid")
+                && !access_point.hw_address().await?.eq_ignore_ascii_case(bssid)
+            {
+                continue;
+            }
+            let strength = access_point.strength().await?;
+            if best
+                .as_ref()
+                .is_none_or(|(best_strength, ..)| strength > *best_strength)
+            {
+                let device_path = OwnedObjectPath::from(wireless.inner().path().clone());
+                best = Some((strength, device_path, path, interface.clone()));
+            }
+        }
+    }
+    let Some((_, device_path, access_point_path, interface)) = best else {
+        return Err(failure(format!("No network with SSID '{ssid}' found.")));
+    };
+
+    let access_point =
+        AccessPointProxy::new_from_path(access_point_path.clone(), &context.connection).await?;
+    let mut settings = ConnectionSettings::new()
+        .with(
+            "connection",
+            "id",
+            options.get("name").map_or(ssid, String::as_str).to_owned(),
+        )
+        .with("connection", "type", "802-11-wireless")
+        .with("802-11-wireless", "ssid", ssid.as_bytes().to_vec());
+    let flags = NM80211ApSecurityFlags::from_bits_retain(
+        access_point.wpa_flags().await? | access_point.rsn_flags().await?,
+    );
+    let privacy = NM80211ApFlags::from_bits_retain(access_point.flags().await?)
+        .contains(NM80211ApFlags::PRIVACY);
+    let key_mgmt = if flags.contains(NM80211ApSecurityFlags::KEY_MGMT_802_1X) {
+        return Err(failure(
+            "802.1X networks need a profile, create one with 'connection add'",
+        ));
+    } else if flags.contains(NM80211ApSecurityFlags::KEY_MGMT_PSK) {
+        Some("wpa-psk")
+    } else if flags.contains(NM80211ApSecurityFlags::KEY_MGMT_SAE) {
+        Some("sae")
+    } else if privacy {
+        Some("none")
+    } else {
+        None
+    };
+    if let Some(key_mgmt) = key_mgmt {
+        let password =
+            password.ok_or_else(|| failure("Secrets were required, but not provided."))?;
+        settings.set("802-11-wireless-security", "key-mgmt", key_mgmt);
+        if key_mgmt == "none" {
+            settings.set("802-11-wireless-security", "wep-key0", password.to_owned());
+            settings.set("802-11-wireless-security", "wep-key-type", 1u32);
+        } else {
+            settings.set("802-11-wireless-security", "psk", password.to_owned());
+        }
+    }
+
+    let (_, active_path) = context
+        .nm
+        .add_and_activate_connection(settings.to_dbus(), &device_path, &access_point_path)
+        .await?;
+    let device = DeviceProxy::new_from_path(device_path, &context.connection).await?;
+    wait_activated(context, active_path.clone(), Some(&device)).await?;
+
+    let active = ActiveProxy::new_from_path(active_path, &context.connection).await?;
+    println!(
+        "Device '{interface}' successfully activated with '{}'.",
+        active.uuid().await?
+    );
+    Ok(())
+}
+
+/// The `IP4.*` and `IP6.*` entries of `device show` and `connection show`.
+pub async fn ip_entries(
+    context: &Context,
+    ip4_path: OwnedObjectPath,
+    ip6_path: OwnedObjectPath,
+) -> Result<Vec<(String, String)>> {
+    let mut entries = Vec::new();
+    if let Some(path) = optional(ip4_path) {
+        let config = IP4ConfigProxy::new_from_path(path, &context.connection).await?;
+        let nameservers = config
+            .nameservers()
+            .await?
+            .into_iter()
+            .map(|server| Ipv4Addr::from(server.to_ne_bytes()).to_string())
+            .collect();
+        ip_config_entries(
+            &mut entries,
+            "IP4",
+            config.address_data().await?,
+            config.gateway().await?,
+            config.route_data().await?,
+            nameservers,
+            config.domains().await?,
+        );
+    }
+    if let Some(path) = optional(ip6_path) {
+        let config = IP6ConfigProxy::new_from_path(path, &context.connection).await?;
+        let nameservers = config
+            .nameservers()
+            .await?
+            .into_iter()
+            .filter_map(|server| <[u8; 16]>::try_from(server).ok())
+            .map(|server| Ipv6Addr::from(server).to_string())
+            .collect();
+        ip_config_entries(
+            &mut entries,
+            "IP6",
+            config.address_data().await?,
+            config.gateway().await?,
+            config.route_data().await?,
+            nameservers,
+            config.domains().await?,
+        );
+    }
+    Ok(entries)
+}
+
+fn ip_config_entries(
+    entries: &mut Vec<(String, String)>,
+    section: &str,
+    addresses: Vec<HashMap<String, OwnedValue>>,
+    gateway: String,
+    routes: Vec<HashMap<String, OwnedValue>>,
+    nameservers: Vec<String>,
+    domains: Vec<String>,
+) {
+    let cidr = |data: &HashMap<String, OwnedValue>, key: &str| -> Option<String> {
+        let address: &str = data.get(key)?.downcast_ref().ok()?;
+        let prefix: u32 = data.get("prefix")?.downcast_ref().ok()?;
+        Some(format!("{address}/{prefix}"))
+    };
+    let addresses = addresses
+        .iter()
+        .filter_map(|address| cidr(address, "address"))
+        .collect();
+    let routes = routes
+        .iter()
+        .filter_map(|route| {
+            let mut text = format!("dst = {}", cidr(route, "dest")?);
+            if let Some(next_hop) = route
+                .get("next-hop")
+                .and_then(|next_hop| next_hop.downcast_ref::<&str>().ok())
+            {
+                text.push_str(&format!(", nh = {next_hop}"));
+            }
+            if let Some(metric) = route
+                .get("metric")
+                .and_then(|metric| metric.downcast_ref::<u32>().ok())
+            {
+                text.push_str(&format!(", mt = {metric}"));
+            }
+            Some(text)
+        })
+        .collect();
+
+    push_numbered(entries, section, "ADDRESS", addresses);
+    entries.push((format!("{section}.GATEWAY"), gateway));
+    push_numbered(entries, section, "ROUTE", routes);
+    push_numbered(entries, section, "DNS", nameservers);
+    push_numbered(entries, section, "DOMAIN", domains);
+}
+
+/// Adds `SECTION.NAME[n]` entries, numbered from 1 like nmcli does.
+fn push_numbered(
+    entries: &mut Vec<(String, String)>,
+    section: &str,
+    name: &str,
+    values: Vec<String>,
+) {
+    for (idx, value) in values.into_iter().enumerate() {
+        entries.push((format!("{section}.{name}[{}]", idx + 1), value));
+    }
+}
+
+/// The Wi-Fi devices with their interface names, only `ifname` if given.
+async fn wifi_devices<'a>(
+    context: &'a Context,
+    ifname: Option<&str>,
+) -> Result<Vec<(String, WirelessProxy<'a>)>> {
+    let mut devices = Vec::new();
+    for path in context.nm.get_devices().await? {
+        let device = DeviceProxy::new_from_path(path.clone(), &context.connection).await?;
+        if device.device_type().await? != NMDeviceType::WIFI as u32 {
+            continue;
+        }
+        let interface = device.interface().await?;
+        if ifname.is_some_and(|ifname| ifname != interface) {
+            continue;
+        }
+        devices.push((
+            interface,
+            WirelessProxy::new_from_path(path, &context.connection).await?,
+        ));
+    }
+    match (devices.is_empty(), ifname) {
+        (true, Some(ifname)) => Err(failure(format!("Device '{ifname}' is not a Wi-Fi device."))),
+        (true, None) => Err(failure("No Wi-Fi device found.")),
+        (false, _) => Ok(devices),
+    }
+}
+
+/// Requests a scan and waits for it to finish. Scans are rate limited, a rejected request
+/// just lists the current results.
+async fn scan(wireless: &WirelessProxy<'_>) -> Result<()> {
+    let mut last_scan = wireless.receive_last_scan_changed().await;
+    if wireless
+        .request_scan_with(&Default::default())
+        .await
+        .is_err()
+    {
+        return Ok(());
+    }
+    let finished = async {
+        // The first item is the current value
+        last_scan.next().await;
+        last_scan.next().await;
+    };
+    let timed_out = async {
+        Timer::after(SCAN_TIMEOUT).await;
+    };
+    future::or(finished, timed_out).await;
+    Ok(())
+}
+
+/// How long ago a `LastScan` timestamp (milliseconds of CLOCK_BOOTTIME) was.
+fn scan_age(last_scan: i64) -> Duration {
+    let uptime = std::fs::read_to_string("/proc/uptime")
+        .ok()
+        .and_then(|uptime| uptime.split_whitespace().next()?.parse::<f64>().ok());
+    match (uptime, u64::try_from(last_scan)) {
+        (Some(uptime), Ok(last_scan)) => {
+            Duration::from_secs_f64(uptime).saturating_sub(Duration::from_millis(last_scan))
+        }
+        _ => Duration::MAX,
+    }
+}
+
+/// The security nmcli lists for an access point, e.g. `WPA1 WPA2`.
+async fn security(access_point: &AccessPointProxy<'_>) -> Result<String> {
+    let privacy = NM80211ApFlags::from_bits_retain(access_point.flags().await?)
+        .contains(NM80211ApFlags::PRIVACY);
+    let wpa = NM80211ApSecurityFlags::from_bits_retain(access_point.wpa_flags().await?);
+    let rsn = NM80211ApSecurityFlags::from_bits_retain(access_point.rsn_flags().await?);
+
+    let mut security = Vec::new();
+    if privacy && wpa.is_empty() && rsn.is_empty() {
+        security.push("WEP");
+    }
+    if !wpa.is_empty() {
+        security.push("WPA1");
+    }
+    if rsn
+        .intersects(NM80211ApSecurityFlags::KEY_MGMT_PSK | NM80211ApSecurityFlags::KEY_MGMT_802_1X)
+    {
+        security.push("WPA2");
+    }
+    if rsn.contains(NM80211ApSecurityFlags::KEY_MGMT_SAE) {
+        security.push("WPA3");
+    }
+    if rsn
+        .intersects(NM80211ApSecurityFlags::KEY_MGMT_OWE | NM80211ApSecurityFlags::KEY_MGMT_OWE_TM)
+    {
+        security.push("OWE");
+    }
+    if (wpa | rsn).contains(NM80211ApSecurityFlags::KEY_MGMT_802_1X) {
+        security.push("802.1X");
+    }
+    Ok(security.join(" "))
+}
+
+fn bars(strength: u8) -> &'static str {
+    match strength {
+        81.. => "▂▄▆█",
+        56.. => "▂▄▆_",
+        31.. => "▂▄__",
+        6.. => "▂___",
+        _ => "____",
+    }
+}
+
+fn mode_name(mode: u32) -> String {
+    match NM80211Mode::try_from(mode) {
+        Ok(NM80211Mode::INFRA) => "Infra",
+        Ok(NM80211Mode::ADHOC) => "Ad-Hoc",
+        Ok(NM80211Mode::MESH) => "Mesh",
+        Ok(NM80211Mode::AP) => "AP",
+        _ => "",
+    }
+    .to_owned()
+}
+
+/// The type names nmcli prints, e.g. `ethernet` or `wifi-p2p`.
+fn type_name(device_type: u32) -> String {
+    NMDeviceType::try_from(device_type)
+        .unwrap_or(NMDeviceType::UNKNOWN)
+        .as_str()
+        .replace('_', "-")
+}
+
+/// The state names nmcli prints.
+fn state_name(state: u32) -> String {
+    let name = match NMDeviceState::try_from(state) {
+        Ok(NMDeviceState::PREPARE) => "connecting (prepare)",
+        Ok(NMDeviceState::CONFIG) => "connecting (configuring)",
+        Ok(NMDeviceState::NEED_AUTH) => "connecting (need authentication)",
+        Ok(NMDeviceState::IP_CONFIG) => "connecting (getting IP configuration)",
+        Ok(NMDeviceState::IP_CHECK) => "connecting (checking IP connectivity)",
+        Ok(NMDeviceState::SECONDARIES) => "connecting (starting secondary connections)",
+        Ok(NMDeviceState::ACTIVATED) => "connected",
+        Ok(NMDeviceState::FAILED) => "connection failed",
+        Ok(state) => state.as_str(),
+        Err(_) => "unknown",
+    };
+    name.to_owned()
+}
+
+fn connectivity_name(connectivity: u32) -> String {
+    NMConnectivityState::try_from(connectivity)
+        .map(|connectivity| connectivity.to_string())
+        .unwrap_or_default()
+}
+
+/// `None` for the `/` path NetworkManager uses for unset object references.
+pub fn optional(path: OwnedObjectPath) -> Option<OwnedObjectPath> {
+    (path.as_str() != "/").then_some(path)
+}
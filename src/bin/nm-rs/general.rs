@@ -0,0 +1,124 @@
+//! `general status` and `radio`.
+
+use rusty_network_manager::dbus_interface_types::{NMConnectivityState, NMMetered, NMState};
+use zbus::Result;
+
+use crate::output::Fields;
+use crate::{Context, Radio, Switch};
+
+const GENERAL_FIELDS: Fields = Fields {
+    all: &[
+        "RUNNING",
+        "VERSION",
+        "STATE",
+        "STARTUP",
+        "CONNECTIVITY",
+        "NETWORKING",
+        "WIFI-HW",
+        "WIFI",
+        "WWAN-HW",
+        "WWAN",
+        "METERED",
+    ],
+    common: &[
+        "STATE",
+        "CONNECTIVITY",
+        "WIFI-HW",
+        "WIFI",
+        "WWAN-HW",
+        "WWAN",
+    ],
+};
+
+const RADIO_FIELDS: Fields = Fields {
+    all: &["WIFI-HW", "WIFI", "WWAN-HW", "WWAN"],
+    common: &["WIFI-HW", "WIFI", "WWAN-HW", "WWAN"],
+};
+
+const WIFI_FIELDS: Fields = Fields {
+    all: &["WIFI"],
+    common: &["WIFI"],
+};
+
+const WWAN_FIELDS: Fields = Fields {
+    all: &["WWAN"],
+    common: &["WWAN"],
+};
+
+pub async fn status(context: &Context) -> Result<()> {
+    let nm = &context.nm;
+    let row = vec![
+        "running".to_owned(),
+        nm.version().await?,
+        state_name(nm.state().await?),
+        match nm.startup().await? {
+            true => "starting",
+            false => "started",
+        }
+        .to_owned(),
+        NMConnectivityState::try_from(nm.connectivity().await?)
+            .unwrap_or(NMConnectivityState::UNKNOWN)
+            .to_string(),
+        enabled(nm.networking_enabled().await?),
+        enabled(nm.wireless_hardware_enabled().await?),
+        enabled(nm.wireless_enabled().await?),
+        enabled(nm.wwan_hardware_enabled().await?),
+        enabled(nm.wwan_enabled().await?),
+        metered_name(nm.metered().await?),
+    ];
+    context.output.table(&GENERAL_FIELDS, &[row])
+}
+
+pub async fn radio(context: &Context, radio: Radio, switch: Option<Switch>) -> Result<()> {
+    let nm = &context.nm;
+    let Some(switch) = switch else {
+        let row = vec![
+            enabled(nm.wireless_hardware_enabled().await?),
+            enabled(nm.wireless_enabled().await?),
+            enabled(nm.wwan_hardware_enabled().await?),
+            enabled(nm.wwan_enabled().await?),
+        ];
+        return match radio {
+            Radio::All => context.output.table(&RADIO_FIELDS, &[row]),
+            Radio::Wifi => context.output.table(&WIFI_FIELDS, &[vec![row[1].clone()]]),
+            Radio::Wwan => context.output.table(&WWAN_FIELDS, &[vec![row[3].clone()]]),
+        };
+    };
+
+    let on = switch == Switch::On;
+    if matches!(radio, Radio::All | Radio::Wifi) {
+        nm.set_wireless_enabled(on).await?;
+    }
+    if matches!(radio, Radio::All | Radio::Wwan) {
+        nm.set_wwan_enabled(on).await?;
+    }
+    Ok(())
+}
+
+/// The state names nmcli prints.
+fn state_name(state: u32) -> String {
+    match NMState::try_from(state) {
+        Ok(NMState::CONNECTED_LOCAL) => "connected (local only)".to_owned(),
+        Ok(NMState::CONNECTED_SITE) => "connected (site only)".to_owned(),
+        Ok(NMState::CONNECTED_GLOBAL) => "connected".to_owned(),
+        Ok(state) => state.to_string(),
+        Err(_) => NMState::UNKNOWN.to_string(),
+    }
+}
+
+fn metered_name(metered: u32) -> String {
+    match NMMetered::try_from(metered) {
+        Ok(NMMetered::GUESS_YES) => "yes (guessed)".to_owned(),
+        Ok(NMMetered::GUESS_NO) => "no (guessed)".to_owned(),
+        Ok(metered) => metered.to_string(),
+        Err(_) => NMMetered::UNKNOWN.to_string(),
+    }
+}
+
+fn enabled(enabled: bool) -> String {
+    match enabled {
+        true => "enabled",
+        false => "disabled",
+    }
+    .to_owned()
+}
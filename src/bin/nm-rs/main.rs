@@ -0,0 +1,351 @@
+//! # nm-rs
+//!
+//! A command line client for NetworkManager modelled on `nmcli`, built on the proxies of this
+//! crate. It implements the common subcommands with nmcli's argument syntax, tabular and
+//! multiline output, terse output (`-t`) and field selection (`-f`):
+//!
+//! ```text
+//! nm-rs general status
+//! nm-rs device status|show [IFNAME]|connect IFNAME|disconnect IFNAME...
+//! nm-rs device wifi list [--rescan auto|yes|no] [ifname IFNAME] [bssid BSSID]
+//! nm-rs device wifi connect SSID [password PASSWORD] [ifname IFNAME] [bssid BSSID] [name NAME] [hidden yes|no]
+//! nm-rs connection show [--active] [--show-secrets] [[id|uuid|path] ID...]
+//! nm-rs connection up|down [id|uuid|path] ID [ifname IFNAME]
+//! nm-rs connection add type TYPE [ifname IFNAME] [con-name NAME] [setting.property VALUE...]
+//! nm-rs connection modify [id|uuid|path] ID [+|-]setting.property VALUE...
+//! nm-rs connection delete [id|uuid|path] ID...
+//! nm-rs connection import type openvpn|wireguard file FILE
+//! nm-rs connection export [id|uuid|path] ID [FILE]
+//! nm-rs radio [all|wifi|wwan] [on|off]
+//! ```
+
+mod connection;
+mod device;
+mod general;
+mod output;
+
+use std::collections::HashMap;
+use std::process::ExitCode;
+use std::time::{Duration, Instant};
+
+use clap::{Parser, Subcommand, ValueEnum};
+use rusty_network_manager::dbus_interface_types::NMDeviceStateReason;
+use rusty_network_manager::{Activation, ActiveProxy, DeviceProxy, NetworkManagerProxy};
+use zbus::zvariant::OwnedObjectPath;
+use zbus::{Connection, Error, Result};
+
+use output::{Mode, Output};
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "nm-rs",
+    version,
+    about = "Command line client for NetworkManager",
+    infer_subcommands = true
+)]
+struct Cli {
+    /// Terse output: values only, separated by ':'
+    #[arg(short, long, global = true)]
+    terse: bool,
+
+    /// Output mode
+    #[arg(short, long, global = true, value_enum)]
+    mode: Option<Mode>,
+
+    /// Comma separated fields to print, or 'all'
+    #[arg(short, long, global = true)]
+    fields: Option<String>,
+
+    /// Seconds to wait for activations to finish, 0 to not wait
+    #[arg(short, long, global = true, default_value_t = 90)]
+    wait: u64,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// NetworkManager's general status
+    General {
+        #[command(subcommand)]
+        command: Option<GeneralCommand>,
+    },
+    /// Network devices
+    Device {
+        #[command(subcommand)]
+        command: Option<DeviceCommand>,
+    },
+    /// Connection profiles
+    Connection {
+        #[command(subcommand)]
+        command: Option<ConnectionCommand>,
+    },
+    /// Radio switches
+    Radio {
+        /// The radios to show or switch
+        #[arg(value_enum, default_value_t = Radio::All)]
+        radio: Radio,
+        /// Switches the radio on or off
+        #[arg(value_enum)]
+        switch: Option<Switch>,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum GeneralCommand {
+    /// Overall state, connectivity and radios
+    Status,
+}
+
+#[derive(Debug, Subcommand)]
+enum DeviceCommand {
+    /// Lists the devices
+    Status,
+    /// Details of one or all devices
+    Show { ifname: Option<String> },
+    /// Activates the best available profile on a device
+    Connect { ifname: String },
+    /// Disconnects devices
+    Disconnect {
+        #[arg(required = true)]
+        ifnames: Vec<String>,
+    },
+    /// Wi-Fi networks
+    Wifi {
+        #[command(subcommand)]
+        command: Option<WifiCommand>,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum WifiCommand {
+    /// Lists the access points in range
+    List {
+        /// Whether to scan first; 'auto' scans when the last scan is older than 30 seconds
+        #[arg(long, value_enum, default_value_t = Rescan::Auto)]
+        rescan: Rescan,
+        /// ifname IFNAME, bssid BSSID
+        args: Vec<String>,
+    },
+    /// Creates a profile for a network in range and activates it
+    Connect {
+        ssid: String,
+        /// password PASSWORD, ifname IFNAME, bssid BSSID, name NAME, hidden yes|no
+        args: Vec<String>,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum ConnectionCommand {
+    /// Lists the profiles, or shows the details of some
+    Show {
+        /// Only lists active profiles
+        #[arg(long)]
+        active: bool,
+        /// Includes secrets in the details
+        #[arg(short, long)]
+        show_secrets: bool,
+        /// [id|uuid|path] ID...
+        ids: Vec<String>,
+    },
+    /// Activates a profile
+    Up {
+        /// [id|uuid|path] ID [ifname IFNAME]
+        #[arg(required = true)]
+        args: Vec<String>,
+    },
+    /// Deactivates active profiles
+    Down {
+        /// [id|uuid|path] ID...
+        #[arg(required = true)]
+        ids: Vec<String>,
+    },
+    /// Adds a profile
+    Add {
+        /// type TYPE [ifname IFNAME] [con-name NAME] [autoconnect yes|no] [setting.property VALUE...]
+        #[arg(required = true, trailing_var_arg = true, allow_hyphen_values = true)]
+        args: Vec<String>,
+    },
+    /// Changes properties of a profile, '+' and '-' add to and remove from lists
+    Modify {
+        /// [id|uuid|path] ID [+|-]setting.property VALUE...
+        #[arg(required = true, trailing_var_arg = true, allow_hyphen_values = true)]
+        args: Vec<String>,
+    },
+    /// Deletes profiles
+    Delete {
+        /// [id|uuid|path] ID...
+        #[arg(required = true)]
+        ids: Vec<String>,
+    },
+    /// Imports an OpenVPN or WireGuard configuration
+    Import {
+        /// type openvpn|wireguard file FILE
+        #[arg(required = true)]
+        args: Vec<String>,
+    },
+    /// Exports a WireGuard profile as a wg-quick configuration
+    Export {
+        /// [id|uuid|path] ID [FILE]
+        #[arg(required = true)]
+        args: Vec<String>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Radio {
+    All,
+    Wifi,
+    Wwan,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Switch {
+    On,
+    Off,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Rescan {
+    Auto,
+    Yes,
+    No,
+}
+
+/// What every command needs: the bus, the NetworkManager proxy and the global options.
+struct Context {
+    connection: Connection,
+    nm: NetworkManagerProxy<'static>,
+    output: Output,
+    /// `None` when activations aren't waited for.
+    timeout: Option<Duration>,
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    match async_io::block_on(run(cli)) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("Error: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(cli: Cli) -> Result<()> {
+    let connection = Connection::system().await?;
+    let context = Context {
+        nm: NetworkManagerProxy::new(&connection).await?,
+        connection,
+        output: Output {
+            terse: cli.terse,
+            mode: cli.mode,
+            fields: cli.fields,
+        },
+        timeout: (cli.wait > 0).then(|| Duration::from_secs(cli.wait)),
+    };
+
+    match cli.command {
+        None | Some(Command::General { .. }) => general::status(&context).await,
+        Some(Command::Radio { radio, switch }) => general::radio(&context, radio, switch).await,
+        Some(Command::Device { command }) => match command.unwrap_or(DeviceCommand::Status) {
+            DeviceCommand::Status => device::status(&context).await,
+            DeviceCommand::Show { ifname } => device::show(&context, ifname.as_deref()).await,
+            DeviceCommand::Connect { ifname } => device::connect(&context, &ifname).await,
+            DeviceCommand::Disconnect { ifnames } => device::disconnect(&context, &ifnames).await,
+            DeviceCommand::Wifi { command } => match command {
+                None => device::wifi_list(&context, Rescan::Auto, &[]).await,
+                Some(WifiCommand::List { rescan, args }) => {
+                    device::wifi_list(&context, rescan, &args).await
+                }
+                Some(WifiCommand::Connect { ssid, args }) => {
+                    device::wifi_connect(&context, &ssid, &args).await
+                }
+            },
+        },
+        Some(Command::Connection { command }) => match command {
+            None => connection::show(&context, false, false, &[]).await,
+            Some(ConnectionCommand::Show {
+                active,
+                show_secrets,
+                ids,
+            }) => connection::show(&context, active, show_secrets, &ids).await,
+            Some(ConnectionCommand::Up { args }) => connection::up(&context, &args).await,
+            Some(ConnectionCommand::Down { ids }) => connection::down(&context, &ids).await,
+            Some(ConnectionCommand::Add { args }) => connection::add(&context, &args).await,
+            Some(ConnectionCommand::Modify { args }) => connection::modify(&context, &args).await,
+            Some(ConnectionCommand::Delete { ids }) => connection::delete(&context, &ids).await,
+            Some(ConnectionCommand::Import { args }) => connection::import(&context, &args).await,
+            Some(ConnectionCommand::Export { args }) => connection::export(&context, &args).await,
+        },
+    }
+}
+
+fn failure(message: impl Into<String>) -> Error {
+    Error::Failure(message.into())
+}
+
+/// Parses nmcli style `keyword value` pairs, rejecting keywords not in `allowed`.
+fn keywords(args: &[String], allowed: &[&str]) -> Result<HashMap<String, String>> {
+    let mut keywords = HashMap::new();
+    let mut args = args.iter();
+    while let Some(keyword) = args.next() {
+        if !allowed.contains(&keyword.as_str()) {
+            return Err(failure(format!(
+                "invalid argument '{keyword}'; expected one of: {}",
+                allowed.join(", ")
+            )));
+        }
+        let value = args
+            .next()
+            .ok_or_else(|| failure(format!("missing value for '{keyword}'")))?;
+        keywords.insert(keyword.clone(), value.clone());
+    }
+    Ok(keywords)
+}
+
+/// Finds a device by interface name.
+async fn find_device<'a>(
+    context: &'a Context,
+    ifname: &str,
+) -> Result<(OwnedObjectPath, DeviceProxy<'a>)> {
+    for path in context.nm.get_devices().await? {
+        let device = DeviceProxy::new_from_path(path.clone(), &context.connection).await?;
+        if device.interface().await? == ifname {
+            return Ok((path, device));
+        }
+    }
+    Err(failure(format!("Device '{ifname}' not found.")))
+}
+
+/// Waits for an activation to finish, unless waiting was disabled with `--wait 0`.
+///
+/// On failure the state reason of `device` is reported, as the active connection is gone by
+/// then.
+async fn wait_activated(
+    context: &Context,
+    active_path: OwnedObjectPath,
+    device: Option<&DeviceProxy<'_>>,
+) -> Result<()> {
+    let Some(timeout) = context.timeout else {
+        return Ok(());
+    };
+    let active = ActiveProxy::new_from_path(active_path, &context.connection).await?;
+    match active.wait_activated(Instant::now() + timeout).await? {
+        Activation::Activated => Ok(()),
+        Activation::Failed => {
+            let reason = match device {
+                Some(device) => {
+                    let (_, reason) = device.state_reason().await?;
+                    NMDeviceStateReason::try_from(reason)
+                        .map(|reason| format!(" ({reason})"))
+                        .unwrap_or_default()
+                }
+                None => String::new(),
+            };
+            Err(failure(format!("Connection activation failed{reason}.")))
+        }
+        Activation::TimedOut => Err(failure("Timeout expired while waiting for the activation.")),
+    }
+}
@@ -0,0 +1,184 @@
+//! nmcli style output: aligned tables, `NAME: value` listings, terse output and field selection.
+
+use clap::ValueEnum;
+use zbus::{Error, Result};
+
+/// Width of the name column of multiline output, as used by nmcli.
+const NAME_WIDTH: usize = 40;
+
+/// Layout of table output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Mode {
+    Tabular,
+    Multiline,
+}
+
+/// The columns a table can show; `common` are printed when no fields are selected.
+pub struct Fields {
+    pub all: &'static [&'static str],
+    pub common: &'static [&'static str],
+}
+
+/// Output options shared by all commands.
+#[derive(Debug, Clone, Default)]
+pub struct Output {
+    /// Print values only, separated by `:`.
+    pub terse: bool,
+    pub mode: Option<Mode>,
+    /// The `--fields` argument.
+    pub fields: Option<String>,
+}
+
+impl Output {
+    /// Prints `rows`, which hold a value for every field in `fields.all`.
+    pub fn table(&self, fields: &Fields, rows: &[Vec<String>]) -> Result<()> {
+        let selected = self
+            .selected_fields()
+            .filter(|selected| !is_only(selected, "common"));
+        let columns: Vec<usize> = match selected {
+            None => fields
+                .common
+                .iter()
+                .map(|name| fields.all.iter().position(|field| field == name).unwrap())
+                .collect(),
+            Some(selected) if is_only(&selected, "all") => (0..fields.all.len()).collect(),
+            Some(selected) => selected
+                .iter()
+                .map(|name| {
+                    fields
+                        .all
+                        .iter()
+                        .position(|field| field.eq_ignore_ascii_case(name))
+                        .ok_or_else(|| invalid_field(name, fields.all))
+                })
+                .collect::<Result<_>>()?,
+        };
+
+        if self.mode == Some(Mode::Multiline) {
+            for row in rows {
+                for &column in &columns {
+                    self.print_entry(fields.all[column], &row[column]);
+                }
+            }
+            return Ok(());
+        }
+
+        if self.terse {
+            for row in rows {
+                let values: Vec<String> =
+                    columns.iter().map(|&column| escape(&row[column])).collect();
+                println!("{}", values.join(":"));
+            }
+            return Ok(());
+        }
+
+        let cell = |value: &str| -> String {
+            match value {
+                "" => "--".to_owned(),
+                value => value.to_owned(),
+            }
+        };
+        let widths: Vec<usize> = columns
+            .iter()
+            .map(|&column| {
+                rows.iter()
+                    .map(|row| cell(&row[column]).chars().count())
+                    .chain([fields.all[column].len()])
+                    .max()
+                    .unwrap_or_default()
+            })
+            .collect();
+        let print_line = |values: Vec<String>| {
+            let line: String = values
+                .iter()
+                .zip(&widths)
+                .map(|(value, width)| format!("{value:<width$}  "))
+                .collect();
+            println!("{}", line.trim_end());
+        };
+
+        print_line(
+            columns
+                .iter()
+                .map(|&column| fields.all[column].to_owned())
+                .collect(),
+        );
+        for row in rows {
+            print_line(columns.iter().map(|&column| cell(&row[column])).collect());
+        }
+        Ok(())
+    }
+
+    /// Prints `SECTION.NAME: value` entries of a single object.
+    ///
+    /// Fields are selected by section, like `IP4`, or by full name, like `GENERAL.STATE`;
+    /// numbered entries such as `IP4.ADDRESS[2]` are selected by their name without the index.
+    /// `all` and `common` both print every entry.
+    pub fn list(&self, sections: &[&str], entries: &[(String, String)]) -> Result<()> {
+        let selected = self
+            .selected_fields()
+            .filter(|selected| !is_only(selected, "all") && !is_only(selected, "common"));
+        if let Some(selected) = &selected {
+            for name in selected {
+                let section = name.split('.').next().unwrap_or_default();
+                if !sections
+                    .iter()
+                    .any(|known| known.eq_ignore_ascii_case(section))
+                {
+                    return Err(invalid_field(name, sections));
+                }
+            }
+        }
+
+        for (name, value) in entries {
+            let plain_name = name.split('[').next().unwrap_or_default();
+            let section = name.split('.').next().unwrap_or_default();
+            let wanted = selected.as_ref().is_none_or(|selected| {
+                selected.iter().any(|field| {
+                    field.eq_ignore_ascii_case(section) || field.eq_ignore_ascii_case(plain_name)
+                })
+            });
+            if wanted {
+                self.print_entry(name, value);
+            }
+        }
+        Ok(())
+    }
+
+    fn print_entry(&self, name: &str, value: &str) {
+        if self.terse {
+            println!("{name}:{}", escape(value));
+        } else {
+            let value = if value.is_empty() { "--" } else { value };
+            println!("{:<NAME_WIDTH$}{value}", format!("{name}:"));
+        }
+    }
+
+    fn selected_fields(&self) -> Option<Vec<String>> {
+        let fields = self.fields.as_ref()?;
+        Some(
+            fields
+                .split(',')
+                .map(|field| field.trim().to_owned())
+                .filter(|field| !field.is_empty())
+                .collect(),
+        )
+    }
+}
+
+/// Whether `selected` is just the `keyword`, like `all` or `common`.
+fn is_only(selected: &[String], keyword: &str) -> bool {
+    matches!(selected, [field] if field == keyword)
+}
+
+fn invalid_field(name: &str, allowed: &[&str]) -> Error {
+    Error::Failure(format!(
+        "invalid field '{name}'; allowed fields: {}",
+        allowed.join(",")
+    ))
+}
+
+/// Escapes the separator of terse output, like nmcli does.
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace(':', "\\:")
+}
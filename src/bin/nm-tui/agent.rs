@@ -0,0 +1,203 @@
+//! A secret agent that asks the operator for passwords and keys.
+//!
+//! NetworkManager calls `GetSecrets` on registered agents when an activation needs secrets it
+//! doesn't have, e.g. the PSK of a network that was joined without one. The request is handed
+//! to the UI as [`Event::Secrets`] and answered once the operator submits or cancels the
+//! prompt. Secrets aren't stored by the agent: NetworkManager saves them with the profile.
+
+use std::collections::HashMap;
+
+use async_channel::Sender;
+use rusty_network_manager::dbus_interface_types::{
+    NMSecretAgentCapabilities, NMSecretAgentGetSecretsFlags,
+};
+use rusty_network_manager::{AgentManagerProxy, ConnectionSettings};
+use zbus::Connection;
+use zbus::zvariant::{OwnedObjectPath, OwnedValue, Value};
+
+use crate::Event;
+
+const AGENT_PATH: &str = "/org/freedesktop/NetworkManager/SecretAgent";
+const IDENTIFIER: &str = "rusty_network_manager.nm-tui";
+
+/// Hints starting with this carry a message for the user rather than a secret name.
+const VPN_MESSAGE_HINT: &str = "x-vpn-message:";
+
+/// A `GetSecrets` call waiting for the operator.
+#[derive(Debug)]
+pub struct SecretRequest {
+    pub connection_id: String,
+    pub connection_path: OwnedObjectPath,
+    pub setting_name: String,
+    /// Names of the secret properties to ask for.
+    pub secrets: Vec<String>,
+    /// Messages from a VPN plugin to show with the prompt.
+    pub messages: Vec<String>,
+    /// Takes the secrets by name, or `None` when the operator cancelled. Dropping it answers
+    /// that the request was cancelled by the agent.
+    pub reply: Sender<Option<HashMap<String, String>>>,
+}
+
+#[derive(Debug, zbus::DBusError)]
+#[zbus(prefix = "org.freedesktop.NetworkManager.SecretAgent")]
+enum AgentError {
+    #[zbus(error)]
+    ZBus(zbus::Error),
+    UserCanceled(String),
+    AgentCanceled(String),
+    NoSecrets(String),
+}
+
+struct Agent {
+    events: Sender<Event>,
+}
+
+#[zbus::interface(name = "org.freedesktop.NetworkManager.SecretAgent")]
+impl Agent {
+    async fn get_secrets(
+        &self,
+        connection: HashMap<String, HashMap<String, OwnedValue>>,
+        connection_path: OwnedObjectPath,
+        setting_name: String,
+        hints: Vec<String>,
+        flags: u32,
+    ) -> Result<HashMap<String, HashMap<String, OwnedValue>>, AgentError> {
+        if flags & NMSecretAgentGetSecretsFlags::ALLOW_INTERACTION as u32 == 0 {
+            return Err(AgentError::NoSecrets(
+                "secrets can only be entered interactively".to_owned(),
+            ));
+        }
+        let settings = ConnectionSettings::from(connection);
+        let secrets = secret_names(&settings, &setting_name, &hints);
+        if secrets.is_empty() {
+            return Err(AgentError::NoSecrets(format!(
+                "no known secrets for {setting_name}"
+            )));
+        }
+
+        let (reply, replies) = async_channel::bounded(1);
+        let request = SecretRequest {
+            connection_id: settings.id().unwrap_or_default().to_owned(),
+            connection_path,
+            setting_name: setting_name.clone(),
+            secrets,
+            messages: hints
+                .iter()
+                .filter_map(|hint| hint.strip_prefix(VPN_MESSAGE_HINT))
+                .map(ToOwned::to_owned)
+                .collect(),
+            reply,
+        };
+        self.events
+            .send(Event::Secrets(request))
+            .await
+            .map_err(|_| AgentError::AgentCanceled("the agent is shutting down".to_owned()))?;
+
+        match replies.recv().await {
+            Ok(Some(values)) => Ok(secrets_reply(&setting_name, values)?),
+            Ok(None) => Err(AgentError::UserCanceled(
+                "the secrets prompt was cancelled".to_owned(),
+            )),
+            Err(_) => Err(AgentError::AgentCanceled(
+                "the secrets request was cancelled".to_owned(),
+            )),
+        }
+    }
+
+    async fn cancel_get_secrets(&self, connection_path: OwnedObjectPath, setting_name: String) {
+        // Fails only when the UI is gone, which drops the request anyway
+        let _ = self
+            .events
+            .send(Event::CancelSecrets {
+                connection_path,
+                setting_name,
+            })
+            .await;
+    }
+
+    fn save_secrets(
+        &self,
+        _connection: HashMap<String, HashMap<String, OwnedValue>>,
+        _connection_path: OwnedObjectPath,
+    ) {
+    }
+
+    fn delete_secrets(
+        &self,
+        _connection: HashMap<String, HashMap<String, OwnedValue>>,
+        _connection_path: OwnedObjectPath,
+    ) {
+    }
+}
+
+/// Serves the agent on `connection` and registers it with NetworkManager. Requests are sent
+/// to `events`.
+pub async fn register(connection: &Connection, events: Sender<Event>) -> zbus::Result<()> {
+    connection
+        .object_server()
+        .at(AGENT_PATH, Agent { events })
+        .await?;
+    AgentManagerProxy::new(connection)
+        .await?
+        .register_with_capabilities(IDENTIFIER, NMSecretAgentCapabilities::VPN_HINTS as u32)
+        .await
+}
+
+pub async fn unregister(connection: &Connection) -> zbus::Result<()> {
+    AgentManagerProxy::new(connection).await?.unregister().await
+}
+
+/// The secrets NetworkManager expects for `setting`. The hints name them when present,
+/// otherwise they follow from the authentication method of the profile.
+fn secret_names(settings: &ConnectionSettings, setting: &str, hints: &[String]) -> Vec<String> {
+    let hints: Vec<String> = hints
+        .iter()
+        .filter(|hint| !hint.starts_with(VPN_MESSAGE_HINT))
+        .cloned()
+        .collect();
+    if !hints.is_empty() {
+        return hints;
+    }
+
+    let names: &[&str] = match setting {
+        "802-11-wireless-security" => {
+            match settings.get_str(setting, "key-mgmt").unwrap_or_default() {
+                "wpa-psk" | "sae" => &["psk"],
+                "ieee8021x" if settings.get_str(setting, "auth-alg") == Some("leap") => {
+                    &["leap-password"]
+                }
+                "none" => {
+                    let index = settings
+                        .get_u32(setting, "wep-tx-keyidx")
+                        .unwrap_or_default();
+                    return vec![format!("wep-key{index}")];
+                }
+                _ => &[],
+            }
+        }
+        "802-1x" => match settings.get_strings(setting, "eap").as_deref() {
+            Some([eap, ..]) if eap == "tls" => &["private-key-password"],
+            _ => &["password"],
+        },
+        "adsl" | "cdma" | "gsm" | "pppoe" | "vpn" => &["password"],
+        "macsec" => &["mka-cak"],
+        "wireguard" => &["private-key"],
+        _ => &[],
+    };
+    names.iter().map(|name| (*name).to_owned()).collect()
+}
+
+/// The `GetSecrets` result: the secrets of `setting`, or `vpn.secrets` for VPNs.
+fn secrets_reply(
+    setting: &str,
+    values: HashMap<String, String>,
+) -> zbus::Result<HashMap<String, HashMap<String, OwnedValue>>> {
+    let properties = match setting {
+        "vpn" => HashMap::from([("secrets".to_owned(), Value::from(values).try_to_owned()?)]),
+        _ => values
+            .into_iter()
+            .map(|(name, value)| Ok((name, Value::from(value).try_to_owned()?)))
+            .collect::<zbus::Result<_>>()?,
+    };
+    Ok(HashMap::from([(setting.to_owned(), properties)]))
+}
@@ -0,0 +1,501 @@
+//! UI state and key handling.
+
+use std::collections::{HashMap, VecDeque};
+use std::iter;
+
+use async_channel::Receiver;
+use async_io::block_on;
+use ratatui::DefaultTerminal;
+use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::widgets::TableState;
+use rusty_network_manager::{
+    DeviceProxy, NetworkManagerProxy, WirelessProxy, apply_property_text, property_text_entries,
+};
+use zbus::zvariant::{ObjectPath, OwnedObjectPath};
+use zbus::{Connection, Error, Result};
+
+use crate::agent::SecretRequest;
+use crate::model::{self, Model};
+use crate::{Event, ui};
+
+/// The profile properties the editor shows, with their labels.
+const EDITOR_FIELDS: &[(&str, &str)] = &[
+    ("IPv4 method", "ipv4.method"),
+    ("IPv4 addresses", "ipv4.addresses"),
+    ("IPv4 gateway", "ipv4.gateway"),
+    ("IPv4 DNS servers", "ipv4.dns"),
+    ("IPv6 method", "ipv6.method"),
+    ("IPv6 addresses", "ipv6.addresses"),
+    ("IPv6 gateway", "ipv6.gateway"),
+    ("IPv6 DNS servers", "ipv6.dns"),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tab {
+    Devices,
+    Wifi,
+    Profiles,
+}
+
+impl Tab {
+    pub const ALL: [Tab; 3] = [Tab::Devices, Tab::Wifi, Tab::Profiles];
+
+    pub fn title(self) -> &'static str {
+        match self {
+            Tab::Devices => "Devices",
+            Tab::Wifi => "Wi-Fi",
+            Tab::Profiles => "Profiles",
+        }
+    }
+
+    pub fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// A list of text inputs, used by the editor and the secrets prompt.
+#[derive(Debug, Clone)]
+pub struct Form {
+    pub title: String,
+    /// Shown above the fields.
+    pub message: String,
+    pub fields: Vec<Field>,
+    pub focus: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct Field {
+    pub label: String,
+    pub value: String,
+    /// Shown as `*`, for secrets.
+    pub masked: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FormAction {
+    Submit,
+    Cancel,
+}
+
+impl Form {
+    fn handle_key(&mut self, key: KeyEvent) -> Option<FormAction> {
+        let value = &mut self.fields[self.focus].value;
+        match key.code {
+            KeyCode::Enter => return Some(FormAction::Submit),
+            KeyCode::Esc => return Some(FormAction::Cancel),
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                return Some(FormAction::Cancel);
+            }
+            KeyCode::Backspace => {
+                value.pop();
+            }
+            KeyCode::Char('u') if key.modifiers.contains(KeyModifiers::CONTROL) => value.clear(),
+            KeyCode::Char(c) => value.push(c),
+            KeyCode::Down | KeyCode::Tab => self.focus = (self.focus + 1) % self.fields.len(),
+            KeyCode::Up | KeyCode::BackTab => {
+                self.focus = (self.focus + self.fields.len() - 1) % self.fields.len();
+            }
+            _ => {}
+        }
+        None
+    }
+}
+
+/// Edits the IP settings of a profile.
+#[derive(Debug, Clone)]
+pub struct Editor {
+    profile: OwnedObjectPath,
+    pub form: Form,
+}
+
+/// A secrets request shown to the operator.
+#[derive(Debug)]
+pub struct Prompt {
+    request: SecretRequest,
+    pub form: Form,
+}
+
+pub struct App {
+    connection: Connection,
+    nm: NetworkManagerProxy<'static>,
+    pub model: Model,
+    pub tab: Tab,
+    /// Selection of each tab's list.
+    pub tables: [TableState; 3],
+    /// The result of the last action.
+    pub status: String,
+    pub editor: Option<Editor>,
+    /// Secrets requests, the first one is shown.
+    pub prompts: VecDeque<Prompt>,
+    quit: bool,
+}
+
+impl App {
+    pub async fn new(connection: Connection) -> Result<App> {
+        Ok(App {
+            nm: NetworkManagerProxy::new(&connection).await?,
+            model: Model::load(&connection).await?,
+            connection,
+            tab: Tab::Devices,
+            tables: Default::default(),
+            status: String::new(),
+            editor: None,
+            prompts: VecDeque::new(),
+            quit: false,
+        })
+    }
+
+    /// Draws and handles events until the operator quits.
+    pub fn run(&mut self, terminal: &mut DefaultTerminal, events: &Receiver<Event>) -> Result<()> {
+        while !self.quit {
+            terminal.draw(|frame| ui::draw(frame, self))?;
+            let Ok(event) = events.recv_blocking() else {
+                break;
+            };
+            // Signals come in bursts, e.g. for every access point of a scan
+            let mut changed = false;
+            for event in iter::once(event).chain(iter::from_fn(|| events.try_recv().ok())) {
+                match event {
+                    Event::Changed => changed = true,
+                    event => self.handle(event),
+                }
+            }
+            if changed {
+                self.reload();
+            }
+        }
+        Ok(())
+    }
+
+    pub fn selected(&self, tab: Tab) -> Option<usize> {
+        self.tables[tab.index()].selected()
+    }
+
+    fn handle(&mut self, event: Event) {
+        match event {
+            Event::Key(key) => self.handle_key(key),
+            Event::Resize | Event::Changed => {}
+            Event::Secrets(request) => self.prompts.push_back(prompt(request)),
+            Event::CancelSecrets {
+                connection_path,
+                setting_name,
+            } => {
+                // Dropping the reply sender answers the request
+                self.prompts.retain(|prompt| {
+                    prompt.request.connection_path != connection_path
+                        || prompt.request.setting_name != setting_name
+                });
+            }
+            Event::WatchFailed(message) => {
+                self.status = format!("Stopped watching for changes: {message}");
+            }
+        }
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) {
+        if let Some(prompt) = self.prompts.front_mut() {
+            match prompt.form.handle_key(key) {
+                Some(FormAction::Submit) => {
+                    let values = prompt
+                        .request
+                        .secrets
+                        .iter()
+                        .cloned()
+                        .zip(prompt.form.fields.iter().map(|field| field.value.clone()))
+                        .collect();
+                    let _ = prompt.request.reply.try_send(Some(values));
+                    self.prompts.pop_front();
+                }
+                Some(FormAction::Cancel) => {
+                    let _ = prompt.request.reply.try_send(None);
+                    self.prompts.pop_front();
+                }
+                None => {}
+            }
+            return;
+        }
+
+        if let Some(editor) = &mut self.editor {
+            match editor.form.handle_key(key) {
+                Some(FormAction::Submit) => {
+                    let editor = editor.clone();
+                    match block_on(self.save(&editor)) {
+                        Ok(()) => {
+                            self.status = format!(
+                                "Saved '{}', reactivate it to apply the changes.",
+                                editor.form.title
+                            );
+                            self.editor = None;
+                        }
+                        Err(err) => self.status = format!("Error: {err}"),
+                    }
+                }
+                Some(FormAction::Cancel) => self.editor = None,
+                None => {}
+            }
+            return;
+        }
+
+        let table = self.tab.index();
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => self.quit = true,
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => self.quit = true,
+            KeyCode::Tab | KeyCode::Right => {
+                self.tab = Tab::ALL[(self.tab.index() + 1) % Tab::ALL.len()];
+            }
+            KeyCode::BackTab | KeyCode::Left => {
+                self.tab = Tab::ALL[(self.tab.index() + Tab::ALL.len() - 1) % Tab::ALL.len()];
+            }
+            KeyCode::Down | KeyCode::Char('j') => self.tables[table].select_next(),
+            KeyCode::Up | KeyCode::Char('k') => self.tables[table].select_previous(),
+            KeyCode::Enter => self.report(block_on(self.activate())),
+            KeyCode::Char('d') => self.report(block_on(self.deactivate())),
+            KeyCode::Char('e') if self.tab == Tab::Profiles => match block_on(self.edit()) {
+                Ok(editor) => self.editor = editor,
+                Err(err) => self.status = format!("Error: {err}"),
+            },
+            KeyCode::Char('r') if self.tab == Tab::Wifi => self.report(block_on(self.rescan())),
+            _ => {}
+        }
+        self.clamp_selection();
+    }
+
+    /// Shows the result of an action in the status line.
+    fn report(&mut self, result: Result<String>) {
+        self.status = match result {
+            Ok(message) => message,
+            Err(err) => format!("Error: {err}"),
+        };
+    }
+
+    fn reload(&mut self) {
+        match block_on(Model::load(&self.connection)) {
+            Ok(model) => {
+                self.model = model;
+                self.clamp_selection();
+            }
+            Err(err) => self.status = format!("Error: {err}"),
+        }
+    }
+
+    fn clamp_selection(&mut self) {
+        let lengths = [
+            self.model.devices.len(),
+            self.model.networks.len(),
+            self.model.profiles.len(),
+        ];
+        for (table, length) in self.tables.iter_mut().zip(lengths) {
+            let selected = match table.selected() {
+                _ if length == 0 => None,
+                Some(selected) => Some(selected.min(length - 1)),
+                None => Some(0),
+            };
+            table.select(selected);
+        }
+    }
+
+    /// Connects the selected device or network, or activates the selected profile.
+    async fn activate(&self) -> Result<String> {
+        let root = ObjectPath::from_static_str_unchecked("/");
+        match self.tab {
+            Tab::Devices => {
+                let Some(device) = self.selected(Tab::Devices).map(|i| &self.model.devices[i])
+                else {
+                    return Ok(String::new());
+                };
+                self.nm
+                    .activate_connection(&root, &device.path, &root)
+                    .await?;
+                Ok(format!("Connecting {}...", device.interface))
+            }
+            Tab::Wifi => {
+                let Some(network) = self.selected(Tab::Wifi).map(|i| &self.model.networks[i])
+                else {
+                    return Ok(String::new());
+                };
+                let profile = self
+                    .model
+                    .profiles
+                    .iter()
+                    .find(|profile| profile.ssid.as_ref() == Some(&network.ssid));
+                match profile {
+                    Some(profile) => {
+                        self.nm
+                            .activate_connection(
+                                &profile.path,
+                                &network.device,
+                                &network.access_point,
+                            )
+                            .await?;
+                    }
+                    // NetworkManager completes the profile from the access point and asks the
+                    // agent for the password
+                    None => {
+                        self.nm
+                            .add_and_activate_connection(
+                                HashMap::new(),
+                                &network.device,
+                                &network.access_point,
+                            )
+                            .await?;
+                    }
+                }
+                Ok(format!("Connecting to {}...", network.ssid))
+            }
+            Tab::Profiles => {
+                let Some(profile) = self
+                    .selected(Tab::Profiles)
+                    .map(|i| &self.model.profiles[i])
+                else {
+                    return Ok(String::new());
+                };
+                self.nm
+                    .activate_connection(&profile.path, &root, &root)
+                    .await?;
+                Ok(format!("Activating '{}'...", profile.id))
+            }
+        }
+    }
+
+    /// Disconnects the selected device or deactivates the selected profile.
+    async fn deactivate(&self) -> Result<String> {
+        match self.tab {
+            Tab::Devices => {
+                let Some(device) = self.selected(Tab::Devices).map(|i| &self.model.devices[i])
+                else {
+                    return Ok(String::new());
+                };
+                DeviceProxy::new_from_path(device.path.clone(), &self.connection)
+                    .await?
+                    .disconnect()
+                    .await?;
+                Ok(format!("Disconnected {}.", device.interface))
+            }
+            Tab::Wifi => Ok(String::new()),
+            Tab::Profiles => {
+                let Some(profile) = self
+                    .selected(Tab::Profiles)
+                    .map(|i| &self.model.profiles[i])
+                else {
+                    return Ok(String::new());
+                };
+                let Some(active) = &profile.active else {
+                    return Ok(format!("'{}' is not active.", profile.id));
+                };
+                self.nm.deactivate_connection(active).await?;
+                Ok(format!("Deactivated '{}'.", profile.id))
+            }
+        }
+    }
+
+    async fn rescan(&self) -> Result<String> {
+        let mut devices = Vec::new();
+        for network in &self.model.networks {
+            if !devices.contains(&network.device) {
+                devices.push(network.device.clone());
+            }
+        }
+        for device in &self.model.devices {
+            if device.device_type == "wifi" && !devices.contains(&device.path) {
+                devices.push(device.path.clone());
+            }
+        }
+        for device in devices {
+            WirelessProxy::new_from_path(device, &self.connection)
+                .await?
+                .request_scan(HashMap::new())
+                .await?;
+        }
+        Ok("Scanning...".to_owned())
+    }
+
+    /// Opens the editor for the selected profile.
+    async fn edit(&self) -> Result<Option<Editor>> {
+        let Some(profile) = self
+            .selected(Tab::Profiles)
+            .map(|i| &self.model.profiles[i])
+        else {
+            return Ok(None);
+        };
+        let (_, settings) = model::load_profile(&self.connection, profile.path.clone()).await?;
+        let entries: HashMap<String, String> =
+            property_text_entries(&settings).into_iter().collect();
+        let fields = EDITOR_FIELDS
+            .iter()
+            .map(|(label, name)| Field {
+                label: (*label).to_owned(),
+                value: entries.get(*name).cloned().unwrap_or_default(),
+                masked: false,
+            })
+            .collect();
+
+        Ok(Some(Editor {
+            profile: profile.path.clone(),
+            form: Form {
+                title: profile.id.clone(),
+                message: "Lists are comma separated, addresses in CIDR notation.".to_owned(),
+                fields,
+                focus: 0,
+            },
+        }))
+    }
+
+    async fn save(&self, editor: &Editor) -> Result<()> {
+        let (proxy, mut settings) =
+            model::load_profile(&self.connection, editor.profile.clone()).await?;
+        for ((_, name), field) in EDITOR_FIELDS.iter().zip(&editor.form.fields) {
+            let value = field.value.trim();
+            if !value.is_empty() {
+                apply_property_text(&mut settings, name, value)?;
+                continue;
+            }
+            let (setting, property) = name.split_once('.').unwrap_or_default();
+            settings.remove(setting, property);
+            if property == "addresses" {
+                settings.remove(setting, "address-data");
+            }
+        }
+        proxy
+            .update(settings.to_dbus())
+            .await
+            .map_err(|err| Error::Failure(format!("failed to save: {err}")))
+    }
+}
+
+fn prompt(request: SecretRequest) -> Prompt {
+    let mut message = format!("'{}' needs secrets to connect.", request.connection_id);
+    for line in &request.messages {
+        message.push('\n');
+        message.push_str(line);
+    }
+    let fields = request
+        .secrets
+        .iter()
+        .map(|name| Field {
+            label: secret_label(name).to_owned(),
+            value: String::new(),
+            masked: true,
+        })
+        .collect();
+    Prompt {
+        form: Form {
+            title: "Authentication required".to_owned(),
+            message,
+            fields,
+            focus: 0,
+        },
+        request,
+    }
+}
+
+fn secret_label(name: &str) -> &str {
+    match name {
+        "psk" | "password" | "leap-password" => "Password",
+        "wep-key0" | "wep-key1" | "wep-key2" | "wep-key3" => "WEP key",
+        "private-key" => "Private key",
+        "private-key-password" => "Private key password",
+        "pin" => "PIN",
+        "mka-cak" => "MKA CAK",
+        name => name,
+    }
+}
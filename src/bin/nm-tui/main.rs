@@ -0,0 +1,94 @@
+//! # nm-tui
+//!
+//! An interactive terminal UI for NetworkManager in the spirit of `nmtui`, for machines
+//! without a desktop such as kiosks. It lists the devices, the Wi-Fi networks in range and the
+//! profiles, kept up to date from NetworkManager's signals, and can:
+//!
+//! - connect and disconnect devices, and join Wi-Fi networks,
+//! - activate and deactivate profiles,
+//! - edit the IPv4 and IPv6 addresses, gateways and DNS servers of a profile,
+//! - prompt for passwords and keys, as a secret agent, when an activation needs them.
+//!
+//! Tab switches between the lists, Enter connects or activates the selected entry, and the
+//! bottom line lists the other keys.
+
+mod agent;
+mod app;
+mod model;
+mod ui;
+mod watch;
+
+use std::process::ExitCode;
+use std::thread;
+
+use async_channel::Sender;
+use ratatui::crossterm::event::{self, KeyEvent, KeyEventKind};
+use zbus::zvariant::OwnedObjectPath;
+use zbus::{Connection, Result};
+
+use agent::SecretRequest;
+use app::App;
+
+/// What the UI reacts to.
+#[derive(Debug)]
+pub enum Event {
+    Key(KeyEvent),
+    Resize,
+    /// Something the lists show changed.
+    Changed,
+    Secrets(SecretRequest),
+    /// NetworkManager no longer needs the secrets of a request.
+    CancelSecrets {
+        connection_path: OwnedObjectPath,
+        setting_name: String,
+    },
+    WatchFailed(String),
+}
+
+fn main() -> ExitCode {
+    match async_io::block_on(Connection::system()).and_then(run) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("Error: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(connection: Connection) -> Result<()> {
+    let (sender, events) = async_channel::unbounded();
+    async_io::block_on(agent::register(&connection, sender.clone()))?;
+    let mut app = async_io::block_on(App::new(connection.clone()))?;
+
+    let watcher = connection.clone();
+    let watch_events = sender.clone();
+    thread::spawn(move || {
+        if let Err(err) = async_io::block_on(watch::watch(&watcher, &watch_events)) {
+            let _ = watch_events.send_blocking(Event::WatchFailed(err.to_string()));
+        }
+    });
+    thread::spawn(move || read_terminal(&sender));
+
+    let mut terminal = ratatui::init();
+    let result = app.run(&mut terminal, &events);
+    ratatui::restore();
+
+    // NetworkManager also drops the agent when the process leaves the bus
+    let _ = async_io::block_on(agent::unregister(&connection));
+    result
+}
+
+/// Forwards key presses and resizes until the UI is gone.
+fn read_terminal(events: &Sender<Event>) {
+    loop {
+        let event = match event::read() {
+            Ok(event::Event::Key(key)) if key.kind == KeyEventKind::Press => Event::Key(key),
+            Ok(event::Event::Resize(..)) => Event::Resize,
+            Ok(_) => continue,
+            Err(_) => return,
+        };
+        if events.send_blocking(event).is_err() {
+            return;
+        }
+    }
+}
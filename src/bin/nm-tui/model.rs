@@ -0,0 +1,234 @@
+//! What the lists show, read from NetworkManager.
+
+use std::collections::HashMap;
+
+use rusty_network_manager::dbus_interface_types::{NMDeviceState, NMDeviceType};
+use rusty_network_manager::{
+    AccessPointProxy, ActiveProxy, ConnectionSettings, DeviceProxy, NM80211ApFlags,
+    NM80211ApSecurityFlags, NetworkManagerProxy, SettingsConnectionProxy, SettingsProxy,
+    WirelessProxy, connection_type_alias, get_settings_with_secrets,
+};
+use zbus::zvariant::OwnedObjectPath;
+use zbus::{Connection, Result};
+
+#[derive(Debug, Clone, Default)]
+pub struct Model {
+    /// Sorted by interface name.
+    pub devices: Vec<Device>,
+    /// The strongest access point of each network, networks in use first.
+    pub networks: Vec<Network>,
+    /// Active profiles first, then by name.
+    pub profiles: Vec<Profile>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Device {
+    pub path: OwnedObjectPath,
+    pub interface: String,
+    pub device_type: String,
+    pub state: String,
+    /// Name of the active connection, empty when there is none.
+    pub connection: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Network {
+    pub device: OwnedObjectPath,
+    pub interface: String,
+    pub access_point: OwnedObjectPath,
+    pub ssid: String,
+    /// Percent
+    pub strength: u8,
+    pub security: String,
+    pub in_use: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct Profile {
+    pub path: OwnedObjectPath,
+    pub id: String,
+    pub connection_type: String,
+    /// The network of Wi-Fi profiles.
+    pub ssid: Option<String>,
+    /// The active connection, when the profile is active.
+    pub active: Option<OwnedObjectPath>,
+    /// Interface names of the devices the profile is active on, comma separated.
+    pub devices: String,
+}
+
+impl Model {
+    pub async fn load(connection: &Connection) -> Result<Model> {
+        let nm = NetworkManagerProxy::new(connection).await?;
+
+        // Profile path to active connection path and interface names
+        let mut active = HashMap::new();
+        for path in nm.active_connections().await? {
+            let proxy = ActiveProxy::new_from_path(path.clone(), connection).await?;
+            let mut interfaces = Vec::new();
+            for device in proxy.devices().await? {
+                let device = DeviceProxy::new_from_path(device, connection).await?;
+                interfaces.push(device.interface().await?);
+            }
+            active.insert(proxy.connection().await?, (path, interfaces.join(",")));
+        }
+
+        let mut devices = Vec::new();
+        let mut networks = Vec::new();
+        for path in nm.get_devices().await? {
+            let device = DeviceProxy::new_from_path(path.clone(), connection).await?;
+            let interface = device.interface().await?;
+            let device_type = NMDeviceType::try_from(device.device_type().await?)
+                .unwrap_or(NMDeviceType::UNKNOWN);
+            let active_connection = device.active_connection().await?;
+            let connection_name = match active_connection.as_str() {
+                "/" => String::new(),
+                _ => {
+                    ActiveProxy::new_from_path(active_connection, connection)
+                        .await?
+                        .id()
+                        .await?
+                }
+            };
+
+            if device_type == NMDeviceType::WIFI {
+                networks.extend(wifi_networks(connection, &path, &interface).await?);
+            }
+            devices.push(Device {
+                path,
+                interface,
+                device_type: device_type.as_str().replace('_', "-"),
+                state: NMDeviceState::try_from(device.state().await?)
+                    .unwrap_or(NMDeviceState::UNKNOWN)
+                    .to_string(),
+                connection: connection_name,
+            });
+        }
+        devices.sort_by(|a, b| a.interface.cmp(&b.interface));
+        networks.sort_by(|a, b| {
+            b.in_use
+                .cmp(&a.in_use)
+                .then_with(|| b.strength.cmp(&a.strength))
+                .then_with(|| a.ssid.cmp(&b.ssid))
+        });
+
+        let mut profiles = Vec::new();
+        for path in SettingsProxy::new(connection)
+            .await?
+            .list_connections()
+            .await?
+        {
+            let proxy = SettingsConnectionProxy::new_from_path(path.clone(), connection).await?;
+            let settings = ConnectionSettings::from(proxy.get_settings().await?);
+            let (active, devices) = active.remove(&path).unzip();
+            profiles.push(Profile {
+                id: settings.id().unwrap_or_default().to_owned(),
+                connection_type: connection_type_alias(
+                    settings.connection_type().unwrap_or_default(),
+                )
+                .to_owned(),
+                ssid: settings
+                    .get_bytes("802-11-wireless", "ssid")
+                    .map(|ssid| String::from_utf8_lossy(&ssid).into_owned()),
+                active,
+                devices: devices.unwrap_or_default(),
+                path,
+            });
+        }
+        profiles.sort_by(|a, b| {
+            b.active
+                .is_some()
+                .cmp(&a.active.is_some())
+                .then_with(|| a.id.cmp(&b.id))
+        });
+
+        Ok(Model {
+            devices,
+            networks,
+            profiles,
+        })
+    }
+}
+
+/// Reads a profile with its secrets, so it can be sent back with `Update` without losing
+/// them. Secrets that can't be read, e.g. because an agent owns them, are left out.
+pub async fn load_profile(
+    connection: &Connection,
+    path: OwnedObjectPath,
+) -> Result<(SettingsConnectionProxy<'_>, ConnectionSettings)> {
+    let proxy = SettingsConnectionProxy::new_from_path(path, connection).await?;
+    let settings = get_settings_with_secrets(&proxy).await?;
+    Ok((proxy, settings))
+}
+
+/// The visible networks of a Wi-Fi device, one entry per SSID.
+async fn wifi_networks(
+    connection: &Connection,
+    device: &OwnedObjectPath,
+    interface: &str,
+) -> Result<Vec<Network>> {
+    let wireless = WirelessProxy::new_from_path(device.clone(), connection).await?;
+    let active_access_point = wireless.active_access_point().await?;
+
+    let mut networks: Vec<Network> = Vec::new();
+    for path in wireless.get_access_points().await? {
+        let access_point = AccessPointProxy::new_from_path(path.clone(), connection).await?;
+        let ssid = String::from_utf8_lossy(&access_point.ssid().await?).into_owned();
+        if ssid.is_empty() {
+            // Hidden networks can only be joined by name
+            continue;
+        }
+        let network = Network {
+            device: device.clone(),
+            interface: interface.to_owned(),
+            in_use: path == active_access_point,
+            access_point: path,
+            ssid,
+            strength: access_point.strength().await?,
+            security: security(&access_point).await?,
+        };
+        match networks.iter_mut().find(|known| known.ssid == network.ssid) {
+            Some(known) => {
+                let in_use = known.in_use || network.in_use;
+                if network.strength > known.strength {
+                    *known = network;
+                }
+                known.in_use = in_use;
+            }
+            None => networks.push(network),
+        }
+    }
+    Ok(networks)
+}
+
+/// The security of an access point as nmcli names it, e.g. `WPA2 WPA3`.
+async fn security(access_point: &AccessPointProxy<'_>) -> Result<String> {
+    let privacy = NM80211ApFlags::from_bits_retain(access_point.flags().await?)
+        .contains(NM80211ApFlags::PRIVACY);
+    let wpa = NM80211ApSecurityFlags::from_bits_retain(access_point.wpa_flags().await?);
+    let rsn = NM80211ApSecurityFlags::from_bits_retain(access_point.rsn_flags().await?);
+
+    let mut security = Vec::new();
+    if privacy && wpa.is_empty() && rsn.is_empty() {
+        security.push("WEP");
+    }
+    if !wpa.is_empty() {
+        security.push("WPA1");
+    }
+    if rsn
+        .intersects(NM80211ApSecurityFlags::KEY_MGMT_PSK | NM80211ApSecurityFlags::KEY_MGMT_802_1X)
+    {
+        security.push("WPA2");
+    }
+    if rsn.contains(NM80211ApSecurityFlags::KEY_MGMT_SAE) {
+        security.push("WPA3");
+    }
+    if rsn
+        .intersects(NM80211ApSecurityFlags::KEY_MGMT_OWE | NM80211ApSecurityFlags::KEY_MGMT_OWE_TM)
+    {
+        security.push("OWE");
+    }
+    if (wpa | rsn).contains(NM80211ApSecurityFlags::KEY_MGMT_802_1X) {
+        security.push("802.1X");
+    }
+    Ok(security.join(" "))
+}
@@ -0,0 +1,183 @@
+//! Drawing.
+
+use ratatui::Frame;
+use ratatui::layout::{Constraint, Flex, Layout, Position, Rect};
+use ratatui::style::{Style, Stylize};
+use ratatui::text::{Line, Span, Text};
+use ratatui::widgets::{Block, Clear, Paragraph, Row, Table, Tabs, Wrap};
+
+use crate::app::{App, Form, Tab};
+
+const LABEL_WIDTH: u16 = 24;
+
+pub fn draw(frame: &mut Frame, app: &mut App) {
+    let [tabs, body, status, help] = Layout::vertical([
+        Constraint::Length(1),
+        Constraint::Fill(1),
+        Constraint::Length(1),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+
+    frame.render_widget(
+        Tabs::new(Tab::ALL.map(Tab::title))
+            .select(Tab::ALL.iter().position(|tab| *tab == app.tab))
+            .highlight_style(Style::new().bold().reversed()),
+        tabs,
+    );
+    draw_list(frame, app, body);
+    frame.render_widget(Paragraph::new(app.status.as_str()), status);
+    frame.render_widget(Paragraph::new(help_line(app)).dim(), help);
+
+    if let Some(prompt) = app.prompts.front() {
+        draw_form(frame, &prompt.form);
+    } else if let Some(editor) = &app.editor {
+        draw_form(frame, &editor.form);
+    }
+}
+
+fn draw_list(frame: &mut Frame, app: &mut App, area: Rect) {
+    let (header, widths, rows): (&[&str], &[Constraint], Vec<Row>) = match app.tab {
+        Tab::Devices => (
+            &["DEVICE", "TYPE", "STATE", "CONNECTION"],
+            &[
+                Constraint::Length(16),
+                Constraint::Length(12),
+                Constraint::Length(14),
+                Constraint::Fill(1),
+            ],
+            app.model
+                .devices
+                .iter()
+                .map(|device| {
+                    Row::new([
+                        device.interface.as_str(),
+                        &device.device_type,
+                        &device.state,
+                        &device.connection,
+                    ])
+                })
+                .collect(),
+        ),
+        Tab::Wifi => (
+            &["", "SSID", "SIGNAL", "SECURITY", "DEVICE"],
+            &[
+                Constraint::Length(1),
+                Constraint::Fill(1),
+                Constraint::Length(6),
+                Constraint::Length(16),
+                Constraint::Length(16),
+            ],
+            app.model
+                .networks
+                .iter()
+                .map(|network| {
+                    Row::new([
+                        if network.in_use { "*" } else { "" }.to_owned(),
+                        network.ssid.clone(),
+                        format!("{:>5}%", network.strength),
+                        network.security.clone(),
+                        network.interface.clone(),
+                    ])
+                })
+                .collect(),
+        ),
+        Tab::Profiles => (
+            &["", "NAME", "TYPE", "DEVICE"],
+            &[
+                Constraint::Length(1),
+                Constraint::Fill(1),
+                Constraint::Length(16),
+                Constraint::Length(16),
+            ],
+            app.model
+                .profiles
+                .iter()
+                .map(|profile| {
+                    Row::new([
+                        if profile.active.is_some() { "*" } else { "" },
+                        &profile.id,
+                        &profile.connection_type,
+                        &profile.devices,
+                    ])
+                })
+                .collect(),
+        ),
+    };
+
+    let table = Table::new(rows, widths)
+        .header(Row::new(header.iter().copied()).bold())
+        .row_highlight_style(Style::new().reversed())
+        .block(Block::bordered());
+    frame.render_stateful_widget(table, area, &mut app.tables[app.tab.index()]);
+}
+
+fn help_line(app: &App) -> &'static str {
+    if !app.prompts.is_empty() || app.editor.is_some() {
+        return "Up/Down: field  Enter: save  Esc: cancel  Ctrl-U: clear";
+    }
+    match app.tab {
+        Tab::Devices => "Tab: next list  Enter: connect  d: disconnect  q: quit",
+        Tab::Wifi => "Tab: next list  Enter: connect  r: rescan  q: quit",
+        Tab::Profiles => "Tab: next list  Enter: activate  d: deactivate  e: edit IP  q: quit",
+    }
+}
+
+/// Draws a form in a box over the lists, with the cursor in the focused field.
+fn draw_form(frame: &mut Frame, form: &Form) {
+    let message = Text::from(form.message.as_str());
+    let height = message.height() as u16 + form.fields.len() as u16 + 3;
+    let area = centered(frame.area(), 72, height);
+    let block = Block::bordered().title(form.title.as_str());
+    let inner = block.inner(area);
+    frame.render_widget(Clear, area);
+    frame.render_widget(block, area);
+
+    let [message_area, fields_area] = Layout::vertical([
+        Constraint::Length(message.height() as u16 + 1),
+        Constraint::Fill(1),
+    ])
+    .areas(inner);
+    frame.render_widget(
+        Paragraph::new(message).wrap(Wrap { trim: false }),
+        message_area,
+    );
+
+    let lines: Vec<Line> = form
+        .fields
+        .iter()
+        .enumerate()
+        .map(|(index, field)| {
+            let value = match field.masked {
+                true => "*".repeat(field.value.chars().count()),
+                false => field.value.clone(),
+            };
+            let label = Span::raw(format!(
+                "{:<width$}",
+                field.label,
+                width = LABEL_WIDTH as usize
+            ));
+            match index == form.focus {
+                true => Line::from(vec![label.bold(), Span::raw(value)]),
+                false => Line::from(vec![label, Span::raw(value)]),
+            }
+        })
+        .collect();
+    frame.render_widget(Paragraph::new(lines), fields_area);
+
+    let focused = &form.fields[form.focus];
+    frame.set_cursor_position(Position::new(
+        fields_area.x + LABEL_WIDTH + focused.value.chars().count() as u16,
+        fields_area.y + form.focus as u16,
+    ));
+}
+
+fn centered(area: Rect, width: u16, height: u16) -> Rect {
+    let [area] = Layout::horizontal([Constraint::Length(width)])
+        .flex(Flex::Center)
+        .areas(area);
+    let [area] = Layout::vertical([Constraint::Length(height)])
+        .flex(Flex::Center)
+        .areas(area);
+    area
+}
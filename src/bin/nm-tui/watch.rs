@@ -0,0 +1,101 @@
+//! Turns NetworkManager's signals into [`Event::Changed`].
+
+use std::pin::Pin;
+
+use async_channel::Sender;
+use futures_lite::{Stream, StreamExt, stream};
+use rusty_network_manager::dbus_interface_types::NMDeviceType;
+use rusty_network_manager::{DeviceProxy, NetworkManagerProxy, SettingsProxy, WirelessProxy};
+use zbus::{Connection, Result};
+
+use crate::Event;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Change {
+    /// A device appeared or went away, so the per-device signals need to be subscribed again.
+    Devices,
+    Other,
+}
+
+type Changes<'a> = Pin<Box<dyn Stream<Item = Change> + Send + 'a>>;
+
+/// Sends [`Event::Changed`] whenever a device, access point, profile or active connection
+/// changes, until the receiver is dropped.
+pub async fn watch(connection: &Connection, events: &Sender<Event>) -> Result<()> {
+    loop {
+        let mut changes = changes(connection).await?;
+        while let Some(change) = changes.next().await {
+            if events.send(Event::Changed).await.is_err() {
+                return Ok(());
+            }
+            if change == Change::Devices {
+                break;
+            }
+        }
+    }
+}
+
+async fn changes(connection: &Connection) -> Result<Changes<'_>> {
+    let nm = NetworkManagerProxy::new(connection).await?;
+    let settings = SettingsProxy::new(connection).await?;
+
+    let mut changes: Vec<Changes<'_>> = vec![
+        Box::pin(nm.receive_device_added().await?.map(|_| Change::Devices)),
+        Box::pin(nm.receive_device_removed().await?.map(|_| Change::Devices)),
+        Box::pin(
+            nm.receive_active_connections_changed()
+                .await
+                .map(|_| Change::Other),
+        ),
+        Box::pin(
+            settings
+                .receive_new_connection()
+                .await?
+                .map(|_| Change::Other),
+        ),
+        Box::pin(
+            settings
+                .receive_connection_removed()
+                .await?
+                .map(|_| Change::Other),
+        ),
+    ];
+    for path in nm.get_devices().await? {
+        let device = DeviceProxy::new_from_path(path.clone(), connection).await?;
+        changes.push(Box::pin(
+            device
+                .receive_device_state_changed()
+                .await?
+                .map(|_| Change::Other),
+        ));
+        if device.device_type().await? != NMDeviceType::WIFI as u32 {
+            continue;
+        }
+        let wireless = WirelessProxy::new_from_path(path, connection).await?;
+        changes.push(Box::pin(
+            wireless
+                .receive_access_point_added()
+                .await?
+                .map(|_| Change::Other),
+        ));
+        changes.push(Box::pin(
+            wireless
+                .receive_access_point_removed()
+                .await?
+                .map(|_| Change::Other),
+        ));
+        // Signal strengths are updated with every scan
+        changes.push(Box::pin(
+            wireless
+                .receive_last_scan_changed()
+                .await
+                .map(|_| Change::Other),
+        ));
+    }
+
+    Ok(changes
+        .into_iter()
+        .fold(Box::pin(stream::pending()), |all, changes| {
+            Box::pin(all.race(changes))
+        }))
+}
@@ -9,7 +9,7 @@ pub use network_manager::access_point_flags::NM80211ApFlags;
 #[cfg(feature = "access_point")]
 pub use network_manager::access_point_security_flags::NM80211ApSecurityFlags;
 #[cfg(feature = "active")]
-pub use network_manager::active::ActiveProxy;
+pub use network_manager::active::{Activation, ActiveProxy};
 #[cfg(feature = "adsl")]
 pub use network_manager::adsl::AdslProxy;
 #[cfg(feature = "agent_manager")]
@@ -21,23 +21,58 @@ pub use network_manager::bond::BondProxy;
 #[cfg(feature = "bridge")]
 pub use network_manager::bridge::BridgeProxy;
 #[cfg(feature = "access_point")]
-pub use network_manager::channel::Channel;
+pub use network_manager::channel::{Band, Channel};
 #[cfg(feature = "checkpoint")]
 pub use network_manager::checkpoint::CheckpointProxy;
 #[cfg(feature = "connection")]
 pub use network_manager::connection::ConnectionProxy;
+pub use network_manager::connection_settings::{
+    ConnectionSettings, PropertyChange, REDACTED, Redacted, SECRET_SETTINGS, is_secret_property,
+    secret_flags_property,
+};
+#[cfg(feature = "connectivity")]
+pub use network_manager::connectivity::{
+    AddressFamily, ConnectivityChange, ConnectivityCheck, ConnectivityMonitor, ConnectivityScope,
+};
+#[cfg(feature = "desired_state")]
+pub use network_manager::desired_state::{
+    AppliedChanges, BondConfig, BridgeConfig, DesiredState, DnsState, InterfaceState,
+    InterfaceStatus, InterfaceType, IpAddressState, IpState, RouteState, VlanConfig, apply,
+};
 #[cfg(feature = "device")]
 pub use network_manager::device::DeviceProxy;
+#[cfg(feature = "wireless")]
+pub use network_manager::device_wifi_capabilities::NMDeviceWifiCapabilities;
 #[cfg(feature = "dhcp4config")]
 pub use network_manager::dhcp4config::DHCP4ConfigProxy;
 #[cfg(feature = "dhcp6config")]
 pub use network_manager::dhcp6config::DHCP6ConfigProxy;
 #[cfg(feature = "dummy")]
 pub use network_manager::dummy::DummyProxy;
+#[cfg(feature = "failover")]
+pub use network_manager::failover::{FailoverEvent, FailoverLogEntry, FailoverManager, Probe};
 #[cfg(feature = "generic")]
 pub use network_manager::generic::GenericProxy;
+#[cfg(feature = "hidden_network")]
+pub use network_manager::hidden_network::{
+    HiddenConnection, connect_hidden, hidden_network_settings,
+};
+#[cfg(feature = "hotspot")]
+pub use network_manager::hotspot::{Hotspot, HotspotSecurity, create_hotspot, hotspot_settings};
 #[cfg(feature = "hsr")]
 pub use network_manager::hsr::HsrProxy;
+#[cfg(feature = "ifupdown")]
+pub use network_manager::ifupdown::import_ifupdown;
+#[cfg(any(
+    feature = "desired_state",
+    feature = "ifupdown",
+    feature = "netplan",
+    feature = "networkd",
+    feature = "openvpn",
+    feature = "wg_quick",
+    feature = "wpa_supplicant"
+))]
+pub use network_manager::import::{ImportReport, ParseError, Untranslated};
 #[cfg(feature = "infiniband")]
 pub use network_manager::infiniband::InfinibandProxy;
 #[cfg(feature = "ip4config")]
@@ -54,12 +89,22 @@ pub use network_manager::lowpan::LowpanProxy;
 pub use network_manager::macsec::MacsecProxy;
 #[cfg(feature = "macvlan")]
 pub use network_manager::macvlan::MacvlanProxy;
+#[cfg(feature = "metrics")]
+pub use network_manager::metrics::{
+    CONTENT_TYPE, MetricFamily, MetricKind, Metrics, MetricsCollector, Sample,
+};
 #[cfg(feature = "modem")]
 pub use network_manager::modem::ModemProxy;
+#[cfg(feature = "netplan")]
+pub use network_manager::netplan::{export_netplan, export_netplan_connections, import_netplan};
+#[cfg(feature = "networkd")]
+pub use network_manager::networkd::import_networkd;
 #[cfg(feature = "nsp")]
 pub use network_manager::nsp::NspProxy;
 #[cfg(feature = "olpc_mesh")]
 pub use network_manager::olpc_mesh::OlpcMeshProxy;
+#[cfg(feature = "openvpn")]
+pub use network_manager::openvpn::{InlineFile, OPENVPN_SERVICE_TYPE, OvpnImport, import_ovpn};
 #[cfg(feature = "ovs_bridge")]
 pub use network_manager::ovs_bridge::OvsBridgeProxy;
 #[cfg(feature = "ovs_interface")]
@@ -70,18 +115,44 @@ pub use network_manager::ovs_port::OvsPortProxy;
 //pub use network_manager::plugin::
 #[cfg(feature = "ppp")]
 pub use network_manager::ppp::PPPProxy;
+#[cfg(any(feature = "cli", feature = "tui"))]
+pub use network_manager::property_text::{
+    apply_property_text, connection_type_alias, property_text_entries, setting_name,
+};
+#[cfg(feature = "reapply")]
+pub use network_manager::reapply::{ReapplyOutcome, can_reapply, modify_applied};
+#[cfg(feature = "roaming")]
+pub use network_manager::roaming::{RoamingEvent, RoamingMonitor};
 #[cfg(feature = "secret_agent")]
 pub use network_manager::secret_agent::SecretAgentProxy;
 #[cfg(feature = "settings")]
 pub use network_manager::settings::SettingsProxy;
 #[cfg(feature = "settings")]
 pub use network_manager::settings_connection::SettingsConnectionProxy;
+#[cfg(feature = "snapshot")]
+pub use network_manager::snapshot::{
+    AccessPointSnapshot, ActiveConnectionSnapshot, DeviceDetails, DeviceSnapshot, IpConfigSnapshot,
+    Logging, ProfileSnapshot, PropertyValue, Radios, RouteSnapshot, Snapshot,
+};
+#[cfg(feature = "snapshot")]
+pub use network_manager::snapshot_diff::{Change, ObjectChanges, SnapshotDiff};
 #[cfg(feature = "statistics")]
 pub use network_manager::statistics::StatisticsProxy;
+#[cfg(feature = "survey")]
+pub use network_manager::survey::{
+    BssidSummary, ChannelUsage, Observation, Survey, SurveyRecorder,
+};
 #[cfg(feature = "team")]
 pub use network_manager::team::TeamProxy;
+#[cfg(feature = "traffic")]
+pub use network_manager::traffic::{TrafficMonitor, TrafficSample};
 #[cfg(feature = "tun")]
 pub use network_manager::tun::TunProxy;
+#[cfg(feature = "settings")]
+pub use network_manager::update::{
+    Persistence, UpdateOptions, get_connection_secrets, get_settings_with_secrets, preview_update,
+    update_connection, update_preserving_secrets,
+};
 #[cfg(feature = "veth")]
 pub use network_manager::veth::VethProxy;
 #[cfg(feature = "vlan")]
@@ -90,18 +161,26 @@ pub use network_manager::vlan::VlanProxy;
 pub use network_manager::vrf::VrfProxy;
 #[cfg(feature = "vxlan")]
 pub use network_manager::vxlan::VxlanProxy;
+#[cfg(feature = "wg_quick")]
+pub use network_manager::wg_quick::{
+    WireGuardConfig, WireGuardInterface, WireGuardPeer, import_wg_quick,
+};
 #[cfg(feature = "wi_max")]
 pub use network_manager::wi_max::WiMaxProxy;
 #[cfg(feature = "wifi_p2p")]
 pub use network_manager::wifi_p2p::WifiP2PProxy;
 #[cfg(feature = "wifi_p2ppeer")]
 pub use network_manager::wifi_p2ppeer::WifiP2PPeerProxy;
+#[cfg(feature = "wifi_uri")]
+pub use network_manager::wifi_uri::{WifiUri, WifiUriError, WifiUriSecurity};
 #[cfg(feature = "wire_guard")]
 pub use network_manager::wire_guard::WireGuardProxy;
 #[cfg(feature = "wired")]
 pub use network_manager::wired::WiredProxy;
 #[cfg(feature = "wireless")]
-pub use network_manager::wireless::WirelessProxy;
+pub use network_manager::wireless::{ScanOptions, WirelessProxy};
+#[cfg(feature = "wpa_supplicant")]
+pub use network_manager::wpa_supplicant::import_wpa_supplicant;
 #[cfg(feature = "wpan")]
 pub use network_manager::wpan::WpanProxy;
 
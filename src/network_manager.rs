@@ -11,6 +11,50 @@
 //!
 //! [Writing a client proxy]: https://dbus2.github.io/zbus/client.html
 //! [D-Bus standard interfaces]: https://dbus.freedesktop.org/doc/dbus-specification.html#standard-interfaces,
+/// Implements `Serialize`/`Deserialize` for a bitflags type as a list of lower case flag
+/// names, e.g. `["privacy", "wps"]`, like the names the generated enums use. Bits without a
+/// name are kept as a hex number.
+#[cfg(all(feature = "serde", any(feature = "access_point", feature = "wireless")))]
+macro_rules! serde_flag_names {
+    ($name:ident) => {
+        impl serde::Serialize for $name {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                let mut names = self.iter_names();
+                let mut list: Vec<String> = names
+                    .by_ref()
+                    .map(|(name, _)| name.to_lowercase())
+                    .collect();
+                let unknown = names.remaining().bits();
+                if unknown != 0 {
+                    list.push(format!("{unknown:#x}"));
+                }
+                serializer.collect_seq(list)
+            }
+        }
+
+        impl<'de> serde::Deserialize<'de> for $name {
+            fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                let mut flags = Self::empty();
+                for name in Vec::<String>::deserialize(deserializer)? {
+                    let flag = match name.strip_prefix("0x") {
+                        Some(hex) => u32::from_str_radix(hex, 16)
+                            .ok()
+                            .map(Self::from_bits_retain),
+                        None => Self::from_name(&name.to_ascii_uppercase()),
+                    };
+                    flags |= flag.ok_or_else(|| {
+                        serde::de::Error::custom(format!(
+                            "unknown {} flag `{name}`",
+                            stringify!($name)
+                        ))
+                    })?;
+                }
+                Ok(flags)
+            }
+        }
+    };
+}
+
 #[cfg(feature = "access_point")]
 pub mod access_point;
 
@@ -38,18 +82,43 @@ pub mod channel;
 pub mod checkpoint;
 #[cfg(feature = "connection")]
 pub mod connection;
+pub mod connection_settings;
+#[cfg(feature = "connectivity")]
+pub mod connectivity;
+#[cfg(feature = "desired_state")]
+pub mod desired_state;
 #[cfg(feature = "device")]
 pub mod device;
+#[cfg(feature = "wireless")]
+pub mod device_wifi_capabilities;
 #[cfg(feature = "dhcp4config")]
 pub mod dhcp4config;
 #[cfg(feature = "dhcp6config")]
 pub mod dhcp6config;
 #[cfg(feature = "dummy")]
 pub mod dummy;
+#[cfg(feature = "failover")]
+pub mod failover;
 #[cfg(feature = "generic")]
 pub mod generic;
+#[cfg(feature = "hidden_network")]
+pub mod hidden_network;
+#[cfg(feature = "hotspot")]
+pub mod hotspot;
 #[cfg(feature = "hsr")]
 pub mod hsr;
+#[cfg(feature = "ifupdown")]
+pub mod ifupdown;
+#[cfg(any(
+    feature = "desired_state",
+    feature = "ifupdown",
+    feature = "netplan",
+    feature = "networkd",
+    feature = "openvpn",
+    feature = "wg_quick",
+    feature = "wpa_supplicant"
+))]
+pub mod import;
 #[cfg(feature = "infiniband")]
 pub mod infiniband;
 #[cfg(feature = "ip4config")]
@@ -66,12 +135,20 @@ pub mod lowpan;
 pub mod macsec;
 #[cfg(feature = "macvlan")]
 pub mod macvlan;
+#[cfg(feature = "metrics")]
+pub mod metrics;
 #[cfg(feature = "modem")]
 pub mod modem;
+#[cfg(feature = "netplan")]
+pub mod netplan;
+#[cfg(feature = "networkd")]
+pub mod networkd;
 #[cfg(feature = "nsp")]
 pub mod nsp;
 #[cfg(feature = "olpc_mesh")]
 pub mod olpc_mesh;
+#[cfg(feature = "openvpn")]
+pub mod openvpn;
 #[cfg(feature = "ovs_bridge")]
 pub mod ovs_bridge;
 #[cfg(feature = "ovs_interface")]
@@ -82,18 +159,34 @@ pub mod ovs_port;
 pub mod plugin;
 #[cfg(feature = "ppp")]
 pub mod ppp;
+#[cfg(any(feature = "cli", feature = "tui"))]
+pub mod property_text;
+#[cfg(feature = "reapply")]
+pub mod reapply;
+#[cfg(feature = "roaming")]
+pub mod roaming;
 #[cfg(feature = "secret_agent")]
 pub mod secret_agent;
 #[cfg(feature = "settings")]
 pub mod settings;
 #[cfg(feature = "settings")]
 pub mod settings_connection;
+#[cfg(feature = "snapshot")]
+pub mod snapshot;
+#[cfg(feature = "snapshot")]
+pub mod snapshot_diff;
 #[cfg(feature = "statistics")]
 pub mod statistics;
+#[cfg(feature = "survey")]
+pub mod survey;
 #[cfg(feature = "team")]
 pub mod team;
+#[cfg(feature = "traffic")]
+pub mod traffic;
 #[cfg(feature = "tun")]
 pub mod tun;
+#[cfg(feature = "settings")]
+pub mod update;
 #[cfg(feature = "veth")]
 pub mod veth;
 #[cfg(feature = "vlan")]
@@ -102,27 +195,118 @@ pub mod vlan;
 pub mod vrf;
 #[cfg(feature = "vxlan")]
 pub mod vxlan;
+#[cfg(feature = "wg_quick")]
+pub mod wg_quick;
 #[cfg(feature = "wi_max")]
 pub mod wi_max;
 #[cfg(feature = "wifi_p2p")]
 pub mod wifi_p2p;
 #[cfg(feature = "wifi_p2ppeer")]
 pub mod wifi_p2ppeer;
+#[cfg(feature = "wifi_uri")]
+pub mod wifi_uri;
 #[cfg(feature = "wire_guard")]
 pub mod wire_guard;
 #[cfg(feature = "wired")]
 pub mod wired;
 #[cfg(feature = "wireless")]
 pub mod wireless;
+#[cfg(feature = "wpa_supplicant")]
+pub mod wpa_supplicant;
 #[cfg(feature = "wpan")]
 pub mod wpan;
 
 #[allow(non_camel_case_types)]
 #[allow(unused)]
 pub mod dbus_interface_types {
+    use std::fmt;
+    use std::str::FromStr;
+
     use num_enum::TryFromPrimitive;
 
     include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
+
+    /// Error returned when parsing an unknown name into one of the generated enums.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct ParseEnumError {
+        /// Name of the enum that was parsed into, e.g. `NMDeviceState`.
+        pub enum_name: &'static str,
+        /// The name that did not match any variant.
+        pub name: String,
+    }
+
+    impl fmt::Display for ParseEnumError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "unknown {} `{}`", self.enum_name, self.name)
+        }
+    }
+
+    impl std::error::Error for ParseEnumError {}
+
+    /// Implements `as_str`, `Display`, `FromStr` and, with the `serde` feature, string based
+    /// `Serialize`/`Deserialize` for a generated enum. Invoked from the build script output
+    /// with the stable lower case name of every variant.
+    macro_rules! enum_names {
+        ($name:ident { $($variant:ident => $text:literal,)* }) => {
+            impl $name {
+                /// The stable lower case name of the variant, e.g. `"activated"`.
+                pub const fn as_str(&self) -> &'static str {
+                    match self {
+                        $(Self::$variant => $text,)*
+                    }
+                }
+            }
+
+            impl fmt::Display for $name {
+                fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                    f.write_str(self.as_str())
+                }
+            }
+
+            impl FromStr for $name {
+                type Err = ParseEnumError;
+
+                /// Parses the name returned by [`Self::as_str`], ignoring ASCII case.
+                fn from_str(s: &str) -> Result<Self, Self::Err> {
+                    match s.to_ascii_lowercase().as_str() {
+                        $($text => Ok(Self::$variant),)*
+                        _ => Err(ParseEnumError {
+                            enum_name: stringify!($name),
+                            name: s.to_owned(),
+                        }),
+                    }
+                }
+            }
+
+            #[cfg(feature = "serde")]
+            impl serde::Serialize for $name {
+                fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                    serializer.serialize_str(self.as_str())
+                }
+            }
+
+            #[cfg(feature = "serde")]
+            impl<'de> serde::Deserialize<'de> for $name {
+                fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                    let name = String::deserialize(deserializer)?;
+                    name.parse().map_err(serde::de::Error::custom)
+                }
+            }
+        };
+    }
+
+    include!(concat!(env!("OUT_DIR"), "/enum_names.rs"));
+
+    /// The stable name of a generated enum variant, or the number for unknown values.
+    #[cfg(any(feature = "metrics", feature = "snapshot"))]
+    pub(crate) fn enum_name<T>(value: u32) -> String
+    where
+        T: TryFrom<u32> + fmt::Display,
+    {
+        T::try_from(value)
+            .map(|value| value.to_string())
+            .unwrap_or_else(|_| value.to_string())
+    }
 }
 
 use zbus::proxy;
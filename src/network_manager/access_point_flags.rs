@@ -1,4 +1,7 @@
 #![allow(clippy::bad_bit_mask)]
+use std::fmt;
+use std::str::FromStr;
+
 use bitflags::bitflags;
 
 bitflags! {
@@ -11,3 +14,22 @@ bitflags! {
         const WPS_PIN = 0x00000008;
     }
 }
+
+/// Formats the set flags in the bitflags text format, e.g. `PRIVACY | WPS`, which
+/// [`FromStr`] parses back.
+impl fmt::Display for NM80211ApFlags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        bitflags::parser::to_writer(self, f)
+    }
+}
+
+impl FromStr for NM80211ApFlags {
+    type Err = bitflags::parser::ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        bitflags::parser::from_str(s)
+    }
+}
+
+#[cfg(feature = "serde")]
+serde_flag_names!(NM80211ApFlags);
@@ -1,4 +1,7 @@
 #![allow(clippy::bad_bit_mask)]
+use std::fmt;
+use std::str::FromStr;
+
 use bitflags::bitflags;
 
 bitflags! {
@@ -21,3 +24,22 @@ bitflags! {
         const KEY_MGMT_EAP_SUITE_B_192 = 0x00002000;
     }
 }
+
+/// Formats the offered ciphers and key management suites in the bitflags text format, e.g.
+/// `PAIR_CCMP | GROUP_CCMP | KEY_MGMT_PSK`.
+impl fmt::Display for NM80211ApSecurityFlags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        bitflags::parser::to_writer(self, f)
+    }
+}
+
+impl FromStr for NM80211ApSecurityFlags {
+    type Err = bitflags::parser::ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        bitflags::parser::from_str(s)
+    }
+}
+
+#[cfg(feature = "serde")]
+serde_flag_names!(NM80211ApSecurityFlags);
@@ -11,8 +11,14 @@
 //!
 //! [Writing a client proxy]: https://dbus2.github.io/zbus/client.html
 //! [D-Bus standard interfaces]: https://dbus.freedesktop.org/doc/dbus-specification.html#standard-interfaces,
+use std::time::Instant;
+
+use async_io::Timer;
+use futures_lite::{StreamExt, future};
 use zbus::{Connection, Result, proxy};
 
+use super::dbus_interface_types::NMActiveConnectionState;
+
 impl ActiveProxy<'_> {
     pub async fn new_from_path(
         device_path: zbus::zvariant::OwnedObjectPath,
@@ -23,6 +29,46 @@ impl ActiveProxy<'_> {
             .build()
             .await
     }
+
+    /// Waits until the activation has finished, or until `deadline`.
+    pub async fn wait_activated(&self, deadline: Instant) -> Result<Activation> {
+        let mut changes = self.receive_state_changed().await;
+
+        let activated = async {
+            let mut state = self.state().await?;
+            loop {
+                match NMActiveConnectionState::try_from(state) {
+                    Ok(NMActiveConnectionState::ACTIVATED) => return Ok(Activation::Activated),
+                    Ok(
+                        NMActiveConnectionState::DEACTIVATING
+                        | NMActiveConnectionState::DEACTIVATED,
+                    ) => {
+                        return Ok(Activation::Failed);
+                    }
+                    _ => {}
+                }
+                match changes.next().await {
+                    Some(change) => state = change.get().await?,
+                    None => return Ok(Activation::Failed),
+                }
+            }
+        };
+        let timed_out = async {
+            Timer::at(deadline).await;
+            Ok(Activation::TimedOut)
+        };
+
+        future::or(activated, timed_out).await
+    }
+}
+
+/// How [`ActiveProxy::wait_activated`] ended.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Activation {
+    Activated,
+    /// The connection was deactivated, or disappeared, before it was activated.
+    Failed,
+    TimedOut,
 }
 
 #[proxy(
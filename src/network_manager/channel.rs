@@ -1,3 +1,4 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Channel {
     pub channel: u32,
     pub frequency: u32,
@@ -257,4 +258,51 @@ impl Channel {
             },
         ]
     }
+
+    /// Looks up the channel for a frequency in MHz.
+    pub fn from_frequency(frequency: u32) -> Option<Channel> {
+        Band::from_frequency(frequency)?
+            .channels()
+            .into_iter()
+            .find(|channel| channel.frequency == frequency)
+    }
+}
+
+/// An 802.11 frequency band, as used by the `802-11-wireless.band` property.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Band {
+    /// 5GHz
+    A,
+    /// 2.4GHz
+    Bg,
+}
+
+impl Band {
+    pub fn from_frequency(frequency: u32) -> Option<Band> {
+        match frequency {
+            2400..=2500 => Some(Band::Bg),
+            4900..=5900 => Some(Band::A),
+            _ => None,
+        }
+    }
+
+    /// The known channels of the band, without the trailing zero entry.
+    pub fn channels(&self) -> Vec<Channel> {
+        let channels: Vec<Channel> = match self {
+            Band::A => Channel::a_frequencies().into(),
+            Band::Bg => Channel::gb_frequencies().into(),
+        };
+        channels
+            .into_iter()
+            .filter(|channel| channel.channel != 0)
+            .collect()
+    }
+
+    /// The value NetworkManager uses for this band in `802-11-wireless.band`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Band::A => "a",
+            Band::Bg => "bg",
+        }
+    }
 }
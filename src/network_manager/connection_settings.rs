@@ -0,0 +1,710 @@
+//! Owned connection settings as exchanged with `org.freedesktop.NetworkManager.Settings`.
+//!
+//! NetworkManager describes a connection profile as a map of setting names (`connection`,
+//! `802-11-wireless`, `ipv4`, ...) to maps of property names and values. The proxies return
+//! this as `a{sa{sv}}` and accept it back as borrowed maps; [`ConnectionSettings`] owns the
+//! data so it can be built, inspected and edited before being handed to a proxy.
+//!
+//! Settings read with `GetSecrets` hold credentials. The `Debug` output of
+//! [`ConnectionSettings`] masks them, as does [`ConnectionSettings::redacted`], which also
+//! formats the settings for logs with `Display`.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt::{self, Display};
+
+use zbus::zvariant::{OwnedValue, Value};
+
+/// What secret values are replaced with by [`ConnectionSettings::redact_secrets`].
+pub const REDACTED: &str = "<redacted>";
+
+/// Settings that can hold secrets, which `GetSettings` leaves out.
+pub const SECRET_SETTINGS: &[&str] = &[
+    "802-11-wireless-security",
+    "802-1x",
+    "adsl",
+    "cdma",
+    "gsm",
+    "macsec",
+    "pppoe",
+    "vpn",
+    "wireguard",
+];
+
+/// Properties holding secrets, with the property holding their `NMSettingSecretFlags`.
+/// `vpn.secrets` is a dictionary of secrets and the peers in `wireguard.peers` carry a
+/// `preshared-key` each, these are handled separately.
+const SECRET_PROPERTIES: &[(&str, &str, &str)] = &[
+    (
+        "802-11-wireless-security",
+        "leap-password",
+        "leap-password-flags",
+    ),
+    ("802-11-wireless-security", "psk", "psk-flags"),
+    ("802-11-wireless-security", "wep-key0", "wep-key-flags"),
+    ("802-11-wireless-security", "wep-key1", "wep-key-flags"),
+    ("802-11-wireless-security", "wep-key2", "wep-key-flags"),
+    ("802-11-wireless-security", "wep-key3", "wep-key-flags"),
+    ("802-1x", "ca-cert-password", "ca-cert-password-flags"),
+    (
+        "802-1x",
+        "client-cert-password",
+        "client-cert-password-flags",
+    ),
+    ("802-1x", "password", "password-flags"),
+    ("802-1x", "password-raw", "password-raw-flags"),
+    (
+        "802-1x",
+        "phase2-ca-cert-password",
+        "phase2-ca-cert-password-flags",
+    ),
+    (
+        "802-1x",
+        "phase2-client-cert-password",
+        "phase2-client-cert-password-flags",
+    ),
+    (
+        "802-1x",
+        "phase2-private-key-password",
+        "phase2-private-key-password-flags",
+    ),
+    ("802-1x", "pin", "pin-flags"),
+    (
+        "802-1x",
+        "private-key-password",
+        "private-key-password-flags",
+    ),
+    ("adsl", "password", "password-flags"),
+    ("cdma", "password", "password-flags"),
+    ("gsm", "password", "password-flags"),
+    ("gsm", "pin", "pin-flags"),
+    ("macsec", "mka-cak", "mka-cak-flags"),
+    ("pppoe", "password", "password-flags"),
+    ("wireguard", "private-key", "private-key-flags"),
+];
+
+/// Whether `setting.property` holds secrets. `vpn.secrets` and `wireguard.peers` count, as
+/// they carry secrets among their entries.
+pub fn is_secret_property(setting: &str, property: &str) -> bool {
+    matches!(
+        (setting, property),
+        ("vpn", "secrets") | ("wireguard", "peers")
+    ) || SECRET_PROPERTIES
+        .iter()
+        .any(|(name, secret, _)| *name == setting && *secret == property)
+}
+
+/// The property of the same setting holding the `NMSettingSecretFlags` of a secret, e.g.
+/// `psk-flags` for `802-11-wireless-security.psk`, which says whether NetworkManager or an
+/// agent keeps the secret.
+///
+/// `None` for properties that aren't secrets, and for `vpn.secrets` and `wireguard.peers`,
+/// whose entries are flagged one by one: the flags of a `vpn.secrets` entry are the `vpn.data`
+/// entry named after it with `-flags` appended, and those of the preshared key of a WireGuard
+/// peer are the peer's `preshared-key-flags`.
+pub fn secret_flags_property(setting: &str, property: &str) -> Option<&'static str> {
+    SECRET_PROPERTIES
+        .iter()
+        .find(|(name, secret, _)| *name == setting && *secret == property)
+        .map(|(_, _, flags)| *flags)
+}
+
+/// A property that differs between two [`ConnectionSettings`], see
+/// [`ConnectionSettings::diff`]. `before` is `None` for added properties and `after` for
+/// removed ones.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PropertyChange {
+    pub setting: String,
+    pub property: String,
+    pub before: Option<Value<'static>>,
+    pub after: Option<Value<'static>>,
+}
+
+/// An owned `a{sa{sv}}` connection settings map.
+///
+/// `Debug` masks secrets, see [`redacted`](Self::redacted).
+#[derive(Clone, Default, PartialEq)]
+pub struct ConnectionSettings {
+    settings: HashMap<String, HashMap<String, Value<'static>>>,
+}
+
+impl ConnectionSettings {
+    pub fn new() -> ConnectionSettings {
+        ConnectionSettings::default()
+    }
+
+    /// Sets `setting.property`, creating the setting if needed.
+    pub fn set(&mut self, setting: &str, property: &str, value: impl Into<Value<'static>>) {
+        self.settings
+            .entry(setting.to_owned())
+            .or_default()
+            .insert(property.to_owned(), value.into());
+    }
+
+    /// Builder style variant of [`ConnectionSettings::set`].
+    pub fn with(mut self, setting: &str, property: &str, value: impl Into<Value<'static>>) -> Self {
+        self.set(setting, property, value);
+        self
+    }
+
+    /// Removes `setting.property`, dropping the setting once it is empty.
+    pub fn remove(&mut self, setting: &str, property: &str) -> Option<Value<'static>> {
+        let properties = self.settings.get_mut(setting)?;
+        let value = properties.remove(property);
+        if properties.is_empty() {
+            self.settings.remove(setting);
+        }
+        value
+    }
+
+    /// Ensures `setting` is present even when it has no properties.
+    pub fn add_setting(&mut self, setting: &str) {
+        self.settings.entry(setting.to_owned()).or_default();
+    }
+
+    pub fn remove_setting(&mut self, setting: &str) -> Option<HashMap<String, Value<'static>>> {
+        self.settings.remove(setting)
+    }
+
+    pub fn contains_setting(&self, setting: &str) -> bool {
+        self.settings.contains_key(setting)
+    }
+
+    pub fn setting(&self, setting: &str) -> Option<&HashMap<String, Value<'static>>> {
+        self.settings.get(setting)
+    }
+
+    pub fn get(&self, setting: &str, property: &str) -> Option<&Value<'static>> {
+        self.settings.get(setting)?.get(property)
+    }
+
+    pub fn get_str(&self, setting: &str, property: &str) -> Option<&str> {
+        self.get(setting, property)?.downcast_ref().ok()
+    }
+
+    pub fn get_u32(&self, setting: &str, property: &str) -> Option<u32> {
+        self.get(setting, property)?.downcast_ref().ok()
+    }
+
+    pub fn get_i32(&self, setting: &str, property: &str) -> Option<i32> {
+        self.get(setting, property)?.downcast_ref().ok()
+    }
+
+    pub fn get_i64(&self, setting: &str, property: &str) -> Option<i64> {
+        self.get(setting, property)?.downcast_ref().ok()
+    }
+
+    pub fn get_u64(&self, setting: &str, property: &str) -> Option<u64> {
+        self.get(setting, property)?.downcast_ref().ok()
+    }
+
+    pub fn get_bool(&self, setting: &str, property: &str) -> Option<bool> {
+        self.get(setting, property)?.downcast_ref().ok()
+    }
+
+    /// Returns a byte array property such as `802-11-wireless.ssid`.
+    pub fn get_bytes(&self, setting: &str, property: &str) -> Option<Vec<u8>> {
+        self.get(setting, property)?
+            .try_clone()
+            .ok()?
+            .downcast()
+            .ok()
+    }
+
+    /// Returns a string array property such as `ipv4.dns-search`.
+    pub fn get_strings(&self, setting: &str, property: &str) -> Option<Vec<String>> {
+        self.get(setting, property)?
+            .try_clone()
+            .ok()?
+            .downcast()
+            .ok()
+    }
+
+    /// Returns a dictionary array property such as `ipv4.address-data`.
+    pub fn get_dicts(
+        &self,
+        setting: &str,
+        property: &str,
+    ) -> Option<Vec<HashMap<String, OwnedValue>>> {
+        self.get(setting, property)?
+            .try_clone()
+            .ok()?
+            .downcast()
+            .ok()
+    }
+
+    /// Returns a string dictionary property such as `bond.options`.
+    pub fn get_string_map(&self, setting: &str, property: &str) -> Option<HashMap<String, String>> {
+        self.get(setting, property)?
+            .try_clone()
+            .ok()?
+            .downcast()
+            .ok()
+    }
+
+    /// Iterates over the setting names.
+    pub fn settings(&self) -> impl Iterator<Item = &str> {
+        self.settings.keys().map(String::as_str)
+    }
+
+    /// Iterates over every `(setting, property, value)` triple.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str, &Value<'static>)> {
+        self.settings.iter().flat_map(|(setting, properties)| {
+            properties
+                .iter()
+                .map(move |(property, value)| (setting.as_str(), property.as_str(), value))
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.settings.is_empty()
+    }
+
+    /// The properties that differ in `later`, ordered by setting and property name.
+    pub fn diff(&self, later: &ConnectionSettings) -> Vec<PropertyChange> {
+        let properties: BTreeSet<(&str, &str)> = self
+            .iter()
+            .chain(later.iter())
+            .map(|(setting, property, _)| (setting, property))
+            .collect();
+        properties
+            .into_iter()
+            .filter_map(|(setting, property)| {
+                let before = self.get(setting, property);
+                let after = later.get(setting, property);
+                (before != after).then(|| PropertyChange {
+                    setting: setting.to_owned(),
+                    property: property.to_owned(),
+                    before: before.cloned(),
+                    after: after.cloned(),
+                })
+            })
+            .collect()
+    }
+
+    /// Copies every property of `other` into `self`, replacing existing values.
+    pub fn merge(&mut self, other: ConnectionSettings) {
+        for (setting, properties) in other.settings {
+            self.settings.entry(setting).or_default().extend(properties);
+        }
+    }
+
+    /// Merges the result of
+    /// [`SettingsConnectionProxy::get_secrets`](crate::SettingsConnectionProxy::get_secrets)
+    /// into the settings.
+    ///
+    /// Works like [`merge`](Self::merge), except for `wireguard.peers`: the secrets only list
+    /// the public and preshared key of each peer, so they are merged into the matching peer.
+    pub fn merge_secrets(&mut self, secrets: ConnectionSettings) {
+        for (setting, properties) in secrets.settings {
+            for (property, value) in properties {
+                if setting == "wireguard" && property == "peers" {
+                    self.merge_peer_secrets(&value, true);
+                } else {
+                    self.set(&setting, &property, value);
+                }
+            }
+        }
+    }
+
+    /// Like [`merge_secrets`](Self::merge_secrets), but only adds the secrets that are
+    /// missing: secrets already set are kept, down to single entries of `vpn.secrets` and the
+    /// preshared keys of `wireguard.peers`. Settings missing from `self` are skipped.
+    pub fn fill_secrets(&mut self, secrets: ConnectionSettings) {
+        for (setting, properties) in secrets.settings {
+            if !self.contains_setting(&setting) {
+                continue;
+            }
+            for (property, value) in properties {
+                match (setting.as_str(), property.as_str()) {
+                    ("vpn", "secrets") => {
+                        let Ok(mut filled) = HashMap::<String, String>::try_from(value) else {
+                            continue;
+                        };
+                        filled.extend(self.get_string_map("vpn", "secrets").unwrap_or_default());
+                        self.set("vpn", "secrets", filled);
+                    }
+                    ("wireguard", "peers") => self.merge_peer_secrets(&value, false),
+                    _ if self.get(&setting, &property).is_none() => {
+                        self.set(&setting, &property, value);
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    /// Formats the settings with their secrets masked, see [`Redacted`].
+    pub fn redacted(&self) -> Redacted<'_> {
+        Redacted {
+            settings: self,
+            show_secrets: false,
+        }
+    }
+
+    /// Replaces every secret with `"<redacted>"`, keeping the names of `vpn.secrets`.
+    pub fn redact_secrets(&mut self) {
+        for (setting, property, _) in SECRET_PROPERTIES {
+            if self.get(setting, property).is_some() {
+                self.set(setting, property, REDACTED);
+            }
+        }
+        if let Some(secrets) = self.get_string_map("vpn", "secrets") {
+            let secrets: HashMap<String, String> = secrets
+                .into_keys()
+                .map(|key| (key, REDACTED.to_owned()))
+                .collect();
+            self.set("vpn", "secrets", secrets);
+        }
+        if let Some(peers) = self.get_dicts("wireguard", "peers") {
+            let peers: Vec<HashMap<String, Value<'static>>> = peers
+                .into_iter()
+                .map(|peer| {
+                    peer.into_iter()
+                        .map(|(key, value)| {
+                            let value = match key.as_str() {
+                                "preshared-key" => Value::from(REDACTED),
+                                _ => Value::from(value),
+                            };
+                            (key, value)
+                        })
+                        .collect()
+                })
+                .collect();
+            self.set("wireguard", "peers", peers);
+        }
+    }
+
+    /// Copies the secrets of `secret_peers` into the peer with the same public key, replacing
+    /// the secrets a peer already has only with `overwrite`.
+    fn merge_peer_secrets(&mut self, secret_peers: &Value<'_>, overwrite: bool) {
+        let Some(peers) = self.get_dicts("wireguard", "peers") else {
+            return;
+        };
+        let secret_peers: Vec<HashMap<String, OwnedValue>> = secret_peers
+            .try_clone()
+            .ok()
+            .and_then(|value| value.downcast().ok())
+            .unwrap_or_default();
+
+        let peers: Vec<HashMap<String, Value<'static>>> = peers
+            .into_iter()
+            .map(|peer| {
+                let secrets = secret_peers
+                    .iter()
+                    .find(|secrets| secrets.get("public-key") == peer.get("public-key"));
+                let mut peer: HashMap<String, Value<'static>> = peer
+                    .into_iter()
+                    .map(|(key, value)| (key, Value::from(value)))
+                    .collect();
+                for (key, value) in secrets.into_iter().flatten() {
+                    if !overwrite && peer.contains_key(key) {
+                        continue;
+                    }
+                    if let Ok(value) = value.try_clone() {
+                        peer.insert(key.clone(), Value::from(value));
+                    }
+                }
+                peer
+            })
+            .collect();
+        self.set("wireguard", "peers", peers);
+    }
+
+    /// `connection.id`
+    pub fn id(&self) -> Option<&str> {
+        self.get_str("connection", "id")
+    }
+
+    /// `connection.uuid`
+    pub fn uuid(&self) -> Option<&str> {
+        self.get_str("connection", "uuid")
+    }
+
+    /// `connection.type`
+    pub fn connection_type(&self) -> Option<&str> {
+        self.get_str("connection", "type")
+    }
+
+    /// Borrows the settings in the form expected by the proxy methods, e.g.
+    /// [`SettingsProxy::add_connection`](crate::SettingsProxy::add_connection).
+    pub fn to_dbus(&self) -> HashMap<&str, HashMap<&str, Value<'_>>> {
+        self.settings
+            .iter()
+            .map(|(setting, properties)| {
+                (
+                    setting.as_str(),
+                    properties
+                        .iter()
+                        .map(|(property, value)| (property.as_str(), value.clone()))
+                        .collect(),
+                )
+            })
+            .collect()
+    }
+}
+
+impl fmt::Debug for ConnectionSettings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("ConnectionSettings")
+            .field(&self.redacted())
+            .finish()
+    }
+}
+
+/// Formats [`ConnectionSettings`] for logs, with secrets replaced by `"<redacted>"` unless
+/// [`show_secrets`](Self::show_secrets) is used.
+///
+/// `Display` writes one `setting.property: value` line per property and `Debug` a map of maps,
+/// both ordered by name.
+#[derive(Clone, Copy)]
+pub struct Redacted<'a> {
+    settings: &'a ConnectionSettings,
+    show_secrets: bool,
+}
+
+impl Redacted<'_> {
+    /// Leaves the secrets unmasked, e.g. for an export the user asked to include them in.
+    pub fn show_secrets(mut self) -> Self {
+        self.show_secrets = true;
+        self
+    }
+
+    fn with_properties<T>(
+        &self,
+        f: impl FnOnce(BTreeMap<&str, BTreeMap<&str, &Value<'static>>>) -> T,
+    ) -> T {
+        let masked;
+        let settings = match self.show_secrets {
+            true => self.settings,
+            false => {
+                let mut settings = self.settings.clone();
+                settings.redact_secrets();
+                masked = settings;
+                &masked
+            }
+        };
+        let mut properties: BTreeMap<&str, BTreeMap<&str, &Value<'static>>> = BTreeMap::new();
+        for (setting, property, value) in settings.iter() {
+            properties
+                .entry(setting)
+                .or_default()
+                .insert(property, value);
+        }
+        f(properties)
+    }
+}
+
+impl Display for Redacted<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.with_properties(|settings| {
+            for (setting, properties) in settings {
+                for (property, value) in properties {
+                    writeln!(f, "{setting}.{property}: {value}")?;
+                }
+            }
+            Ok(())
+        })
+    }
+}
+
+impl fmt::Debug for Redacted<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.with_properties(|settings| f.debug_map().entries(settings).finish())
+    }
+}
+
+impl From<HashMap<String, HashMap<String, OwnedValue>>> for ConnectionSettings {
+    fn from(settings: HashMap<String, HashMap<String, OwnedValue>>) -> Self {
+        ConnectionSettings {
+            settings: settings
+                .into_iter()
+                .map(|(setting, properties)| {
+                    (
+                        setting,
+                        properties
+                            .into_iter()
+                            .map(|(property, value)| (property, Value::from(value)))
+                            .collect(),
+                    )
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<ConnectionSettings> for HashMap<String, HashMap<String, Value<'static>>> {
+    fn from(settings: ConnectionSettings) -> Self {
+        settings.settings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECURITY: &str = "802-11-wireless-security";
+
+    fn peer(public_key: &str, preshared_key: Option<&str>) -> HashMap<String, Value<'static>> {
+        let mut peer =
+            HashMap::from([("public-key".to_owned(), Value::from(public_key.to_owned()))]);
+        if let Some(preshared_key) = preshared_key {
+            peer.insert(
+                "preshared-key".to_owned(),
+                Value::from(preshared_key.to_owned()),
+            );
+        }
+        peer
+    }
+
+    /// The preshared key of every peer, by public key.
+    fn preshared_keys(settings: &ConnectionSettings) -> Vec<(String, Option<String>)> {
+        settings
+            .get_dicts("wireguard", "peers")
+            .unwrap()
+            .iter()
+            .map(|peer| {
+                let key = |name: &str| peer.get(name).and_then(|value| value.downcast_ref().ok());
+                (key("public-key").unwrap(), key("preshared-key"))
+            })
+            .collect()
+    }
+
+    fn wireguard(peers: Vec<HashMap<String, Value<'static>>>) -> ConnectionSettings {
+        ConnectionSettings::new().with("wireguard", "peers", peers)
+    }
+
+    #[test]
+    fn merge_secrets_overwrites() {
+        let mut settings = wireguard(vec![peer("A", None), peer("B", Some("old"))])
+            .with(SECURITY, "key-mgmt", "wpa-psk")
+            .with(SECURITY, "psk", "old");
+        let secrets = wireguard(vec![peer("B", Some("b")), peer("A", Some("a"))])
+            .with(SECURITY, "psk", "new");
+
+        settings.merge_secrets(secrets);
+        assert_eq!(settings.get_str(SECURITY, "psk"), Some("new"));
+        assert_eq!(settings.get_str(SECURITY, "key-mgmt"), Some("wpa-psk"));
+        assert_eq!(
+            preshared_keys(&settings),
+            [
+                ("A".to_owned(), Some("a".to_owned())),
+                ("B".to_owned(), Some("b".to_owned())),
+            ]
+        );
+    }
+
+    #[test]
+    fn fill_secrets_keeps_existing_ones() {
+        let mut settings = wireguard(vec![peer("A", None), peer("B", Some("old"))])
+            .with(SECURITY, "psk", "old")
+            .with(
+                "vpn",
+                "secrets",
+                HashMap::from([("password".to_owned(), "old".to_owned())]),
+            );
+        let secrets = wireguard(vec![peer("A", Some("a")), peer("B", Some("b"))])
+            .with(SECURITY, "psk", "new")
+            .with(SECURITY, "wep-key0", "wep")
+            .with(
+                "vpn",
+                "secrets",
+                HashMap::from([
+                    ("password".to_owned(), "new".to_owned()),
+                    ("otp".to_owned(), "123456".to_owned()),
+                ]),
+            )
+            .with("802-1x", "password", "eap");
+
+        settings.fill_secrets(secrets);
+        assert_eq!(settings.get_str(SECURITY, "psk"), Some("old"));
+        assert_eq!(settings.get_str(SECURITY, "wep-key0"), Some("wep"));
+        assert_eq!(
+            settings.get_string_map("vpn", "secrets"),
+            Some(HashMap::from([
+                ("password".to_owned(), "old".to_owned()),
+                ("otp".to_owned(), "123456".to_owned()),
+            ]))
+        );
+        assert_eq!(
+            preshared_keys(&settings),
+            [
+                ("A".to_owned(), Some("a".to_owned())),
+                ("B".to_owned(), Some("old".to_owned())),
+            ]
+        );
+        assert!(!settings.contains_setting("802-1x"));
+    }
+
+    #[test]
+    fn redacts_secrets_but_not_their_flags() {
+        let mut settings = wireguard(vec![peer("A", Some("a")), peer("B", None)])
+            .with(SECURITY, "psk", "hunter2")
+            .with(SECURITY, "psk-flags", 1u32)
+            .with("wireguard", "private-key", "private")
+            .with(
+                "vpn",
+                "secrets",
+                HashMap::from([("password".to_owned(), "hunter2".to_owned())]),
+            );
+
+        settings.redact_secrets();
+        assert_eq!(settings.get_str(SECURITY, "psk"), Some(REDACTED));
+        assert_eq!(settings.get_u32(SECURITY, "psk-flags"), Some(1));
+        assert_eq!(settings.get_str(SECURITY, "wep-key0"), None);
+        assert_eq!(settings.get_str("wireguard", "private-key"), Some(REDACTED));
+        assert_eq!(
+            settings.get_string_map("vpn", "secrets"),
+            Some(HashMap::from([(
+                "password".to_owned(),
+                REDACTED.to_owned()
+            )]))
+        );
+        assert_eq!(
+            preshared_keys(&settings),
+            [
+                ("A".to_owned(), Some(REDACTED.to_owned())),
+                ("B".to_owned(), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn formatting_masks_secrets() {
+        let settings = ConnectionSettings::new()
+            .with("connection", "id", "home")
+            .with(SECURITY, "psk", "hunter2")
+            .with(SECURITY, "psk-flags", 0u32);
+
+        let redacted = settings.redacted().to_string();
+        assert_eq!(
+            redacted,
+            "802-11-wireless-security.psk: \"<redacted>\"\n\
+             802-11-wireless-security.psk-flags: uint32 0\n\
+             connection.id: \"home\"\n"
+        );
+        assert!(!format!("{settings:?}").contains("hunter2"));
+        assert!(!format!("{:?}", settings.redacted()).contains("hunter2"));
+        assert!(format!("{settings:?}").contains(REDACTED));
+
+        let shown = settings.redacted().show_secrets();
+        assert!(shown.to_string().contains("hunter2"));
+        assert!(format!("{shown:?}").contains("hunter2"));
+    }
+
+    #[test]
+    fn finds_secret_flags_properties() {
+        assert_eq!(secret_flags_property(SECURITY, "psk"), Some("psk-flags"));
+        assert_eq!(
+            secret_flags_property(SECURITY, "wep-key2"),
+            Some("wep-key-flags")
+        );
+        assert_eq!(secret_flags_property(SECURITY, "psk-flags"), None);
+        assert!(!is_secret_property(SECURITY, "psk-flags"));
+
+        // Flagged entry by entry
+        for (setting, property) in [("vpn", "secrets"), ("wireguard", "peers")] {
+            assert!(is_secret_property(setting, property));
+            assert_eq!(secret_flags_property(setting, property), None);
+        }
+    }
+}
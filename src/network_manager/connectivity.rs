@@ -0,0 +1,341 @@
+//! Connectivity monitor with captive portal detection.
+//!
+//! Follows the global `Connectivity` of NetworkManager and the `Ip4Connectivity` and
+//! `Ip6Connectivity` of every device and turns their changes into [`ConnectivityChange`]s.
+//! When a state becomes [`NMConnectivityState::PORTAL`] the change carries the URL to open
+//! for logging in.
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt::{self, Display};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use futures_lite::stream::Race;
+use futures_lite::{Stream, StreamExt, stream};
+use zbus::fdo::PropertiesChanged;
+use zbus::message::Type;
+use zbus::zvariant::{OwnedObjectPath, Value};
+use zbus::{Connection, MatchRule, MessageStream, Result};
+
+use super::NetworkManagerProxy;
+use super::dbus_interface_types::NMConnectivityState;
+use super::device::DeviceProxy;
+
+const NETWORK_MANAGER_INTERFACE: &str = "org.freedesktop.NetworkManager";
+const DEVICE_INTERFACE: &str = "org.freedesktop.NetworkManager.Device";
+
+/// Shortest time between two checks started by [`ConnectivityCheck::check`] by default.
+const DEFAULT_DEBOUNCE: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AddressFamily {
+    Ipv4,
+    Ipv6,
+}
+
+impl AddressFamily {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AddressFamily::Ipv4 => "ipv4",
+            AddressFamily::Ipv6 => "ipv6",
+        }
+    }
+}
+
+impl Display for AddressFamily {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// What a connectivity state applies to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConnectivityScope {
+    /// The overall connectivity NetworkManager reports, the best of all devices.
+    Global,
+    /// One address family of a device.
+    Device {
+        device: OwnedObjectPath,
+        interface: String,
+        family: AddressFamily,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConnectivityChange {
+    pub scope: ConnectivityScope,
+    /// `None` when the previous state isn't known, e.g. for a device that appeared after the
+    /// monitor was created.
+    pub from: Option<NMConnectivityState>,
+    pub to: NMConnectivityState,
+    /// The URL to open for logging in to the captive portal, when `to` is
+    /// [`NMConnectivityState::PORTAL`]. This is NetworkManager's connectivity check URI, which
+    /// the portal intercepts and redirects to its login page.
+    pub portal_url: Option<String>,
+}
+
+/// Forces a connectivity re-check, see [`ConnectivityMonitor::checker`].
+///
+/// Clones share the time of the last check, so a kiosk can hand one to every view that wants
+/// to re-check without them piling up.
+#[derive(Clone)]
+pub struct ConnectivityCheck {
+    nm: NetworkManagerProxy<'static>,
+    debounce: Duration,
+    last_check: Arc<Mutex<Option<Instant>>>,
+}
+
+impl ConnectivityCheck {
+    /// Has NetworkManager check the connectivity now and returns the global result.
+    ///
+    /// A check within the debounce time of the previous one returns the current state
+    /// instead. Changes found by the check are also reported by the monitor.
+    pub async fn check(&self) -> Result<NMConnectivityState> {
+        let debounced = {
+            let mut last_check = self
+                .last_check
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            let now = Instant::now();
+            match *last_check {
+                Some(last) if now.duration_since(last) < self.debounce => true,
+                _ => {
+                    *last_check = Some(now);
+                    false
+                }
+            }
+        };
+
+        let state = match debounced {
+            true => self.nm.connectivity().await?,
+            false => self.nm.check_connectivity().await?,
+        };
+        Ok(connectivity_state(state))
+    }
+}
+
+struct WatchedDevice {
+    interface: String,
+    ip4: Option<NMConnectivityState>,
+    ip6: Option<NMConnectivityState>,
+}
+
+/// Monitors the global and per-device connectivity.
+///
+/// The states at creation aren't reported, see [`global`](Self::global) and
+/// [`device`](Self::device). Devices that appear later are picked up with their first change.
+pub struct ConnectivityMonitor {
+    connection: Connection,
+    check: ConnectivityCheck,
+    global: NMConnectivityState,
+    devices: HashMap<OwnedObjectPath, WatchedDevice>,
+    changes: Race<MessageStream, MessageStream>,
+    /// Changes that came with the same signal as the one reported last, without portal URL.
+    pending: VecDeque<ConnectivityChange>,
+}
+
+impl ConnectivityMonitor {
+    pub async fn new(connection: &Connection) -> Result<ConnectivityMonitor> {
+        let global_changes = properties_changed(connection, NETWORK_MANAGER_INTERFACE).await?;
+        let device_changes = properties_changed(connection, DEVICE_INTERFACE).await?;
+
+        let nm: NetworkManagerProxy<'static> = NetworkManagerProxy::new(connection).await?;
+        let global = connectivity_state(nm.connectivity().await?);
+        let mut devices = HashMap::new();
+        for path in nm.get_devices().await? {
+            let device = DeviceProxy::new_from_path(path.clone(), connection).await?;
+            devices.insert(
+                path,
+                WatchedDevice {
+                    interface: device.interface().await?,
+                    ip4: Some(connectivity_state(device.ip4_connectivity().await?)),
+                    ip6: Some(connectivity_state(device.ip6_connectivity().await?)),
+                },
+            );
+        }
+
+        Ok(ConnectivityMonitor {
+            connection: connection.clone(),
+            check: ConnectivityCheck {
+                nm,
+                debounce: DEFAULT_DEBOUNCE,
+                last_check: Arc::new(Mutex::new(None)),
+            },
+            global,
+            devices,
+            changes: global_changes.race(device_changes),
+            pending: VecDeque::new(),
+        })
+    }
+
+    /// Ignores forced re-checks within `debounce` of the previous one, 5 seconds by default.
+    pub fn with_debounce(mut self, debounce: Duration) -> Self {
+        self.check.debounce = debounce;
+        self
+    }
+
+    /// The last known global connectivity.
+    pub fn global(&self) -> NMConnectivityState {
+        self.global
+    }
+
+    /// The last known connectivity of a device for an address family.
+    pub fn device(
+        &self,
+        device: &OwnedObjectPath,
+        family: AddressFamily,
+    ) -> Option<NMConnectivityState> {
+        let watched = self.devices.get(device)?;
+        match family {
+            AddressFamily::Ipv4 => watched.ip4,
+            AddressFamily::Ipv6 => watched.ip6,
+        }
+    }
+
+    /// The captive portal URL, when the global connectivity is [`NMConnectivityState::PORTAL`].
+    pub async fn portal_url(&self) -> Result<Option<String>> {
+        match self.global {
+            NMConnectivityState::PORTAL => portal_url(&self.check.nm).await,
+            _ => Ok(None),
+        }
+    }
+
+    /// A handle for forcing re-checks that keeps working after
+    /// [`into_stream`](Self::into_stream).
+    pub fn checker(&self) -> ConnectivityCheck {
+        self.check.clone()
+    }
+
+    /// Has NetworkManager check the connectivity now, see [`ConnectivityCheck::check`].
+    pub async fn check(&self) -> Result<NMConnectivityState> {
+        self.check.check().await
+    }
+
+    /// Waits for the next change.
+    pub async fn next(&mut self) -> Option<Result<ConnectivityChange>> {
+        loop {
+            if let Some(mut change) = self.pending.pop_front() {
+                if change.to == NMConnectivityState::PORTAL {
+                    change.portal_url = match portal_url(&self.check.nm).await {
+                        Ok(url) => url,
+                        Err(err) => return Some(Err(err)),
+                    };
+                }
+                return Some(Ok(change));
+            }
+
+            let message = match self.changes.next().await? {
+                Ok(message) => message,
+                Err(err) => return Some(Err(err)),
+            };
+            let Some(path) = message.header().path().cloned().map(OwnedObjectPath::from) else {
+                continue;
+            };
+            let Some(signal) = PropertiesChanged::from_message(message) else {
+                continue;
+            };
+            let args = match signal.args() {
+                Ok(args) => args,
+                Err(err) => return Some(Err(err)),
+            };
+            let state = |name: &str| match args.changed_properties.get(name) {
+                Some(Value::U32(state)) => Some(connectivity_state(*state)),
+                _ => None,
+            };
+
+            if args.interface_name.as_str() == NETWORK_MANAGER_INTERFACE {
+                if let Some(to) = state("Connectivity")
+                    && to != self.global
+                {
+                    let from = std::mem::replace(&mut self.global, to);
+                    self.pending.push_back(ConnectivityChange {
+                        scope: ConnectivityScope::Global,
+                        from: Some(from),
+                        to,
+                        portal_url: None,
+                    });
+                }
+            } else {
+                let updates = [
+                    (AddressFamily::Ipv4, state("Ip4Connectivity")),
+                    (AddressFamily::Ipv6, state("Ip6Connectivity")),
+                ];
+                if updates.iter().all(|(_, to)| to.is_none()) {
+                    continue;
+                }
+                // Failing means the device went away again
+                if !self.devices.contains_key(&path) && self.add_device(&path).await.is_err() {
+                    continue;
+                }
+                let Some(watched) = self.devices.get_mut(&path) else {
+                    continue;
+                };
+                for (family, to) in updates {
+                    let Some(to) = to else {
+                        continue;
+                    };
+                    let last = match family {
+                        AddressFamily::Ipv4 => &mut watched.ip4,
+                        AddressFamily::Ipv6 => &mut watched.ip6,
+                    };
+                    let from = last.replace(to);
+                    if from == Some(to) {
+                        continue;
+                    }
+                    self.pending.push_back(ConnectivityChange {
+                        scope: ConnectivityScope::Device {
+                            device: path.clone(),
+                            interface: watched.interface.clone(),
+                            family,
+                        },
+                        from,
+                        to,
+                        portal_url: None,
+                    });
+                }
+            }
+        }
+    }
+
+    /// Turns the monitor into a [`Stream`] of changes. Take a [`checker`](Self::checker)
+    /// first to keep forcing re-checks.
+    pub fn into_stream(self) -> impl Stream<Item = Result<ConnectivityChange>> {
+        stream::unfold(self, |mut monitor| async move {
+            monitor.next().await.map(|change| (change, monitor))
+        })
+    }
+
+    async fn add_device(&mut self, path: &OwnedObjectPath) -> Result<()> {
+        let device = DeviceProxy::new_from_path(path.clone(), &self.connection).await?;
+        self.devices.insert(
+            path.clone(),
+            WatchedDevice {
+                interface: device.interface().await?,
+                ip4: None,
+                ip6: None,
+            },
+        );
+        Ok(())
+    }
+}
+
+/// Subscribes to the `PropertiesChanged` signals NetworkManager sends for `interface`.
+async fn properties_changed(connection: &Connection, interface: &str) -> Result<MessageStream> {
+    let rule = MatchRule::builder()
+        .msg_type(Type::Signal)
+        .sender("org.freedesktop.NetworkManager")?
+        .interface("org.freedesktop.DBus.Properties")?
+        .member("PropertiesChanged")?
+        .arg(0, interface)?
+        .build();
+    MessageStream::for_match_rule(rule, connection, None).await
+}
+
+async fn portal_url(nm: &NetworkManagerProxy<'_>) -> Result<Option<String>> {
+    let uri = nm.connectivity_check_uri().await?;
+    Ok(Some(uri).filter(|uri| !uri.is_empty()))
+}
+
+fn connectivity_state(state: u32) -> NMConnectivityState {
+    NMConnectivityState::try_from(state).unwrap_or(NMConnectivityState::UNKNOWN)
+}
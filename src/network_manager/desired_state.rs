@@ -0,0 +1,715 @@
+//! nmstate-style declarative network configuration.
+//!
+//! A [`DesiredState`] document lists interfaces with their type, state and IP configuration.
+//! [`apply`] compares it with the profiles NetworkManager has, adds, updates or deletes profiles
+//! to match and activates them in dependency order: controllers before their ports and parents
+//! before their VLANs. Everything happens inside a checkpoint, so a failure rolls devices and
+//! profiles back to where they were.
+//!
+//! Interfaces the document doesn't list are left alone, as are the settings of a profile the
+//! document doesn't describe.
+//!
+//! ```yaml
+//! interfaces:
+//!   - name: bond0
+//!     type: bond
+//!     link-aggregation:
+//!       mode: active-backup
+//!       port: [eth0, eth1]
+//!     ipv4:
+//!       address:
+//!         - ip: 192.0.2.10
+//!           prefix-length: 24
+//!   - name: eth0
+//!     type: ethernet
+//!   - name: eth1
+//!     type: ethernet
+//! routes:
+//!   - destination: 0.0.0.0/0
+//!     next-hop-interface: bond0
+//!     next-hop-address: 192.0.2.1
+//! dns:
+//!   server: [192.0.2.1]
+//! ```
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use zbus::zvariant::{ObjectPath, OwnedObjectPath, Value};
+use zbus::{Connection, Result};
+
+use super::NetworkManagerProxy;
+use super::active::{Activation, ActiveProxy};
+use super::connection_settings::ConnectionSettings;
+use super::dbus_interface_types::NMCheckpointCreateFlags;
+use super::device::DeviceProxy;
+use super::import::{add_address, add_dns, new_uuid, parse_address};
+use super::settings::SettingsProxy;
+use super::settings_connection::SettingsConnectionProxy;
+use super::update::{Persistence, UpdateOptions, update_preserving_secrets};
+
+const ETHERNET: &str = "802-3-ethernet";
+
+/// How long the checkpoint outlives the activation timeout before NetworkManager rolls it back
+/// by itself, in case the caller goes away halfway through.
+const ROLLBACK_MARGIN_SECS: u64 = 30;
+
+/// The desired network configuration.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct DesiredState {
+    #[serde(default)]
+    pub interfaces: Vec<InterfaceState>,
+    /// Routes, added to the profile of their `next-hop-interface`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub routes: Vec<RouteState>,
+    /// Name servers, configured on the interfaces holding a default route of the same family,
+    /// or the first interface with an IP configuration of that family.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dns: Option<DnsState>,
+}
+
+impl DesiredState {
+    pub fn from_yaml(input: &str) -> std::result::Result<DesiredState, serde_yaml::Error> {
+        serde_yaml::from_str(input)
+    }
+
+    pub fn to_yaml(&self) -> std::result::Result<String, serde_yaml::Error> {
+        serde_yaml::to_string(self)
+    }
+
+    fn interface(&self, name: &str) -> Option<&InterfaceState> {
+        self.interfaces
+            .iter()
+            .find(|interface| interface.name == name)
+    }
+
+    /// The controller `name` is a port of and its `connection.slave-type`.
+    fn controller(&self, name: &str) -> Option<(&str, &'static str)> {
+        self.interfaces.iter().find_map(|interface| {
+            let (ports, port_type) = match (&interface.link_aggregation, &interface.bridge) {
+                (Some(bond), _) => (&bond.port, "bond"),
+                (None, Some(bridge)) => (&bridge.port, "bridge"),
+                (None, None) => return None,
+            };
+            ports
+                .iter()
+                .any(|port| port == name)
+                .then_some((interface.name.as_str(), port_type))
+        })
+    }
+
+    /// Rejects documents that can't be turned into profiles, before anything is changed.
+    fn validate(&self) -> Result<()> {
+        let mut names = HashSet::new();
+        for interface in &self.interfaces {
+            let name = &interface.name;
+            if !names.insert(name) {
+                return Err(failure(format!("interface {name} is listed twice")));
+            }
+            let missing = match interface.kind {
+                InterfaceType::Bond => interface.link_aggregation.is_none(),
+                InterfaceType::Vlan => interface.vlan.is_none(),
+                _ => false,
+            };
+            if missing {
+                return Err(failure(format!(
+                    "interface {name} lacks its {} configuration",
+                    interface.kind.as_str()
+                )));
+            }
+
+            let ports = interface
+                .link_aggregation
+                .iter()
+                .map(|bond| &bond.port)
+                .chain(interface.bridge.iter().map(|bridge| &bridge.port));
+            for port in ports.flatten() {
+                if self.interface(port).is_none() {
+                    return Err(failure(format!("port {port} of {name} is not listed")));
+                }
+            }
+        }
+
+        for route in &self.routes {
+            if route.parse_destination().is_none() {
+                return Err(failure(format!(
+                    "route destination {} is not in CIDR notation",
+                    route.destination
+                )));
+            }
+            if self.interface(&route.next_hop_interface).is_none() {
+                return Err(failure(format!(
+                    "route to {} uses unlisted interface {}",
+                    route.destination, route.next_hop_interface
+                )));
+            }
+        }
+
+        // Fails on dependency cycles
+        for interface in &self.interfaces {
+            self.depth(&interface.name, &mut Vec::new())?;
+        }
+        Ok(())
+    }
+
+    /// The number of interfaces that have to be up before `name`.
+    fn depth<'a>(&'a self, name: &'a str, seen: &mut Vec<&'a str>) -> Result<usize> {
+        if seen.contains(&name) {
+            return Err(failure(format!("interface {name} depends on itself")));
+        }
+        let Some(interface) = self.interface(name) else {
+            return Ok(0);
+        };
+        seen.push(name);
+        let lower = match (&interface.vlan, self.controller(name)) {
+            (Some(vlan), _) => Some(vlan.base_iface.as_str()),
+            (None, Some((controller, _))) => Some(controller),
+            (None, None) => None,
+        };
+        let depth = match lower {
+            Some(lower) => self.depth(lower, seen)? + 1,
+            None => 0,
+        };
+        seen.pop();
+        Ok(depth)
+    }
+
+    /// Whether `interface` carries the IPv6 (or IPv4) name servers.
+    fn has_dns(&self, interface: &InterfaceState, ipv6: bool) -> bool {
+        let is_default =
+            |route: &RouteState| route.destination == if ipv6 { "::/0" } else { "0.0.0.0/0" };
+        let configured = |interface: &InterfaceState| {
+            let ip = if ipv6 {
+                &interface.ipv6
+            } else {
+                &interface.ipv4
+            };
+            ip.as_ref().is_some_and(|ip| ip.enabled) && self.controller(&interface.name).is_none()
+        };
+
+        if self.routes.iter().any(is_default) {
+            self.routes
+                .iter()
+                .any(|route| is_default(route) && route.next_hop_interface == interface.name)
+        } else {
+            self.interfaces
+                .iter()
+                .find(|interface| configured(interface))
+                .is_some_and(|first| first.name == interface.name)
+        }
+    }
+
+    /// The profile for `interface`, without `connection.uuid`.
+    fn settings(&self, interface: &InterfaceState) -> ConnectionSettings {
+        let name = &interface.name;
+        let connection_type = interface.kind.connection_type();
+        let mut settings = ConnectionSettings::new()
+            .with("connection", "id", name.clone())
+            .with("connection", "type", connection_type)
+            .with("connection", "interface-name", name.clone())
+            .with(
+                "connection",
+                "autoconnect",
+                interface.state == InterfaceStatus::Up,
+            );
+        settings.add_setting(connection_type);
+
+        if let Some(bond) = &interface.link_aggregation {
+            let mut options: HashMap<String, String> = bond
+                .options
+                .iter()
+                .map(|(option, value)| (option.clone(), value.clone()))
+                .collect();
+            options.insert("mode".to_owned(), bond.mode.clone());
+            settings.set("bond", "options", options);
+        }
+        if let Some(stp) = interface.bridge.as_ref().and_then(|bridge| bridge.stp) {
+            settings.set("bridge", "stp", stp);
+        }
+        if let Some(vlan) = &interface.vlan {
+            settings.set("vlan", "parent", vlan.base_iface.clone());
+            settings.set("vlan", "id", u32::from(vlan.id));
+        }
+        if let Some(mtu) = interface.mtu {
+            settings.set(ETHERNET, "mtu", mtu);
+        }
+
+        if let Some((controller, port_type)) = self.controller(name) {
+            settings.set("connection", "master", controller.to_owned());
+            settings.set("connection", "slave-type", port_type);
+            return settings;
+        }
+
+        for (family, ip, ipv6) in [
+            ("ipv4", &interface.ipv4, false),
+            ("ipv6", &interface.ipv6, true),
+        ] {
+            let disabled = IpState {
+                enabled: false,
+                ..IpState::default()
+            };
+            let ip = ip.as_ref().unwrap_or(&disabled);
+            let method = match (ip.enabled, ip.dhcp, ip.address.is_empty(), ipv6) {
+                (false, ..) => "disabled",
+                (true, true, ..) => "auto",
+                (true, false, false, _) => "manual",
+                (true, false, true, false) => "disabled",
+                (true, false, true, true) => "link-local",
+            };
+            settings.set(family, "method", method);
+            // Listed so stale values are replaced on update
+            settings.set(family, "address-data", Vec::<HashMap<String, Value>>::new());
+            settings.set(family, "route-data", Vec::<HashMap<String, Value>>::new());
+            settings.set(family, "dns-search", Vec::<String>::new());
+            if ipv6 {
+                settings.set(family, "dns", Vec::<Vec<u8>>::new());
+            } else {
+                settings.set(family, "dns", Vec::<u32>::new());
+            }
+            if !ip.enabled {
+                continue;
+            }
+
+            for address in &ip.address {
+                add_address(
+                    &mut settings,
+                    &format!("{}/{}", address.ip, address.prefix_length),
+                );
+            }
+            let routes: Vec<HashMap<String, Value<'static>>> = self
+                .routes
+                .iter()
+                .filter(|route| &route.next_hop_interface == name && route.is_ipv6() == ipv6)
+                .filter_map(RouteState::to_dbus)
+                .collect();
+            settings.set(family, "route-data", routes);
+
+            if let Some(dns) = &self.dns
+                && self.has_dns(interface, ipv6)
+            {
+                for server in dns.server.iter().filter(|server| server.is_ipv6() == ipv6) {
+                    add_dns(&mut settings, *server);
+                }
+                settings.set(family, "dns-search", dns.search.clone());
+            }
+        }
+
+        settings
+    }
+}
+
+/// One interface of a [`DesiredState`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct InterfaceState {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub kind: InterfaceType,
+    #[serde(default)]
+    pub state: InterfaceStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mtu: Option<u32>,
+    /// Left out means disabled. Ignored on ports.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ipv4: Option<IpState>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ipv6: Option<IpState>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub link_aggregation: Option<BondConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bridge: Option<BridgeConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vlan: Option<VlanConfig>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum InterfaceType {
+    Ethernet,
+    Bond,
+    LinuxBridge,
+    Vlan,
+    Dummy,
+}
+
+impl InterfaceType {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            InterfaceType::Ethernet => "ethernet",
+            InterfaceType::Bond => "bond",
+            InterfaceType::LinuxBridge => "linux-bridge",
+            InterfaceType::Vlan => "vlan",
+            InterfaceType::Dummy => "dummy",
+        }
+    }
+
+    /// The `connection.type` of the profile.
+    pub fn connection_type(self) -> &'static str {
+        match self {
+            InterfaceType::Ethernet => ETHERNET,
+            InterfaceType::Bond => "bond",
+            InterfaceType::LinuxBridge => "bridge",
+            InterfaceType::Vlan => "vlan",
+            InterfaceType::Dummy => "dummy",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum InterfaceStatus {
+    /// The profile exists and is active.
+    #[default]
+    Up,
+    /// The profile exists but the interface is disconnected.
+    Down,
+    /// No profile for the interface exists.
+    Absent,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct IpState {
+    #[serde(default = "enabled")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub dhcp: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub address: Vec<IpAddressState>,
+}
+
+fn enabled() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct IpAddressState {
+    pub ip: IpAddr,
+    pub prefix_length: u8,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct BondConfig {
+    pub mode: String,
+    /// Kernel bond options other than `mode`, e.g. `miimon`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub options: BTreeMap<String, String>,
+    #[serde(default)]
+    pub port: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct BridgeConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stp: Option<bool>,
+    #[serde(default)]
+    pub port: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct VlanConfig {
+    pub base_iface: String,
+    pub id: u16,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct RouteState {
+    /// In CIDR notation, `0.0.0.0/0` or `::/0` for a default route.
+    pub destination: String,
+    pub next_hop_interface: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_hop_address: Option<IpAddr>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metric: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub table_id: Option<u32>,
+}
+
+impl RouteState {
+    fn is_ipv6(&self) -> bool {
+        self.destination.contains(':')
+    }
+
+    /// The destination address and prefix length, `None` unless it is in CIDR notation.
+    fn parse_destination(&self) -> Option<(IpAddr, u32)> {
+        self.destination.contains('/').then_some(())?;
+        parse_address(&self.destination)
+    }
+
+    fn to_dbus(&self) -> Option<HashMap<String, Value<'static>>> {
+        let (dest, prefix) = self.parse_destination()?;
+        let mut route = HashMap::from([
+            ("dest".to_owned(), Value::from(dest.to_string())),
+            ("prefix".to_owned(), Value::from(prefix)),
+        ]);
+        if let Some(next_hop) = self.next_hop_address {
+            route.insert("next-hop".to_owned(), Value::from(next_hop.to_string()));
+        }
+        if let Some(metric) = self.metric {
+            route.insert("metric".to_owned(), Value::from(metric));
+        }
+        if let Some(table) = self.table_id {
+            route.insert("table".to_owned(), Value::from(table));
+        }
+        Some(route)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct DnsState {
+    #[serde(default)]
+    pub server: Vec<IpAddr>,
+    #[serde(default)]
+    pub search: Vec<String>,
+}
+
+/// The interfaces [`apply`] changed, by what happened to them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AppliedChanges {
+    pub added: Vec<String>,
+    pub updated: Vec<String>,
+    pub deleted: Vec<String>,
+    pub activated: Vec<String>,
+    pub deactivated: Vec<String>,
+}
+
+impl AppliedChanges {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.updated.is_empty()
+            && self.deleted.is_empty()
+            && self.activated.is_empty()
+            && self.deactivated.is_empty()
+    }
+}
+
+/// Makes NetworkManager match `desired`.
+///
+/// Interfaces that should be up are activated even when already active, so changed profiles
+/// take effect; `timeout` bounds the whole apply, including the wait for all of them to finish
+/// activating. On any failure the checkpoint taken beforehand is rolled back and the error
+/// returned.
+pub async fn apply(
+    connection: &Connection,
+    desired: &DesiredState,
+    timeout: Duration,
+) -> Result<AppliedChanges> {
+    desired.validate()?;
+    let deadline = Instant::now() + timeout;
+
+    let nm = NetworkManagerProxy::new(connection).await?;
+    let flags = NMCheckpointCreateFlags::DELETE_NEW_CONNECTIONS as u32
+        | NMCheckpointCreateFlags::DISCONNECT_NEW_DEVICES as u32;
+    // 0 would disable the automatic rollback
+    let rollback_timeout =
+        u32::try_from(timeout.as_secs().saturating_add(ROLLBACK_MARGIN_SECS)).unwrap_or(u32::MAX);
+    let checkpoint = nm.checkpoint_create(&[], rollback_timeout, flags).await?;
+
+    match apply_changes(connection, &nm, desired, deadline).await {
+        Ok(changes) => {
+            nm.checkpoint_destroy(&checkpoint).await?;
+            Ok(changes)
+        }
+        Err(err) => {
+            // The original error is the useful one
+            let _ = nm.checkpoint_rollback(&checkpoint).await;
+            Err(err)
+        }
+    }
+}
+
+async fn apply_changes(
+    connection: &Connection,
+    nm: &NetworkManagerProxy<'_>,
+    desired: &DesiredState,
+    deadline: Instant,
+) -> Result<AppliedChanges> {
+    let mut changes = AppliedChanges::default();
+
+    // Existing profiles by interface name
+    let settings_proxy = SettingsProxy::new(connection).await?;
+    let mut profiles: HashMap<String, Vec<(OwnedObjectPath, ConnectionSettings)>> = HashMap::new();
+    for path in settings_proxy.list_connections().await? {
+        let proxy = SettingsConnectionProxy::new_from_path(path.clone(), connection).await?;
+        let settings = ConnectionSettings::from(proxy.get_settings().await?);
+        if let Some(interface) = settings.get_str("connection", "interface-name") {
+            profiles
+                .entry(interface.to_owned())
+                .or_default()
+                .push((path, settings));
+        }
+    }
+
+    // Lower interfaces last when taking interfaces down, first when bringing them up
+    let mut order: Vec<(usize, &InterfaceState)> = Vec::new();
+    for interface in &desired.interfaces {
+        order.push((desired.depth(&interface.name, &mut Vec::new())?, interface));
+    }
+    order.sort_by_key(|(depth, _)| std::cmp::Reverse(*depth));
+
+    let mut to_activate = Vec::new();
+    for (_, interface) in &order {
+        let name = &interface.name;
+        let existing = profiles.remove(name).unwrap_or_default();
+
+        if interface.state == InterfaceStatus::Absent {
+            for (path, _) in &existing {
+                // Deleting an active profile also deactivates it
+                SettingsConnectionProxy::new_from_path(path.clone(), connection)
+                    .await?
+                    .delete()
+                    .await?;
+            }
+            if !existing.is_empty() {
+                changes.deleted.push(name.clone());
+            }
+            continue;
+        }
+
+        let wanted = desired.settings(interface);
+        let current = existing
+            .iter()
+            .filter(|(_, settings)| settings.connection_type() == wanted.connection_type())
+            .max_by_key(|(_, settings)| settings.id() == Some(name.as_str()));
+        let path = match current {
+            Some((path, settings)) => {
+                if let Some(updated) = updated_settings(settings, &wanted) {
+                    // `GetSettings` left the secrets out, an update without them drops them
+                    let proxy =
+                        SettingsConnectionProxy::new_from_path(path.clone(), connection).await?;
+                    let options = UpdateOptions::new().with_persistence(Persistence::ToDisk);
+                    update_preserving_secrets(&proxy, &updated, &options).await?;
+                    changes.updated.push(name.clone());
+                }
+                path.clone()
+            }
+            None => {
+                let settings = wanted.with("connection", "uuid", new_uuid());
+                let path = settings_proxy.add_connection(settings.to_dbus()).await?;
+                changes.added.push(name.clone());
+                path
+            }
+        };
+
+        match interface.state {
+            InterfaceStatus::Up => to_activate.push((name, path)),
+            InterfaceStatus::Down => {
+                if disconnect(connection, nm, name).await? {
+                    changes.deactivated.push(name.clone());
+                }
+            }
+            InterfaceStatus::Absent => unreachable!(),
+        }
+    }
+
+    let root = ObjectPath::try_from("/")?;
+    let mut active = Vec::new();
+    for (name, path) in to_activate.into_iter().rev() {
+        let active_path = nm.activate_connection(&path, &root, &root).await?;
+        active.push((name, active_path));
+    }
+    // Controllers only finish activating once their ports are attached, so wait after
+    // activating everything
+    for (name, active_path) in active {
+        wait_activated(connection, name, active_path, deadline).await?;
+        changes.activated.push(name.clone());
+    }
+
+    Ok(changes)
+}
+
+/// Disconnects the device of `interface`, returns whether it was connected.
+async fn disconnect(
+    connection: &Connection,
+    nm: &NetworkManagerProxy<'_>,
+    interface: &str,
+) -> Result<bool> {
+    let Ok(path) = nm.get_device_by_ip_iface(interface).await else {
+        return Ok(false);
+    };
+    let device = DeviceProxy::new_from_path(path, connection).await?;
+    if device.active_connection().await?.as_str() == "/" {
+        return Ok(false);
+    }
+    device.disconnect().await?;
+    Ok(true)
+}
+
+async fn wait_activated(
+    connection: &Connection,
+    interface: &str,
+    active_path: OwnedObjectPath,
+    deadline: Instant,
+) -> Result<()> {
+    let active = ActiveProxy::new_from_path(active_path, connection).await?;
+    match active.wait_activated(deadline).await? {
+        Activation::Activated => Ok(()),
+        Activation::Failed | Activation::TimedOut => {
+            Err(failure(format!("{interface} failed to activate")))
+        }
+    }
+}
+
+/// Properties NetworkManager leaves out of `GetSettings` while they have these values.
+fn is_default(setting: &str, property: &str, value: &Value<'_>) -> bool {
+    match (setting, property, value) {
+        ("connection", "autoconnect", Value::Bool(true)) => true,
+        (_, _, Value::Array(array)) => array.is_empty(),
+        _ => false,
+    }
+}
+
+/// `current` with the properties of `wanted`, or `None` when nothing would change.
+///
+/// `ipv4` and `ipv6` drop the deprecated `addresses`, `routes` and `gateway` properties, as
+/// `wanted` describes them through `address-data` and `route-data`; otherwise NetworkManager
+/// would keep using the old values.
+fn updated_settings(
+    current: &ConnectionSettings,
+    wanted: &ConnectionSettings,
+) -> Option<ConnectionSettings> {
+    let mut updated = current.clone();
+    let mut changed = false;
+    for (setting, property, value) in wanted.iter() {
+        let same = match current.get(setting, property) {
+            Some(current) => current == value,
+            None => is_default(setting, property, value),
+        };
+        if !same {
+            updated.set(setting, property, value.try_clone().ok()?);
+            changed = true;
+        }
+    }
+
+    if wanted.get("connection", "master").is_none() {
+        for property in ["master", "slave-type"] {
+            changed |= updated.remove("connection", property).is_some();
+        }
+    }
+    if !changed {
+        return None;
+    }
+
+    // NetworkManager reports these next to their replacements, so they don't count as changes
+    for setting in ["ipv4", "ipv6"] {
+        if wanted.contains_setting(setting) {
+            for property in ["addresses", "routes", "gateway"] {
+                updated.remove(setting, property);
+            }
+        }
+    }
+    Some(updated)
+}
+
+fn failure(message: String) -> zbus::Error {
+    zbus::Error::Failure(message)
+}
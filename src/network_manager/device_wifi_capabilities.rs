@@ -0,0 +1,43 @@
+#![allow(clippy::bad_bit_mask)]
+use std::fmt;
+use std::str::FromStr;
+
+use bitflags::bitflags;
+
+bitflags! {
+    /// 802.11 specific device encryption and authentication capabilities.
+    pub struct NMDeviceWifiCapabilities: u32 {
+        const NONE          = 0x00000000;
+        const CIPHER_WEP40  = 0x00000001;
+        const CIPHER_WEP104 = 0x00000002;
+        const CIPHER_TKIP   = 0x00000004;
+        const CIPHER_CCMP   = 0x00000008;
+        const WPA           = 0x00000010;
+        const RSN           = 0x00000020;
+        const AP            = 0x00000040;
+        const ADHOC         = 0x00000080;
+        const FREQ_VALID    = 0x00000100;
+        const FREQ_2GHZ     = 0x00000200;
+        const FREQ_5GHZ     = 0x00000400;
+        const MESH          = 0x00001000;
+        const IBSS_RSN      = 0x00002000;
+    }
+}
+
+/// Formats the capabilities in the bitflags text format, e.g. `CIPHER_CCMP | RSN | AP`.
+impl fmt::Display for NMDeviceWifiCapabilities {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        bitflags::parser::to_writer(self, f)
+    }
+}
+
+impl FromStr for NMDeviceWifiCapabilities {
+    type Err = bitflags::parser::ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        bitflags::parser::from_str(s)
+    }
+}
+
+#[cfg(feature = "serde")]
+serde_flag_names!(NMDeviceWifiCapabilities);
@@ -0,0 +1,398 @@
+//! WAN failover between uplinks using route metrics.
+//!
+//! [`FailoverManager`] is given uplinks in order of preference and polls their health: the
+//! IPv4 connectivity NetworkManager found for the device and, optionally, a custom probe.
+//! Healthy uplinks keep their order, failed ones move behind every healthy one. The order is
+//! applied as `ipv4.route-metric` and `ipv6.route-metric` through
+//! [`DeviceProxy::reapply`], which changes the routes without taking the link down and leaves
+//! the profiles themselves untouched.
+//!
+//! An uplink has to fail for the failure delay, 0 by default, before it loses its place and stay
+//! healthy for the hold-down time, 30 seconds by default, before it gets it back, so a flapping
+//! link doesn't move the default route back and forth.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant, SystemTime};
+
+use async_io::Timer;
+use futures_lite::{Stream, future, stream};
+use zbus::{Connection, Result};
+
+use super::NetworkManagerProxy;
+use super::connection_settings::ConnectionSettings;
+use super::dbus_interface_types::NMConnectivityState;
+use super::device::DeviceProxy;
+
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(5);
+const DEFAULT_HOLD_DOWN: Duration = Duration::from_secs(30);
+const DEFAULT_BASE_METRIC: i64 = 100;
+const DEFAULT_METRIC_STEP: i64 = 100;
+const DEFAULT_LOG_CAPACITY: usize = 100;
+
+/// A custom health check, called with the interface name of an uplink.
+pub type Probe = Box<dyn Fn(&str) -> future::Boxed<bool> + Send + Sync>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FailoverEvent {
+    /// An uplink failed for longer than the failure delay.
+    Down { interface: String },
+    /// An uplink stayed healthy for the hold-down time.
+    Up { interface: String },
+    /// The most preferred healthy uplink changed. `None` when no uplink is healthy.
+    PrimaryChanged {
+        from: Option<String>,
+        to: Option<String>,
+    },
+    /// The route metric of an uplink was reapplied.
+    MetricChanged {
+        interface: String,
+        route_metric: i64,
+    },
+    /// The route metrics of an uplink were put back to those of its profile.
+    MetricRestored { interface: String },
+}
+
+/// An event with the time it happened.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FailoverLogEntry {
+    pub at: SystemTime,
+    pub event: FailoverEvent,
+}
+
+/// Route metrics as they were before the manager changed them.
+#[derive(Debug, Clone, Copy)]
+struct OriginalMetrics {
+    ipv4: Option<i64>,
+    ipv6: Option<i64>,
+}
+
+struct Uplink {
+    interface: String,
+    /// The health acted on, which lags behind the probes by the failure delay or hold-down.
+    healthy: bool,
+    /// When the probes started to disagree with `healthy`.
+    changed_since: Option<Instant>,
+    original: Option<OriginalMetrics>,
+}
+
+/// Moves the default route between uplinks by their health.
+///
+/// Per-device connectivity needs NetworkManager's connectivity checking to be enabled. While it
+/// is disabled every device reports [`NMConnectivityState::UNKNOWN`], which counts as healthy
+/// only when there is a [`probe`](Self::with_probe) to decide.
+pub struct FailoverManager {
+    connection: Connection,
+    uplinks: Vec<Uplink>,
+    probe: Option<Probe>,
+    interval: Duration,
+    failure_delay: Duration,
+    hold_down: Duration,
+    base_metric: i64,
+    metric_step: i64,
+    primary: Option<String>,
+    last_poll: Option<Instant>,
+    pending: VecDeque<FailoverEvent>,
+    log: VecDeque<FailoverLogEntry>,
+    log_capacity: usize,
+}
+
+impl FailoverManager {
+    /// Creates a manager for the uplinks with the given interface names, most preferred
+    /// first. Modems are named by their IP interface, e.g. `wwan0`.
+    pub fn new<I>(connection: &Connection, interfaces: I) -> FailoverManager
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        FailoverManager {
+            connection: connection.clone(),
+            uplinks: interfaces
+                .into_iter()
+                .map(|interface| Uplink {
+                    interface: interface.into(),
+                    healthy: true,
+                    changed_since: None,
+                    original: None,
+                })
+                .collect(),
+            probe: None,
+            interval: DEFAULT_INTERVAL,
+            failure_delay: Duration::ZERO,
+            hold_down: DEFAULT_HOLD_DOWN,
+            base_metric: DEFAULT_BASE_METRIC,
+            metric_step: DEFAULT_METRIC_STEP,
+            primary: None,
+            last_poll: None,
+            pending: VecDeque::new(),
+            log: VecDeque::new(),
+            log_capacity: DEFAULT_LOG_CAPACITY,
+        }
+    }
+
+    /// Also requires `probe` to succeed for an uplink to be healthy, e.g. a ping through the
+    /// interface.
+    pub fn with_probe<F>(mut self, probe: F) -> Self
+    where
+        F: Fn(&str) -> future::Boxed<bool> + Send + Sync + 'static,
+    {
+        self.probe = Some(Box::new(probe));
+        self
+    }
+
+    /// Time between polls by [`next`](Self::next), 5 seconds by default.
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// How long an uplink has to fail before it loses its place, 0 by default.
+    pub fn with_failure_delay(mut self, delay: Duration) -> Self {
+        self.failure_delay = delay;
+        self
+    }
+
+    /// How long a failed uplink has to be healthy before it gets its place back, 30 seconds by
+    /// default.
+    pub fn with_hold_down(mut self, hold_down: Duration) -> Self {
+        self.hold_down = hold_down;
+        self
+    }
+
+    /// The route metric of the uplink in place `n`, counting from 0, is `base + n * step`.
+    /// 100 and 100 by default.
+    pub fn with_metrics(mut self, base: i64, step: i64) -> Self {
+        self.base_metric = base;
+        self.metric_step = step;
+        self
+    }
+
+    /// Number of events kept in the [`log`](Self::log), 100 by default.
+    pub fn with_log_capacity(mut self, capacity: usize) -> Self {
+        self.log_capacity = capacity;
+        self
+    }
+
+    /// The most preferred healthy uplink, as of the last poll.
+    pub fn primary(&self) -> Option<&str> {
+        self.primary.as_deref()
+    }
+
+    /// The most recent events, oldest first.
+    pub fn log(&self) -> impl Iterator<Item = &FailoverLogEntry> {
+        self.log.iter()
+    }
+
+    /// Probes every uplink once and reapplies the route metrics that need to change.
+    pub async fn poll(&mut self) -> Result<Vec<FailoverEvent>> {
+        let connection = self.connection.clone();
+        let nm = NetworkManagerProxy::new(&connection).await?;
+        let now = Instant::now();
+        let mut events = Vec::new();
+
+        let mut devices = Vec::with_capacity(self.uplinks.len());
+        for idx in 0..self.uplinks.len() {
+            let device = match nm
+                .get_device_by_ip_iface(&self.uplinks[idx].interface)
+                .await
+            {
+                Ok(path) => Some(DeviceProxy::new_from_path(path, &connection).await?),
+                Err(_) => None,
+            };
+            let healthy = match &device {
+                Some(device) => self.probe(&self.uplinks[idx].interface, device).await?,
+                None => false,
+            };
+            devices.push(device);
+
+            let delay = match healthy {
+                true => self.hold_down,
+                false => self.failure_delay,
+            };
+            let uplink = &mut self.uplinks[idx];
+            if healthy == uplink.healthy {
+                uplink.changed_since = None;
+                continue;
+            }
+            let since = *uplink.changed_since.get_or_insert(now);
+            if now.duration_since(since) < delay {
+                continue;
+            }
+            uplink.healthy = healthy;
+            uplink.changed_since = None;
+            let interface = uplink.interface.clone();
+            events.push(match healthy {
+                true => FailoverEvent::Up { interface },
+                false => FailoverEvent::Down { interface },
+            });
+        }
+
+        // Healthy uplinks in order of preference, then the failed ones
+        let mut order: Vec<usize> = (0..self.uplinks.len()).collect();
+        order.sort_by_key(|idx| !self.uplinks[*idx].healthy);
+
+        let primary = order
+            .first()
+            .map(|idx| &self.uplinks[*idx])
+            .filter(|uplink| uplink.healthy)
+            .map(|uplink| uplink.interface.clone());
+        if primary != self.primary {
+            events.push(FailoverEvent::PrimaryChanged {
+                from: std::mem::replace(&mut self.primary, primary.clone()),
+                to: primary,
+            });
+        }
+
+        // Logged before reapplying, so a failure to reapply doesn't lose them
+        for event in &events {
+            self.record(event.clone());
+        }
+
+        for (place, idx) in order.into_iter().enumerate() {
+            let Some(device) = &devices[idx] else {
+                continue;
+            };
+            let route_metric = self.base_metric + place as i64 * self.metric_step;
+            if self.set_metric(idx, device, route_metric).await? {
+                let event = FailoverEvent::MetricChanged {
+                    interface: self.uplinks[idx].interface.clone(),
+                    route_metric,
+                };
+                self.record(event.clone());
+                events.push(event);
+            }
+        }
+        Ok(events)
+    }
+
+    /// Waits for the next event, polling every [`interval`](Self::with_interval).
+    pub async fn next(&mut self) -> Option<Result<FailoverEvent>> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Some(Ok(event));
+            }
+            if let Some(last_poll) = self.last_poll {
+                Timer::at(last_poll + self.interval).await;
+            }
+            self.last_poll = Some(Instant::now());
+            match self.poll().await {
+                Ok(events) => self.pending.extend(events),
+                Err(err) => return Some(Err(err)),
+            }
+        }
+    }
+
+    /// Turns the manager into a [`Stream`] of events.
+    pub fn into_stream(self) -> impl Stream<Item = Result<FailoverEvent>> {
+        stream::unfold(self, |mut manager| async move {
+            manager.next().await.map(|event| (event, manager))
+        })
+    }
+
+    /// Puts back the route metrics the manager changed, on the uplinks that are still active.
+    pub async fn restore(&mut self) -> Result<()> {
+        let nm = NetworkManagerProxy::new(&self.connection).await?;
+        for idx in 0..self.uplinks.len() {
+            let Some(original) = self.uplinks[idx].original else {
+                continue;
+            };
+            let Ok(path) = nm
+                .get_device_by_ip_iface(&self.uplinks[idx].interface)
+                .await
+            else {
+                continue;
+            };
+            let device = DeviceProxy::new_from_path(path, &self.connection).await?;
+            if let Some((settings, version_id)) = applied_connection(&device).await {
+                let mut settings = settings;
+                restore_metric(&mut settings, "ipv4", original.ipv4);
+                restore_metric(&mut settings, "ipv6", original.ipv6);
+                device.reapply(settings.to_dbus(), version_id, 0).await?;
+            }
+            self.uplinks[idx].original = None;
+            self.record(FailoverEvent::MetricRestored {
+                interface: self.uplinks[idx].interface.clone(),
+            });
+        }
+        Ok(())
+    }
+
+    async fn probe(&self, interface: &str, device: &DeviceProxy<'_>) -> Result<bool> {
+        let connectivity = NMConnectivityState::try_from(device.ip4_connectivity().await?)
+            .unwrap_or(NMConnectivityState::UNKNOWN);
+        let healthy = match connectivity {
+            NMConnectivityState::FULL => true,
+            NMConnectivityState::UNKNOWN => self.probe.is_some(),
+            _ => false,
+        };
+        match &self.probe {
+            Some(probe) if healthy => Ok(probe(interface).await),
+            _ => Ok(healthy),
+        }
+    }
+
+    /// Reapplies the applied connection of an uplink with `route_metric`, returning whether
+    /// anything changed. Inactive devices have nothing to reapply.
+    async fn set_metric(
+        &mut self,
+        idx: usize,
+        device: &DeviceProxy<'_>,
+        route_metric: i64,
+    ) -> Result<bool> {
+        let Some((mut settings, version_id)) = applied_connection(device).await else {
+            return Ok(false);
+        };
+        let families: Vec<&str> = ["ipv4", "ipv6"]
+            .into_iter()
+            .filter(|family| settings.contains_setting(family))
+            .filter(|family| settings.get_i64(family, "route-metric") != Some(route_metric))
+            .collect();
+        if families.is_empty() {
+            return Ok(false);
+        }
+
+        let uplink = &mut self.uplinks[idx];
+        uplink.original.get_or_insert(OriginalMetrics {
+            ipv4: settings.get_i64("ipv4", "route-metric"),
+            ipv6: settings.get_i64("ipv6", "route-metric"),
+        });
+        for family in families {
+            settings.set(family, "route-metric", route_metric);
+        }
+        device.reapply(settings.to_dbus(), version_id, 0).await?;
+        Ok(true)
+    }
+
+    fn record(&mut self, event: FailoverEvent) {
+        if self.log_capacity == 0 {
+            return;
+        }
+        if self.log.len() == self.log_capacity {
+            self.log.pop_front();
+        }
+        self.log.push_back(FailoverLogEntry {
+            at: SystemTime::now(),
+            event,
+        });
+    }
+}
+
+/// The applied connection of a device with its version id, `None` while it isn't active.
+async fn applied_connection(device: &DeviceProxy<'_>) -> Option<(ConnectionSettings, u64)> {
+    let (settings, version_id) = device.get_applied_connection(0).await.ok()?;
+    Some((ConnectionSettings::from(settings), version_id))
+}
+
+/// Puts back a route metric of the profile, `None` meaning it wasn't set.
+fn restore_metric(settings: &mut ConnectionSettings, family: &str, route_metric: Option<i64>) {
+    if !settings.contains_setting(family) {
+        return;
+    }
+    match route_metric {
+        Some(route_metric) => settings.set(family, "route-metric", route_metric),
+        None => {
+            settings.remove(family, "route-metric");
+            // Keep the setting itself, removing it would change how the family is configured
+            settings.add_setting(family);
+        }
+    }
+}
@@ -0,0 +1,144 @@
+//! Connecting to networks that don't broadcast their SSID.
+//!
+//! A hidden network only shows up in scan results once it has been probed for by SSID, so the
+//! profile is created with `802-11-wireless.hidden=true` and activated without a specific
+//! access point; NetworkManager then probes for it while connecting.
+
+use std::time::{Duration, Instant};
+
+use zbus::zvariant::{ObjectPath, OwnedObjectPath};
+use zbus::{Connection, Result};
+
+use super::NetworkManagerProxy;
+use super::access_point::AccessPointProxy;
+use super::active::{Activation, ActiveProxy};
+use super::connection_settings::ConnectionSettings;
+use super::device::DeviceProxy;
+use super::settings_connection::SettingsConnectionProxy;
+use super::wireless::{ScanOptions, WirelessProxy};
+
+/// Builds the settings for a hidden network, secured with WPA-PSK when `password` is set.
+pub fn hidden_network_settings(ssid: &[u8], password: Option<&str>) -> ConnectionSettings {
+    let mut settings = ConnectionSettings::new()
+        .with(
+            "connection",
+            "id",
+            String::from_utf8_lossy(ssid).into_owned(),
+        )
+        .with("connection", "type", "802-11-wireless")
+        .with("802-11-wireless", "ssid", ssid.to_vec())
+        .with("802-11-wireless", "mode", "infrastructure")
+        .with("802-11-wireless", "hidden", true)
+        .with("ipv4", "method", "auto")
+        .with("ipv6", "method", "auto");
+
+    if let Some(password) = password {
+        settings.set("802-11-wireless", "security", "802-11-wireless-security");
+        settings.set("802-11-wireless-security", "key-mgmt", "wpa-psk");
+        settings.set("802-11-wireless-security", "psk", password.to_owned());
+    }
+
+    settings
+}
+
+/// The result of [`connect_hidden`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HiddenConnection {
+    pub settings_path: OwnedObjectPath,
+    pub active_path: OwnedObjectPath,
+    /// The access point the device connected to, `None` when it isn't known yet: hidden
+    /// access points may only be listed a while after the connection is up.
+    pub access_point: Option<OwnedObjectPath>,
+}
+
+/// Creates a hidden network profile for `ssid` on `device`, activates it and waits up to
+/// `timeout` for the connection to come up.
+///
+/// When the activation fails or doesn't finish in time, it is deactivated and the profile is
+/// deleted before the error is returned.
+pub async fn connect_hidden(
+    device: &DeviceProxy<'_>,
+    ssid: &[u8],
+    password: Option<&str>,
+    timeout: Duration,
+) -> Result<HiddenConnection> {
+    let connection = device.inner().connection().clone();
+    let device_path = OwnedObjectPath::from(device.inner().path().clone());
+    let wireless = WirelessProxy::new_from_path(device_path.clone(), &connection).await?;
+
+    // NetworkManager probes for hidden profiles by itself, probing right away just speeds it
+    // up. Scans are rate limited so a failure here isn't fatal.
+    let _ = wireless
+        .request_scan_with(&ScanOptions {
+            ssids: vec![ssid.to_vec()],
+        })
+        .await;
+
+    let nm = NetworkManagerProxy::new(&connection).await?;
+    let (settings_path, active_path) = nm
+        .add_and_activate_connection(
+            hidden_network_settings(ssid, password).to_dbus(),
+            &device_path,
+            &ObjectPath::try_from("/")?,
+        )
+        .await?;
+
+    let outcome = match ActiveProxy::new_from_path(active_path.clone(), &connection).await {
+        Ok(active) => active.wait_activated(Instant::now() + timeout).await,
+        Err(err) => Err(err),
+    };
+    let outcome = match outcome {
+        Ok(Activation::Activated) => Ok(find_access_point(&connection, &wireless, ssid).await),
+        Ok(Activation::Failed | Activation::TimedOut) => Err(zbus::Error::Failure(format!(
+            "could not connect to hidden network '{}'",
+            String::from_utf8_lossy(ssid)
+        ))),
+        Err(err) => Err(err),
+    };
+    match outcome {
+        Ok(access_point) => Ok(HiddenConnection {
+            settings_path,
+            active_path,
+            access_point,
+        }),
+        Err(err) => {
+            // Best effort, the activation may already be gone
+            let _ = nm.deactivate_connection(&active_path).await;
+            if let Ok(profile) =
+                SettingsConnectionProxy::new_from_path(settings_path, &connection).await
+            {
+                let _ = profile.delete().await;
+            }
+            Err(err)
+        }
+    }
+}
+
+/// The access point the device is connected to, or else one with `ssid`.
+///
+/// Failing to read them doesn't fail the connection, which is up already.
+async fn find_access_point(
+    connection: &Connection,
+    wireless: &WirelessProxy<'_>,
+    ssid: &[u8],
+) -> Option<OwnedObjectPath> {
+    let active = wireless.active_access_point().await.ok()?;
+    if active.as_str() != "/" {
+        return Some(active);
+    }
+    // Hidden access points are only listed by GetAllAccessPoints
+    for path in wireless.get_all_access_points().await.ok()? {
+        if has_ssid(connection, &path, ssid).await {
+            return Some(path);
+        }
+    }
+    None
+}
+
+/// Whether the access point at `path` has `ssid`, `false` when it disappeared meanwhile.
+async fn has_ssid(connection: &Connection, path: &OwnedObjectPath, ssid: &[u8]) -> bool {
+    match AccessPointProxy::new_from_path(path.clone(), connection).await {
+        Ok(access_point) => access_point.ssid().await.is_ok_and(|found| found == ssid),
+        Err(_) => false,
+    }
+}
@@ -0,0 +1,262 @@
+//! Wi-Fi hotspot (access point mode) helper.
+//!
+//! Builds an `802-11-wireless` profile with `mode=ap` and `ipv4.method=shared`, activates it
+//! on a wireless device and returns a [`Hotspot`] handle that can report connected clients
+//! and tear the hotspot down again.
+
+use std::collections::{HashMap, HashSet};
+
+use zbus::zvariant::{ObjectPath, OwnedObjectPath, Value};
+use zbus::{Connection, Result};
+
+use super::channel::Band;
+use super::connection_settings::ConnectionSettings;
+use super::device::DeviceProxy;
+use super::device_wifi_capabilities::NMDeviceWifiCapabilities;
+use super::settings_connection::SettingsConnectionProxy;
+use super::wireless::WirelessProxy;
+use super::{NetworkManagerProxy, active::ActiveProxy};
+
+const ARP_TABLE: &str = "/proc/net/arp";
+
+/// Security of the hotspot network.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HotspotSecurity {
+    Open,
+    /// WPA2 personal, the password must be 8 to 63 characters.
+    Wpa2(String),
+    /// WPA3 personal (SAE), the password must not be empty.
+    Wpa3(String),
+}
+
+impl HotspotSecurity {
+    /// Checks the password, so a bad one fails before anything is activated.
+    pub fn validate(&self) -> Result<()> {
+        match self {
+            HotspotSecurity::Open => Ok(()),
+            HotspotSecurity::Wpa2(password) => match password.chars().count() {
+                8..=63 => Ok(()),
+                length => Err(zbus::Error::Failure(format!(
+                    "WPA2 password must be 8 to 63 characters, got {length}"
+                ))),
+            },
+            HotspotSecurity::Wpa3(password) if password.is_empty() => Err(zbus::Error::Failure(
+                "WPA3 password must not be empty".to_owned(),
+            )),
+            HotspotSecurity::Wpa3(_) => Ok(()),
+        }
+    }
+}
+
+/// Builds the connection settings for a hotspot on `interface`.
+pub fn hotspot_settings(
+    interface: &str,
+    ssid: &str,
+    security: &HotspotSecurity,
+    band: Option<Band>,
+    channel: Option<u32>,
+) -> ConnectionSettings {
+    let mut settings = ConnectionSettings::new()
+        .with("connection", "id", format!("Hotspot {ssid}"))
+        .with("connection", "type", "802-11-wireless")
+        .with("connection", "autoconnect", false)
+        .with("connection", "interface-name", interface.to_owned())
+        .with("802-11-wireless", "ssid", ssid.as_bytes().to_vec())
+        .with("802-11-wireless", "mode", "ap")
+        .with("ipv4", "method", "shared")
+        .with("ipv6", "method", "ignore");
+
+    if let Some(band) = band {
+        settings.set("802-11-wireless", "band", band.as_str());
+    }
+    if let Some(channel) = channel {
+        settings.set("802-11-wireless", "channel", channel);
+    }
+
+    match security {
+        HotspotSecurity::Open => {}
+        HotspotSecurity::Wpa2(password) => {
+            settings.set("802-11-wireless", "security", "802-11-wireless-security");
+            settings.set("802-11-wireless-security", "key-mgmt", "wpa-psk");
+            settings.set("802-11-wireless-security", "proto", vec!["rsn"]);
+            settings.set("802-11-wireless-security", "pairwise", vec!["ccmp"]);
+            settings.set("802-11-wireless-security", "group", vec!["ccmp"]);
+            settings.set("802-11-wireless-security", "psk", password.clone());
+        }
+        HotspotSecurity::Wpa3(password) => {
+            settings.set("802-11-wireless", "security", "802-11-wireless-security");
+            settings.set("802-11-wireless-security", "key-mgmt", "sae");
+            settings.set("802-11-wireless-security", "psk", password.clone());
+        }
+    }
+
+    settings
+}
+
+/// Creates and activates a hotspot on `device`.
+///
+/// Fails without touching the device if the password is invalid, the device can't act as an
+/// access point, doesn't support the requested band or security, or `channel` isn't a channel
+/// of `band`.
+pub async fn create_hotspot(
+    device: &DeviceProxy<'_>,
+    ssid: &str,
+    security: HotspotSecurity,
+    band: Option<Band>,
+    channel: Option<u32>,
+) -> Result<Hotspot> {
+    security.validate()?;
+
+    let connection = device.inner().connection().clone();
+    let device_path = OwnedObjectPath::from(device.inner().path().clone());
+
+    let wireless = WirelessProxy::new_from_path(device_path.clone(), &connection).await?;
+    let capabilities =
+        NMDeviceWifiCapabilities::from_bits_retain(wireless.wireless_capabilities().await?);
+    check_capabilities(capabilities, band, &security)?;
+
+    if let Some(channel) = channel {
+        let Some(band) = band else {
+            return Err(zbus::Error::Failure(
+                "a band is required when a channel is set".to_owned(),
+            ));
+        };
+        if !band.channels().iter().any(|c| c.channel == channel) {
+            return Err(zbus::Error::Failure(format!(
+                "channel {channel} is not valid for band {}",
+                band.as_str()
+            )));
+        }
+    }
+
+    let interface = device.interface().await?;
+    let settings = hotspot_settings(&interface, ssid, &security, band, channel);
+
+    let mut options = HashMap::new();
+    options.insert("persist", Value::new("memory"));
+
+    let nm = NetworkManagerProxy::new(&connection).await?;
+    let (settings_path, active_path, _) = nm
+        .add_and_activate_connection2(
+            settings.to_dbus(),
+            &device_path,
+            &ObjectPath::try_from("/")?,
+            options,
+        )
+        .await?;
+
+    Ok(Hotspot {
+        connection,
+        interface,
+        settings_path,
+        active_path,
+    })
+}
+
+fn check_capabilities(
+    capabilities: NMDeviceWifiCapabilities,
+    band: Option<Band>,
+    security: &HotspotSecurity,
+) -> Result<()> {
+    if !capabilities.contains(NMDeviceWifiCapabilities::AP) {
+        return Err(zbus::Error::Failure(
+            "device does not support access point mode".to_owned(),
+        ));
+    }
+
+    // NetworkManager doesn't report SAE support, RSN with CCMP is what both WPA2 and WPA3
+    // hotspots need at least.
+    let rsn = NMDeviceWifiCapabilities::RSN | NMDeviceWifiCapabilities::CIPHER_CCMP;
+    let name = match security {
+        HotspotSecurity::Open => None,
+        HotspotSecurity::Wpa2(_) => Some("WPA2"),
+        HotspotSecurity::Wpa3(_) => Some("WPA3"),
+    };
+    if let Some(name) = name
+        && !capabilities.contains(rsn)
+    {
+        return Err(zbus::Error::Failure(format!(
+            "device does not support {name} (RSN with CCMP)"
+        )));
+    }
+
+    // Without FREQ_VALID the driver doesn't report band support, so let NetworkManager decide.
+    if let Some(band) = band {
+        let band_capability = match band {
+            Band::A => NMDeviceWifiCapabilities::FREQ_5GHZ,
+            Band::Bg => NMDeviceWifiCapabilities::FREQ_2GHZ,
+        };
+        if capabilities.contains(NMDeviceWifiCapabilities::FREQ_VALID)
+            && !capabilities.contains(band_capability)
+        {
+            return Err(zbus::Error::Failure(format!(
+                "device does not support band {}",
+                band.as_str()
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// A running hotspot created by [`create_hotspot`].
+///
+/// The profile is kept in memory only; call [`Hotspot::stop`] to tear it down.
+pub struct Hotspot {
+    connection: Connection,
+    interface: String,
+    settings_path: OwnedObjectPath,
+    active_path: OwnedObjectPath,
+}
+
+impl Hotspot {
+    pub fn interface(&self) -> &str {
+        &self.interface
+    }
+
+    pub fn settings_path(&self) -> &OwnedObjectPath {
+        &self.settings_path
+    }
+
+    pub fn active_path(&self) -> &OwnedObjectPath {
+        &self.active_path
+    }
+
+    pub async fn active_connection(&self) -> Result<ActiveProxy<'_>> {
+        ActiveProxy::new_from_path(self.active_path.clone(), &self.connection).await
+    }
+
+    /// Counts the clients seen on the hotspot interface.
+    ///
+    /// A client is any MAC address with a complete ARP entry on the interface. DHCP leases
+    /// aren't counted, they outlive the clients that took them.
+    pub fn connected_clients(&self) -> std::io::Result<usize> {
+        Ok(arp_clients(&std::fs::read_to_string(ARP_TABLE)?, &self.interface).len())
+    }
+
+    /// Deletes the hotspot profile, which also deactivates it.
+    pub async fn stop(self) -> Result<()> {
+        SettingsConnectionProxy::new_from_path(self.settings_path, &self.connection)
+            .await?
+            .delete()
+            .await
+    }
+}
+
+/// MAC addresses of complete ARP entries on `interface`.
+fn arp_clients(arp_table: &str, interface: &str) -> HashSet<String> {
+    const ATF_COM: u32 = 0x2;
+
+    arp_table
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let [_, _, flags, hw_address, _, device] = fields[..] else {
+                return None;
+            };
+            let flags = u32::from_str_radix(flags.trim_start_matches("0x"), 16).ok()?;
+            (device == interface && flags & ATF_COM != 0).then(|| hw_address.to_lowercase())
+        })
+        .collect()
+}
@@ -0,0 +1,629 @@
+//! Import of ifupdown `/etc/network/interfaces` files.
+//!
+//! The `inet` and `inet6` stanzas of an interface are merged into one profile, further stanzas
+//! of a family adding secondary addresses. Bonds, bridges and VLANs are recognised by their
+//! `bond-*`, `bridge_ports` and `vlan-raw-device` options (or a `<device>.<id>` interface
+//! name); their members become port profiles, created for members without a stanza of their
+//! own. Files pulled in with `source` are not read.
+
+use std::collections::{HashMap, HashSet};
+use std::net::IpAddr;
+
+use super::connection_settings::ConnectionSettings;
+use super::import::{
+    BOND_OPTIONS, ImportReport, ParseError, add_address, add_dns, netmask_prefix, new_uuid,
+};
+
+const ETHERNET: &str = "802-3-ethernet";
+
+/// Parses an `/etc/network/interfaces` file.
+pub fn import_ifupdown(input: &str) -> Result<ImportReport, ParseError> {
+    let mut report = ImportReport::default();
+    let mut interfaces: Vec<Interface> = Vec::new();
+    let mut auto: HashSet<String> = HashSet::new();
+    // The stanza options belong to
+    let mut current = Stanza::None;
+
+    for (line_number, line) in logical_lines(input) {
+        let mut words = line.split_whitespace();
+        let Some(keyword) = words.next() else {
+            continue;
+        };
+
+        match keyword {
+            "auto" | "allow-auto" | "allow-hotplug" => {
+                auto.extend(words.map(ToOwned::to_owned));
+                current = Stanza::None;
+            }
+            "iface" => {
+                let (Some(name), Some(family), Some(method_name)) =
+                    (words.next(), words.next(), words.next())
+                else {
+                    return Err(ParseError::new(
+                        line_number,
+                        "expected iface <name> <family> <method>",
+                    ));
+                };
+                let family = match family {
+                    "inet" => Family::Inet,
+                    "inet6" => Family::Inet6,
+                    _ => {
+                        report.skip(line_number, family, "unsupported address family");
+                        current = Stanza::Ignored;
+                        continue;
+                    }
+                };
+
+                let idx = match interfaces.iter().position(|iface| iface.name == name) {
+                    Some(idx) => idx,
+                    None => {
+                        interfaces.push(Interface::new(name, line_number));
+                        interfaces.len() - 1
+                    }
+                };
+                // Further stanzas of a family add addresses, the first one sets the method
+                let method = match family {
+                    Family::Inet => &mut interfaces[idx].inet,
+                    Family::Inet6 => &mut interfaces[idx].inet6,
+                };
+                method.get_or_insert_with(|| method_name.to_owned());
+                current = Stanza::Iface(idx, family, line_number);
+            }
+            "mapping" => {
+                report.skip(line_number, keyword, "no NetworkManager equivalent");
+                current = Stanza::Ignored;
+            }
+            "source" | "source-directory" | "no-auto-down" | "no-scripts" => {
+                report.skip(line_number, keyword, "no NetworkManager equivalent");
+                current = Stanza::None;
+            }
+            // `allow-<class>` lines for classes other than the ones above
+            keyword if keyword.starts_with("allow-") => {
+                report.skip(line_number, keyword, "no NetworkManager equivalent");
+                current = Stanza::None;
+            }
+            _ => {
+                let (idx, family, stanza) = match current {
+                    Stanza::Iface(idx, family, stanza) => (idx, family, stanza),
+                    Stanza::Ignored => {
+                        report.skip(line_number, keyword, "option of an ignored stanza");
+                        continue;
+                    }
+                    Stanza::None => {
+                        return Err(ParseError::new(
+                            line_number,
+                            "option outside of an iface stanza",
+                        ));
+                    }
+                };
+                interfaces[idx].options.push(Directive {
+                    line: line_number,
+                    stanza,
+                    family,
+                    // ifupdown treats `_` and `-` in option names the same
+                    name: keyword.replace('_', "-"),
+                    value: words.collect::<Vec<_>>().join(" "),
+                });
+            }
+        }
+    }
+
+    // Members named by their controller, with the controller name and port type
+    let mut ports: Vec<(String, String, &'static str)> = Vec::new();
+    for interface in &interfaces {
+        for (option, port_type) in [("bond-slaves", "bond"), ("bridge-ports", "bridge")] {
+            if let Some(members) = interface.option(option) {
+                for member in members.split_whitespace() {
+                    if member != "none" {
+                        ports.push((member.to_owned(), interface.name.clone(), port_type));
+                    }
+                }
+            }
+        }
+        // Members can also name their bond instead
+        if let Some(master) = interface.option("bond-master") {
+            ports.push((interface.name.clone(), master.to_owned(), "bond"));
+        }
+    }
+
+    for interface in &interfaces {
+        if interface.inet.as_deref() == Some("loopback") {
+            continue;
+        }
+        let port = ports
+            .iter()
+            .find(|(member, _, _)| *member == interface.name);
+        let settings =
+            import_interface(interface, port, auto.contains(&interface.name), &mut report);
+        report.connections.push(settings);
+    }
+
+    // Members without a stanza of their own
+    let mut added = HashSet::new();
+    for (member, controller, port_type) in &ports {
+        if interfaces.iter().any(|iface| iface.name == *member) || !added.insert(member) {
+            continue;
+        }
+        let mut settings = base(member, ETHERNET, true);
+        settings.add_setting(ETHERNET);
+        settings.set("connection", "master", controller.clone());
+        settings.set("connection", "slave-type", *port_type);
+        report.connections.push(settings);
+    }
+
+    Ok(report)
+}
+
+/// The stanza option lines belong to.
+#[derive(Clone, Copy)]
+enum Stanza {
+    /// Before the first stanza or after a single line like `auto`.
+    None,
+    /// An `iface` stanza, by index into the interfaces, family and line of the stanza.
+    Iface(usize, Family, usize),
+    /// A stanza that isn't translated, like `mapping`, whose options are reported.
+    Ignored,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Family {
+    Inet,
+    Inet6,
+}
+
+struct Directive {
+    line: usize,
+    /// The line of the `iface` stanza the option is in.
+    stanza: usize,
+    family: Family,
+    name: String,
+    value: String,
+}
+
+struct Interface {
+    name: String,
+    line: usize,
+    inet: Option<String>,
+    inet6: Option<String>,
+    options: Vec<Directive>,
+}
+
+impl Interface {
+    fn new(name: &str, line: usize) -> Interface {
+        Interface {
+            name: name.to_owned(),
+            line,
+            inet: None,
+            inet6: None,
+            options: Vec::new(),
+        }
+    }
+
+    fn option(&self, name: &str) -> Option<&str> {
+        self.options
+            .iter()
+            .find(|option| option.name == name)
+            .map(|option| option.value.as_str())
+    }
+
+    /// The VLAN parent and ID, from `vlan-raw-device` or a `<device>.<id>` name.
+    fn vlan(&self) -> Option<(String, Option<u32>)> {
+        let from_name = self
+            .name
+            .rsplit_once('.')
+            .and_then(|(parent, id)| Some((parent.to_owned(), id.parse().ok()?)));
+        let id_from_name = |name: &str| {
+            name.trim_start_matches(|c: char| !c.is_ascii_digit())
+                .parse()
+                .ok()
+        };
+        match self.option("vlan-raw-device") {
+            Some(parent) => Some((
+                parent.to_owned(),
+                from_name
+                    .map(|(_, id)| id)
+                    .or_else(|| id_from_name(&self.name)),
+            )),
+            None => from_name.map(|(parent, id)| (parent, Some(id))),
+        }
+    }
+}
+
+fn import_interface(
+    interface: &Interface,
+    port: Option<&(String, String, &'static str)>,
+    auto: bool,
+    report: &mut ImportReport,
+) -> ConnectionSettings {
+    let is_bond = interface
+        .options
+        .iter()
+        .any(|option| option.name.starts_with("bond-") && option.name != "bond-master");
+    let is_bridge = interface.option("bridge-ports").is_some();
+    let vlan = interface.vlan();
+
+    let connection_type = if is_bond {
+        "bond"
+    } else if is_bridge {
+        "bridge"
+    } else if vlan.is_some() {
+        "vlan"
+    } else {
+        ETHERNET
+    };
+    let mut settings = base(&interface.name, connection_type, auto);
+
+    match connection_type {
+        "bond" => {
+            let mut options = HashMap::new();
+            for option in &interface.options {
+                let Some(name) = option.name.strip_prefix("bond-") else {
+                    continue;
+                };
+                let name = name.replace('-', "_");
+                if name == "slaves" {
+                    continue;
+                }
+                if BOND_OPTIONS.contains(&name.as_str()) {
+                    let value = option
+                        .value
+                        .split_whitespace()
+                        .collect::<Vec<_>>()
+                        .join(",");
+                    options.insert(name, value);
+                } else {
+                    report.skip(option.line, &option.name, "unsupported bond option");
+                }
+            }
+            settings.set("bond", "options", options);
+        }
+        "bridge" => {
+            settings.add_setting("bridge");
+            for option in &interface.options {
+                let property = match option.name.as_str() {
+                    "bridge-stp" => {
+                        settings.set(
+                            "bridge",
+                            "stp",
+                            matches!(option.value.as_str(), "on" | "yes"),
+                        );
+                        continue;
+                    }
+                    "bridge-fd" => "forward-delay",
+                    "bridge-hello" => "hello-time",
+                    "bridge-maxage" => "max-age",
+                    "bridge-ageing" => "ageing-time",
+                    "bridge-bridgeprio" => "priority",
+                    name if name.starts_with("bridge-") && name != "bridge-ports" => {
+                        report.skip(option.line, name, "unsupported bridge option");
+                        continue;
+                    }
+                    _ => continue,
+                };
+                match option.value.parse::<f64>() {
+                    // Delays may be given with a fraction, NetworkManager takes whole seconds
+                    Ok(value) if value >= 0.0 && value <= f64::from(u32::MAX) => {
+                        settings.set("bridge", property, value.round() as u32)
+                    }
+                    _ => report.skip(option.line, &option.name, "invalid value"),
+                }
+            }
+        }
+        "vlan" => {
+            if let Some((parent, id)) = vlan {
+                settings.set("vlan", "parent", parent);
+                match id {
+                    Some(id) => settings.set("vlan", "id", id),
+                    None => report.skip(interface.line, &interface.name, "no VLAN ID in the name"),
+                }
+            }
+        }
+        _ => settings.add_setting(ETHERNET),
+    }
+
+    if let Some((_, controller, port_type)) = port {
+        settings.set("connection", "master", controller.clone());
+        settings.set("connection", "slave-type", *port_type);
+    } else {
+        import_ip(interface, &mut settings, report);
+    }
+
+    for option in &interface.options {
+        match option.name.as_str() {
+            "mtu" => match option.value.parse::<u32>() {
+                Ok(mtu) => settings.set(ETHERNET, "mtu", mtu),
+                Err(_) => report.skip(option.line, &option.name, "invalid value"),
+            },
+            "hwaddress" => {
+                // `hwaddress [ether] <mac>`
+                match option.value.split_whitespace().last() {
+                    Some(mac) => settings.set(ETHERNET, "assigned-mac-address", mac.to_owned()),
+                    None => report.skip(option.line, &option.name, "invalid value"),
+                }
+            }
+            "address" | "netmask" | "gateway" | "dns-nameservers" | "dns-search" | "metric"
+            | "bond-master" | "bridge-ports" | "vlan-raw-device" => {}
+            name if name.starts_with("bond-") || name.starts_with("bridge-") => {}
+            "pre-up" | "up" | "post-up" | "pre-down" | "down" | "post-down" => {
+                report.skip(option.line, &option.name, "hook commands are not run")
+            }
+            _ => report.skip(option.line, &option.name, "no NetworkManager equivalent"),
+        }
+    }
+
+    settings
+}
+
+fn import_ip(interface: &Interface, settings: &mut ConnectionSettings, report: &mut ImportReport) {
+    let ipv4_method = match interface.inet.as_deref() {
+        Some("static") => "manual",
+        Some("dhcp") => "auto",
+        Some("manual") | None => "disabled",
+        Some(method) => {
+            report.skip(interface.line, method, "unsupported inet method");
+            "disabled"
+        }
+    };
+    let ipv6_method = match interface.inet6.as_deref() {
+        Some("static") => "manual",
+        Some("auto") => "auto",
+        Some("dhcp") => "dhcp",
+        Some("manual") | None => "ignore",
+        Some(method) => {
+            report.skip(interface.line, method, "unsupported inet6 method");
+            "ignore"
+        }
+    };
+    settings.set("ipv4", "method", ipv4_method);
+    settings.set("ipv6", "method", ipv6_method);
+
+    for family in [Family::Inet, Family::Inet6] {
+        let options: Vec<&Directive> = interface
+            .options
+            .iter()
+            .filter(|option| option.family == family)
+            .collect();
+        let find = |name: &str| options.iter().find(|option| option.name == name);
+        let setting = match family {
+            Family::Inet => "ipv4",
+            Family::Inet6 => "ipv6",
+        };
+
+        // Prefix lengths by stanza: a dotted mask for inet, a prefix length for inet6
+        let mut prefixes = HashMap::new();
+        for netmask in options.iter().filter(|option| option.name == "netmask") {
+            match netmask_prefix(&netmask.value).or_else(|| netmask.value.parse().ok()) {
+                Some(prefix) => {
+                    prefixes.entry(netmask.stanza).or_insert(prefix);
+                }
+                None => report.skip(netmask.line, &netmask.name, "invalid value"),
+            }
+        }
+        // Every `address` line, secondary addresses included
+        for directive in options.iter().filter(|option| option.name == "address") {
+            let address = match prefixes.get(&directive.stanza) {
+                Some(prefix) if !directive.value.contains('/') => {
+                    format!("{}/{prefix}", directive.value)
+                }
+                _ => directive.value.clone(),
+            };
+            if !add_address(settings, &address) {
+                report.skip(directive.line, &directive.name, "invalid value");
+            }
+        }
+        if let Some(gateway) = find("gateway") {
+            settings.set(setting, "gateway", gateway.value.clone());
+        }
+        if let Some(metric) = find("metric") {
+            match metric.value.parse::<i64>() {
+                Ok(metric) => settings.set(setting, "route-metric", metric),
+                Err(_) => report.skip(metric.line, &metric.name, "invalid value"),
+            }
+        }
+        if let Some(servers) = find("dns-nameservers") {
+            for server in servers.value.split_whitespace() {
+                match server.parse::<IpAddr>() {
+                    Ok(server) => add_dns(settings, server),
+                    Err(_) => report.skip(servers.line, &servers.name, "invalid address"),
+                }
+            }
+        }
+        if let Some(search) = find("dns-search") {
+            let domains: Vec<String> = search
+                .value
+                .split_whitespace()
+                .map(ToOwned::to_owned)
+                .collect();
+            settings.set(setting, "dns-search", domains);
+        }
+    }
+}
+
+/// The `connection` setting, named the way NetworkManager's own ifupdown plugin names the
+/// profiles it reads.
+fn base(name: &str, connection_type: &'static str, auto: bool) -> ConnectionSettings {
+    ConnectionSettings::new()
+        .with("connection", "id", format!("Ifupdown ({name})"))
+        .with("connection", "uuid", new_uuid())
+        .with("connection", "type", connection_type)
+        .with("connection", "interface-name", name.to_owned())
+        .with("connection", "autoconnect", auto)
+}
+
+/// Joins `\` continuation lines and drops comments and blank lines, keeping the number of the
+/// first physical line.
+fn logical_lines(input: &str) -> Vec<(usize, String)> {
+    let mut lines = Vec::new();
+    let mut pending: Option<(usize, String)> = None;
+
+    for (idx, line) in input.lines().enumerate() {
+        let (line_number, mut text) = pending.take().unwrap_or((idx + 1, String::new()));
+        let trimmed = line.trim();
+        if text.is_empty() && trimmed.starts_with('#') {
+            continue;
+        }
+        match trimmed.strip_suffix('\\') {
+            Some(continued) => {
+                text.push_str(continued);
+                text.push(' ');
+                pending = Some((line_number, text));
+            }
+            None => {
+                text.push_str(trimmed);
+                if !text.trim().is_empty() {
+                    lines.push((line_number, text));
+                }
+            }
+        }
+    }
+    if let Some((line_number, text)) = pending {
+        lines.push((line_number, text));
+    }
+
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use zbus::zvariant::Value;
+
+    use super::*;
+
+    const CONFIG: &str = "\
+# The loopback interface
+auto lo
+iface lo inet loopback
+
+auto eth0
+iface eth0 inet static
+    address 192.168.1.10
+    netmask 255.255.255.0
+    gateway 192.168.1.1
+    dns-nameservers 192.168.1.1 \\
+        192.168.1.2
+    up ip route add 10.0.0.0/8 via 192.168.1.254
+iface eth0 inet6 auto
+
+allow-hotplug bond0
+iface bond0 inet dhcp
+    bond-slaves eth1 eth2
+    bond-mode active-backup
+    bond_miimon 100
+
+auto br0
+iface br0 inet manual
+    bridge_ports eth3
+    bridge_fd 1.5
+    bridge_maxage -1
+
+mapping eth4
+    script /usr/local/sbin/map-scheme
+    map HOME eth4-home
+
+allow-ovs br1
+iface can0 can static
+    bitrate 125000
+
+iface eth0 inet static
+    address 192.168.1.11/24
+    address 10.0.0.10
+    netmask 255.0.0.0
+";
+
+    fn find<'a>(report: &'a ImportReport, name: &str) -> &'a ConnectionSettings {
+        report
+            .connections
+            .iter()
+            .find(|settings| settings.get_str("connection", "interface-name") == Some(name))
+            .unwrap()
+    }
+
+    #[test]
+    fn imports_interfaces() {
+        let report = import_ifupdown(CONFIG).unwrap();
+        assert_eq!(report.connections.len(), 6);
+
+        let eth0 = find(&report, "eth0");
+        assert_eq!(eth0.id(), Some("Ifupdown (eth0)"));
+        assert_eq!(eth0.get_bool("connection", "autoconnect"), Some(true));
+        assert_eq!(eth0.get_str("ipv4", "method"), Some("manual"));
+        assert_eq!(eth0.get_str("ipv4", "gateway"), Some("192.168.1.1"));
+        assert_eq!(eth0.get_str("ipv6", "method"), Some("auto"));
+        let addresses = eth0.get_dicts("ipv4", "address-data").unwrap();
+        let addresses: Vec<(String, u32)> = addresses
+            .iter()
+            .map(|address| {
+                (
+                    address["address"].downcast_ref::<String>().unwrap(),
+                    address["prefix"].downcast_ref::<u32>().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            addresses,
+            [
+                ("192.168.1.10".to_owned(), 24),
+                ("192.168.1.11".to_owned(), 24),
+                ("10.0.0.10".to_owned(), 8),
+            ]
+        );
+        assert_eq!(
+            eth0.get("ipv4", "dns"),
+            Some(&Value::from(vec![
+                u32::from_ne_bytes([192, 168, 1, 1]),
+                u32::from_ne_bytes([192, 168, 1, 2]),
+            ]))
+        );
+
+        let bond0 = find(&report, "bond0");
+        assert_eq!(bond0.connection_type(), Some("bond"));
+        let options = bond0.get_string_map("bond", "options").unwrap();
+        assert_eq!(options["mode"], "active-backup");
+        assert_eq!(options["miimon"], "100");
+        for port in ["eth1", "eth2"] {
+            assert_eq!(
+                find(&report, port).get_str("connection", "master"),
+                Some("bond0")
+            );
+        }
+
+        let br0 = find(&report, "br0");
+        assert_eq!(br0.connection_type(), Some("bridge"));
+        assert_eq!(br0.get_u32("bridge", "forward-delay"), Some(2));
+        assert_eq!(br0.get_u32("bridge", "max-age"), None);
+        assert_eq!(
+            find(&report, "eth3").get_str("connection", "slave-type"),
+            Some("bridge")
+        );
+    }
+
+    #[test]
+    fn reports_ignored_stanzas_and_options() {
+        let report = import_ifupdown(CONFIG).unwrap();
+        let mut skipped: Vec<(usize, &str)> = report
+            .untranslated
+            .iter()
+            .map(|untranslated| (untranslated.line, untranslated.directive.as_str()))
+            .collect();
+        skipped.sort();
+        assert_eq!(
+            skipped,
+            [
+                (12, "up"),
+                (25, "bridge-maxage"),
+                (27, "mapping"),
+                (28, "script"),
+                (29, "map"),
+                (31, "allow-ovs"),
+                (32, "can"),
+                (33, "bitrate"),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_option_outside_of_stanza() {
+        let err = import_ifupdown("address 10.0.0.1\n").unwrap_err();
+        assert_eq!(err.line, 1);
+    }
+}
@@ -0,0 +1,277 @@
+//! Types shared by the importers of foreign network configuration formats.
+
+#[cfg(any(
+    feature = "desired_state",
+    feature = "ifupdown",
+    feature = "networkd",
+    feature = "wg_quick"
+))]
+use std::collections::HashMap;
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::{BuildHasher, Hasher};
+#[cfg(any(
+    feature = "desired_state",
+    feature = "ifupdown",
+    feature = "networkd",
+    feature = "wg_quick"
+))]
+use std::net::IpAddr;
+#[cfg(feature = "ifupdown")]
+use std::net::Ipv4Addr;
+
+#[cfg(any(
+    feature = "desired_state",
+    feature = "ifupdown",
+    feature = "networkd",
+    feature = "wg_quick"
+))]
+use zbus::zvariant::Value;
+
+use super::connection_settings::ConnectionSettings;
+
+/// The connection profiles translated from a configuration file.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ImportReport {
+    /// Profiles ready for [`SettingsProxy::add_connection`](crate::SettingsProxy::add_connection).
+    pub connections: Vec<ConnectionSettings>,
+    /// Directives that were skipped.
+    pub untranslated: Vec<Untranslated>,
+}
+
+#[cfg(any(
+    feature = "ifupdown",
+    feature = "netplan",
+    feature = "networkd",
+    feature = "openvpn",
+    feature = "wg_quick",
+    feature = "wpa_supplicant"
+))]
+impl ImportReport {
+    pub(crate) fn skip(&mut self, line: usize, directive: &str, reason: &str) {
+        self.untranslated.push(Untranslated {
+            line,
+            directive: directive.to_owned(),
+            reason: reason.to_owned(),
+        });
+    }
+}
+
+/// A directive that couldn't be translated to NetworkManager settings.
+///
+/// Only the directive name is kept, never its value, so reports can be logged safely.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Untranslated {
+    /// 1-based line number.
+    pub line: usize,
+    pub directive: String,
+    pub reason: String,
+}
+
+impl fmt::Display for Untranslated {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}: {}", self.line, self.directive, self.reason)
+    }
+}
+
+/// A configuration file that couldn't be parsed at all.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    /// 1-based line number.
+    pub line: usize,
+    pub message: String,
+}
+
+#[cfg(any(
+    feature = "ifupdown",
+    feature = "netplan",
+    feature = "networkd",
+    feature = "openvpn",
+    feature = "wg_quick",
+    feature = "wpa_supplicant"
+))]
+impl ParseError {
+    pub(crate) fn new(line: usize, message: impl Into<String>) -> ParseError {
+        ParseError {
+            line,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Generates a random (version 4) UUID for `connection.uuid`.
+pub(crate) fn new_uuid() -> String {
+    let mut bytes = [0u8; 16];
+    for chunk in bytes.chunks_mut(8) {
+        let random = RandomState::new().build_hasher().finish();
+        chunk.copy_from_slice(&random.to_ne_bytes());
+    }
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+
+    let hex: String = bytes.iter().map(|byte| format!("{byte:02x}")).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+/// Encodes a certificate or key path the way NetworkManager expects in `802-1x` blob
+/// properties: `file://<path>` followed by a NUL byte.
+#[cfg(any(feature = "netplan", feature = "wpa_supplicant"))]
+pub(crate) fn file_blob(path: &str) -> Vec<u8> {
+    let mut blob = format!("file://{path}").into_bytes();
+    blob.push(0);
+    blob
+}
+
+/// Decodes a string of hex digit pairs.
+#[cfg(feature = "wpa_supplicant")]
+pub(crate) fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) || !hex.is_ascii() {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|idx| u8::from_str_radix(&hex[idx..idx + 2], 16).ok())
+        .collect()
+}
+
+/// Parses a colon separated MAC address into its 6 bytes.
+#[cfg(any(feature = "netplan", feature = "networkd", feature = "wpa_supplicant"))]
+pub(crate) fn parse_mac(mac: &str) -> Option<Vec<u8>> {
+    mac.split(':')
+        .map(|byte| u8::from_str_radix(byte, 16).ok())
+        .collect::<Option<Vec<u8>>>()
+        .filter(|bytes| bytes.len() == 6)
+}
+
+/// Parses an address in CIDR notation, the prefix defaults to a single host.
+#[cfg(any(
+    feature = "desired_state",
+    feature = "ifupdown",
+    feature = "netplan",
+    feature = "networkd",
+    feature = "wg_quick"
+))]
+pub(crate) fn parse_address(address: &str) -> Option<(IpAddr, u32)> {
+    let (ip, prefix) = address.split_once('/').unwrap_or((address, ""));
+    let ip = ip.parse::<IpAddr>().ok()?;
+    let max_prefix = if ip.is_ipv4() { 32 } else { 128 };
+    let prefix = match prefix {
+        "" => max_prefix,
+        prefix => prefix
+            .parse::<u32>()
+            .ok()
+            .filter(|&prefix| prefix <= max_prefix)?,
+    };
+    Some((ip, prefix))
+}
+
+/// Appends an address in CIDR notation to `ipv4.address-data` or `ipv6.address-data`,
+/// depending on its family. Returns `false` for anything that isn't an address.
+#[cfg(any(
+    feature = "desired_state",
+    feature = "ifupdown",
+    feature = "networkd",
+    feature = "wg_quick"
+))]
+pub(crate) fn add_address(settings: &mut ConnectionSettings, address: &str) -> bool {
+    let Some((ip, prefix)) = parse_address(address) else {
+        return false;
+    };
+    let family = if ip.is_ipv4() { "ipv4" } else { "ipv6" };
+
+    let mut addresses: Vec<HashMap<String, Value<'static>>> = settings
+        .get_dicts(family, "address-data")
+        .unwrap_or_default()
+        .into_iter()
+        .map(|address| {
+            address
+                .into_iter()
+                .map(|(key, value)| (key, Value::from(value)))
+                .collect()
+        })
+        .collect();
+    addresses.push(HashMap::from([
+        ("address".to_owned(), Value::from(ip.to_string())),
+        ("prefix".to_owned(), Value::from(prefix)),
+    ]));
+    settings.set(family, "address-data", addresses);
+    true
+}
+
+/// Appends a DNS server to `ipv4.dns` or `ipv6.dns`, which NetworkManager takes as integers in
+/// network byte order and byte arrays respectively.
+#[cfg(any(feature = "desired_state", feature = "ifupdown", feature = "networkd"))]
+pub(crate) fn add_dns(settings: &mut ConnectionSettings, server: IpAddr) {
+    match server {
+        IpAddr::V4(server) => {
+            let mut servers: Vec<u32> = settings
+                .get("ipv4", "dns")
+                .and_then(|dns| dns.try_clone().ok()?.downcast().ok())
+                .unwrap_or_default();
+            servers.push(u32::from_ne_bytes(server.octets()));
+            settings.set("ipv4", "dns", servers);
+        }
+        IpAddr::V6(server) => {
+            let mut servers: Vec<Vec<u8>> = settings
+                .get("ipv6", "dns")
+                .and_then(|dns| dns.try_clone().ok()?.downcast().ok())
+                .unwrap_or_default();
+            servers.push(server.octets().to_vec());
+            settings.set("ipv6", "dns", servers);
+        }
+    }
+}
+
+/// Converts a dotted IPv4 netmask like `255.255.255.0` to a prefix length.
+#[cfg(feature = "ifupdown")]
+pub(crate) fn netmask_prefix(netmask: &str) -> Option<u32> {
+    let mask = u32::from(netmask.parse::<Ipv4Addr>().ok()?);
+    (mask.leading_ones() == mask.count_ones()).then_some(mask.count_ones())
+}
+
+/// Kernel bond options accepted in `bond.options`.
+#[cfg(feature = "ifupdown")]
+pub(crate) const BOND_OPTIONS: &[&str] = &[
+    "mode",
+    "miimon",
+    "updelay",
+    "downdelay",
+    "arp_interval",
+    "arp_ip_target",
+    "arp_validate",
+    "arp_all_targets",
+    "primary",
+    "primary_reselect",
+    "fail_over_mac",
+    "use_carrier",
+    "ad_select",
+    "xmit_hash_policy",
+    "resend_igmp",
+    "lacp_rate",
+    "num_grat_arp",
+    "num_unsol_na",
+    "all_slaves_active",
+    "min_links",
+    "lp_interval",
+    "packets_per_slave",
+    "tlb_dynamic_lb",
+    "ad_actor_sys_prio",
+    "ad_user_port_key",
+    "ad_actor_system",
+    "peer_notif_delay",
+];
@@ -0,0 +1,381 @@
+//! Prometheus metrics.
+//!
+//! [`MetricsCollector::collect`] reads the NetworkManager state, the devices with their
+//! traffic counters and the active connections, and returns [`Metrics`], which formats as the
+//! Prometheus text exposition format. The `nm-exporter` binary serves it over HTTP.
+//!
+//! Enumerations are exported as their numeric value with the lower case name in a label, e.g.
+//! `networkmanager_state{state="connected_global"} 70`.
+
+use std::fmt::{self, Display};
+use std::time::Duration;
+
+use zbus::zvariant::OwnedObjectPath;
+use zbus::{Connection, Result};
+
+use super::NetworkManagerProxy;
+use super::access_point::AccessPointProxy;
+use super::active::ActiveProxy;
+use super::dbus_interface_types::{
+    NMActiveConnectionState, NMConnectivityState, NMDeviceState, NMDeviceType, NMState, enum_name,
+};
+use super::device::DeviceProxy;
+use super::statistics::StatisticsProxy;
+use super::wired::WiredProxy;
+use super::wireless::WirelessProxy;
+
+/// The `Content-Type` of the text exposition format.
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Every metric with its type and help text, in the order they are written.
+const FAMILIES: &[(&str, MetricKind, &str)] = &[
+    (
+        "networkmanager_up",
+        MetricKind::Gauge,
+        "Whether NetworkManager could be reached.",
+    ),
+    (
+        "networkmanager_state",
+        MetricKind::Gauge,
+        "Overall networking state (NMState).",
+    ),
+    (
+        "networkmanager_connectivity",
+        MetricKind::Gauge,
+        "Global connectivity (NMConnectivityState).",
+    ),
+    (
+        "networkmanager_device_state",
+        MetricKind::Gauge,
+        "Device state (NMDeviceState).",
+    ),
+    (
+        "networkmanager_device_mtu_bytes",
+        MetricKind::Gauge,
+        "Device MTU.",
+    ),
+    (
+        "networkmanager_device_carrier",
+        MetricKind::Gauge,
+        "Whether a wired device has carrier.",
+    ),
+    (
+        "networkmanager_device_speed_mbps",
+        MetricKind::Gauge,
+        "Link speed of a wired device in Mbit/s.",
+    ),
+    (
+        "networkmanager_device_receive_bytes_total",
+        MetricKind::Counter,
+        "Bytes received by the device.",
+    ),
+    (
+        "networkmanager_device_transmit_bytes_total",
+        MetricKind::Counter,
+        "Bytes sent by the device.",
+    ),
+    (
+        "networkmanager_wifi_bitrate_kbps",
+        MetricKind::Gauge,
+        "Current bitrate of a Wi-Fi device in Kbit/s.",
+    ),
+    (
+        "networkmanager_wifi_signal_strength_percent",
+        MetricKind::Gauge,
+        "Signal strength of the access point a Wi-Fi device is associated with.",
+    ),
+    (
+        "networkmanager_wifi_frequency_mhz",
+        MetricKind::Gauge,
+        "Frequency of the access point a Wi-Fi device is associated with.",
+    ),
+    (
+        "networkmanager_active_connection_state",
+        MetricKind::Gauge,
+        "State of an active connection (NMActiveConnectionState).",
+    ),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetricKind {
+    Gauge,
+    Counter,
+}
+
+impl MetricKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            MetricKind::Gauge => "gauge",
+            MetricKind::Counter => "counter",
+        }
+    }
+}
+
+/// One time series of a metric.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sample {
+    pub labels: Vec<(&'static str, String)>,
+    pub value: f64,
+}
+
+/// A metric and its samples.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MetricFamily {
+    pub name: &'static str,
+    pub kind: MetricKind,
+    pub help: &'static str,
+    pub samples: Vec<Sample>,
+}
+
+/// The metrics of one collection.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Metrics {
+    pub families: Vec<MetricFamily>,
+}
+
+impl Metrics {
+    fn new() -> Metrics {
+        Metrics {
+            families: FAMILIES
+                .iter()
+                .map(|(name, kind, help)| MetricFamily {
+                    name,
+                    kind: *kind,
+                    help,
+                    samples: Vec::new(),
+                })
+                .collect(),
+        }
+    }
+
+    /// The metrics to serve when NetworkManager can't be reached: `networkmanager_up 0`.
+    pub fn unavailable() -> Metrics {
+        let mut metrics = Metrics::new();
+        metrics.push("networkmanager_up", Vec::new(), 0.0);
+        metrics
+    }
+
+    /// Looks up a metric by name.
+    pub fn family(&self, name: &str) -> Option<&MetricFamily> {
+        self.families.iter().find(|family| family.name == name)
+    }
+
+    /// Adds the samples of `other`, which has the same families.
+    fn append(&mut self, other: Metrics) {
+        for (family, other) in self.families.iter_mut().zip(other.families) {
+            family.samples.extend(other.samples);
+        }
+    }
+
+    fn push(&mut self, name: &str, labels: Vec<(&'static str, String)>, value: f64) {
+        if let Some(family) = self.families.iter_mut().find(|family| family.name == name) {
+            family.samples.push(Sample { labels, value });
+        }
+    }
+}
+
+impl Display for Metrics {
+    /// Formats the metrics in the text exposition format, leaving out metrics without samples.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for family in &self.families {
+            if family.samples.is_empty() {
+                continue;
+            }
+            writeln!(f, "# HELP {} {}", family.name, family.help)?;
+            writeln!(f, "# TYPE {} {}", family.name, family.kind.as_str())?;
+            for sample in &family.samples {
+                f.write_str(family.name)?;
+                for (idx, (name, value)) in sample.labels.iter().enumerate() {
+                    let separator = if idx == 0 { '{' } else { ',' };
+                    write!(f, "{separator}{name}=\"{}\"", escape_label(value))?;
+                }
+                if !sample.labels.is_empty() {
+                    f.write_str("}")?;
+                }
+                writeln!(f, " {}", sample.value)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Reads the metrics from NetworkManager.
+pub struct MetricsCollector {
+    connection: Connection,
+    refresh_rate: Duration,
+}
+
+impl MetricsCollector {
+    /// `refresh_rate` is how often the traffic counters should be updated. NetworkManager
+    /// doesn't update them by default, so devices with a slower or no refresh rate get this
+    /// one. That needs the `org.freedesktop.NetworkManager.network-control` permission; without
+    /// it the counters stay as they are.
+    pub fn new(connection: &Connection, refresh_rate: Duration) -> MetricsCollector {
+        MetricsCollector {
+            connection: connection.clone(),
+            refresh_rate,
+        }
+    }
+
+    pub async fn collect(&self) -> Result<Metrics> {
+        let mut metrics = Metrics::new();
+        let nm = NetworkManagerProxy::new(&self.connection).await?;
+        metrics.push("networkmanager_up", Vec::new(), 1.0);
+
+        let state = nm.state().await?;
+        metrics.push(
+            "networkmanager_state",
+            vec![("state", enum_name::<NMState>(state))],
+            state.into(),
+        );
+        let connectivity = nm.connectivity().await?;
+        metrics.push(
+            "networkmanager_connectivity",
+            vec![(
+                "connectivity",
+                enum_name::<NMConnectivityState>(connectivity),
+            )],
+            connectivity.into(),
+        );
+
+        // Devices and active connections can go away while they are read, they are left out
+        for path in nm.get_devices().await? {
+            match self.device(path).await {
+                Ok(device) => metrics.append(device),
+                Err(zbus::Error::MethodError(..) | zbus::Error::FDO(_)) => {}
+                Err(err) => return Err(err),
+            }
+        }
+
+        for path in nm.active_connections().await? {
+            match self.active_connection(path, &mut metrics).await {
+                Ok(()) => {}
+                Err(zbus::Error::MethodError(..) | zbus::Error::FDO(_)) => {}
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(metrics)
+    }
+
+    /// The metrics of one device, in a [`Metrics`] of their own so a device that goes away
+    /// halfway leaves nothing behind.
+    async fn device(&self, path: OwnedObjectPath) -> Result<Metrics> {
+        let mut metrics = Metrics::new();
+        let device = DeviceProxy::new_from_path(path.clone(), &self.connection).await?;
+        let device_type =
+            NMDeviceType::try_from(device.device_type().await?).unwrap_or(NMDeviceType::UNKNOWN);
+        let labels = vec![
+            ("interface", device.interface().await?),
+            ("type", device_type.to_string()),
+        ];
+
+        let state = device.state().await?;
+        let mut state_labels = labels.clone();
+        state_labels.push(("state", enum_name::<NMDeviceState>(state)));
+        metrics.push("networkmanager_device_state", state_labels, state.into());
+        metrics.push(
+            "networkmanager_device_mtu_bytes",
+            labels.clone(),
+            device.mtu().await?.into(),
+        );
+
+        let statistics = StatisticsProxy::new_from_path(path.clone(), &self.connection).await?;
+        self.enable_statistics(&statistics).await?;
+        metrics.push(
+            "networkmanager_device_receive_bytes_total",
+            labels.clone(),
+            statistics.rx_bytes().await? as f64,
+        );
+        metrics.push(
+            "networkmanager_device_transmit_bytes_total",
+            labels.clone(),
+            statistics.tx_bytes().await? as f64,
+        );
+
+        match device_type {
+            NMDeviceType::ETHERNET => {
+                let wired = WiredProxy::new_from_path(path, &self.connection).await?;
+                metrics.push(
+                    "networkmanager_device_carrier",
+                    labels.clone(),
+                    f64::from(u8::from(wired.carrier().await?)),
+                );
+                metrics.push(
+                    "networkmanager_device_speed_mbps",
+                    labels,
+                    wired.speed().await?.into(),
+                );
+            }
+            NMDeviceType::WIFI => {
+                let wireless = WirelessProxy::new_from_path(path, &self.connection).await?;
+                metrics.push(
+                    "networkmanager_wifi_bitrate_kbps",
+                    labels.clone(),
+                    wireless.bitrate().await?.into(),
+                );
+                let access_point = wireless.active_access_point().await?;
+                if access_point.as_str() == "/" {
+                    return Ok(metrics);
+                }
+                let access_point =
+                    AccessPointProxy::new_from_path(access_point, &self.connection).await?;
+                let mut labels = labels;
+                labels.push((
+                    "ssid",
+                    String::from_utf8_lossy(&access_point.ssid().await?).into_owned(),
+                ));
+                labels.push(("bssid", access_point.hw_address().await?));
+                metrics.push(
+                    "networkmanager_wifi_signal_strength_percent",
+                    labels.clone(),
+                    access_point.strength().await?.into(),
+                );
+                metrics.push(
+                    "networkmanager_wifi_frequency_mhz",
+                    labels,
+                    access_point.frequency().await?.into(),
+                );
+            }
+            _ => {}
+        }
+        Ok(metrics)
+    }
+
+    /// Pushes the state of an active connection once all its properties are read.
+    async fn active_connection(&self, path: OwnedObjectPath, metrics: &mut Metrics) -> Result<()> {
+        let active = ActiveProxy::new_from_path(path, &self.connection).await?;
+        let state = active.state().await?;
+        let labels = vec![
+            ("id", active.id().await?),
+            ("uuid", active.uuid().await?),
+            ("type", active.type_().await?),
+            ("state", enum_name::<NMActiveConnectionState>(state)),
+        ];
+        metrics.push(
+            "networkmanager_active_connection_state",
+            labels,
+            state.into(),
+        );
+        Ok(())
+    }
+
+    /// Raises the refresh rate of the traffic counters of a device to the collector's.
+    async fn enable_statistics(&self, statistics: &StatisticsProxy<'_>) -> Result<()> {
+        let refresh_rate_ms = u32::try_from(self.refresh_rate.as_millis()).unwrap_or(u32::MAX);
+        let current = statistics.refresh_rate_ms().await?;
+        if refresh_rate_ms != 0 && (current == 0 || current > refresh_rate_ms) {
+            // Not being allowed to isn't worth failing the collection over
+            let _ = statistics.set_refresh_rate_ms(refresh_rate_ms).await;
+        }
+        Ok(())
+    }
+}
+
+/// Escapes a label value: backslashes, double quotes and line feeds.
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
@@ -0,0 +1,1567 @@
+//! Conversion between netplan YAML and NetworkManager profiles.
+//!
+//! Covers the `ethernets`, `wifis`, `bonds`, `bridges`, `vlans` and `tunnels` stanzas of netplan
+//! version 2. Bond and bridge members become ports of their controller, the way netplan's own
+//! NetworkManager renderer generates them. Every Wi-Fi access point becomes its own profile.
+//!
+//! YAML documents carry no line information once parsed, so [`Untranslated`] entries refer to
+//! keys by their path, like `ethernets.eth0.optional`, with line 0.
+//!
+//! [`Untranslated`]: super::import::Untranslated
+
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use serde_yaml::{Mapping, Number, Value as Yaml};
+use zbus::zvariant::Value;
+
+use super::connection_settings::ConnectionSettings;
+use super::import::{ImportReport, ParseError, file_blob, new_uuid, parse_address, parse_mac};
+use super::settings_connection::SettingsConnectionProxy;
+use super::update::get_connection_secrets;
+use super::wg_quick::{WireGuardConfig, WireGuardInterface, WireGuardPeer, dict_str, dict_u32};
+
+const ETHERNET: &str = "802-3-ethernet";
+const WIRELESS: &str = "802-11-wireless";
+const SECURITY: &str = "802-11-wireless-security";
+const IEEE8021X: &str = "802-1x";
+
+/// Keys handled by [`import_ip`].
+const IP_KEYS: &[&str] = &[
+    "dhcp4",
+    "dhcp6",
+    "addresses",
+    "gateway4",
+    "gateway6",
+    "routes",
+    "nameservers",
+    "dhcp4-overrides",
+    "dhcp6-overrides",
+    "ipv6-privacy",
+];
+
+/// Keys handled for every kind of definition.
+const COMMON_KEYS: &[&str] = &["networkmanager", "match", "set-name", "mtu", "macaddress"];
+
+/// netplan bond parameters and the kernel option names used in `bond.options`.
+const BOND_OPTIONS: &[(&str, &str)] = &[
+    ("mode", "mode"),
+    ("lacp-rate", "lacp_rate"),
+    ("mii-monitor-interval", "miimon"),
+    ("min-links", "min_links"),
+    ("transmit-hash-policy", "xmit_hash_policy"),
+    ("ad-select", "ad_select"),
+    ("all-members-active", "all_slaves_active"),
+    ("arp-interval", "arp_interval"),
+    ("arp-ip-targets", "arp_ip_target"),
+    ("arp-validate", "arp_validate"),
+    ("arp-all-targets", "arp_all_targets"),
+    ("up-delay", "updelay"),
+    ("down-delay", "downdelay"),
+    ("fail-over-mac-policy", "fail_over_mac"),
+    ("gratuitous-arp", "num_grat_arp"),
+    ("packets-per-member", "packets_per_slave"),
+    ("primary-reselect-policy", "primary_reselect"),
+    ("resend-igmp", "resend_igmp"),
+    ("learn-packet-interval", "lp_interval"),
+    ("primary", "primary"),
+];
+
+/// netplan bridge parameters that map to `u32` properties of the `bridge` setting.
+const BRIDGE_OPTIONS: &[(&str, &str)] = &[
+    ("priority", "priority"),
+    ("forward-delay", "forward-delay"),
+    ("hello-time", "hello-time"),
+    ("max-age", "max-age"),
+    ("ageing-time", "ageing-time"),
+];
+
+/// `ip-tunnel.mode` values, `NMIPTunnelMode`.
+const TUNNEL_MODES: &[(u32, &str)] = &[
+    (1, "ipip"),
+    (2, "gre"),
+    (3, "sit"),
+    (4, "isatap"),
+    (5, "vti"),
+    (6, "ip6ip6"),
+    (7, "ipip6"),
+    (8, "ip6gre"),
+    (9, "vti6"),
+    (10, "gretap"),
+    (11, "ip6gretap"),
+];
+
+/// Parses a netplan YAML document.
+pub fn import_netplan(input: &str) -> Result<ImportReport, ParseError> {
+    let document: Yaml = serde_yaml::from_str(input).map_err(|err| {
+        ParseError::new(
+            err.location().map_or(0, |location| location.line()),
+            err.to_string(),
+        )
+    })?;
+    let Some(network) = document.get("network") else {
+        return Err(ParseError::new(0, "missing network key"));
+    };
+
+    let mut report = ImportReport::default();
+    for (key, _) in entries(Some(network)) {
+        if !matches!(
+            key.as_str(),
+            "version"
+                | "renderer"
+                | "ethernets"
+                | "wifis"
+                | "bonds"
+                | "bridges"
+                | "vlans"
+                | "tunnels"
+        ) {
+            report.skip(0, &key, "no NetworkManager equivalent");
+        }
+    }
+
+    // Bond and bridge members, by interface
+    let mut controllers: HashMap<String, Controller> = HashMap::new();
+    for (stanza, port_type) in [("bonds", "bond"), ("bridges", "bridge")] {
+        for (name, definition) in entries(network.get(stanza)) {
+            for member in strings(definition.get("interfaces")) {
+                controllers.insert(
+                    member,
+                    Controller {
+                        name: name.clone(),
+                        port_type,
+                        parameters: definition.get("parameters"),
+                    },
+                );
+            }
+        }
+    }
+
+    let mut importer = Importer {
+        report,
+        controllers,
+    };
+    for (name, definition) in entries(network.get("ethernets")) {
+        importer.ethernet(&name, definition);
+    }
+    for (name, definition) in entries(network.get("wifis")) {
+        importer.wifi(&name, definition);
+    }
+    for (name, definition) in entries(network.get("bonds")) {
+        importer.bond(&name, definition);
+    }
+    for (name, definition) in entries(network.get("bridges")) {
+        importer.bridge(&name, definition);
+    }
+    for (name, definition) in entries(network.get("vlans")) {
+        importer.vlan(&name, definition);
+    }
+    for (name, definition) in entries(network.get("tunnels")) {
+        importer.tunnel(&name, definition);
+    }
+
+    Ok(importer.report)
+}
+
+struct Controller<'a> {
+    name: String,
+    port_type: &'static str,
+    parameters: Option<&'a Yaml>,
+}
+
+struct Importer<'a> {
+    report: ImportReport,
+    controllers: HashMap<String, Controller<'a>>,
+}
+
+impl Importer<'_> {
+    fn ethernet(&mut self, name: &str, definition: &Yaml) {
+        let path = format!("ethernets.{name}");
+        let mut settings = base(name, definition, ETHERNET);
+        settings.add_setting(ETHERNET);
+        if let Some(mac) = definition
+            .get("match")
+            .and_then(|m| scalar(m.get("macaddress")))
+        {
+            match parse_mac(&mac) {
+                Some(mac) => settings.set(ETHERNET, "mac-address", mac),
+                None => self
+                    .report
+                    .skip(0, &format!("{path}.match.macaddress"), "invalid value"),
+            }
+        }
+        if let Some(mac) = scalar(definition.get("macaddress")) {
+            settings.set(ETHERNET, "assigned-mac-address", mac);
+        }
+        if let Some(mtu) = u32_value(definition.get("mtu")) {
+            settings.set(ETHERNET, "mtu", mtu);
+        }
+        if definition.get("wakeonlan").is_some_and(flag) {
+            // NM_SETTING_WIRED_WAKE_ON_LAN_MAGIC
+            settings.set(ETHERNET, "wake-on-lan", 64u32);
+        }
+
+        self.finish(name, &path, definition, settings, &["wakeonlan"]);
+    }
+
+    fn wifi(&mut self, name: &str, definition: &Yaml) {
+        let path = format!("wifis.{name}");
+        for (ssid, access_point) in entries(definition.get("access-points")) {
+            let ap_path = format!("{path}.access-points.{ssid}");
+            let mut settings = base(&format!("{name}-{ssid}"), access_point, WIRELESS);
+            if let Some(interface_name) = interface_name(name, definition) {
+                settings.set("connection", "interface-name", interface_name);
+            }
+            settings.set(WIRELESS, "ssid", ssid.as_bytes().to_vec());
+            if let Some(mtu) = u32_value(definition.get("mtu")) {
+                settings.set(WIRELESS, "mtu", mtu);
+            }
+            if let Some(mac) = scalar(definition.get("macaddress")) {
+                settings.set(WIRELESS, "assigned-mac-address", mac);
+            }
+
+            for (key, value) in entries(Some(access_point)) {
+                match key.as_str() {
+                    "networkmanager" => {}
+                    "mode" => {
+                        settings.set(WIRELESS, "mode", scalar(Some(value)).unwrap_or_default())
+                    }
+                    "hidden" => settings.set(WIRELESS, "hidden", flag(value)),
+                    "band" => match scalar(Some(value)).as_deref() {
+                        Some("5GHz") => settings.set(WIRELESS, "band", "a"),
+                        Some("2.4GHz") => settings.set(WIRELESS, "band", "bg"),
+                        _ => self
+                            .report
+                            .skip(0, &format!("{ap_path}.{key}"), "invalid value"),
+                    },
+                    "channel" => match u32_value(Some(value)) {
+                        Some(channel) => settings.set(WIRELESS, "channel", channel),
+                        None => self
+                            .report
+                            .skip(0, &format!("{ap_path}.{key}"), "invalid value"),
+                    },
+                    "bssid" => match scalar(Some(value)).as_deref().and_then(parse_mac) {
+                        Some(bssid) => settings.set(WIRELESS, "bssid", bssid),
+                        None => self
+                            .report
+                            .skip(0, &format!("{ap_path}.{key}"), "invalid value"),
+                    },
+                    "password" => {
+                        settings.set(SECURITY, "key-mgmt", "wpa-psk");
+                        settings.set(SECURITY, "psk", scalar(Some(value)).unwrap_or_default());
+                    }
+                    "auth" => self.wifi_auth(&mut settings, &ap_path, value),
+                    _ => self.report.skip(
+                        0,
+                        &format!("{ap_path}.{key}"),
+                        "no NetworkManager equivalent",
+                    ),
+                }
+            }
+            if settings.contains_setting(SECURITY) {
+                settings.set(WIRELESS, "security", SECURITY);
+            }
+
+            self.finish(name, &path, definition, settings, &["access-points"]);
+        }
+    }
+
+    fn wifi_auth(&mut self, settings: &mut ConnectionSettings, path: &str, auth: &Yaml) {
+        let key_management = scalar(auth.get("key-management")).unwrap_or_else(|| "psk".to_owned());
+        let key_mgmt = match key_management.as_str() {
+            "none" => return,
+            "psk" => "wpa-psk",
+            "sae" => "sae",
+            "eap" | "eap-sha256" | "eap-suite-b-192" => "wpa-eap",
+            "802.1x" => "ieee8021x",
+            _ => {
+                self.report.skip(
+                    0,
+                    &format!("{path}.auth.key-management"),
+                    "unsupported value",
+                );
+                return;
+            }
+        };
+        settings.set(SECURITY, "key-mgmt", key_mgmt);
+        let eap = matches!(key_mgmt, "wpa-eap" | "ieee8021x");
+
+        for (key, value) in entries(Some(auth)) {
+            let Some(value) = scalar(Some(value)) else {
+                continue;
+            };
+            match key.as_str() {
+                "key-management" => {}
+                "password" if eap => settings.set(IEEE8021X, "password", value),
+                "password" => settings.set(SECURITY, "psk", value),
+                "method" => settings.set(IEEE8021X, "eap", vec![value]),
+                "identity" | "anonymous-identity" | "phase2-auth" => {
+                    settings.set(IEEE8021X, &key, value)
+                }
+                "ca-certificate" => settings.set(IEEE8021X, "ca-cert", file_blob(&value)),
+                "client-certificate" => settings.set(IEEE8021X, "client-cert", file_blob(&value)),
+                "client-key" => settings.set(IEEE8021X, "private-key", file_blob(&value)),
+                "client-key-password" => settings.set(IEEE8021X, "private-key-password", value),
+                _ => self.report.skip(
+                    0,
+                    &format!("{path}.auth.{key}"),
+                    "no NetworkManager equivalent",
+                ),
+            }
+        }
+    }
+
+    fn bond(&mut self, name: &str, definition: &Yaml) {
+        let path = format!("bonds.{name}");
+        let mut settings = base(name, definition, "bond");
+        let mut options = HashMap::new();
+        for (key, value) in entries(definition.get("parameters")) {
+            let option = BOND_OPTIONS.iter().find(|(parameter, _)| {
+                *parameter == key || legacy_bond_parameter(&key) == Some(*parameter)
+            });
+            match option {
+                Some((_, option)) => {
+                    let value = match value {
+                        Yaml::Sequence(_) => strings(Some(value)).join(","),
+                        Yaml::Bool(enabled) => u8::from(*enabled).to_string(),
+                        _ => scalar(Some(value)).unwrap_or_default(),
+                    };
+                    options.insert(option.to_string(), value);
+                }
+                None => self.report.skip(
+                    0,
+                    &format!("{path}.parameters.{key}"),
+                    "no NetworkManager equivalent",
+                ),
+            }
+        }
+        settings.set("bond", "options", options);
+        if let Some(mtu) = u32_value(definition.get("mtu")) {
+            settings.set(ETHERNET, "mtu", mtu);
+        }
+
+        self.finish(
+            name,
+            &path,
+            definition,
+            settings,
+            &["interfaces", "parameters"],
+        );
+    }
+
+    fn bridge(&mut self, name: &str, definition: &Yaml) {
+        let path = format!("bridges.{name}");
+        let mut settings = base(name, definition, "bridge");
+        settings.add_setting("bridge");
+        for (key, value) in entries(definition.get("parameters")) {
+            if key == "stp" {
+                settings.set("bridge", "stp", flag(value));
+            } else if let Some((_, property)) = BRIDGE_OPTIONS
+                .iter()
+                .find(|(parameter, _)| *parameter == key)
+            {
+                match u32_value(Some(value)) {
+                    Some(value) => settings.set("bridge", property, value),
+                    None => {
+                        self.report
+                            .skip(0, &format!("{path}.parameters.{key}"), "invalid value")
+                    }
+                }
+            } else if key != "path-cost" && key != "port-priority" {
+                // path-cost and port-priority are set on the ports
+                self.report.skip(
+                    0,
+                    &format!("{path}.parameters.{key}"),
+                    "no NetworkManager equivalent",
+                );
+            }
+        }
+        if let Some(mtu) = u32_value(definition.get("mtu")) {
+            settings.set(ETHERNET, "mtu", mtu);
+        }
+
+        self.finish(
+            name,
+            &path,
+            definition,
+            settings,
+            &["interfaces", "parameters"],
+        );
+    }
+
+    fn vlan(&mut self, name: &str, definition: &Yaml) {
+        let path = format!("vlans.{name}");
+        let mut settings = base(name, definition, "vlan");
+        match u32_value(definition.get("id")) {
+            Some(id) => settings.set("vlan", "id", id),
+            None => self
+                .report
+                .skip(0, &format!("{path}.id"), "missing or invalid"),
+        }
+        if let Some(link) = scalar(definition.get("link")) {
+            settings.set("vlan", "parent", link);
+        }
+        if let Some(mtu) = u32_value(definition.get("mtu")) {
+            settings.set(ETHERNET, "mtu", mtu);
+        }
+
+        self.finish(name, &path, definition, settings, &["id", "link"]);
+    }
+
+    fn tunnel(&mut self, name: &str, definition: &Yaml) {
+        let path = format!("tunnels.{name}");
+        let mode = scalar(definition.get("mode")).unwrap_or_default();
+        let keys = definition.get("keys");
+
+        let settings = match mode.as_str() {
+            "wireguard" => {
+                let private_key = scalar(keys.and_then(|keys| keys.get("private")));
+                if private_key
+                    .as_deref()
+                    .is_some_and(|key| key.starts_with('/'))
+                {
+                    self.report.skip(
+                        0,
+                        &format!("{path}.keys.private"),
+                        "key files are not supported",
+                    );
+                }
+                let config = WireGuardConfig {
+                    name: name.to_owned(),
+                    interface: WireGuardInterface {
+                        private_key: private_key.filter(|key| !key.starts_with('/')),
+                        listen_port: u32_value(definition.get("port"))
+                            .and_then(|port| u16::try_from(port).ok()),
+                        fw_mark: u32_value(definition.get("mark")),
+                        mtu: u32_value(definition.get("mtu")),
+                        ..WireGuardInterface::default()
+                    },
+                    peers: sequence(definition.get("peers"))
+                        .iter()
+                        .map(|peer| {
+                            let keys = peer.get("keys");
+                            WireGuardPeer {
+                                public_key: scalar(keys.and_then(|keys| keys.get("public")))
+                                    .unwrap_or_default(),
+                                preshared_key: scalar(keys.and_then(|keys| keys.get("shared"))),
+                                endpoint: scalar(peer.get("endpoint")),
+                                allowed_ips: strings(peer.get("allowed-ips")),
+                                persistent_keepalive: u32_value(peer.get("keepalive")),
+                            }
+                        })
+                        .collect(),
+                };
+                let mut settings = config.to_settings();
+                settings.merge(base(name, definition, "wireguard"));
+                settings
+            }
+            "vxlan" => {
+                let mut settings = base(name, definition, "vxlan");
+                if let Some(id) = u32_value(definition.get("id")) {
+                    settings.set("vxlan", "id", id);
+                }
+                for (key, property) in
+                    [("local", "local"), ("remote", "remote"), ("link", "parent")]
+                {
+                    if let Some(value) = scalar(definition.get(key)) {
+                        settings.set("vxlan", property, value);
+                    }
+                }
+                if let Some(port) = u32_value(definition.get("port")) {
+                    settings.set("vxlan", "destination-port", port);
+                }
+                if let Some(ttl) = u32_value(definition.get("ttl")) {
+                    settings.set("vxlan", "ttl", ttl);
+                }
+                settings
+            }
+            _ => {
+                let Some((mode, _)) = TUNNEL_MODES.iter().find(|(_, name)| *name == mode) else {
+                    self.report
+                        .skip(0, &format!("{path}.mode"), "unsupported tunnel mode");
+                    return;
+                };
+                let mut settings = base(name, definition, "ip-tunnel");
+                settings.set("ip-tunnel", "mode", *mode);
+                for (key, property) in
+                    [("local", "local"), ("remote", "remote"), ("link", "parent")]
+                {
+                    if let Some(value) = scalar(definition.get(key)) {
+                        settings.set("ip-tunnel", property, value);
+                    }
+                }
+                if let Some(ttl) = u32_value(definition.get("ttl")) {
+                    settings.set("ip-tunnel", "ttl", ttl);
+                }
+                if let Some(mtu) = u32_value(definition.get("mtu")) {
+                    settings.set("ip-tunnel", "mtu", mtu);
+                }
+                // `key` sets both directions, `keys` each one separately
+                let input = scalar(keys.and_then(|keys| keys.get("input")))
+                    .or_else(|| scalar(definition.get("key")));
+                let output = scalar(keys.and_then(|keys| keys.get("output")))
+                    .or_else(|| scalar(definition.get("key")));
+                if let Some(input) = input {
+                    settings.set("ip-tunnel", "input-key", input);
+                }
+                if let Some(output) = output {
+                    settings.set("ip-tunnel", "output-key", output);
+                }
+                settings
+            }
+        };
+
+        self.finish(
+            name,
+            &path,
+            definition,
+            settings,
+            &[
+                "mode", "local", "remote", "link", "id", "port", "ttl", "key", "keys", "peers",
+                "mark",
+            ],
+        );
+    }
+
+    /// Adds the IP configuration or the port settings, reports unknown keys and stores the
+    /// profile.
+    fn finish(
+        &mut self,
+        name: &str,
+        path: &str,
+        definition: &Yaml,
+        mut settings: ConnectionSettings,
+        known: &[&str],
+    ) {
+        match self.controllers.get(name) {
+            Some(controller) => {
+                settings.set("connection", "master", controller.name.clone());
+                settings.set("connection", "slave-type", controller.port_type);
+                if controller.port_type == "bridge" {
+                    for (parameter, property) in
+                        [("path-cost", "path-cost"), ("port-priority", "priority")]
+                    {
+                        if let Some(value) = u32_value(
+                            controller
+                                .parameters
+                                .and_then(|parameters| parameters.get(parameter))
+                                .and_then(|values| values.get(name)),
+                        ) {
+                            settings.set("bridge-port", property, value);
+                        }
+                    }
+                }
+                for (key, _) in entries(Some(definition)) {
+                    if IP_KEYS.contains(&key.as_str()) {
+                        self.skip(&format!("{path}.{key}"), "ports have no IP configuration");
+                    }
+                }
+            }
+            None => import_ip(&mut settings, path, definition, &mut |directive, reason| {
+                self.skip(directive, reason)
+            }),
+        }
+
+        for (key, _) in entries(Some(definition)) {
+            if !known.contains(&key.as_str())
+                && !COMMON_KEYS.contains(&key.as_str())
+                && !IP_KEYS.contains(&key.as_str())
+            {
+                self.skip(&format!("{path}.{key}"), "no NetworkManager equivalent");
+            }
+        }
+
+        self.report.connections.push(settings);
+    }
+
+    /// Reports a key once, Wi-Fi definitions are visited once per access point.
+    fn skip(&mut self, directive: &str, reason: &str) {
+        if !self
+            .report
+            .untranslated
+            .iter()
+            .any(|untranslated| untranslated.directive == directive)
+        {
+            self.report.skip(0, directive, reason);
+        }
+    }
+}
+
+/// The `connection` setting, named like netplan names its profiles unless the definition
+/// carries the NetworkManager name and UUID.
+fn base(name: &str, definition: &Yaml, connection_type: &'static str) -> ConnectionSettings {
+    let networkmanager = definition.get("networkmanager");
+    let id = scalar(networkmanager.and_then(|nm| nm.get("name")))
+        .unwrap_or_else(|| format!("netplan-{name}"));
+    let uuid = scalar(networkmanager.and_then(|nm| nm.get("uuid"))).unwrap_or_else(new_uuid);
+
+    let mut settings = ConnectionSettings::new()
+        .with("connection", "id", id)
+        .with("connection", "uuid", uuid)
+        .with("connection", "type", connection_type);
+    if connection_type != WIRELESS
+        && let Some(interface_name) = interface_name(name, definition)
+    {
+        settings.set("connection", "interface-name", interface_name);
+    }
+    settings
+}
+
+/// The interface a definition applies to, `None` when it matches by other properties.
+fn interface_name(name: &str, definition: &Yaml) -> Option<String> {
+    if let Some(set_name) = scalar(definition.get("set-name")) {
+        return Some(set_name);
+    }
+    match definition.get("match") {
+        Some(matches) => scalar(matches.get("name")).filter(|name| !name.contains(['*', '?', '['])),
+        None => Some(name.to_owned()),
+    }
+}
+
+/// Adds the IP configuration of the definition at `path`, reporting addresses, routes and
+/// name servers that can't be translated through `skip`.
+fn import_ip(
+    settings: &mut ConnectionSettings,
+    path: &str,
+    definition: &Yaml,
+    skip: &mut impl FnMut(&str, &str),
+) {
+    let mut addresses: [Vec<HashMap<String, Value<'static>>>; 2] = Default::default();
+    for (idx, address) in sequence(definition.get("addresses")).iter().enumerate() {
+        // Either a plain string or a mapping of the address to its options
+        let address = match address {
+            Yaml::Mapping(mapping) => mapping.keys().next().and_then(|key| scalar(Some(key))),
+            _ => scalar(Some(address)),
+        };
+        let Some((ip, prefix)) = address.as_deref().and_then(parse_address) else {
+            skip(&format!("{path}.addresses[{idx}]"), "invalid address");
+            continue;
+        };
+        addresses[usize::from(ip.is_ipv6())].push(HashMap::from([
+            ("address".to_owned(), Value::from(ip.to_string())),
+            ("prefix".to_owned(), Value::from(prefix)),
+        ]));
+    }
+
+    let mut routes: [Vec<HashMap<String, Value<'static>>>; 2] = Default::default();
+    for (idx, route) in sequence(definition.get("routes")).iter().enumerate() {
+        let directive = format!("{path}.routes[{idx}]");
+        let Some(to) = scalar(route.get("to")) else {
+            skip(&directive, "route without to");
+            continue;
+        };
+        let via = scalar(route.get("via"));
+        let ipv6 = match via.as_deref().and_then(|via| via.parse::<IpAddr>().ok()) {
+            Some(via) => via.is_ipv6(),
+            None => to.contains(':'),
+        };
+        let to = match to.as_str() {
+            "default" if ipv6 => "::/0".to_owned(),
+            "default" => "0.0.0.0/0".to_owned(),
+            _ => to,
+        };
+        let Some((dest, prefix)) = parse_address(&to) else {
+            skip(&directive, "invalid route destination");
+            continue;
+        };
+
+        let mut route_data = HashMap::from([
+            ("dest".to_owned(), Value::from(dest.to_string())),
+            ("prefix".to_owned(), Value::from(prefix)),
+        ]);
+        if let Some(via) = via {
+            route_data.insert("next-hop".to_owned(), Value::from(via));
+        }
+        if let Some(metric) = u32_value(route.get("metric")) {
+            route_data.insert("metric".to_owned(), Value::from(metric));
+        }
+        if let Some(table) = u32_value(route.get("table")) {
+            route_data.insert("table".to_owned(), Value::from(table));
+        }
+        if route.get("on-link").is_some_and(flag) {
+            route_data.insert("onlink".to_owned(), Value::from(true));
+        }
+        routes[usize::from(ipv6)].push(route_data);
+    }
+
+    let nameservers = definition.get("nameservers");
+    let mut ipv4_dns = Vec::new();
+    let mut ipv6_dns = Vec::new();
+    let servers = sequence(nameservers.and_then(|nameservers| nameservers.get("addresses")));
+    for (idx, server) in servers.iter().enumerate() {
+        match scalar(Some(server)).and_then(|server| server.parse::<IpAddr>().ok()) {
+            Some(IpAddr::V4(ip)) => ipv4_dns.push(u32::from_ne_bytes(ip.octets())),
+            Some(IpAddr::V6(ip)) => ipv6_dns.push(ip.octets().to_vec()),
+            None => skip(
+                &format!("{path}.nameservers.addresses[{idx}]"),
+                "invalid address",
+            ),
+        }
+    }
+
+    let [ipv4_addresses, ipv6_addresses] = addresses;
+    let [ipv4_routes, ipv6_routes] = routes;
+    for (family, dhcp, addresses, routes, gateway, overrides, unconfigured) in [
+        (
+            "ipv4",
+            "dhcp4",
+            ipv4_addresses,
+            ipv4_routes,
+            "gateway4",
+            "dhcp4-overrides",
+            "disabled",
+        ),
+        (
+            "ipv6",
+            "dhcp6",
+            ipv6_addresses,
+            ipv6_routes,
+            "gateway6",
+            "dhcp6-overrides",
+            "ignore",
+        ),
+    ] {
+        let method = if definition.get(dhcp).is_some_and(flag) {
+            "auto"
+        } else if !addresses.is_empty() {
+            "manual"
+        } else {
+            unconfigured
+        };
+        settings.set(family, "method", method);
+        if !addresses.is_empty() {
+            settings.set(family, "address-data", addresses);
+        }
+        if !routes.is_empty() {
+            settings.set(family, "route-data", routes);
+        }
+        if let Some(gateway) = scalar(definition.get(gateway)) {
+            settings.set(family, "gateway", gateway);
+        }
+
+        let overrides = definition.get(overrides);
+        if let Some(metric) =
+            overrides.and_then(|overrides| u32_value(overrides.get("route-metric")))
+        {
+            settings.set(family, "route-metric", i64::from(metric));
+        }
+        if overrides
+            .and_then(|overrides| overrides.get("use-dns"))
+            .is_some_and(|value| !flag(value))
+        {
+            settings.set(family, "ignore-auto-dns", true);
+        }
+        if overrides
+            .and_then(|overrides| overrides.get("use-routes"))
+            .is_some_and(|value| !flag(value))
+        {
+            settings.set(family, "ignore-auto-routes", true);
+        }
+    }
+
+    if !ipv4_dns.is_empty() {
+        settings.set("ipv4", "dns", ipv4_dns);
+    }
+    if !ipv6_dns.is_empty() {
+        settings.set("ipv6", "dns", ipv6_dns);
+    }
+    let search = strings(nameservers.and_then(|nameservers| nameservers.get("search")));
+    if !search.is_empty() {
+        settings.set("ipv4", "dns-search", search);
+    }
+    if definition.get("ipv6-privacy").is_some_and(flag) {
+        // NM_SETTING_IP6_CONFIG_PRIVACY_PREFER_TEMP_ADDR
+        settings.set("ipv6", "ip6-privacy", 2i32);
+    }
+}
+
+/// Formats NetworkManager profiles as a netplan document.
+///
+/// Profiles of types netplan has no stanza for are left out. Bond and bridge ports are listed
+/// as `interfaces` of their controller.
+pub fn export_netplan(connections: &[ConnectionSettings]) -> String {
+    let mut stanzas: [(&str, Mapping); 6] = [
+        ("ethernets", Mapping::new()),
+        ("wifis", Mapping::new()),
+        ("bonds", Mapping::new()),
+        ("bridges", Mapping::new()),
+        ("vlans", Mapping::new()),
+        ("tunnels", Mapping::new()),
+    ];
+
+    // Controllers can be referenced by interface name or UUID
+    let controller_names: HashMap<&str, String> = connections
+        .iter()
+        .filter_map(|settings| Some((settings.uuid()?, definition_name(settings))))
+        .collect();
+    let mut ports: HashMap<String, Vec<&ConnectionSettings>> = HashMap::new();
+    for settings in connections {
+        if let Some(master) = settings.get_str("connection", "master") {
+            let controller = controller_names
+                .get(master)
+                .cloned()
+                .unwrap_or_else(|| master.to_owned());
+            ports.entry(controller).or_default().push(settings);
+        }
+    }
+
+    for settings in connections {
+        let name = definition_name(settings);
+        let mut definition = Mapping::new();
+        let stanza = match settings.connection_type() {
+            Some(ETHERNET) => {
+                export_ethernet(settings, &mut definition);
+                "ethernets"
+            }
+            Some(WIRELESS) => {
+                export_wifi(settings, &name, &mut stanzas[1].1);
+                continue;
+            }
+            Some("bond") => {
+                export_bond(settings, &mut definition);
+                "bonds"
+            }
+            Some("bridge") => {
+                export_bridge(settings, &mut definition, &ports);
+                "bridges"
+            }
+            Some("vlan") => {
+                if let Some(id) = settings.get_u32("vlan", "id") {
+                    insert(&mut definition, "id", number(id));
+                }
+                if let Some(parent) = settings.get_str("vlan", "parent") {
+                    insert(&mut definition, "link", parent);
+                }
+                "vlans"
+            }
+            Some("ip-tunnel" | "vxlan" | "wireguard") => {
+                export_tunnel(settings, &mut definition);
+                "tunnels"
+            }
+            _ => continue,
+        };
+
+        if let Some(members) = ports.get(&name) {
+            let members: Vec<Yaml> = members
+                .iter()
+                .map(|member| Yaml::from(definition_name(member)))
+                .collect();
+            insert(&mut definition, "interfaces", members);
+        }
+        export_common(settings, &name, &mut definition);
+
+        let index = stanzas
+            .iter()
+            .position(|(key, _)| *key == stanza)
+            .unwrap_or_default();
+        stanzas[index]
+            .1
+            .insert(Yaml::from(name), Yaml::Mapping(definition));
+    }
+
+    let mut network = Mapping::new();
+    insert(&mut network, "version", number(2));
+    insert(&mut network, "renderer", "NetworkManager");
+    for (key, stanza) in stanzas {
+        if !stanza.is_empty() {
+            insert(&mut network, key, stanza);
+        }
+    }
+    let mut document = Mapping::new();
+    insert(&mut document, "network", network);
+
+    // Serializing a plain YAML value can't fail
+    serde_yaml::to_string(&document).unwrap_or_default()
+}
+
+/// Reads saved profiles, including the secrets NetworkManager can return, and formats them as
+/// a netplan document.
+pub async fn export_netplan_connections(
+    connections: &[SettingsConnectionProxy<'_>],
+) -> zbus::Result<String> {
+    let mut all_settings = Vec::with_capacity(connections.len());
+    for connection in connections {
+        let mut settings = ConnectionSettings::from(connection.get_settings().await?);
+        let secrets = get_connection_secrets(connection, &settings).await?;
+        settings.merge_secrets(secrets);
+        all_settings.push(settings);
+    }
+    Ok(export_netplan(&all_settings))
+}
+
+/// The key of a profile in its netplan stanza.
+fn definition_name(settings: &ConnectionSettings) -> String {
+    settings
+        .get_str("connection", "interface-name")
+        .or(settings.id())
+        .unwrap_or_default()
+        .to_owned()
+}
+
+/// Adds the NetworkManager name and UUID and, unless the profile is a port, the IP
+/// configuration.
+fn export_common(settings: &ConnectionSettings, name: &str, definition: &mut Mapping) {
+    if settings.get_str("connection", "interface-name") != Some(name)
+        && !definition.contains_key("match")
+    {
+        insert(definition, "match", match_any());
+    }
+    if settings.get_str("connection", "master").is_none() {
+        export_ip(settings, definition);
+    }
+    let mut networkmanager = Mapping::new();
+    if let Some(uuid) = settings.uuid() {
+        insert(&mut networkmanager, "uuid", uuid);
+    }
+    if let Some(id) = settings.id() {
+        insert(&mut networkmanager, "name", id);
+    }
+    insert(definition, "networkmanager", networkmanager);
+}
+
+fn export_ethernet(settings: &ConnectionSettings, definition: &mut Mapping) {
+    if let Some(mac) = settings
+        .get_bytes(ETHERNET, "mac-address")
+        .filter(|mac| !mac.is_empty())
+    {
+        let mut matches = Mapping::new();
+        insert(&mut matches, "macaddress", format_mac(&mac));
+        insert(definition, "match", matches);
+    }
+    if let Some(mac) = settings.get_str(ETHERNET, "assigned-mac-address") {
+        insert(definition, "macaddress", mac);
+    }
+    if let Some(mtu) = settings.get_u32(ETHERNET, "mtu").filter(|&mtu| mtu != 0) {
+        insert(definition, "mtu", number(mtu));
+    }
+    if settings
+        .get_u32(ETHERNET, "wake-on-lan")
+        .is_some_and(|wol| wol & 64 != 0)
+    {
+        insert(definition, "wakeonlan", true);
+    }
+}
+
+/// Adds the profile as an access point of its interface, several profiles can share one.
+fn export_wifi(settings: &ConnectionSettings, name: &str, wifis: &mut Mapping) {
+    let Some(ssid) = settings.get_bytes(WIRELESS, "ssid") else {
+        return;
+    };
+    let mut access_point = Mapping::new();
+    if let Some(mode) = settings.get_str(WIRELESS, "mode") {
+        insert(&mut access_point, "mode", mode);
+    }
+    if settings.get_bool(WIRELESS, "hidden") == Some(true) {
+        insert(&mut access_point, "hidden", true);
+    }
+    match settings.get_str(WIRELESS, "band") {
+        Some("a") => insert(&mut access_point, "band", "5GHz"),
+        Some("bg") => insert(&mut access_point, "band", "2.4GHz"),
+        _ => {}
+    }
+    if let Some(channel) = settings
+        .get_u32(WIRELESS, "channel")
+        .filter(|&channel| channel != 0)
+    {
+        insert(&mut access_point, "channel", number(channel));
+    }
+    if let Some(bssid) = settings
+        .get_bytes(WIRELESS, "bssid")
+        .filter(|bssid| !bssid.is_empty())
+    {
+        insert(&mut access_point, "bssid", format_mac(&bssid));
+    }
+
+    if let Some(key_mgmt) = settings.get_str(SECURITY, "key-mgmt") {
+        let mut auth = Mapping::new();
+        let key_management = match key_mgmt {
+            "wpa-psk" => "psk",
+            "wpa-eap" => "eap",
+            "ieee8021x" => "802.1x",
+            other => other,
+        };
+        insert(&mut auth, "key-management", key_management);
+        if let Some(psk) = settings.get_str(SECURITY, "psk") {
+            insert(&mut auth, "password", psk);
+        }
+        if let Some(method) = settings
+            .get_strings(IEEE8021X, "eap")
+            .and_then(|methods| methods.into_iter().next())
+        {
+            insert(&mut auth, "method", method);
+        }
+        for property in ["identity", "anonymous-identity", "phase2-auth", "password"] {
+            if let Some(value) = settings.get_str(IEEE8021X, property) {
+                insert(&mut auth, property, value);
+            }
+        }
+        for (property, key) in [
+            ("ca-cert", "ca-certificate"),
+            ("client-cert", "client-certificate"),
+            ("private-key", "client-key"),
+        ] {
+            if let Some(path) = settings
+                .get_bytes(IEEE8021X, property)
+                .as_deref()
+                .and_then(blob_path)
+            {
+                insert(&mut auth, key, path);
+            }
+        }
+        if let Some(password) = settings.get_str(IEEE8021X, "private-key-password") {
+            insert(&mut auth, "client-key-password", password);
+        }
+        insert(&mut access_point, "auth", auth);
+    }
+
+    let mut networkmanager = Mapping::new();
+    if let Some(uuid) = settings.uuid() {
+        insert(&mut networkmanager, "uuid", uuid);
+    }
+    if let Some(id) = settings.id() {
+        insert(&mut networkmanager, "name", id);
+    }
+    insert(&mut access_point, "networkmanager", networkmanager);
+
+    let definition = wifis.entry(Yaml::from(name.to_owned())).or_insert_with(|| {
+        let mut definition = Mapping::new();
+        if settings.get_str("connection", "interface-name").is_none() {
+            insert(&mut definition, "match", match_any());
+        }
+        if let Some(mtu) = settings.get_u32(WIRELESS, "mtu").filter(|&mtu| mtu != 0) {
+            insert(&mut definition, "mtu", number(mtu));
+        }
+        export_ip(settings, &mut definition);
+        insert(&mut definition, "access-points", Mapping::new());
+        Yaml::Mapping(definition)
+    });
+    if let Some(Yaml::Mapping(access_points)) = definition.get_mut("access-points") {
+        access_points.insert(
+            Yaml::from(String::from_utf8_lossy(&ssid).into_owned()),
+            Yaml::Mapping(access_point),
+        );
+    }
+}
+
+fn export_bond(settings: &ConnectionSettings, definition: &mut Mapping) {
+    let options = settings
+        .get_string_map("bond", "options")
+        .unwrap_or_default();
+    let mut parameters = Mapping::new();
+    for (parameter, option) in BOND_OPTIONS {
+        let Some(value) = options.get(*option) else {
+            continue;
+        };
+        let value = match *parameter {
+            "arp-ip-targets" => Yaml::Sequence(value.split(',').map(Yaml::from).collect()),
+            "all-members-active" => Yaml::Bool(value == "1"),
+            _ => match value.parse::<u64>() {
+                Ok(value) => number(value),
+                Err(_) => Yaml::from(value.clone()),
+            },
+        };
+        insert(&mut parameters, parameter, value);
+    }
+    if !parameters.is_empty() {
+        insert(definition, "parameters", parameters);
+    }
+    if let Some(mtu) = settings.get_u32(ETHERNET, "mtu").filter(|&mtu| mtu != 0) {
+        insert(definition, "mtu", number(mtu));
+    }
+}
+
+fn export_bridge(
+    settings: &ConnectionSettings,
+    definition: &mut Mapping,
+    ports: &HashMap<String, Vec<&ConnectionSettings>>,
+) {
+    let mut parameters = Mapping::new();
+    if let Some(stp) = settings.get_bool("bridge", "stp") {
+        insert(&mut parameters, "stp", stp);
+    }
+    for (parameter, property) in BRIDGE_OPTIONS {
+        if let Some(value) = settings.get_u32("bridge", property) {
+            insert(&mut parameters, parameter, number(value));
+        }
+    }
+    for (parameter, property) in [("path-cost", "path-cost"), ("port-priority", "priority")] {
+        let mut values = Mapping::new();
+        for port in ports.get(&definition_name(settings)).into_iter().flatten() {
+            if let Some(value) = port.get_u32("bridge-port", property) {
+                values.insert(Yaml::from(definition_name(port)), number(value));
+            }
+        }
+        if !values.is_empty() {
+            insert(&mut parameters, parameter, values);
+        }
+    }
+    if !parameters.is_empty() {
+        insert(definition, "parameters", parameters);
+    }
+    if let Some(mtu) = settings.get_u32(ETHERNET, "mtu").filter(|&mtu| mtu != 0) {
+        insert(definition, "mtu", number(mtu));
+    }
+}
+
+fn export_tunnel(settings: &ConnectionSettings, definition: &mut Mapping) {
+    match settings.connection_type() {
+        Some("wireguard") => {
+            insert(definition, "mode", "wireguard");
+            let Some(config) = WireGuardConfig::from_settings(settings) else {
+                return;
+            };
+            if let Some(private_key) = &config.interface.private_key {
+                let mut keys = Mapping::new();
+                insert(&mut keys, "private", private_key.as_str());
+                insert(definition, "keys", keys);
+            }
+            if let Some(port) = config.interface.listen_port {
+                insert(definition, "port", number(port));
+            }
+            if let Some(mark) = config.interface.fw_mark {
+                insert(definition, "mark", number(mark));
+            }
+            if let Some(mtu) = config.interface.mtu {
+                insert(definition, "mtu", number(mtu));
+            }
+            let peers: Vec<Yaml> = config
+                .peers
+                .iter()
+                .map(|peer| {
+                    let mut yaml = Mapping::new();
+                    let mut keys = Mapping::new();
+                    insert(&mut keys, "public", peer.public_key.as_str());
+                    if let Some(preshared_key) = &peer.preshared_key {
+                        insert(&mut keys, "shared", preshared_key.as_str());
+                    }
+                    insert(&mut yaml, "keys", keys);
+                    if let Some(endpoint) = &peer.endpoint {
+                        insert(&mut yaml, "endpoint", endpoint.as_str());
+                    }
+                    let allowed_ips: Vec<Yaml> =
+                        peer.allowed_ips.iter().cloned().map(Yaml::from).collect();
+                    insert(&mut yaml, "allowed-ips", allowed_ips);
+                    if let Some(keepalive) = peer.persistent_keepalive {
+                        insert(&mut yaml, "keepalive", number(keepalive));
+                    }
+                    Yaml::Mapping(yaml)
+                })
+                .collect();
+            insert(definition, "peers", peers);
+        }
+        Some("vxlan") => {
+            insert(definition, "mode", "vxlan");
+            if let Some(id) = settings.get_u32("vxlan", "id") {
+                insert(definition, "id", number(id));
+            }
+            for (property, key) in [("local", "local"), ("remote", "remote"), ("parent", "link")] {
+                if let Some(value) = settings.get_str("vxlan", property) {
+                    insert(definition, key, value);
+                }
+            }
+            if let Some(port) = settings.get_u32("vxlan", "destination-port") {
+                insert(definition, "port", number(port));
+            }
+            if let Some(ttl) = settings.get_u32("vxlan", "ttl").filter(|&ttl| ttl != 0) {
+                insert(definition, "ttl", number(ttl));
+            }
+        }
+        _ => {
+            let mode = settings.get_u32("ip-tunnel", "mode").and_then(|mode| {
+                TUNNEL_MODES
+                    .iter()
+                    .find(|(value, _)| *value == mode)
+                    .map(|(_, name)| *name)
+            });
+            if let Some(mode) = mode {
+                insert(definition, "mode", mode);
+            }
+            for (property, key) in [("local", "local"), ("remote", "remote"), ("parent", "link")] {
+                if let Some(value) = settings.get_str("ip-tunnel", property) {
+                    insert(definition, key, value);
+                }
+            }
+            if let Some(ttl) = settings.get_u32("ip-tunnel", "ttl").filter(|&ttl| ttl != 0) {
+                insert(definition, "ttl", number(ttl));
+            }
+            if let Some(mtu) = settings.get_u32("ip-tunnel", "mtu").filter(|&mtu| mtu != 0) {
+                insert(definition, "mtu", number(mtu));
+            }
+            let mut keys = Mapping::new();
+            for (property, key) in [("input-key", "input"), ("output-key", "output")] {
+                if let Some(value) = settings.get_str("ip-tunnel", property) {
+                    insert(&mut keys, key, value);
+                }
+            }
+            if !keys.is_empty() {
+                insert(definition, "keys", keys);
+            }
+        }
+    }
+}
+
+fn export_ip(settings: &ConnectionSettings, definition: &mut Mapping) {
+    let mut addresses = Vec::new();
+    let mut routes = Vec::new();
+    let mut nameservers = Vec::new();
+
+    for (family, dhcp, gateway, overrides) in [
+        ("ipv4", "dhcp4", "gateway4", "dhcp4-overrides"),
+        ("ipv6", "dhcp6", "gateway6", "dhcp6-overrides"),
+    ] {
+        let dynamic = matches!(settings.get_str(family, "method"), Some("auto" | "dhcp"));
+        if dynamic {
+            insert(definition, dhcp, true);
+        }
+        for address in settings
+            .get_dicts(family, "address-data")
+            .unwrap_or_default()
+        {
+            if let (Some(ip), Some(prefix)) =
+                (dict_str(&address, "address"), dict_u32(&address, "prefix"))
+            {
+                addresses.push(Yaml::from(format!("{ip}/{prefix}")));
+            }
+        }
+        if let Some(gateway_address) = settings
+            .get_str(family, "gateway")
+            .filter(|gateway| !gateway.is_empty())
+        {
+            insert(definition, gateway, gateway_address);
+        }
+        for route in settings.get_dicts(family, "route-data").unwrap_or_default() {
+            let (Some(dest), Some(prefix)) = (dict_str(&route, "dest"), dict_u32(&route, "prefix"))
+            else {
+                continue;
+            };
+            let mut yaml = Mapping::new();
+            if prefix == 0 {
+                insert(&mut yaml, "to", "default");
+            } else {
+                insert(&mut yaml, "to", format!("{dest}/{prefix}"));
+            }
+            if let Some(via) = dict_str(&route, "next-hop") {
+                insert(&mut yaml, "via", via);
+            }
+            if let Some(metric) = dict_u32(&route, "metric") {
+                insert(&mut yaml, "metric", number(metric));
+            }
+            if let Some(table) = dict_u32(&route, "table") {
+                insert(&mut yaml, "table", number(table));
+            }
+            if route
+                .get("onlink")
+                .and_then(|onlink| onlink.downcast_ref::<bool>().ok())
+                == Some(true)
+            {
+                insert(&mut yaml, "on-link", true);
+            }
+            routes.push(Yaml::Mapping(yaml));
+        }
+
+        if dynamic {
+            let mut dhcp_overrides = Mapping::new();
+            if let Some(metric) = settings
+                .get(family, "route-metric")
+                .and_then(|metric| metric.downcast_ref::<i64>().ok())
+                .filter(|&metric| metric >= 0)
+            {
+                insert(&mut dhcp_overrides, "route-metric", number(metric));
+            }
+            if settings.get_bool(family, "ignore-auto-dns") == Some(true) {
+                insert(&mut dhcp_overrides, "use-dns", false);
+            }
+            if settings.get_bool(family, "ignore-auto-routes") == Some(true) {
+                insert(&mut dhcp_overrides, "use-routes", false);
+            }
+            if !dhcp_overrides.is_empty() {
+                insert(definition, overrides, dhcp_overrides);
+            }
+        }
+    }
+
+    if let Some(servers) = settings
+        .get("ipv4", "dns")
+        .and_then(|dns| dns.try_clone().ok()?.downcast::<Vec<u32>>().ok())
+    {
+        nameservers.extend(
+            servers
+                .into_iter()
+                .map(|server| Yaml::from(Ipv4Addr::from(server.to_ne_bytes()).to_string())),
+        );
+    }
+    if let Some(servers) = settings
+        .get("ipv6", "dns")
+        .and_then(|dns| dns.try_clone().ok()?.downcast::<Vec<Vec<u8>>>().ok())
+    {
+        nameservers.extend(servers.into_iter().filter_map(|server| {
+            let octets: [u8; 16] = server.try_into().ok()?;
+            Some(Yaml::from(Ipv6Addr::from(octets).to_string()))
+        }));
+    }
+
+    if !addresses.is_empty() {
+        insert(definition, "addresses", addresses);
+    }
+    if !routes.is_empty() {
+        insert(definition, "routes", routes);
+    }
+    let search = settings
+        .get_strings("ipv4", "dns-search")
+        .unwrap_or_default();
+    if !nameservers.is_empty() || !search.is_empty() {
+        let mut yaml = Mapping::new();
+        if !nameservers.is_empty() {
+            insert(&mut yaml, "addresses", nameservers);
+        }
+        if !search.is_empty() {
+            let search: Vec<Yaml> = search.into_iter().map(Yaml::from).collect();
+            insert(&mut yaml, "search", search);
+        }
+        insert(definition, "nameservers", yaml);
+    }
+    if settings
+        .get("ipv6", "ip6-privacy")
+        .and_then(|privacy| privacy.downcast_ref::<i32>().ok())
+        == Some(2)
+    {
+        insert(definition, "ipv6-privacy", true);
+    }
+}
+
+/// netplan still accepts the old `*-slave*` names of some bond parameters.
+fn legacy_bond_parameter(parameter: &str) -> Option<&'static str> {
+    match parameter {
+        "all-slaves-active" => Some("all-members-active"),
+        "packets-per-slave" => Some("packets-per-member"),
+        _ => None,
+    }
+}
+
+/// The entries of a mapping with string keys, in document order.
+fn entries(value: Option<&Yaml>) -> Vec<(String, &Yaml)> {
+    match value {
+        Some(Yaml::Mapping(mapping)) => mapping
+            .iter()
+            .filter_map(|(key, value)| Some((scalar(Some(key))?, value)))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn sequence(value: Option<&Yaml>) -> &[Yaml] {
+    match value {
+        Some(Yaml::Sequence(sequence)) => sequence,
+        _ => &[],
+    }
+}
+
+fn strings(value: Option<&Yaml>) -> Vec<String> {
+    sequence(value)
+        .iter()
+        .filter_map(|value| scalar(Some(value)))
+        .collect()
+}
+
+/// A string, number or boolean as a string.
+fn scalar(value: Option<&Yaml>) -> Option<String> {
+    match value? {
+        Yaml::String(value) => Some(value.clone()),
+        Yaml::Number(value) => Some(value.to_string()),
+        Yaml::Bool(value) => Some(value.to_string()),
+        _ => None,
+    }
+}
+
+fn u32_value(value: Option<&Yaml>) -> Option<u32> {
+    scalar(value)?.parse().ok()
+}
+
+/// netplan booleans, which may also be written as strings.
+fn flag(value: &Yaml) -> bool {
+    match value {
+        Yaml::Bool(value) => *value,
+        Yaml::String(value) => matches!(value.as_str(), "true" | "yes" | "on"),
+        _ => false,
+    }
+}
+
+fn number(value: impl Into<Number>) -> Yaml {
+    Yaml::Number(value.into())
+}
+
+fn insert(mapping: &mut Mapping, key: &str, value: impl Into<Yaml>) {
+    mapping.insert(Yaml::from(key), value.into());
+}
+
+fn format_mac(mac: &[u8]) -> String {
+    mac.iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+/// A `match` for profiles that aren't bound to an interface: any interface of the
+/// definition's kind. An empty `match` is rejected by netplan.
+fn match_any() -> Mapping {
+    let mut matches = Mapping::new();
+    insert(&mut matches, "name", "*");
+    matches
+}
+
+/// The path of a `file://` certificate blob.
+fn blob_path(blob: &[u8]) -> Option<String> {
+    let path = blob.strip_prefix(b"file://")?;
+    let path = path.strip_suffix(b"\0").unwrap_or(path);
+    Some(String::from_utf8_lossy(path).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = "
+network:
+  version: 2
+  renderer: networkd
+  ethernets:
+    eth0:
+      addresses: [192.168.1.10/24, 2001:db8::10/64, 192.168.1.11/33]
+      gateway4: 192.168.1.1
+      nameservers:
+        addresses: [192.168.1.1, dns.example.com]
+        search: [example.com]
+      routes:
+        - to: 10.0.0.0/8
+          via: 192.168.1.254
+          metric: 50
+        - via: 192.168.1.253
+        - to: 10.1.0.0/99
+          via: 192.168.1.252
+    eth1: {}
+    eth2: {}
+  bonds:
+    bond0:
+      interfaces: [eth1, eth2]
+      dhcp4: true
+      parameters:
+        mode: active-backup
+        mii-monitor-interval: 100
+  wifis:
+    wlan0:
+      dhcp4: true
+      access-points:
+        home:
+          password: correct horse
+";
+
+    fn find<'a>(report: &'a ImportReport, id: &str) -> &'a ConnectionSettings {
+        report
+            .connections
+            .iter()
+            .find(|settings| settings.id() == Some(id))
+            .unwrap()
+    }
+
+    #[test]
+    fn imports_definitions() {
+        let report = import_netplan(CONFIG).unwrap();
+        assert_eq!(report.connections.len(), 5);
+
+        let eth0 = find(&report, "netplan-eth0");
+        assert_eq!(eth0.get_str("connection", "interface-name"), Some("eth0"));
+        assert_eq!(eth0.get_str("ipv4", "method"), Some("manual"));
+        assert_eq!(eth0.get_str("ipv4", "gateway"), Some("192.168.1.1"));
+        assert_eq!(eth0.get_dicts("ipv4", "address-data").unwrap().len(), 1);
+        assert_eq!(eth0.get_dicts("ipv6", "address-data").unwrap().len(), 1);
+
+        let routes = eth0.get_dicts("ipv4", "route-data").unwrap();
+        assert_eq!(routes.len(), 1);
+        assert_eq!(dict_str(&routes[0], "dest").as_deref(), Some("10.0.0.0"));
+        assert_eq!(dict_u32(&routes[0], "prefix"), Some(8));
+        assert_eq!(dict_u32(&routes[0], "metric"), Some(50));
+
+        let eth1 = find(&report, "netplan-eth1");
+        assert_eq!(eth1.get_str("connection", "master"), Some("bond0"));
+        assert_eq!(eth1.get_str("connection", "slave-type"), Some("bond"));
+
+        let bond0 = find(&report, "netplan-bond0");
+        let options = bond0.get_string_map("bond", "options").unwrap();
+        assert_eq!(options["mode"], "active-backup");
+        assert_eq!(options["miimon"], "100");
+
+        let home = find(&report, "netplan-wlan0-home");
+        assert_eq!(home.get_bytes(WIRELESS, "ssid"), Some(b"home".to_vec()));
+        assert_eq!(home.get_str(SECURITY, "psk"), Some("correct horse"));
+    }
+
+    #[test]
+    fn reports_invalid_addresses_routes_and_nameservers() {
+        let report = import_netplan(CONFIG).unwrap();
+        let skipped: Vec<(&str, &str)> = report
+            .untranslated
+            .iter()
+            .map(|untranslated| {
+                (
+                    untranslated.directive.as_str(),
+                    untranslated.reason.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            skipped,
+            [
+                ("ethernets.eth0.addresses[2]", "invalid address"),
+                ("ethernets.eth0.routes[1]", "route without to"),
+                ("ethernets.eth0.routes[2]", "invalid route destination"),
+                ("ethernets.eth0.nameservers.addresses[1]", "invalid address"),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_document_without_network() {
+        assert!(import_netplan("version: 2\n").is_err());
+    }
+
+    #[test]
+    fn exports_unbound_profiles_with_wildcard_match() {
+        let settings = ConnectionSettings::new()
+            .with("connection", "id", "Wired")
+            .with("connection", "type", ETHERNET)
+            .with("ipv4", "method", "auto");
+        let document: Yaml = serde_yaml::from_str(&export_netplan(&[settings])).unwrap();
+        let definition = &document["network"]["ethernets"]["Wired"];
+        assert_eq!(definition["match"]["name"], Yaml::from("*"));
+        assert_eq!(definition["dhcp4"], Yaml::from(true));
+    }
+
+    #[test]
+    fn round_trips_definitions() {
+        let report = import_netplan(CONFIG).unwrap();
+        let exported = export_netplan(&report.connections);
+        let reimported = import_netplan(&exported).unwrap();
+        assert!(
+            reimported.untranslated.is_empty(),
+            "{:?}",
+            reimported.untranslated
+        );
+
+        let mut ids: Vec<_> = reimported.connections.iter().map(|c| c.id()).collect();
+        ids.sort();
+        assert_eq!(
+            ids,
+            [
+                Some("netplan-bond0"),
+                Some("netplan-eth0"),
+                Some("netplan-eth1"),
+                Some("netplan-eth2"),
+                Some("netplan-wlan0-home"),
+            ]
+        );
+        assert_eq!(
+            find(&reimported, "netplan-eth0").get_dicts("ipv4", "route-data"),
+            find(&report, "netplan-eth0").get_dicts("ipv4", "route-data")
+        );
+    }
+}
@@ -0,0 +1,1016 @@
+//! Import of systemd-networkd `.network` and `.netdev` files.
+//!
+//! A `.netdev` file and the `.network` file matching its name become one profile. Other
+//! `.network` files become Ethernet profiles; `Bond=`, `Bridge=` and `VRF=` turn them into
+//! ports and `VLAN=`/`VXLAN=` name the parent of those netdevs. Like networkd, files are
+//! applied in lexical order of their names and the first `.network` file matching an interface
+//! wins. Only `[Match]` sections with a single, literal `Name=` are matched to interfaces.
+//!
+//! Untranslated keys are reported as `<file> [<section>] <key>`.
+
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use zbus::zvariant::Value;
+
+use super::connection_settings::ConnectionSettings;
+use super::import::{
+    ImportReport, ParseError, add_address, add_dns, new_uuid, parse_address, parse_mac,
+};
+use super::wg_quick::{WireGuardConfig, WireGuardInterface, WireGuardPeer};
+
+const ETHERNET: &str = "802-3-ethernet";
+
+/// Parses `(file name, contents)` pairs of `.network` and `.netdev` files.
+pub fn import_networkd<'a>(
+    files: impl IntoIterator<Item = (&'a str, &'a str)>,
+) -> Result<ImportReport, ParseError> {
+    let mut report = ImportReport::default();
+    let mut files: Vec<(&str, &str)> = files.into_iter().collect();
+    files.sort_by_key(|(name, _)| *name);
+
+    let mut netdevs = Vec::new();
+    let mut networks = Vec::new();
+    for (name, input) in files {
+        if name.ends_with(".netdev") {
+            netdevs.push(Unit::parse(name, input)?);
+        } else if name.ends_with(".network") {
+            networks.push(Unit::parse(name, input)?);
+        } else {
+            report.skip(0, name, "only .network and .netdev files are supported");
+        }
+    }
+
+    // The first .network file for each interface
+    let mut network_for: HashMap<String, usize> = HashMap::new();
+    for (idx, network) in networks.iter().enumerate() {
+        match network.match_name().map(|name| network_for.entry(name)) {
+            Some(Entry::Vacant(entry)) => {
+                entry.insert(idx);
+            }
+            Some(Entry::Occupied(_)) => report.skip(
+                0,
+                &network.file,
+                "shadowed by an earlier file for the interface",
+            ),
+            None => report.skip(0, &network.file, "no single interface name to match"),
+        }
+    }
+
+    // Relations declared in the [Network] section of the lower interface
+    let mut ports: HashMap<String, (String, &'static str)> = HashMap::new();
+    let mut parents: HashMap<String, String> = HashMap::new();
+    for (name, &idx) in &network_for {
+        let network = &networks[idx];
+        for (key, port_type) in [("Bond", "bond"), ("Bridge", "bridge"), ("VRF", "vrf")] {
+            if let Some(controller) = network.get("Network", key) {
+                ports.insert(name.clone(), (controller.value.clone(), port_type));
+            }
+        }
+        for key in ["VLAN", "VXLAN", "MACVLAN"] {
+            for entry in network.values("Network", key) {
+                for netdev in entry.value.split_whitespace() {
+                    parents.insert(netdev.to_owned(), name.clone());
+                }
+            }
+        }
+    }
+
+    let mut used = HashSet::new();
+    for netdev in &netdevs {
+        let Some(mut settings) = import_netdev(netdev, &parents, &mut report) else {
+            continue;
+        };
+        let name = settings
+            .get_str("connection", "interface-name")
+            .unwrap_or_default()
+            .to_owned();
+        let network = network_for.get(&name).map(|&idx| {
+            used.insert(idx);
+            &networks[idx]
+        });
+        apply_network(&mut settings, network, ports.get(&name), &mut report);
+        report.connections.push(settings);
+    }
+
+    let mut names: Vec<(&String, &usize)> = network_for.iter().collect();
+    names.sort_by_key(|(_, idx)| **idx);
+    for (name, &idx) in names {
+        if used.contains(&idx) {
+            continue;
+        }
+        let network = &networks[idx];
+        let mut settings = base(name, ETHERNET);
+        settings.add_setting(ETHERNET);
+        apply_network(&mut settings, Some(network), ports.get(name), &mut report);
+        report.connections.push(settings);
+    }
+
+    Ok(report)
+}
+
+struct Assignment {
+    line: usize,
+    key: String,
+    value: String,
+}
+
+struct Section {
+    name: String,
+    entries: Vec<Assignment>,
+}
+
+struct Unit {
+    file: String,
+    sections: Vec<Section>,
+}
+
+impl Unit {
+    fn parse(file: &str, input: &str) -> Result<Unit, ParseError> {
+        let mut sections: Vec<Section> = Vec::new();
+        for (idx, line) in input.lines().enumerate() {
+            let line_number = idx + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+            if let Some(name) = line
+                .strip_prefix('[')
+                .and_then(|line| line.strip_suffix(']'))
+            {
+                sections.push(Section {
+                    name: name.to_owned(),
+                    entries: Vec::new(),
+                });
+                continue;
+            }
+            let (Some(section), Some((key, value))) = (sections.last_mut(), line.split_once('='))
+            else {
+                return Err(ParseError::new(
+                    line_number,
+                    format!("{file}: expected [Section] or Key=Value"),
+                ));
+            };
+            section.entries.push(Assignment {
+                line: line_number,
+                key: key.trim().to_owned(),
+                value: value.trim().to_owned(),
+            });
+        }
+
+        Ok(Unit {
+            file: file.to_owned(),
+            sections,
+        })
+    }
+
+    fn sections<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Section> {
+        self.sections
+            .iter()
+            .filter(move |section| section.name == name)
+    }
+
+    /// All values of `key`, several sections of the same name are combined.
+    fn values<'a>(
+        &'a self,
+        section: &'a str,
+        key: &'a str,
+    ) -> impl Iterator<Item = &'a Assignment> {
+        self.sections(section)
+            .flat_map(|section| section.entries.iter())
+            .filter(move |entry| entry.key == key)
+    }
+
+    /// The value of a single-valued key, the last assignment wins.
+    fn get(&self, section: &str, key: &str) -> Option<&Assignment> {
+        self.sections
+            .iter()
+            .filter(|candidate| candidate.name == section)
+            .flat_map(|section| section.entries.iter())
+            .rfind(|entry| entry.key == key)
+    }
+
+    fn match_name(&self) -> Option<String> {
+        let name = &self.get("Match", "Name")?.value;
+        (!name.contains([' ', '*', '?', '['])).then(|| name.clone())
+    }
+
+    /// Reports the keys of `section` missing from `supported`.
+    fn skip_unsupported(&self, section: &str, supported: &[&str], report: &mut ImportReport) {
+        for entry in self
+            .sections(section)
+            .flat_map(|section| section.entries.iter())
+        {
+            if !supported.contains(&entry.key.as_str()) {
+                self.skip(entry, section, "no NetworkManager equivalent", report);
+            }
+        }
+    }
+
+    fn skip(&self, entry: &Assignment, section: &str, reason: &str, report: &mut ImportReport) {
+        report.skip(
+            entry.line,
+            &format!("{} [{section}] {}", self.file, entry.key),
+            reason,
+        );
+    }
+}
+
+/// Keys of each `.network` section that are translated.
+const NETWORK_KEYS: &[(&str, &[&str])] = &[
+    ("Match", &["Name", "MACAddress"]),
+    ("Link", &["MTUBytes", "MACAddress"]),
+    (
+        "Network",
+        &[
+            "Address",
+            "Gateway",
+            "DNS",
+            "Domains",
+            "DHCP",
+            "IPv6AcceptRA",
+            "IPv6PrivacyExtensions",
+            "Bond",
+            "Bridge",
+            "VRF",
+            "VLAN",
+            "VXLAN",
+            "MACVLAN",
+        ],
+    ),
+    ("Address", &["Address"]),
+    (
+        "Route",
+        &["Destination", "Gateway", "Metric", "Table", "GatewayOnLink"],
+    ),
+    (
+        "DHCPv4",
+        &[
+            "RouteMetric",
+            "UseDNS",
+            "UseRoutes",
+            "ClientIdentifier",
+            "Hostname",
+        ],
+    ),
+    ("DHCPv6", &["UseDNS"]),
+];
+
+fn apply_network(
+    settings: &mut ConnectionSettings,
+    network: Option<&Unit>,
+    port: Option<&(String, &'static str)>,
+    report: &mut ImportReport,
+) {
+    // VRF ports keep their own IP configuration
+    let l2_port = port.filter(|(_, port_type)| *port_type != "vrf");
+    if let Some((controller, port_type)) = port {
+        settings.set("connection", "master", controller.clone());
+        settings.set("connection", "slave-type", *port_type);
+    }
+
+    let Some(network) = network else {
+        if l2_port.is_none() {
+            set_methods(settings, false, false, false, false);
+        }
+        return;
+    };
+
+    for section in &network.sections {
+        match NETWORK_KEYS.iter().find(|(name, _)| *name == section.name) {
+            Some((name, keys)) => network.skip_unsupported(name, keys, report),
+            None => {
+                for entry in &section.entries {
+                    network.skip(entry, &section.name, "unsupported section", report);
+                }
+            }
+        }
+    }
+
+    if let Some(mac) = network.get("Match", "MACAddress") {
+        match parse_mac(&mac.value) {
+            Some(mac) => settings.set(ETHERNET, "mac-address", mac),
+            None => network.skip(mac, "Match", "only a single address is supported", report),
+        }
+    }
+    if let Some(mtu) = network.get("Link", "MTUBytes") {
+        match mtu.value.parse::<u32>() {
+            Ok(mtu) => {
+                let setting = match settings.connection_type() {
+                    Some("wireguard") => "wireguard",
+                    _ => ETHERNET,
+                };
+                settings.set(setting, "mtu", mtu);
+            }
+            Err(_) => network.skip(mtu, "Link", "invalid value", report),
+        }
+    }
+    if let Some(mac) = network.get("Link", "MACAddress") {
+        settings.set(ETHERNET, "assigned-mac-address", mac.value.clone());
+    }
+
+    if l2_port.is_some() {
+        for section in ["Network", "Address", "Route"] {
+            for entry in network
+                .values(section, "Address")
+                .chain(network.values(section, "Gateway"))
+            {
+                network.skip(entry, section, "ports have no IP configuration", report);
+            }
+        }
+        return;
+    }
+
+    let addresses = network
+        .values("Network", "Address")
+        .chain(network.values("Address", "Address"));
+    let mut ipv4_addresses = false;
+    let mut ipv6_addresses = false;
+    for entry in addresses {
+        if add_address(settings, &entry.value) {
+            if entry.value.contains(':') {
+                ipv6_addresses = true;
+            } else {
+                ipv4_addresses = true;
+            }
+        } else {
+            network.skip(entry, "Network", "invalid address", report);
+        }
+    }
+
+    let dhcp = network
+        .get("Network", "DHCP")
+        .map_or("no", |entry| entry.value.as_str());
+    let dhcp4 = matches!(dhcp, "yes" | "true" | "ipv4" | "both");
+    let dhcp6 = matches!(dhcp, "yes" | "true" | "ipv6" | "both");
+    let accept_ra = network
+        .get("Network", "IPv6AcceptRA")
+        .is_some_and(|entry| parse_bool(&entry.value) == Some(true));
+    set_methods(
+        settings,
+        dhcp4,
+        ipv4_addresses,
+        dhcp6 || accept_ra,
+        ipv6_addresses,
+    );
+
+    for entry in network.values("Network", "Gateway") {
+        match entry.value.parse::<IpAddr>() {
+            Ok(gateway) => settings.set(family(gateway), "gateway", gateway.to_string()),
+            Err(_) => network.skip(entry, "Network", "invalid address", report),
+        }
+    }
+    for entry in network.values("Network", "DNS") {
+        for server in entry.value.split_whitespace() {
+            match server.parse::<IpAddr>() {
+                Ok(server) => add_dns(settings, server),
+                Err(_) => network.skip(entry, "Network", "invalid address", report),
+            }
+        }
+    }
+    let domains: Vec<String> = network
+        .values("Network", "Domains")
+        .flat_map(|entry| entry.value.split_whitespace())
+        .map(ToOwned::to_owned)
+        .collect();
+    if !domains.is_empty() {
+        settings.set("ipv4", "dns-search", domains);
+    }
+    if let Some(privacy) = network.get("Network", "IPv6PrivacyExtensions") {
+        // NMSettingIP6ConfigPrivacy
+        let value = match privacy.value.as_str() {
+            "prefer-public" => 1i32,
+            "kernel" => -1,
+            value if parse_bool(value) == Some(true) => 2,
+            _ => 0,
+        };
+        settings.set("ipv6", "ip6-privacy", value);
+    }
+
+    for section in network.sections("Route") {
+        import_route(settings, network, section, report);
+    }
+
+    if let Some(metric) = network.get("DHCPv4", "RouteMetric") {
+        match metric.value.parse::<i64>() {
+            Ok(metric) => settings.set("ipv4", "route-metric", metric),
+            Err(_) => network.skip(metric, "DHCPv4", "invalid value", report),
+        }
+    }
+    for (section, setting) in [("DHCPv4", "ipv4"), ("DHCPv6", "ipv6")] {
+        if network
+            .get(section, "UseDNS")
+            .is_some_and(|entry| parse_bool(&entry.value) == Some(false))
+        {
+            settings.set(setting, "ignore-auto-dns", true);
+        }
+    }
+    if network
+        .get("DHCPv4", "UseRoutes")
+        .is_some_and(|entry| parse_bool(&entry.value) == Some(false))
+    {
+        settings.set("ipv4", "ignore-auto-routes", true);
+    }
+    if let Some(client_id) = network.get("DHCPv4", "ClientIdentifier") {
+        settings.set("ipv4", "dhcp-client-id", client_id.value.clone());
+    }
+    if let Some(hostname) = network.get("DHCPv4", "Hostname") {
+        settings.set("ipv4", "dhcp-hostname", hostname.value.clone());
+    }
+}
+
+fn set_methods(
+    settings: &mut ConnectionSettings,
+    dhcp4: bool,
+    ipv4_addresses: bool,
+    ipv6_auto: bool,
+    ipv6_addresses: bool,
+) {
+    let ipv4 = if dhcp4 {
+        "auto"
+    } else if ipv4_addresses {
+        "manual"
+    } else {
+        "disabled"
+    };
+    let ipv6 = if ipv6_auto {
+        "auto"
+    } else if ipv6_addresses {
+        "manual"
+    } else {
+        "ignore"
+    };
+    settings.set("ipv4", "method", ipv4);
+    settings.set("ipv6", "method", ipv6);
+}
+
+fn import_route(
+    settings: &mut ConnectionSettings,
+    network: &Unit,
+    section: &Section,
+    report: &mut ImportReport,
+) {
+    let get = |key: &str| section.entries.iter().rev().find(|entry| entry.key == key);
+    // Leaving out a value would import a different route, so invalid ones skip the route
+    let (Ok(gateway), Ok(metric), Ok(table)) = (
+        route_value::<IpAddr>(network, section, "Gateway", report),
+        route_value::<u32>(network, section, "Metric", report),
+        route_value::<u32>(network, section, "Table", report),
+    ) else {
+        return;
+    };
+    let destination = get("Destination").map(|entry| entry.value.clone());
+    let ipv6 = match (&destination, gateway) {
+        (Some(destination), _) => destination.contains(':'),
+        (None, Some(gateway)) => gateway.is_ipv6(),
+        (None, None) => return,
+    };
+    let destination = destination.unwrap_or_else(|| {
+        if ipv6 {
+            "::/0".to_owned()
+        } else {
+            "0.0.0.0/0".to_owned()
+        }
+    });
+    let Some((dest, prefix)) = parse_address(&destination) else {
+        if let Some(entry) = get("Destination") {
+            network.skip(entry, "Route", "invalid value", report);
+        }
+        return;
+    };
+
+    let mut route = HashMap::from([
+        ("dest".to_owned(), Value::from(dest.to_string())),
+        ("prefix".to_owned(), Value::from(prefix)),
+    ]);
+    if let Some(gateway) = gateway {
+        route.insert("next-hop".to_owned(), Value::from(gateway.to_string()));
+    }
+    if let Some(metric) = metric {
+        route.insert("metric".to_owned(), Value::from(metric));
+    }
+    if let Some(table) = table {
+        route.insert("table".to_owned(), Value::from(table));
+    }
+    if get("GatewayOnLink").is_some_and(|entry| parse_bool(&entry.value) == Some(true)) {
+        route.insert("onlink".to_owned(), Value::from(true));
+    }
+
+    let setting = if ipv6 { "ipv6" } else { "ipv4" };
+    let mut routes: Vec<HashMap<String, Value<'static>>> = settings
+        .get_dicts(setting, "route-data")
+        .unwrap_or_default()
+        .into_iter()
+        .map(|route| {
+            route
+                .into_iter()
+                .map(|(key, value)| (key, Value::from(value)))
+                .collect()
+        })
+        .collect();
+    routes.push(route);
+    settings.set(setting, "route-data", routes);
+}
+
+/// The last `key` of a `[Route]` section, `Err` after reporting it when it doesn't parse.
+fn route_value<T: FromStr>(
+    network: &Unit,
+    section: &Section,
+    key: &str,
+    report: &mut ImportReport,
+) -> Result<Option<T>, ()> {
+    let Some(entry) = section.entries.iter().rev().find(|entry| entry.key == key) else {
+        return Ok(None);
+    };
+    match entry.value.parse() {
+        Ok(value) => Ok(Some(value)),
+        Err(_) => {
+            network.skip(entry, "Route", "invalid value", report);
+            Err(())
+        }
+    }
+}
+
+/// `[Bond]` keys and the kernel options they set, with whether the value is a duration that
+/// has to be converted to milliseconds.
+const BOND_KEYS: &[(&str, &str, bool)] = &[
+    ("Mode", "mode", false),
+    ("TransmitHashPolicy", "xmit_hash_policy", false),
+    ("LACPTransmitRate", "lacp_rate", false),
+    ("MIIMonitorSec", "miimon", true),
+    ("UpDelaySec", "updelay", true),
+    ("DownDelaySec", "downdelay", true),
+    ("ARPIntervalSec", "arp_interval", true),
+    ("ARPIPTargets", "arp_ip_target", false),
+    ("ARPValidate", "arp_validate", false),
+    ("ARPAllTargets", "arp_all_targets", false),
+    ("PrimaryReselectPolicy", "primary_reselect", false),
+    ("FailOverMACPolicy", "fail_over_mac", false),
+    ("AdSelect", "ad_select", false),
+    ("MinLinks", "min_links", false),
+    ("GratuitousARP", "num_grat_arp", false),
+    ("AllSlavesActive", "all_slaves_active", false),
+    ("PacketsPerSlave", "packets_per_slave", false),
+    ("ResendIGMP", "resend_igmp", false),
+];
+
+/// `[Bridge]` keys that are durations, and the `bridge` properties taking them in seconds.
+const BRIDGE_DURATIONS: &[(&str, &str)] = &[
+    ("ForwardDelaySec", "forward-delay"),
+    ("HelloTimeSec", "hello-time"),
+    ("MaxAgeSec", "max-age"),
+    ("AgeingTimeSec", "ageing-time"),
+];
+
+fn import_netdev(
+    netdev: &Unit,
+    parents: &HashMap<String, String>,
+    report: &mut ImportReport,
+) -> Option<ConnectionSettings> {
+    let Some(name) = netdev
+        .get("NetDev", "Name")
+        .map(|entry| entry.value.clone())
+    else {
+        report.skip(0, &netdev.file, "no [NetDev] Name");
+        return None;
+    };
+    let kind = netdev
+        .get("NetDev", "Kind")
+        .map_or("", |entry| entry.value.as_str());
+    netdev.skip_unsupported(
+        "NetDev",
+        &["Name", "Kind", "MTUBytes", "MACAddress"],
+        report,
+    );
+
+    let mut settings = match kind {
+        "bond" => {
+            let mut settings = base(&name, "bond");
+            let mut options = HashMap::new();
+            for entry in netdev
+                .sections("Bond")
+                .flat_map(|section| section.entries.iter())
+            {
+                let Some((_, option, duration)) =
+                    BOND_KEYS.iter().find(|(key, _, _)| *key == entry.key)
+                else {
+                    netdev.skip(entry, "Bond", "no NetworkManager equivalent", report);
+                    continue;
+                };
+                let value = if *duration {
+                    duration_ms(&entry.value).map(|ms| ms.to_string())
+                } else if let Some(enabled) =
+                    parse_bool(&entry.value).filter(|_| entry.key == "AllSlavesActive")
+                {
+                    Some(u8::from(enabled).to_string())
+                } else {
+                    Some(entry.value.split_whitespace().collect::<Vec<_>>().join(","))
+                };
+                match value {
+                    Some(value) => {
+                        options.insert(option.to_string(), value);
+                    }
+                    None => netdev.skip(entry, "Bond", "invalid value", report),
+                }
+            }
+            settings.set("bond", "options", options);
+            settings
+        }
+        "bridge" => {
+            let mut settings = base(&name, "bridge");
+            settings.add_setting("bridge");
+            for entry in netdev
+                .sections("Bridge")
+                .flat_map(|section| section.entries.iter())
+            {
+                let key = entry.key.as_str();
+                if let Some((_, property)) = BRIDGE_DURATIONS.iter().find(|(name, _)| *name == key)
+                {
+                    match duration_ms(&entry.value) {
+                        Some(ms) => settings.set("bridge", property, (ms / 1000) as u32),
+                        None => netdev.skip(entry, "Bridge", "invalid value", report),
+                    }
+                    continue;
+                }
+                match (key, parse_bool(&entry.value), entry.value.parse::<u32>()) {
+                    ("STP", Some(stp), _) => settings.set("bridge", "stp", stp),
+                    ("VLANFiltering", Some(enabled), _) => {
+                        settings.set("bridge", "vlan-filtering", enabled)
+                    }
+                    ("MulticastSnooping", Some(enabled), _) => {
+                        settings.set("bridge", "multicast-snooping", enabled)
+                    }
+                    ("Priority", _, Ok(priority)) => settings.set("bridge", "priority", priority),
+                    ("DefaultPVID", _, Ok(pvid)) => {
+                        settings.set("bridge", "vlan-default-pvid", pvid)
+                    }
+                    (
+                        "STP" | "VLANFiltering" | "MulticastSnooping" | "Priority" | "DefaultPVID",
+                        _,
+                        _,
+                    ) => netdev.skip(entry, "Bridge", "invalid value", report),
+                    _ => netdev.skip(entry, "Bridge", "no NetworkManager equivalent", report),
+                }
+            }
+            settings
+        }
+        "vlan" => {
+            let mut settings = base(&name, "vlan");
+            netdev.skip_unsupported("VLAN", &["Id"], report);
+            match netdev
+                .get("VLAN", "Id")
+                .and_then(|entry| entry.value.parse::<u32>().ok())
+            {
+                Some(id) => settings.set("vlan", "id", id),
+                None => report.skip(
+                    0,
+                    &format!("{} [VLAN] Id", netdev.file),
+                    "missing or invalid",
+                ),
+            }
+            match parents.get(&name) {
+                Some(parent) => settings.set("vlan", "parent", parent.clone()),
+                None => report.skip(0, &netdev.file, "no .network file names the VLAN parent"),
+            }
+            settings
+        }
+        "vxlan" => {
+            let mut settings = base(&name, "vxlan");
+            netdev.skip_unsupported(
+                "VXLAN",
+                &[
+                    "VNI",
+                    "Id",
+                    "Remote",
+                    "Group",
+                    "Local",
+                    "DestinationPort",
+                    "TTL",
+                ],
+                report,
+            );
+            let vni = netdev
+                .get("VXLAN", "VNI")
+                .or_else(|| netdev.get("VXLAN", "Id"));
+            if let Some(vni) = vni.and_then(|entry| entry.value.parse::<u32>().ok()) {
+                settings.set("vxlan", "id", vni);
+            }
+            for (key, property) in [
+                ("Remote", "remote"),
+                ("Group", "remote"),
+                ("Local", "local"),
+            ] {
+                if let Some(entry) = netdev.get("VXLAN", key) {
+                    settings.set("vxlan", property, entry.value.clone());
+                }
+            }
+            for (key, property) in [("DestinationPort", "destination-port"), ("TTL", "ttl")] {
+                if let Some(value) = netdev
+                    .get("VXLAN", key)
+                    .and_then(|entry| entry.value.parse::<u32>().ok())
+                {
+                    settings.set("vxlan", property, value);
+                }
+            }
+            if let Some(parent) = parents.get(&name) {
+                settings.set("vxlan", "parent", parent.clone());
+            }
+            settings
+        }
+        "wireguard" => import_wireguard(netdev, &name, report),
+        "vrf" => {
+            let mut settings = base(&name, "vrf");
+            netdev.skip_unsupported("VRF", &["Table"], report);
+            match netdev
+                .get("VRF", "Table")
+                .and_then(|entry| entry.value.parse::<u32>().ok())
+            {
+                Some(table) => settings.set("vrf", "table", table),
+                None => report.skip(
+                    0,
+                    &format!("{} [VRF] Table", netdev.file),
+                    "missing or invalid",
+                ),
+            }
+            settings
+        }
+        _ => {
+            report.skip(0, &netdev.file, "unsupported netdev kind");
+            return None;
+        }
+    };
+
+    if let Some(mtu) = netdev
+        .get("NetDev", "MTUBytes")
+        .and_then(|entry| entry.value.parse::<u32>().ok())
+    {
+        let setting = if kind == "wireguard" {
+            "wireguard"
+        } else {
+            ETHERNET
+        };
+        settings.set(setting, "mtu", mtu);
+    }
+    if let Some(mac) = netdev.get("NetDev", "MACAddress") {
+        settings.set(ETHERNET, "assigned-mac-address", mac.value.clone());
+    }
+
+    Some(settings)
+}
+
+fn import_wireguard(netdev: &Unit, name: &str, report: &mut ImportReport) -> ConnectionSettings {
+    netdev.skip_unsupported(
+        "WireGuard",
+        &["PrivateKey", "ListenPort", "FirewallMark"],
+        report,
+    );
+    netdev.skip_unsupported(
+        "WireGuardPeer",
+        &[
+            "PublicKey",
+            "PresharedKey",
+            "AllowedIPs",
+            "Endpoint",
+            "PersistentKeepalive",
+        ],
+        report,
+    );
+
+    let get = |key: &str| {
+        netdev
+            .get("WireGuard", key)
+            .map(|entry| entry.value.clone())
+    };
+    let config = WireGuardConfig {
+        name: name.to_owned(),
+        interface: WireGuardInterface {
+            private_key: get("PrivateKey"),
+            listen_port: get("ListenPort").and_then(|port| port.parse().ok()),
+            fw_mark: get("FirewallMark").and_then(|mark| mark.parse().ok()),
+            ..WireGuardInterface::default()
+        },
+        peers: netdev
+            .sections("WireGuardPeer")
+            .map(|section| {
+                let get = |key: &str| {
+                    section
+                        .entries
+                        .iter()
+                        .rev()
+                        .find(|entry| entry.key == key)
+                        .map(|entry| entry.value.clone())
+                };
+                WireGuardPeer {
+                    public_key: get("PublicKey").unwrap_or_default(),
+                    preshared_key: get("PresharedKey"),
+                    endpoint: get("Endpoint"),
+                    allowed_ips: section
+                        .entries
+                        .iter()
+                        .filter(|entry| entry.key == "AllowedIPs")
+                        .flat_map(|entry| entry.value.split([',', ' ']))
+                        .filter(|ip| !ip.is_empty())
+                        .map(ToOwned::to_owned)
+                        .collect(),
+                    persistent_keepalive: get("PersistentKeepalive")
+                        .and_then(|keepalive| keepalive.parse().ok()),
+                }
+            })
+            .collect(),
+    };
+
+    config.to_settings()
+}
+
+fn base(name: &str, connection_type: &'static str) -> ConnectionSettings {
+    ConnectionSettings::new()
+        .with("connection", "id", name.to_owned())
+        .with("connection", "uuid", new_uuid())
+        .with("connection", "type", connection_type)
+        .with("connection", "interface-name", name.to_owned())
+}
+
+fn family(ip: IpAddr) -> &'static str {
+    if ip.is_ipv4() { "ipv4" } else { "ipv6" }
+}
+
+/// systemd booleans.
+fn parse_bool(value: &str) -> Option<bool> {
+    match value {
+        "yes" | "true" | "on" | "1" => Some(true),
+        "no" | "false" | "off" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+/// A systemd time span like `100ms`, `2s` or `1min`, plain numbers are seconds.
+fn duration_ms(value: &str) -> Option<u64> {
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: u64 = number.parse().ok()?;
+    let factor = match unit.trim() {
+        "ms" | "msec" => 1,
+        "" | "s" | "sec" => 1000,
+        "min" | "m" => 60_000,
+        _ => return None,
+    };
+    Some(number * factor)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network_manager::wg_quick::{dict_str, dict_u32};
+
+    const UPLINK: &str = "\
+[Match]
+Name=eth0
+
+[Network]
+Address=192.168.1.10/24
+Gateway=192.168.1.1
+DNS=192.168.1.1 2001:db8::1
+Domains=example.com
+VLAN=eth0.10
+LLMNR=no
+
+[Route]
+Destination=10.0.0.0/8
+Gateway=192.168.1.254
+Metric=50
+
+[Route]
+Destination=10.1.0.0/40
+Gateway=192.168.1.253
+
+[Route]
+Destination=172.16.0.0/12
+Metric=high
+";
+
+    const VLAN_NETDEV: &str = "\
+[NetDev]
+Name=eth0.10
+Kind=vlan
+
+[VLAN]
+Id=10
+";
+
+    const VLAN_NETWORK: &str = "\
+[Match]
+Name=eth0.10
+
+[Network]
+DHCP=yes
+";
+
+    const BOND_NETDEV: &str = "\
+[NetDev]
+Name=bond0
+Kind=bond
+
+[Bond]
+Mode=active-backup
+MIIMonitorSec=100ms
+";
+
+    const BOND_PORT: &str = "\
+[Match]
+Name=eth1
+
+[Network]
+Bond=bond0
+";
+
+    fn import() -> ImportReport {
+        import_networkd([
+            ("10-eth0.network", UPLINK),
+            ("20-vlan.netdev", VLAN_NETDEV),
+            ("20-vlan.network", VLAN_NETWORK),
+            ("30-bond.netdev", BOND_NETDEV),
+            ("30-eth1.network", BOND_PORT),
+            ("README", ""),
+        ])
+        .unwrap()
+    }
+
+    fn find<'a>(report: &'a ImportReport, name: &str) -> &'a ConnectionSettings {
+        report
+            .connections
+            .iter()
+            .find(|settings| settings.get_str("connection", "interface-name") == Some(name))
+            .unwrap()
+    }
+
+    #[test]
+    fn imports_networks() {
+        let report = import();
+        assert_eq!(report.connections.len(), 4);
+
+        let eth0 = find(&report, "eth0");
+        assert_eq!(eth0.connection_type(), Some(ETHERNET));
+        assert_eq!(eth0.get_str("ipv4", "method"), Some("manual"));
+        assert_eq!(eth0.get_str("ipv4", "gateway"), Some("192.168.1.1"));
+        assert_eq!(
+            eth0.get_strings("ipv4", "dns-search"),
+            Some(vec!["example.com".to_owned()])
+        );
+        let routes = eth0.get_dicts("ipv4", "route-data").unwrap();
+        assert_eq!(routes.len(), 1);
+        assert_eq!(dict_str(&routes[0], "dest").as_deref(), Some("10.0.0.0"));
+        assert_eq!(dict_u32(&routes[0], "prefix"), Some(8));
+        assert_eq!(dict_u32(&routes[0], "metric"), Some(50));
+    }
+
+    #[test]
+    fn imports_netdevs() {
+        let report = import();
+
+        let vlan = find(&report, "eth0.10");
+        assert_eq!(vlan.connection_type(), Some("vlan"));
+        assert_eq!(vlan.get_u32("vlan", "id"), Some(10));
+        assert_eq!(vlan.get_str("vlan", "parent"), Some("eth0"));
+        assert_eq!(vlan.get_str("ipv4", "method"), Some("auto"));
+
+        let bond = find(&report, "bond0");
+        let options = bond.get_string_map("bond", "options").unwrap();
+        assert_eq!(options["mode"], "active-backup");
+        assert_eq!(options["miimon"], "100");
+
+        let port = find(&report, "eth1");
+        assert_eq!(port.get_str("connection", "master"), Some("bond0"));
+        assert_eq!(port.get_str("connection", "slave-type"), Some("bond"));
+        assert!(port.get("ipv4", "method").is_none());
+    }
+
+    #[test]
+    fn reports_untranslated_keys() {
+        let report = import();
+        let mut skipped: Vec<(usize, &str)> = report
+            .untranslated
+            .iter()
+            .map(|untranslated| (untranslated.line, untranslated.directive.as_str()))
+            .collect();
+        skipped.sort();
+        assert_eq!(
+            skipped,
+            [
+                (0, "README"),
+                (10, "10-eth0.network [Network] LLMNR"),
+                (18, "10-eth0.network [Route] Destination"),
+                (23, "10-eth0.network [Route] Metric"),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_assignment_outside_of_section() {
+        let err = import_networkd([("eth0.network", "Name=eth0\n")]).unwrap_err();
+        assert_eq!(err.line, 1);
+    }
+}
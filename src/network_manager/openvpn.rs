@@ -0,0 +1,464 @@
+//! Import of OpenVPN `.ovpn` client configurations.
+//!
+//! The NetworkManager OpenVPN plugin reads its options from the `vpn.data` string dictionary
+//! and only takes certificates and keys as file paths, so inline `<ca>`, `<cert>`, `<key>`,
+//! `<tls-auth>` and `<tls-crypt>` blocks are returned as [`InlineFile`]s that have to be
+//! written out before the profile is activated.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+
+use super::connection_settings::ConnectionSettings;
+use super::import::{ImportReport, ParseError, new_uuid};
+
+/// The `vpn.service-type` of the OpenVPN plugin.
+pub const OPENVPN_SERVICE_TYPE: &str = "org.freedesktop.NetworkManager.openvpn";
+
+/// The profile translated from an `.ovpn` file.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OvpnImport {
+    pub report: ImportReport,
+    /// Inline blocks the profile refers to by path.
+    pub files: Vec<InlineFile>,
+}
+
+impl OvpnImport {
+    /// Writes the inline blocks to their paths, readable by the owner only.
+    pub fn write_files(&self) -> io::Result<()> {
+        for file in &self.files {
+            if let Some(parent) = file.path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .mode(0o600)
+                .open(&file.path)?
+                .write_all(file.contents.as_bytes())?;
+        }
+        Ok(())
+    }
+}
+
+/// The contents of an inline block and the path the profile expects it at.
+#[derive(Clone, PartialEq, Eq)]
+pub struct InlineFile {
+    pub path: PathBuf,
+    pub contents: String,
+}
+
+impl fmt::Debug for InlineFile {
+    // The contents are usually private keys
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InlineFile")
+            .field("path", &self.path)
+            .field("contents", &format_args!("<{} bytes>", self.contents.len()))
+            .finish()
+    }
+}
+
+/// Parses an `.ovpn` file into a VPN profile named `name`.
+///
+/// Inline blocks are placed in `cert_dir` as `<name>-<block>.pem`, see
+/// [`OvpnImport::write_files`].
+pub fn import_ovpn(name: &str, input: &str, cert_dir: &Path) -> Result<OvpnImport, ParseError> {
+    let mut import = OvpnImport::default();
+    let mut data: HashMap<String, String> = HashMap::new();
+    let mut remotes: Vec<Remote> = Vec::new();
+    let mut default_port = None;
+    let mut default_proto = None;
+    let mut password_auth = false;
+    let mut static_key = false;
+    let mut key_direction = None;
+
+    let file_prefix = name.replace(['/', '\0'], "_");
+    let mut lines = input.lines().enumerate();
+    while let Some((idx, line)) = lines.next() {
+        let line_number = idx + 1;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+
+        if let Some(tag) = line.strip_prefix('<').and_then(|tag| tag.strip_suffix('>')) {
+            let end = format!("</{tag}>");
+            let mut contents = String::new();
+            loop {
+                let Some((_, line)) = lines.next() else {
+                    return Err(ParseError::new(
+                        line_number,
+                        format!("unterminated <{tag}>"),
+                    ));
+                };
+                if line.trim() == end {
+                    break;
+                }
+                contents.push_str(line);
+                contents.push('\n');
+            }
+
+            let key = match tag {
+                "ca" | "cert" | "key" | "tls-crypt" | "tls-crypt-v2" => tag,
+                "tls-auth" => "ta",
+                "secret" => {
+                    static_key = true;
+                    "static-key"
+                }
+                _ => {
+                    import
+                        .report
+                        .skip(line_number, tag, "unsupported inline block");
+                    continue;
+                }
+            };
+            let path = cert_dir.join(format!("{file_prefix}-{tag}.pem"));
+            data.insert(key.to_owned(), path.to_string_lossy().into_owned());
+            import.files.push(InlineFile { path, contents });
+            continue;
+        }
+
+        let args = split_args(line);
+        let Some((directive, args)) = args.split_first() else {
+            continue;
+        };
+        let directive = directive.as_str();
+        let arg = |idx: usize| args.get(idx).map(String::as_str);
+        let mut set = |key: &str, value: &str| {
+            data.insert(key.to_owned(), value.to_owned());
+        };
+
+        match (directive, args.len()) {
+            // NetworkManager always runs OpenVPN as a client that keeps its key and tunnel
+            ("client" | "tls-client" | "pull" | "nobind" | "persist-key" | "persist-tun", _) => {}
+            ("remote", 1..=3) => remotes.push(Remote {
+                host: args[0].clone(),
+                port: arg(1).map(ToOwned::to_owned),
+                proto: arg(2).map(ToOwned::to_owned),
+            }),
+            ("port", 1) => default_port = Some(args[0].clone()),
+            ("proto", 1) => default_proto = Some(args[0].clone()),
+            ("dev", 1) => {
+                let dev = &args[0];
+                if dev.starts_with("tap") {
+                    set("dev-type", "tap");
+                } else if dev.starts_with("tun") {
+                    set("dev-type", "tun");
+                }
+                if dev != "tun" && dev != "tap" {
+                    set("dev", dev);
+                }
+            }
+            ("dev-type", 1) => set("dev-type", &args[0]),
+            ("ca" | "cert" | "key" | "tls-crypt" | "tls-crypt-v2", 1) => set(directive, &args[0]),
+            ("pkcs12", 1) => {
+                // The plugin recognises a PKCS#12 bundle by the same path in all three keys
+                for key in ["ca", "cert", "key"] {
+                    set(key, &args[0]);
+                }
+            }
+            ("tls-auth", 1..=2) => {
+                if args[0] != "[inline]" {
+                    set("ta", &args[0]);
+                }
+                if let Some(direction) = arg(1) {
+                    set("ta-dir", direction);
+                }
+            }
+            ("secret", 1..=2) => {
+                static_key = true;
+                if args[0] != "[inline]" {
+                    set("static-key", &args[0]);
+                }
+                if let Some(direction) = arg(1) {
+                    set("static-key-direction", direction);
+                }
+            }
+            ("key-direction", 1) => key_direction = Some(args[0].clone()),
+            ("cipher" | "auth" | "tls-cipher" | "data-ciphers" | "remote-cert-tls", 1) => {
+                set(directive, &args[0])
+            }
+            ("comp-lzo", 0) => set("comp-lzo", "adaptive"),
+            ("comp-lzo", 1) => match args[0].as_str() {
+                "yes" | "adaptive" => set("comp-lzo", &args[0]),
+                "no" => set("comp-lzo", "no-by-default"),
+                _ => import.report.skip(line_number, directive, "invalid value"),
+            },
+            ("compress", 0) => set("compress", "yes"),
+            ("compress", 1) => set("compress", &args[0]),
+            ("auth-user-pass", 0) => password_auth = true,
+            ("auth-user-pass", 1) => {
+                password_auth = true;
+                import
+                    .report
+                    .skip(line_number, directive, "credential files are not imported");
+            }
+            ("verify-x509-name", 1) => set("verify-x509-name", &format!("subject:{}", args[0])),
+            ("verify-x509-name", 2) => set("verify-x509-name", &format!("{}:{}", args[1], args[0])),
+            ("tls-version-min", 1..=2) => set("tls-version-min", &args[0]),
+            ("tls-version-max", 1) => set("tls-version-max", &args[0]),
+            ("reneg-sec", 1) => set("reneg-seconds", &args[0]),
+            ("tun-mtu", 1) => set("tunnel-mtu", &args[0]),
+            ("fragment", 1) => set("fragment-size", &args[0]),
+            ("mssfix" | "ping" | "ping-exit" | "ping-restart" | "mtu-disc", 1) => {
+                set(directive, &args[0])
+            }
+            ("connect-timeout" | "server-poll-timeout", 1) => set("connect-timeout", &args[0]),
+            ("remote-random" | "float", 0) => set(directive, "yes"),
+            ("http-proxy" | "socks-proxy", 1..) => {
+                set("proxy-type", directive.trim_end_matches("-proxy"));
+                set("proxy-server", &args[0]);
+                if let Some(port) = arg(1) {
+                    set("proxy-port", port);
+                }
+            }
+            _ => import
+                .report
+                .skip(line_number, directive, "no NetworkManager equivalent"),
+        }
+    }
+
+    if remotes.is_empty() {
+        return Err(ParseError::new(input.lines().count().max(1), "no remote"));
+    }
+    // Complete every remote to host:port:proto so the defaults apply to all of them
+    let remotes: Vec<String> = remotes
+        .into_iter()
+        .map(|mut remote| {
+            if remote.port.is_none() {
+                remote.port.clone_from(&default_port);
+            }
+            if remote.port.is_some() && remote.proto.is_none() {
+                remote.proto.clone_from(&default_proto);
+            }
+            remote.to_string()
+        })
+        .collect();
+    data.insert("remote".to_owned(), remotes.join(", "));
+    if default_proto
+        .as_deref()
+        .is_some_and(|proto| proto.starts_with("tcp"))
+    {
+        data.insert("proto-tcp".to_owned(), "yes".to_owned());
+    }
+    if let Some(port) = default_port {
+        data.insert("port".to_owned(), port);
+    }
+
+    if let Some(direction) = key_direction {
+        let key = if static_key {
+            "static-key-direction"
+        } else {
+            "ta-dir"
+        };
+        data.entry(key.to_owned()).or_insert(direction);
+    }
+
+    let has_cert = data.contains_key("cert") && data.contains_key("key");
+    let connection_type = match (static_key, password_auth, has_cert) {
+        (true, _, _) => "static-key",
+        (false, true, true) => "password-tls",
+        (false, true, false) => "password",
+        (false, false, _) => "tls",
+    };
+    data.insert("connection-type".to_owned(), connection_type.to_owned());
+    if password_auth {
+        // The password is owned by a secret agent
+        data.insert("password-flags".to_owned(), "1".to_owned());
+    }
+
+    let settings = ConnectionSettings::new()
+        .with("connection", "id", name.to_owned())
+        .with("connection", "uuid", new_uuid())
+        .with("connection", "type", "vpn")
+        .with("vpn", "service-type", OPENVPN_SERVICE_TYPE)
+        .with("vpn", "data", data)
+        .with("ipv4", "method", "auto")
+        .with("ipv6", "method", "auto");
+    import.report.connections.push(settings);
+
+    Ok(import)
+}
+
+/// A `remote` directive.
+struct Remote {
+    host: String,
+    port: Option<String>,
+    proto: Option<String>,
+}
+
+impl fmt::Display for Remote {
+    /// Formats the remote as the plugin expects it, `host[:port[:proto]]` with IPv6 hosts in
+    /// brackets.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.host.contains(':') {
+            write!(f, "[{}]", self.host)?;
+        } else {
+            f.write_str(&self.host)?;
+        }
+        if let Some(port) = &self.port {
+            write!(f, ":{port}")?;
+            if let Some(proto) = &self.proto {
+                write!(f, ":{proto}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Splits a directive into its arguments, honouring quotes and backslash escapes.
+fn split_args(line: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut in_arg = false;
+    let mut quote = None;
+    let mut chars = line.chars();
+
+    while let Some(c) = chars.next() {
+        match (c, quote) {
+            ('\\', _) => {
+                if let Some(escaped) = chars.next() {
+                    current.push(escaped);
+                }
+                in_arg = true;
+            }
+            ('"' | '\'', None) => {
+                quote = Some(c);
+                in_arg = true;
+            }
+            (c, Some(open)) if c == open => quote = None,
+            (c, None) if c.is_whitespace() => {
+                if in_arg {
+                    args.push(std::mem::take(&mut current));
+                    in_arg = false;
+                }
+            }
+            ('#' | ';', None) if !in_arg => break,
+            (c, _) => {
+                current.push(c);
+                in_arg = true;
+            }
+        }
+    }
+    if in_arg {
+        args.push(current);
+    }
+
+    args
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = "\
+client
+dev tun
+proto udp
+port 1194
+remote vpn.example.com
+remote 2001:db8::1 443 tcp
+remote-random
+auth-user-pass
+cipher AES-256-GCM
+fragment 1300
+route-nopull
+key-direction 1
+<ca>
+-----BEGIN CERTIFICATE-----
+-----END CERTIFICATE-----
+</ca>
+<tls-auth>
+-----BEGIN OpenVPN Static key V1-----
+-----END OpenVPN Static key V1-----
+</tls-auth>
+";
+
+    fn vpn_data(import: &OvpnImport) -> HashMap<String, String> {
+        import.report.connections[0]
+            .get_string_map("vpn", "data")
+            .unwrap()
+    }
+
+    #[test]
+    fn imports_profile() {
+        let import = import_ovpn("work", CONFIG, Path::new("/etc/vpn")).unwrap();
+        let settings = &import.report.connections[0];
+        assert_eq!(settings.id(), Some("work"));
+        assert_eq!(
+            settings.get_str("vpn", "service-type"),
+            Some(OPENVPN_SERVICE_TYPE)
+        );
+
+        let data = vpn_data(&import);
+        assert_eq!(data["connection-type"], "password");
+        assert_eq!(data["password-flags"], "1");
+        assert_eq!(data["dev-type"], "tun");
+        assert_eq!(data["cipher"], "AES-256-GCM");
+        assert_eq!(data["fragment-size"], "1300");
+        assert_eq!(data["remote-random"], "yes");
+        assert_eq!(data["ca"], "/etc/vpn/work-ca.pem");
+        assert_eq!(data["ta"], "/etc/vpn/work-tls-auth.pem");
+        assert_eq!(data["ta-dir"], "1");
+    }
+
+    #[test]
+    fn brackets_ipv6_remotes() {
+        let import = import_ovpn("work", CONFIG, Path::new("/etc/vpn")).unwrap();
+        assert_eq!(
+            vpn_data(&import)["remote"],
+            "vpn.example.com:1194:udp, [2001:db8::1]:443:tcp"
+        );
+    }
+
+    #[test]
+    fn returns_inline_files() {
+        let import = import_ovpn("work", CONFIG, Path::new("/etc/vpn")).unwrap();
+        let paths: Vec<&Path> = import
+            .files
+            .iter()
+            .map(|file| file.path.as_path())
+            .collect();
+        assert_eq!(
+            paths,
+            [
+                Path::new("/etc/vpn/work-ca.pem"),
+                Path::new("/etc/vpn/work-tls-auth.pem")
+            ]
+        );
+        assert_eq!(
+            import.files[0].contents,
+            "-----BEGIN CERTIFICATE-----\n-----END CERTIFICATE-----\n"
+        );
+    }
+
+    #[test]
+    fn reports_untranslated_directives() {
+        let import = import_ovpn("work", CONFIG, Path::new("/etc/vpn")).unwrap();
+        let skipped: Vec<(usize, &str)> = import
+            .report
+            .untranslated
+            .iter()
+            .map(|untranslated| (untranslated.line, untranslated.directive.as_str()))
+            .collect();
+        assert_eq!(skipped, [(11, "route-nopull")]);
+    }
+
+    #[test]
+    fn rejects_config_without_remote() {
+        let err = import_ovpn("work", "client\ndev tun\n", Path::new("/etc/vpn")).unwrap_err();
+        assert_eq!(err.line, 2);
+    }
+
+    #[test]
+    fn splits_quoted_arguments() {
+        assert_eq!(
+            split_args(r#"verify-x509-name "CN=my server" name # comment"#),
+            ["verify-x509-name", "CN=my server", "name"]
+        );
+    }
+}
@@ -0,0 +1,458 @@
+//! Conversion between nmcli style `setting.property` text values and connection settings,
+//! shared by the `nm-rs` and `nm-tui` binaries.
+
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use super::connection_settings::ConnectionSettings;
+use zbus::zvariant::{Signature, Value};
+use zbus::{Error, Result};
+
+/// Short setting names nmcli accepts in place of the D-Bus ones.
+const SETTING_ALIASES: &[(&str, &str)] = &[
+    ("ethernet", "802-3-ethernet"),
+    ("wifi", "802-11-wireless"),
+    ("wifi-sec", "802-11-wireless-security"),
+    ("olpc-mesh", "802-11-olpc-mesh"),
+];
+
+/// Properties whose type can't be told from the text, the rest are strings unless the
+/// profile already has a value of another type.
+const PROPERTY_KINDS: &[(&str, &str, Kind)] = &[
+    ("connection", "autoconnect", Kind::Bool),
+    ("connection", "autoconnect-priority", Kind::Int32),
+    ("connection", "autoconnect-retries", Kind::Int32),
+    ("connection", "metered", Kind::Int32),
+    ("connection", "secondaries", Kind::Strings),
+    ("802-3-ethernet", "auto-negotiate", Kind::Bool),
+    ("802-3-ethernet", "mtu", Kind::UInt32),
+    ("802-3-ethernet", "speed", Kind::UInt32),
+    ("802-11-wireless", "channel", Kind::UInt32),
+    ("802-11-wireless", "hidden", Kind::Bool),
+    ("802-11-wireless", "mtu", Kind::UInt32),
+    ("802-11-wireless", "powersave", Kind::UInt32),
+    ("802-11-wireless", "ssid", Kind::Bytes),
+    ("802-11-wireless-security", "wep-key-type", Kind::UInt32),
+    ("802-11-wireless-security", "wep-tx-keyidx", Kind::UInt32),
+    ("ipv4", "addresses", Kind::Addresses),
+    ("ipv4", "dns", Kind::Dns),
+    ("ipv4", "dns-priority", Kind::Int32),
+    ("ipv4", "dns-search", Kind::Strings),
+    ("ipv4", "ignore-auto-dns", Kind::Bool),
+    ("ipv4", "ignore-auto-routes", Kind::Bool),
+    ("ipv4", "may-fail", Kind::Bool),
+    ("ipv4", "never-default", Kind::Bool),
+    ("ipv4", "route-metric", Kind::Int64),
+    ("ipv4", "routes", Kind::Routes),
+    ("ipv6", "addr-gen-mode", Kind::Int32),
+    ("ipv6", "addresses", Kind::Addresses),
+    ("ipv6", "dns", Kind::Dns),
+    ("ipv6", "dns-priority", Kind::Int32),
+    ("ipv6", "dns-search", Kind::Strings),
+    ("ipv6", "ignore-auto-dns", Kind::Bool),
+    ("ipv6", "ignore-auto-routes", Kind::Bool),
+    ("ipv6", "may-fail", Kind::Bool),
+    ("ipv6", "never-default", Kind::Bool),
+    ("ipv6", "route-metric", Kind::Int64),
+    ("ipv6", "routes", Kind::Routes),
+    ("bridge", "stp", Kind::Bool),
+    ("bridge", "priority", Kind::UInt32),
+    ("bridge-port", "priority", Kind::UInt32),
+    ("bridge-port", "path-cost", Kind::UInt32),
+    ("vlan", "id", Kind::UInt32),
+    ("vxlan", "id", Kind::UInt32),
+    ("vxlan", "destination-port", Kind::UInt32),
+    ("wireguard", "listen-port", Kind::UInt32),
+    ("wireguard", "mtu", Kind::UInt32),
+];
+
+/// How a property value is written as text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Bool,
+    Int32,
+    UInt32,
+    Int64,
+    UInt64,
+    String,
+    Strings,
+    Bytes,
+    /// `address-data`, written as `ipv4.addresses` in CIDR notation.
+    Addresses,
+    /// `route-data`, written as `ipv4.routes` entries of `destination/prefix [next-hop]
+    /// [metric]`.
+    Routes,
+    /// `ipv4.dns` as integers or `ipv6.dns` as byte arrays.
+    Dns,
+    /// String dictionaries like `vpn.data`, written as `key = value, key = value`.
+    StringMap,
+}
+
+/// How a value given for a property is applied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Action {
+    Set,
+    /// `+setting.property`, appends to a list.
+    Append,
+    /// `-setting.property`, removes from a list.
+    Remove,
+}
+
+/// The D-Bus name of a setting or connection type given by its nmcli alias, e.g. `wifi`.
+pub fn setting_name(name: &str) -> &str {
+    SETTING_ALIASES
+        .iter()
+        .find(|(alias, _)| *alias == name)
+        .map_or(name, |(_, setting)| setting)
+}
+
+/// The short name nmcli prints for a `connection.type`.
+pub fn connection_type_alias(connection_type: &str) -> &str {
+    SETTING_ALIASES
+        .iter()
+        .find(|(_, setting)| *setting == connection_type)
+        .map_or(connection_type, |(alias, _)| alias)
+}
+
+/// Applies `[+|-]setting.property value` to `settings`.
+pub fn apply_property_text(
+    settings: &mut ConnectionSettings,
+    name: &str,
+    text: &str,
+) -> Result<()> {
+    let (action, name) = match name.split_at_checked(1) {
+        Some(("+", name)) => (Action::Append, name),
+        Some(("-", name)) => (Action::Remove, name),
+        _ => (Action::Set, name),
+    };
+    let (setting, property) = name
+        .split_once('.')
+        .ok_or_else(|| Error::Failure(format!("invalid property '{name}'")))?;
+    let setting = setting_name(setting);
+    let kind = kind(settings, setting, property);
+    let dbus_property = match kind {
+        Kind::Addresses => "address-data",
+        Kind::Routes => "route-data",
+        _ => property,
+    };
+
+    if action == Action::Set {
+        let value = parse(kind, setting, text).map_err(|message| {
+            Error::Failure(format!(
+                "invalid value '{text}' for {setting}.{property}: {message}"
+            ))
+        })?;
+        if matches!(kind, Kind::Addresses | Kind::Routes) {
+            // The deprecated property would override the -data one when both are set
+            settings.remove(setting, property);
+        }
+        settings.set(setting, dbus_property, value);
+        return Ok(());
+    }
+
+    if !matches!(
+        kind,
+        Kind::Strings | Kind::Addresses | Kind::Routes | Kind::Dns
+    ) {
+        return Err(Error::Failure(format!(
+            "{setting}.{property} is not a list, '+' and '-' only apply to lists"
+        )));
+    }
+    let current = parse_list(&format_property(settings, setting, dbus_property));
+    let given = parse_list(text);
+    let elements: Vec<String> = match action {
+        Action::Append => current.into_iter().chain(given).collect(),
+        _ => current
+            .into_iter()
+            .filter(|element| !given.contains(element))
+            .collect(),
+    };
+    let value = parse(kind, setting, &elements.join(","))
+        .map_err(|message| Error::Failure(format!("invalid value '{text}': {message}")))?;
+    settings.set(setting, dbus_property, value);
+    Ok(())
+}
+
+/// The properties of `settings` as `setting.property` names and text values, sorted, with
+/// `address-data` and `route-data` in place of their deprecated counterparts.
+pub fn property_text_entries(settings: &ConnectionSettings) -> Vec<(String, String)> {
+    let mut entries: Vec<(String, String)> = settings
+        .iter()
+        .filter_map(|(setting, property, _)| {
+            let name = match property {
+                "address-data" => "addresses",
+                "route-data" => "routes",
+                "addresses" | "routes"
+                    if settings.get(setting, &format!("{property}-data")).is_some() =>
+                {
+                    return None;
+                }
+                property => property,
+            };
+            Some((
+                format!("{setting}.{name}"),
+                format_property(settings, setting, property),
+            ))
+        })
+        .collect();
+    entries.sort();
+    entries
+}
+
+fn kind(settings: &ConnectionSettings, setting: &str, property: &str) -> Kind {
+    if let Some((_, _, kind)) = PROPERTY_KINDS
+        .iter()
+        .find(|(known_setting, known_property, _)| {
+            *known_setting == setting && *known_property == property
+        })
+    {
+        return *kind;
+    }
+    match settings.get(setting, property) {
+        Some(Value::Bool(_)) => Kind::Bool,
+        Some(Value::I32(_)) => Kind::Int32,
+        Some(Value::U32(_)) => Kind::UInt32,
+        Some(Value::I64(_)) => Kind::Int64,
+        Some(Value::U64(_)) => Kind::UInt64,
+        Some(Value::Array(array)) if *array.element_signature() == Signature::Str => Kind::Strings,
+        Some(Value::Array(array)) if *array.element_signature() == Signature::U8 => Kind::Bytes,
+        Some(Value::Dict(_)) => Kind::StringMap,
+        _ => Kind::String,
+    }
+}
+
+fn parse(kind: Kind, setting: &str, text: &str) -> std::result::Result<Value<'static>, String> {
+    let text = text.trim();
+    let number_error = |err: std::num::ParseIntError| err.to_string();
+    Ok(match kind {
+        Kind::Bool => Value::from(match text {
+            "yes" | "true" | "on" | "1" => true,
+            "no" | "false" | "off" | "0" => false,
+            _ => return Err("expected yes or no".to_owned()),
+        }),
+        Kind::Int32 => Value::from(text.parse::<i32>().map_err(number_error)?),
+        Kind::UInt32 => Value::from(text.parse::<u32>().map_err(number_error)?),
+        Kind::Int64 => Value::from(text.parse::<i64>().map_err(number_error)?),
+        Kind::UInt64 => Value::from(text.parse::<u64>().map_err(number_error)?),
+        Kind::String => Value::from(text.to_owned()),
+        Kind::Strings => Value::from(parse_list(text)),
+        Kind::Bytes => Value::from(text.as_bytes().to_vec()),
+        Kind::Addresses => {
+            let addresses = parse_list(text)
+                .iter()
+                .map(|address| {
+                    let (ip, prefix) = parse_cidr(setting, address)?;
+                    Ok(HashMap::from([
+                        ("address".to_owned(), Value::from(ip.to_string())),
+                        ("prefix".to_owned(), Value::from(prefix)),
+                    ]))
+                })
+                .collect::<std::result::Result<Vec<_>, String>>()?;
+            Value::from(addresses)
+        }
+        Kind::Routes => {
+            let routes = parse_list(text)
+                .iter()
+                .map(|route| parse_route(setting, route))
+                .collect::<std::result::Result<Vec<_>, String>>()?;
+            Value::from(routes)
+        }
+        Kind::Dns if setting == "ipv4" => {
+            let servers = parse_list(text)
+                .iter()
+                .map(|server| {
+                    let server: Ipv4Addr = server
+                        .parse()
+                        .map_err(|_| format!("invalid address '{server}'"))?;
+                    Ok(u32::from_ne_bytes(server.octets()))
+                })
+                .collect::<std::result::Result<Vec<u32>, String>>()?;
+            Value::from(servers)
+        }
+        Kind::Dns => {
+            let servers = parse_list(text)
+                .iter()
+                .map(|server| {
+                    let server: Ipv6Addr = server
+                        .parse()
+                        .map_err(|_| format!("invalid address '{server}'"))?;
+                    Ok(server.octets().to_vec())
+                })
+                .collect::<std::result::Result<Vec<Vec<u8>>, String>>()?;
+            Value::from(servers)
+        }
+        Kind::StringMap => {
+            let map = parse_list(text)
+                .iter()
+                .map(|pair| {
+                    let (key, value) = pair
+                        .split_once('=')
+                        .ok_or_else(|| format!("expected key = value, got '{pair}'"))?;
+                    Ok((key.trim().to_owned(), value.trim().to_owned()))
+                })
+                .collect::<std::result::Result<HashMap<String, String>, String>>()?;
+            Value::from(map)
+        }
+    })
+}
+
+/// Parses `address[/prefix]` of the family of `setting`, the prefix defaulting to a host.
+fn parse_cidr(setting: &str, text: &str) -> std::result::Result<(IpAddr, u32), String> {
+    let (ip, prefix) = text.split_once('/').unwrap_or((text, ""));
+    let ip: IpAddr = ip.parse().map_err(|_| format!("invalid address '{ip}'"))?;
+    if ip.is_ipv4() != (setting == "ipv4") {
+        return Err(format!("'{ip}' is of the wrong address family"));
+    }
+    let max_prefix = if ip.is_ipv4() { 32 } else { 128 };
+    let prefix = match prefix {
+        "" => max_prefix,
+        prefix => prefix
+            .parse::<u32>()
+            .ok()
+            .filter(|prefix| *prefix <= max_prefix)
+            .ok_or_else(|| format!("invalid prefix '{prefix}'"))?,
+    };
+    Ok((ip, prefix))
+}
+
+/// Parses a `destination/prefix [next-hop] [metric]` route into a `route-data` entry.
+fn parse_route(
+    setting: &str,
+    text: &str,
+) -> std::result::Result<HashMap<String, Value<'static>>, String> {
+    let mut words = text.split_whitespace();
+    let (dest, prefix) = parse_cidr(setting, words.next().unwrap_or_default())?;
+    let mut route = HashMap::from([
+        ("dest".to_owned(), Value::from(dest.to_string())),
+        ("prefix".to_owned(), Value::from(prefix)),
+    ]);
+    for word in words {
+        if let Ok(metric) = word.parse::<u32>()
+            && !route.contains_key("metric")
+        {
+            route.insert("metric".to_owned(), Value::from(metric));
+        } else if !route.contains_key("next-hop") && !route.contains_key("metric") {
+            let next_hop: IpAddr = word
+                .parse()
+                .ok()
+                .filter(|next_hop: &IpAddr| next_hop.is_ipv4() == dest.is_ipv4())
+                .ok_or_else(|| format!("invalid next hop '{word}'"))?;
+            route.insert("next-hop".to_owned(), Value::from(next_hop.to_string()));
+        } else {
+            return Err(format!("unexpected '{word}' in route '{text}'"));
+        }
+    }
+    Ok(route)
+}
+
+/// Splits a comma separated list, the empty string being the empty list.
+fn parse_list(text: &str) -> Vec<String> {
+    text.split(',')
+        .map(str::trim)
+        .filter(|element| !element.is_empty())
+        .map(ToOwned::to_owned)
+        .collect()
+}
+
+/// Formats a property the way nmcli prints it.
+fn format_property(settings: &ConnectionSettings, setting: &str, property: &str) -> String {
+    match (setting, property) {
+        (_, "address-data" | "route-data") => settings
+            .get_dicts(setting, property)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|data| {
+                let address: &str = data
+                    .get("address")
+                    .or_else(|| data.get("dest"))?
+                    .downcast_ref()
+                    .ok()?;
+                let prefix: u32 = data.get("prefix")?.downcast_ref().ok()?;
+                let mut text = format!("{address}/{prefix}");
+                if let Some(next_hop) = data
+                    .get("next-hop")
+                    .and_then(|next_hop| next_hop.downcast_ref::<&str>().ok())
+                {
+                    text.push_str(&format!(" {next_hop}"));
+                }
+                if let Some(metric) = data
+                    .get("metric")
+                    .and_then(|metric| metric.downcast_ref::<u32>().ok())
+                {
+                    text.push_str(&format!(" {metric}"));
+                }
+                Some(text)
+            })
+            .collect::<Vec<_>>()
+            .join(", "),
+        ("ipv4", "dns") => settings
+            .get(setting, property)
+            .and_then(|dns| dns.try_clone().ok()?.downcast::<Vec<u32>>().ok())
+            .unwrap_or_default()
+            .into_iter()
+            .map(|server| Ipv4Addr::from(server.to_ne_bytes()).to_string())
+            .collect::<Vec<_>>()
+            .join(","),
+        ("ipv6", "dns") => settings
+            .get(setting, property)
+            .and_then(|dns| dns.try_clone().ok()?.downcast::<Vec<Vec<u8>>>().ok())
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|server| <[u8; 16]>::try_from(server).ok())
+            .map(|server| Ipv6Addr::from(server).to_string())
+            .collect::<Vec<_>>()
+            .join(","),
+        _ => settings
+            .get(setting, property)
+            .map(format_value)
+            .unwrap_or_default(),
+    }
+}
+
+fn format_value(value: &Value<'_>) -> String {
+    match value {
+        Value::Bool(true) => "yes".to_owned(),
+        Value::Bool(false) => "no".to_owned(),
+        Value::Str(text) => text.to_string(),
+        Value::ObjectPath(path) => path.to_string(),
+        Value::Value(value) => format_value(value),
+        Value::Array(array) if *array.element_signature() == Signature::U8 => {
+            let bytes: Vec<u8> = array
+                .inner()
+                .iter()
+                .filter_map(|byte| byte.downcast_ref::<u8>().ok())
+                .collect();
+            match String::from_utf8(bytes) {
+                Ok(text) if !text.chars().any(char::is_control) => text,
+                Err(err) => hex(err.as_bytes()),
+                Ok(text) => hex(text.as_bytes()),
+            }
+        }
+        Value::Array(array) => array
+            .inner()
+            .iter()
+            .map(|element| match element {
+                Value::Dict(_) => format!("{{ {} }}", format_value(element)),
+                element => format_value(element),
+            })
+            .collect::<Vec<_>>()
+            .join(","),
+        Value::Dict(dict) => {
+            let mut pairs: Vec<String> = dict
+                .iter()
+                .map(|(key, value)| format!("{} = {}", format_value(key), format_value(value)))
+                .collect();
+            pairs.sort();
+            pairs.join(", ")
+        }
+        value => value.to_string(),
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|byte| format!("{byte:02X}"))
+        .collect::<Vec<_>>()
+        .join(":")
+}
@@ -0,0 +1,103 @@
+//! Live changes to the connection applied to a device.
+//!
+//! [`modify_applied`] edits the settings a device is currently running with and hands them to
+//! [`DeviceProxy::reapply`], which changes the device without reactivating it. The profile
+//! itself is left alone, so the change is gone once the connection is reactivated.
+
+use zbus::Result;
+
+use super::connection_settings::{ConnectionSettings, PropertyChange};
+use super::device::DeviceProxy;
+
+/// How often the applied connection is fetched and edited again when it changed in between.
+const MAX_ATTEMPTS: usize = 3;
+
+const VERSION_ID_MISMATCH: &str = "org.freedesktop.NetworkManager.Device.VersionIdMismatch";
+
+/// Settings NetworkManager can change on a device without reactivating it, with the
+/// properties it allows, `None` for all of them. Mirrors the checks NetworkManager makes
+/// before reapplying; device types that allow more, e.g. `wireguard` on WireGuard devices,
+/// aren't covered.
+const REAPPLYABLE: &[(&str, Option<&[&str]>)] = &[
+    (
+        "connection",
+        Some(&[
+            "dns-over-tls",
+            "llmnr",
+            "lldp",
+            "mdns",
+            "metered",
+            "mptcp-flags",
+            "zone",
+        ]),
+    ),
+    ("ipv4", None),
+    ("ipv6", None),
+    ("link", None),
+    ("proxy", None),
+    ("tc", None),
+    ("user", None),
+];
+
+/// What [`modify_applied`] did.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReapplyOutcome {
+    /// The edit didn't change anything, so nothing was reapplied.
+    Unchanged,
+    /// The changes were reapplied.
+    Reapplied { changes: Vec<PropertyChange> },
+    /// Nothing was reapplied, because `blocking` can only change by reactivating the
+    /// connection.
+    NeedsReactivation {
+        changes: Vec<PropertyChange>,
+        blocking: Vec<PropertyChange>,
+    },
+}
+
+/// Whether NetworkManager can change `setting.property` without reactivating the connection.
+pub fn can_reapply(setting: &str, property: &str) -> bool {
+    REAPPLYABLE.iter().any(|(name, properties)| {
+        *name == setting && properties.is_none_or(|properties| properties.contains(&property))
+    })
+}
+
+/// Fetches the connection applied to `device`, lets `edit` change a copy and reapplies it.
+///
+/// The reapply carries the version id of the fetched connection. When something else changed
+/// the applied connection in between, NetworkManager refuses it and the connection is
+/// fetched and edited again, so `edit` may run more than once.
+pub async fn modify_applied<F>(device: &DeviceProxy<'_>, mut edit: F) -> Result<ReapplyOutcome>
+where
+    F: FnMut(&mut ConnectionSettings),
+{
+    let mut attempt = 1;
+    loop {
+        let (applied, version_id) = device.get_applied_connection(0).await?;
+        let applied = ConnectionSettings::from(applied);
+        let mut edited = applied.clone();
+        edit(&mut edited);
+
+        let changes = applied.diff(&edited);
+        if changes.is_empty() {
+            return Ok(ReapplyOutcome::Unchanged);
+        }
+        let blocking: Vec<PropertyChange> = changes
+            .iter()
+            .filter(|change| !can_reapply(&change.setting, &change.property))
+            .cloned()
+            .collect();
+        if !blocking.is_empty() {
+            return Ok(ReapplyOutcome::NeedsReactivation { changes, blocking });
+        }
+
+        match device.reapply(edited.to_dbus(), version_id, 0).await {
+            Ok(()) => return Ok(ReapplyOutcome::Reapplied { changes }),
+            Err(zbus::Error::MethodError(name, _, _))
+                if name.as_str() == VERSION_ID_MISMATCH && attempt < MAX_ATTEMPTS =>
+            {
+                attempt += 1;
+            }
+            Err(err) => return Err(err),
+        }
+    }
+}
@@ -0,0 +1,186 @@
+//! Roaming and signal-quality monitor for the active access point of a wireless device.
+//!
+//! Follows `ActiveAccessPoint` changes on the device and `Strength` changes on the current
+//! access point and turns them into [`RoamingEvent`]s.
+
+use futures_lite::{Stream, StreamExt, future, stream};
+use zbus::proxy::PropertyStream;
+use zbus::zvariant::OwnedObjectPath;
+use zbus::{Connection, Result};
+
+use super::access_point::AccessPointProxy;
+use super::wireless::WirelessProxy;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RoamingEvent {
+    /// The device associated with a different access point. `from_bssid` is `None` when the
+    /// device was previously disconnected.
+    Roamed {
+        from_bssid: Option<String>,
+        to_bssid: String,
+        frequency: u32,
+    },
+    /// The strength of the current access point changed by at least the hysteresis.
+    SignalChanged { strength: u8 },
+    /// The device is no longer associated with an access point.
+    Disconnected,
+}
+
+struct CurrentAccessPoint {
+    bssid: String,
+    strength_changes: Option<PropertyStream<'static, u8>>,
+}
+
+enum Change {
+    AccessPoint(Option<OwnedObjectPath>),
+    Strength(Option<u8>),
+}
+
+/// Monitors the active access point of a wireless device.
+///
+/// The initial state isn't reported as a roam, but the initial strength is reported as a
+/// [`RoamingEvent::SignalChanged`].
+pub struct RoamingMonitor {
+    connection: Connection,
+    access_point_changes: PropertyStream<'static, OwnedObjectPath>,
+    current: Option<CurrentAccessPoint>,
+    started: bool,
+    last_strength: Option<u8>,
+    hysteresis: u8,
+}
+
+impl RoamingMonitor {
+    pub async fn new(wireless: &WirelessProxy<'_>) -> Result<RoamingMonitor> {
+        let connection = wireless.inner().connection().clone();
+        let wireless: WirelessProxy<'static> = WirelessProxy::builder(&connection)
+            .path(OwnedObjectPath::from(wireless.inner().path().clone()))?
+            .build()
+            .await?;
+
+        Ok(RoamingMonitor {
+            access_point_changes: wireless.receive_active_access_point_changed().await,
+            connection,
+            current: None,
+            started: false,
+            last_strength: None,
+            hysteresis: 0,
+        })
+    }
+
+    /// Only report strength changes of at least `hysteresis` percentage points.
+    pub fn with_hysteresis(mut self, hysteresis: u8) -> Self {
+        self.hysteresis = hysteresis;
+        self
+    }
+
+    /// Waits for the next event, returns `None` once the device goes away.
+    pub async fn next(&mut self) -> Option<Result<RoamingEvent>> {
+        loop {
+            let change = match &mut self.current {
+                Some(CurrentAccessPoint {
+                    strength_changes: Some(strength_changes),
+                    ..
+                }) => {
+                    let access_point_changes = &mut self.access_point_changes;
+                    future::or(
+                        async { Change::AccessPoint(next_value(access_point_changes).await) },
+                        async { Change::Strength(next_value(strength_changes).await) },
+                    )
+                    .await
+                }
+                _ => Change::AccessPoint(next_value(&mut self.access_point_changes).await),
+            };
+
+            let event = match change {
+                Change::AccessPoint(None) => return None,
+                Change::AccessPoint(Some(path)) => self.access_point_changed(path).await,
+                Change::Strength(None) => {
+                    // The access point object went away, its replacement comes with the next
+                    // ActiveAccessPoint change.
+                    if let Some(current) = &mut self.current {
+                        current.strength_changes = None;
+                    }
+                    continue;
+                }
+                Change::Strength(Some(strength)) => Ok(self.strength_changed(strength)),
+            };
+
+            match event {
+                Ok(Some(event)) => return Some(Ok(event)),
+                Ok(None) => continue,
+                Err(err) => return Some(Err(err)),
+            }
+        }
+    }
+
+    /// Turns the monitor into a [`Stream`] of events.
+    pub fn into_stream(self) -> impl Stream<Item = Result<RoamingEvent>> {
+        stream::unfold(self, |mut monitor| async move {
+            monitor.next().await.map(|event| (event, monitor))
+        })
+    }
+
+    async fn access_point_changed(
+        &mut self,
+        path: OwnedObjectPath,
+    ) -> Result<Option<RoamingEvent>> {
+        let initial = !std::mem::replace(&mut self.started, true);
+
+        if path.as_str() == "/" {
+            self.last_strength = None;
+            return Ok(self
+                .current
+                .take()
+                .filter(|_| !initial)
+                .map(|_| RoamingEvent::Disconnected));
+        }
+
+        let access_point: AccessPointProxy<'static> = AccessPointProxy::builder(&self.connection)
+            .path(path)?
+            .build()
+            .await?;
+        let bssid = access_point.hw_address().await?;
+        let frequency = access_point.frequency().await?;
+        let strength_changes = Some(access_point.receive_strength_changed().await);
+
+        let previous = self.current.replace(CurrentAccessPoint {
+            bssid: bssid.clone(),
+            strength_changes,
+        });
+        let from_bssid = previous.map(|previous| previous.bssid);
+        if initial || from_bssid.as_ref() == Some(&bssid) {
+            return Ok(None);
+        }
+
+        self.last_strength = None;
+        Ok(Some(RoamingEvent::Roamed {
+            from_bssid,
+            to_bssid: bssid,
+            frequency,
+        }))
+    }
+
+    fn strength_changed(&mut self, strength: u8) -> Option<RoamingEvent> {
+        if let Some(last_strength) = self.last_strength
+            && last_strength.abs_diff(strength) < self.hysteresis.max(1)
+        {
+            return None;
+        }
+
+        self.last_strength = Some(strength);
+        Some(RoamingEvent::SignalChanged { strength })
+    }
+}
+
+async fn next_value<T>(changes: &mut PropertyStream<'static, T>) -> Option<T>
+where
+    T: TryFrom<zbus::zvariant::OwnedValue> + Unpin,
+    T::Error: Into<zbus::Error>,
+{
+    while let Some(change) = changes.next().await {
+        if let Ok(value) = change.get().await {
+            return Some(value);
+        }
+    }
+    None
+}
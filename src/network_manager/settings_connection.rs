@@ -111,4 +111,8 @@ pub trait SettingsConnection {
     /// Unsaved property
     #[zbus(property)]
     fn unsaved(&self) -> zbus::Result<bool>;
+
+    /// VersionId property
+    #[zbus(property)]
+    fn version_id(&self) -> zbus::Result<u64>;
 }
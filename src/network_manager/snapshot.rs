@@ -0,0 +1,635 @@
+//! A serializable capture of the whole NetworkManager state, e.g. for support bundles.
+//!
+//! [`Snapshot::capture`] reads the daemon state, every device with its type-specific
+//! properties, the active connections with their IP and DHCP configuration, and every profile.
+//! NetworkManager leaves secrets out of profiles, and known secret properties that are present
+//! anyway are redacted. The
+//! snapshot derives `Serialize` and `Deserialize`, so it can be written with `serde_json` or
+//! `serde_yaml` and read back later.
+//!
+//! Enumerations are stored by their lower case names (`connected_global`, `ethernet`) and
+//! lists are sorted so two snapshots of the same state are equal.
+
+use std::collections::{BTreeMap, HashMap};
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+use serde::{Deserialize, Serialize};
+use zbus::zvariant::{OwnedObjectPath, OwnedValue, Signature, Value};
+use zbus::{Connection, Result};
+
+use super::NetworkManagerProxy;
+use super::access_point::AccessPointProxy;
+use super::access_point_security_flags::NM80211ApSecurityFlags;
+use super::active::ActiveProxy;
+use super::bond::BondProxy;
+use super::connection_settings::ConnectionSettings;
+use super::dbus_interface_types::{
+    NM80211Mode, NMActiveConnectionState, NMConnectivityState, NMDeviceState, NMDeviceType,
+    NMState, enum_name,
+};
+use super::device::DeviceProxy;
+use super::dhcp4config::DHCP4ConfigProxy;
+use super::dhcp6config::DHCP6ConfigProxy;
+use super::ip4config::IP4ConfigProxy;
+use super::ip6config::IP6ConfigProxy;
+use super::settings::SettingsProxy;
+use super::settings_connection::SettingsConnectionProxy;
+use super::vlan::VlanProxy;
+use super::vxlan::VxlanProxy;
+use super::wired::WiredProxy;
+use super::wireless::WirelessProxy;
+
+/// The NetworkManager state at one point in time.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub version: String,
+    pub state: String,
+    pub connectivity: String,
+    pub networking_enabled: bool,
+    pub radios: Radios,
+    pub logging: Logging,
+    pub devices: Vec<DeviceSnapshot>,
+    pub active_connections: Vec<ActiveConnectionSnapshot>,
+    pub profiles: Vec<ProfileSnapshot>,
+}
+
+/// Radio kill switches, the `*_hardware_enabled` ones are physical switches.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Radios {
+    pub wireless_enabled: bool,
+    pub wireless_hardware_enabled: bool,
+    pub wwan_enabled: bool,
+    pub wwan_hardware_enabled: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Logging {
+    pub level: String,
+    pub domains: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeviceSnapshot {
+    pub interface: String,
+    pub ip_interface: String,
+    pub device_type: String,
+    pub state: String,
+    pub driver: String,
+    pub hw_address: String,
+    pub managed: bool,
+    pub mtu: u32,
+    /// Properties only some device types have.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub details: Option<DeviceDetails>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum DeviceDetails {
+    Wired {
+        /// Mbit/s
+        speed: u32,
+        carrier: bool,
+        perm_hw_address: String,
+    },
+    Bond {
+        carrier: bool,
+        /// Interface names of the ports.
+        slaves: Vec<String>,
+    },
+    Vlan {
+        id: u32,
+        parent: Option<String>,
+    },
+    Vxlan {
+        id: u32,
+        group: String,
+        local: String,
+        dst_port: u16,
+        ttl: u8,
+        parent: Option<String>,
+    },
+    Wifi {
+        mode: String,
+        /// Kbit/s
+        bitrate: u32,
+        /// BSSID of the access point in use.
+        active_access_point: Option<String>,
+        access_points: Vec<AccessPointSnapshot>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccessPointSnapshot {
+    pub ssid: String,
+    pub bssid: String,
+    /// MHz
+    pub frequency: u32,
+    /// Percent
+    pub strength: u8,
+    /// Kbit/s
+    pub max_bitrate: u32,
+    /// WPA and RSN security flag names.
+    pub security: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ActiveConnectionSnapshot {
+    pub id: String,
+    pub uuid: String,
+    pub connection_type: String,
+    pub state: String,
+    pub vpn: bool,
+    /// Whether it holds the IPv4 default route.
+    pub default: bool,
+    pub default6: bool,
+    /// Interface names.
+    pub devices: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ip4: Option<IpConfigSnapshot>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ip6: Option<IpConfigSnapshot>,
+    /// DHCP options as received from the server.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub dhcp4: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub dhcp6: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IpConfigSnapshot {
+    /// In CIDR notation.
+    pub addresses: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gateway: Option<String>,
+    pub routes: Vec<RouteSnapshot>,
+    pub nameservers: Vec<String>,
+    pub domains: Vec<String>,
+    pub searches: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct RouteSnapshot {
+    /// In CIDR notation.
+    pub destination: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_hop: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metric: Option<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProfileSnapshot {
+    pub id: String,
+    pub uuid: String,
+    pub connection_type: String,
+    pub filename: String,
+    pub unsaved: bool,
+    /// Setting name to property name to value, with secrets redacted.
+    pub settings: BTreeMap<String, BTreeMap<String, PropertyValue>>,
+}
+
+/// A setting property in a form every serde format can hold.
+///
+/// Byte arrays such as `802-11-wireless.ssid` become text when they are printable and
+/// colon separated hex otherwise.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum PropertyValue {
+    Bool(bool),
+    Int(i64),
+    UInt(u64),
+    Double(f64),
+    String(String),
+    List(Vec<PropertyValue>),
+    Map(BTreeMap<String, PropertyValue>),
+}
+
+impl From<&Value<'_>> for PropertyValue {
+    fn from(value: &Value<'_>) -> Self {
+        match value {
+            Value::Bool(value) => PropertyValue::Bool(*value),
+            Value::U8(value) => PropertyValue::UInt(u64::from(*value)),
+            Value::U16(value) => PropertyValue::UInt(u64::from(*value)),
+            Value::U32(value) => PropertyValue::UInt(u64::from(*value)),
+            Value::U64(value) => PropertyValue::UInt(*value),
+            Value::I16(value) => PropertyValue::Int(i64::from(*value)),
+            Value::I32(value) => PropertyValue::Int(i64::from(*value)),
+            Value::I64(value) => PropertyValue::Int(*value),
+            Value::F64(value) => PropertyValue::Double(*value),
+            Value::Str(value) => PropertyValue::String(value.to_string()),
+            Value::Signature(value) => PropertyValue::String(value.to_string()),
+            Value::ObjectPath(value) => PropertyValue::String(value.to_string()),
+            Value::Value(value) => PropertyValue::from(value.as_ref()),
+            Value::Array(array) if *array.element_signature() == Signature::U8 => {
+                let bytes: Vec<u8> = array
+                    .inner()
+                    .iter()
+                    .filter_map(|byte| byte.downcast_ref::<u8>().ok())
+                    .collect();
+                PropertyValue::String(bytes_to_string(&bytes))
+            }
+            Value::Array(array) => {
+                PropertyValue::List(array.inner().iter().map(PropertyValue::from).collect())
+            }
+            Value::Dict(dict) => PropertyValue::Map(
+                dict.iter()
+                    .map(|(key, value)| (map_key(key), PropertyValue::from(value)))
+                    .collect(),
+            ),
+            Value::Structure(structure) => {
+                PropertyValue::List(structure.fields().iter().map(PropertyValue::from).collect())
+            }
+            // File descriptors don't occur in settings
+            _ => PropertyValue::String(String::new()),
+        }
+    }
+}
+
+fn map_key(key: &Value<'_>) -> String {
+    match PropertyValue::from(key) {
+        PropertyValue::String(key) => key,
+        PropertyValue::Bool(key) => key.to_string(),
+        PropertyValue::Int(key) => key.to_string(),
+        PropertyValue::UInt(key) => key.to_string(),
+        other => format!("{other:?}"),
+    }
+}
+
+fn bytes_to_string(bytes: &[u8]) -> String {
+    match std::str::from_utf8(bytes) {
+        Ok(text) if !text.is_empty() && !text.chars().any(char::is_control) => text.to_owned(),
+        _ => bytes
+            .iter()
+            .map(|byte| format!("{byte:02X}"))
+            .collect::<Vec<_>>()
+            .join(":"),
+    }
+}
+
+impl Snapshot {
+    /// Reads the current state.
+    pub async fn capture(connection: &Connection) -> Result<Snapshot> {
+        let nm = NetworkManagerProxy::new(connection).await?;
+        let (level, domains) = nm.get_logging().await?;
+
+        let mut snapshot = Snapshot {
+            version: nm.version().await?,
+            state: enum_name::<NMState>(nm.state().await?),
+            connectivity: enum_name::<NMConnectivityState>(nm.connectivity().await?),
+            networking_enabled: nm.networking_enabled().await?,
+            radios: Radios {
+                wireless_enabled: nm.wireless_enabled().await?,
+                wireless_hardware_enabled: nm.wireless_hardware_enabled().await?,
+                wwan_enabled: nm.wwan_enabled().await?,
+                wwan_hardware_enabled: nm.wwan_hardware_enabled().await?,
+            },
+            logging: Logging {
+                level,
+                domains: domains.split(',').map(ToOwned::to_owned).collect(),
+            },
+            devices: Vec::new(),
+            active_connections: Vec::new(),
+            profiles: Vec::new(),
+        };
+
+        // Interface names by path, to refer to ports and parents by name. Devices and active
+        // connections that disappear while being read are left out.
+        let mut devices = Vec::new();
+        let mut interfaces = HashMap::new();
+        for path in nm.get_all_devices().await? {
+            let device = DeviceProxy::new_from_path(path.clone(), connection).await?;
+            match device.interface().await {
+                Ok(interface) => {
+                    interfaces.insert(path.clone(), interface);
+                    devices.push((path, device));
+                }
+                Err(zbus::Error::MethodError(..) | zbus::Error::FDO(_)) => {}
+                Err(err) => return Err(err),
+            }
+        }
+
+        for (path, device) in &devices {
+            match device_snapshot(connection, path, device, &interfaces).await {
+                Ok(device) => snapshot.devices.push(device),
+                Err(zbus::Error::MethodError(..) | zbus::Error::FDO(_)) => {}
+                Err(err) => return Err(err),
+            }
+        }
+        snapshot
+            .devices
+            .sort_by(|a, b| a.interface.cmp(&b.interface));
+
+        for path in nm.active_connections().await? {
+            let active = ActiveProxy::new_from_path(path, connection).await?;
+            match active_connection(connection, &active, &interfaces).await {
+                Ok(active) => snapshot.active_connections.push(active),
+                Err(zbus::Error::MethodError(..) | zbus::Error::FDO(_)) => {}
+                Err(err) => return Err(err),
+            }
+        }
+        snapshot
+            .active_connections
+            .sort_by(|a, b| (&a.id, &a.uuid).cmp(&(&b.id, &b.uuid)));
+
+        let settings = SettingsProxy::new(connection).await?;
+        for path in settings.list_connections().await? {
+            let profile = SettingsConnectionProxy::new_from_path(path, connection).await?;
+            snapshot.profiles.push(profile_snapshot(&profile).await?);
+        }
+        snapshot
+            .profiles
+            .sort_by(|a, b| (&a.id, &a.uuid).cmp(&(&b.id, &b.uuid)));
+
+        Ok(snapshot)
+    }
+}
+
+async fn device_snapshot(
+    connection: &Connection,
+    path: &OwnedObjectPath,
+    device: &DeviceProxy<'_>,
+    interfaces: &HashMap<OwnedObjectPath, String>,
+) -> Result<DeviceSnapshot> {
+    let device_type = device.device_type().await?;
+    Ok(DeviceSnapshot {
+        interface: device.interface().await?,
+        ip_interface: device.ip_interface().await?,
+        device_type: enum_name::<NMDeviceType>(device_type),
+        state: enum_name::<NMDeviceState>(device.state().await?),
+        driver: device.driver().await?,
+        // Only exported since NetworkManager 1.24
+        hw_address: device.hw_address().await.unwrap_or_default(),
+        managed: device.managed().await?,
+        mtu: device.mtu().await?,
+        details: device_details(connection, path, device_type, interfaces).await?,
+    })
+}
+
+async fn device_details(
+    connection: &Connection,
+    path: &OwnedObjectPath,
+    device_type: u32,
+    interfaces: &HashMap<OwnedObjectPath, String>,
+) -> Result<Option<DeviceDetails>> {
+    let interface_of = |path: OwnedObjectPath| interfaces.get(&path).cloned();
+    let path = path.clone();
+
+    let details = match NMDeviceType::try_from(device_type) {
+        Ok(NMDeviceType::ETHERNET) => {
+            let wired = WiredProxy::new_from_path(path, connection).await?;
+            DeviceDetails::Wired {
+                speed: wired.speed().await?,
+                carrier: wired.carrier().await?,
+                perm_hw_address: wired.perm_hw_address().await?,
+            }
+        }
+        Ok(NMDeviceType::BOND) => {
+            let bond = BondProxy::new_from_path(path, connection).await?;
+            DeviceDetails::Bond {
+                carrier: bond.carrier().await?,
+                slaves: bond
+                    .slaves()
+                    .await?
+                    .into_iter()
+                    .filter_map(interface_of)
+                    .collect(),
+            }
+        }
+        Ok(NMDeviceType::VLAN) => {
+            let vlan = VlanProxy::new_from_path(path, connection).await?;
+            DeviceDetails::Vlan {
+                id: vlan.vlan_id().await?,
+                parent: interface_of(vlan.parent().await?),
+            }
+        }
+        Ok(NMDeviceType::VXLAN) => {
+            let vxlan = VxlanProxy::new_from_path(path, connection).await?;
+            DeviceDetails::Vxlan {
+                id: vxlan.id().await?,
+                group: vxlan.group().await?,
+                local: vxlan.local().await?,
+                dst_port: vxlan.dst_port().await?,
+                ttl: vxlan.ttl().await?,
+                parent: interface_of(vxlan.parent().await?),
+            }
+        }
+        Ok(NMDeviceType::WIFI) => {
+            let wireless = WirelessProxy::new_from_path(path, connection).await?;
+            let active_path = wireless.active_access_point().await?;
+            let mut active_access_point = None;
+            let mut access_points = Vec::new();
+            for ap_path in wireless.get_all_access_points().await? {
+                let access_point =
+                    AccessPointProxy::new_from_path(ap_path.clone(), connection).await?;
+                let snapshot = access_point_snapshot(&access_point).await?;
+                if ap_path == active_path {
+                    active_access_point = Some(snapshot.bssid.clone());
+                }
+                access_points.push(snapshot);
+            }
+            access_points.sort_by(|a, b| (&a.ssid, &a.bssid).cmp(&(&b.ssid, &b.bssid)));
+            DeviceDetails::Wifi {
+                mode: enum_name::<NM80211Mode>(wireless.mode().await?),
+                bitrate: wireless.bitrate().await?,
+                active_access_point,
+                access_points,
+            }
+        }
+        _ => return Ok(None),
+    };
+    Ok(Some(details))
+}
+
+async fn access_point_snapshot(access_point: &AccessPointProxy<'_>) -> Result<AccessPointSnapshot> {
+    let flags = access_point.wpa_flags().await? | access_point.rsn_flags().await?;
+    Ok(AccessPointSnapshot {
+        ssid: String::from_utf8_lossy(&access_point.ssid().await?).into_owned(),
+        bssid: access_point.hw_address().await?,
+        frequency: access_point.frequency().await?,
+        strength: access_point.strength().await?,
+        max_bitrate: access_point.max_bitrate().await?,
+        security: NM80211ApSecurityFlags::from_bits_retain(flags)
+            .iter_names()
+            .map(|(name, _)| name.to_lowercase())
+            .collect(),
+    })
+}
+
+async fn active_connection(
+    connection: &Connection,
+    active: &ActiveProxy<'_>,
+    interfaces: &HashMap<OwnedObjectPath, String>,
+) -> Result<ActiveConnectionSnapshot> {
+    let mut devices: Vec<String> = active
+        .devices()
+        .await?
+        .into_iter()
+        .filter_map(|path| interfaces.get(&path).cloned())
+        .collect();
+    devices.sort();
+
+    let ip4 = match optional(active.ip4_config().await?) {
+        Some(path) => {
+            Some(ip4_snapshot(&IP4ConfigProxy::new_from_path(path, connection).await?).await?)
+        }
+        None => None,
+    };
+    let ip6 = match optional(active.ip6_config().await?) {
+        Some(path) => {
+            Some(ip6_snapshot(&IP6ConfigProxy::new_from_path(path, connection).await?).await?)
+        }
+        None => None,
+    };
+    let dhcp4 = match optional(active.dhcp4_config().await?) {
+        Some(path) => dhcp_options(
+            DHCP4ConfigProxy::new_from_path(path, connection)
+                .await?
+                .options()
+                .await?,
+        ),
+        None => BTreeMap::new(),
+    };
+    let dhcp6 = match optional(active.dhcp6_config().await?) {
+        Some(path) => dhcp_options(
+            DHCP6ConfigProxy::new_from_path(path, connection)
+                .await?
+                .options()
+                .await?,
+        ),
+        None => BTreeMap::new(),
+    };
+
+    Ok(ActiveConnectionSnapshot {
+        id: active.id().await?,
+        uuid: active.uuid().await?,
+        connection_type: active.type_().await?,
+        state: enum_name::<NMActiveConnectionState>(active.state().await?),
+        vpn: active.vpn().await?,
+        default: active.default().await?,
+        default6: active.default6().await?,
+        devices,
+        ip4,
+        ip6,
+        dhcp4,
+        dhcp6,
+    })
+}
+
+/// `None` for the `/` path NetworkManager uses for absent objects.
+fn optional(path: OwnedObjectPath) -> Option<OwnedObjectPath> {
+    (path.as_str() != "/").then_some(path)
+}
+
+async fn ip4_snapshot(config: &IP4ConfigProxy<'_>) -> Result<IpConfigSnapshot> {
+    let nameservers = config
+        .nameservers()
+        .await?
+        .into_iter()
+        .map(|server| Ipv4Addr::from(server.to_ne_bytes()).to_string())
+        .collect();
+    Ok(ip_snapshot(
+        config.address_data().await?,
+        config.gateway().await?,
+        config.route_data().await?,
+        nameservers,
+        config.domains().await?,
+        config.searches().await?,
+    ))
+}
+
+async fn ip6_snapshot(config: &IP6ConfigProxy<'_>) -> Result<IpConfigSnapshot> {
+    let nameservers = config
+        .nameservers()
+        .await?
+        .into_iter()
+        .filter_map(|server| <[u8; 16]>::try_from(server).ok())
+        .map(|server| Ipv6Addr::from(server).to_string())
+        .collect();
+    Ok(ip_snapshot(
+        config.address_data().await?,
+        config.gateway().await?,
+        config.route_data().await?,
+        nameservers,
+        config.domains().await?,
+        config.searches().await?,
+    ))
+}
+
+fn ip_snapshot(
+    addresses: Vec<HashMap<String, OwnedValue>>,
+    gateway: String,
+    routes: Vec<HashMap<String, OwnedValue>>,
+    nameservers: Vec<String>,
+    domains: Vec<String>,
+    searches: Vec<String>,
+) -> IpConfigSnapshot {
+    let cidr = |data: &HashMap<String, OwnedValue>, key: &str| -> Option<String> {
+        let address: &str = data.get(key)?.downcast_ref().ok()?;
+        let prefix: u32 = data.get("prefix")?.downcast_ref().ok()?;
+        Some(format!("{address}/{prefix}"))
+    };
+
+    let mut addresses: Vec<String> = addresses
+        .iter()
+        .filter_map(|address| cidr(address, "address"))
+        .collect();
+    addresses.sort();
+    let mut routes: Vec<RouteSnapshot> = routes
+        .iter()
+        .filter_map(|route| {
+            Some(RouteSnapshot {
+                destination: cidr(route, "dest")?,
+                next_hop: route
+                    .get("next-hop")
+                    .and_then(|next_hop| next_hop.downcast_ref::<&str>().ok())
+                    .map(ToOwned::to_owned),
+                metric: route
+                    .get("metric")
+                    .and_then(|metric| metric.downcast_ref::<u32>().ok()),
+            })
+        })
+        .collect();
+    routes.sort();
+
+    IpConfigSnapshot {
+        addresses,
+        gateway: (!gateway.is_empty()).then_some(gateway),
+        routes,
+        nameservers,
+        domains,
+        searches,
+    }
+}
+
+fn dhcp_options(options: HashMap<String, OwnedValue>) -> BTreeMap<String, String> {
+    options
+        .into_iter()
+        .filter_map(|(option, value)| {
+            let value: &str = value.downcast_ref().ok()?;
+            Some((option, value.to_owned()))
+        })
+        .collect()
+}
+
+async fn profile_snapshot(profile: &SettingsConnectionProxy<'_>) -> Result<ProfileSnapshot> {
+    let mut settings = ConnectionSettings::from(profile.get_settings().await?);
+    settings.redact_secrets();
+
+    let mut properties: BTreeMap<String, BTreeMap<String, PropertyValue>> = BTreeMap::new();
+    for (setting, property, value) in settings.iter() {
+        properties
+            .entry(setting.to_owned())
+            .or_default()
+            .insert(property.to_owned(), PropertyValue::from(value));
+    }
+
+    Ok(ProfileSnapshot {
+        id: settings.id().unwrap_or_default().to_owned(),
+        uuid: settings.uuid().unwrap_or_default().to_owned(),
+        connection_type: settings.connection_type().unwrap_or_default().to_owned(),
+        filename: profile.filename().await?,
+        unsaved: profile.unsaved().await?,
+        settings: properties,
+    })
+}
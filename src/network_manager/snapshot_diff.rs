@@ -0,0 +1,674 @@
+//! What changed between two [`Snapshot`]s.
+//!
+//! Devices are matched by interface name, active connections and profiles by UUID. Lists that
+//! are sets, like addresses or routes, report the entries that were added or removed rather
+//! than the whole list. Scan results, DHCP options and `connection.timestamp` are left out: they
+//! change all the time without anything being wrong.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use super::snapshot::{
+    ActiveConnectionSnapshot, DeviceDetails, DeviceSnapshot, IpConfigSnapshot, ProfileSnapshot,
+    PropertyValue, RouteSnapshot, Snapshot,
+};
+
+/// One changed value. `before` is `None` for added values and `after` for removed ones.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    /// Dotted path of the value, e.g. `ip4.addresses` or `ipv4.method`.
+    pub path: String,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+/// The changes of a device, active connection or profile present in both snapshots.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ObjectChanges {
+    /// Interface name or connection id.
+    pub name: String,
+    pub changes: Vec<Change>,
+}
+
+/// The differences between two snapshots, see [`Snapshot::diff`].
+///
+/// `Display` formats it as a report for humans.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SnapshotDiff {
+    /// Daemon state, connectivity, radio switches and logging.
+    pub general: Vec<Change>,
+    pub devices_added: Vec<String>,
+    pub devices_removed: Vec<String>,
+    pub devices_changed: Vec<ObjectChanges>,
+    pub connections_activated: Vec<String>,
+    pub connections_deactivated: Vec<String>,
+    pub connections_changed: Vec<ObjectChanges>,
+    pub profiles_added: Vec<String>,
+    pub profiles_removed: Vec<String>,
+    pub profiles_changed: Vec<ObjectChanges>,
+}
+
+impl SnapshotDiff {
+    pub fn is_empty(&self) -> bool {
+        self.general.is_empty()
+            && self.devices_added.is_empty()
+            && self.devices_removed.is_empty()
+            && self.devices_changed.is_empty()
+            && self.connections_activated.is_empty()
+            && self.connections_deactivated.is_empty()
+            && self.connections_changed.is_empty()
+            && self.profiles_added.is_empty()
+            && self.profiles_removed.is_empty()
+            && self.profiles_changed.is_empty()
+    }
+}
+
+impl Snapshot {
+    /// The changes from `self` to the `later` snapshot.
+    pub fn diff(&self, later: &Snapshot) -> SnapshotDiff {
+        let mut diff = SnapshotDiff::default();
+
+        let mut general = Changes::default();
+        general.value("version", &self.version, &later.version);
+        general.value("state", &self.state, &later.state);
+        general.value("connectivity", &self.connectivity, &later.connectivity);
+        general.value(
+            "networking_enabled",
+            self.networking_enabled,
+            later.networking_enabled,
+        );
+        let (radios, later_radios) = (&self.radios, &later.radios);
+        general.value(
+            "radios.wireless_enabled",
+            radios.wireless_enabled,
+            later_radios.wireless_enabled,
+        );
+        general.value(
+            "radios.wireless_hardware_enabled",
+            radios.wireless_hardware_enabled,
+            later_radios.wireless_hardware_enabled,
+        );
+        general.value(
+            "radios.wwan_enabled",
+            radios.wwan_enabled,
+            later_radios.wwan_enabled,
+        );
+        general.value(
+            "radios.wwan_hardware_enabled",
+            radios.wwan_hardware_enabled,
+            later_radios.wwan_hardware_enabled,
+        );
+        general.value("logging.level", &self.logging.level, &later.logging.level);
+        general.set(
+            "logging.domains",
+            &self.logging.domains,
+            &later.logging.domains,
+        );
+        diff.general = general.0;
+
+        (
+            diff.devices_added,
+            diff.devices_removed,
+            diff.devices_changed,
+        ) = compare(
+            &self.devices,
+            &later.devices,
+            |device| device.interface.clone(),
+            |device| device.interface.clone(),
+            device_changes,
+        );
+
+        (
+            diff.connections_activated,
+            diff.connections_deactivated,
+            diff.connections_changed,
+        ) = compare(
+            &self.active_connections,
+            &later.active_connections,
+            |active| active.uuid.clone(),
+            |active| active.id.clone(),
+            active_connection_changes,
+        );
+
+        (
+            diff.profiles_added,
+            diff.profiles_removed,
+            diff.profiles_changed,
+        ) = compare(
+            &self.profiles,
+            &later.profiles,
+            |profile| profile.uuid.clone(),
+            |profile| profile.id.clone(),
+            profile_changes,
+        );
+
+        diff
+    }
+}
+
+/// Matches `before` and `after` by `key` and returns the names of the added and removed
+/// objects and the changes of the others.
+fn compare<T>(
+    before: &[T],
+    after: &[T],
+    key: impl Fn(&T) -> String,
+    name: impl Fn(&T) -> String,
+    changes: impl Fn(&T, &T) -> Vec<Change>,
+) -> (Vec<String>, Vec<String>, Vec<ObjectChanges>) {
+    let before: BTreeMap<String, &T> = before.iter().map(|object| (key(object), object)).collect();
+    let after: BTreeMap<String, &T> = after.iter().map(|object| (key(object), object)).collect();
+
+    let added = after
+        .iter()
+        .filter(|(key, _)| !before.contains_key(*key))
+        .map(|(_, object)| name(object))
+        .collect();
+    let removed = before
+        .iter()
+        .filter(|(key, _)| !after.contains_key(*key))
+        .map(|(_, object)| name(object))
+        .collect();
+    let changed = before
+        .iter()
+        .filter_map(|(key, object)| {
+            let later = after.get(key)?;
+            let changes = changes(object, later);
+            (!changes.is_empty()).then(|| ObjectChanges {
+                name: name(later),
+                changes,
+            })
+        })
+        .collect();
+
+    (added, removed, changed)
+}
+
+#[derive(Default)]
+struct Changes(Vec<Change>);
+
+impl Changes {
+    fn value(&mut self, path: &str, before: impl ToString, after: impl ToString) {
+        self.option(path, Some(before.to_string()), Some(after.to_string()));
+    }
+
+    fn option(&mut self, path: &str, before: Option<String>, after: Option<String>) {
+        if before != after {
+            self.0.push(Change {
+                path: path.to_owned(),
+                before,
+                after,
+            });
+        }
+    }
+
+    /// Reports the entries only one of `before` and `after` has.
+    fn set<T: Ord + ToString>(&mut self, path: &str, before: &[T], after: &[T]) {
+        let before: BTreeSet<&T> = before.iter().collect();
+        let after: BTreeSet<&T> = after.iter().collect();
+        for removed in before.difference(&after) {
+            self.option(path, Some(removed.to_string()), None);
+        }
+        for added in after.difference(&before) {
+            self.option(path, None, Some(added.to_string()));
+        }
+    }
+}
+
+fn device_changes(before: &DeviceSnapshot, after: &DeviceSnapshot) -> Vec<Change> {
+    let mut changes = Changes::default();
+    changes.value("ip_interface", &before.ip_interface, &after.ip_interface);
+    changes.value("device_type", &before.device_type, &after.device_type);
+    changes.value("state", &before.state, &after.state);
+    changes.value("driver", &before.driver, &after.driver);
+    changes.value("hw_address", &before.hw_address, &after.hw_address);
+    changes.value("managed", before.managed, after.managed);
+    changes.value("mtu", before.mtu, after.mtu);
+
+    let before_details = details_fields(before.details.as_ref());
+    let after_details = details_fields(after.details.as_ref());
+    let fields: BTreeSet<&str> = before_details
+        .keys()
+        .chain(after_details.keys())
+        .copied()
+        .collect();
+    for field in fields {
+        changes.option(
+            field,
+            before_details.get(field).cloned(),
+            after_details.get(field).cloned(),
+        );
+    }
+    if let (
+        Some(DeviceDetails::Bond { slaves, .. }),
+        Some(DeviceDetails::Bond {
+            slaves: later_slaves,
+            ..
+        }),
+    ) = (&before.details, &after.details)
+    {
+        changes.set("slaves", slaves, later_slaves);
+    }
+
+    changes.0
+}
+
+/// The scalar type-specific properties by name.
+fn details_fields(details: Option<&DeviceDetails>) -> BTreeMap<&'static str, String> {
+    let optional = |value: &Option<String>| value.clone().unwrap_or_default();
+    let fields: Vec<(&'static str, String)> = match details {
+        Some(DeviceDetails::Wired {
+            speed,
+            carrier,
+            perm_hw_address,
+        }) => vec![
+            ("speed", speed.to_string()),
+            ("carrier", carrier.to_string()),
+            ("perm_hw_address", perm_hw_address.clone()),
+        ],
+        Some(DeviceDetails::Bond { carrier, .. }) => vec![("carrier", carrier.to_string())],
+        Some(DeviceDetails::Vlan { id, parent }) => {
+            vec![("vlan_id", id.to_string()), ("parent", optional(parent))]
+        }
+        Some(DeviceDetails::Vxlan {
+            id,
+            group,
+            local,
+            dst_port,
+            ttl,
+            parent,
+        }) => vec![
+            ("vxlan_id", id.to_string()),
+            ("group", group.clone()),
+            ("local", local.clone()),
+            ("dst_port", dst_port.to_string()),
+            ("ttl", ttl.to_string()),
+            ("parent", optional(parent)),
+        ],
+        Some(DeviceDetails::Wifi {
+            mode,
+            bitrate,
+            active_access_point,
+            ..
+        }) => vec![
+            ("mode", mode.clone()),
+            ("bitrate", bitrate.to_string()),
+            ("active_access_point", optional(active_access_point)),
+        ],
+        None => Vec::new(),
+    };
+    fields.into_iter().collect()
+}
+
+fn active_connection_changes(
+    before: &ActiveConnectionSnapshot,
+    after: &ActiveConnectionSnapshot,
+) -> Vec<Change> {
+    let mut changes = Changes::default();
+    changes.value("id", &before.id, &after.id);
+    changes.value("state", &before.state, &after.state);
+    changes.value("default", before.default, after.default);
+    changes.value("default6", before.default6, after.default6);
+    changes.set("devices", &before.devices, &after.devices);
+    ip_changes(&mut changes, "ip4", before.ip4.as_ref(), after.ip4.as_ref());
+    ip_changes(&mut changes, "ip6", before.ip6.as_ref(), after.ip6.as_ref());
+    changes.0
+}
+
+fn ip_changes(
+    changes: &mut Changes,
+    family: &str,
+    before: Option<&IpConfigSnapshot>,
+    after: Option<&IpConfigSnapshot>,
+) {
+    let empty = IpConfigSnapshot::default();
+    let before = before.unwrap_or(&empty);
+    let after = after.unwrap_or(&empty);
+
+    changes.set(
+        &format!("{family}.addresses"),
+        &before.addresses,
+        &after.addresses,
+    );
+    changes.option(
+        &format!("{family}.gateway"),
+        before.gateway.clone(),
+        after.gateway.clone(),
+    );
+    changes.set(&format!("{family}.routes"), &before.routes, &after.routes);
+    changes.set(
+        &format!("{family}.nameservers"),
+        &before.nameservers,
+        &after.nameservers,
+    );
+    changes.set(
+        &format!("{family}.domains"),
+        &before.domains,
+        &after.domains,
+    );
+    changes.set(
+        &format!("{family}.searches"),
+        &before.searches,
+        &after.searches,
+    );
+}
+
+impl fmt::Display for RouteSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.destination)?;
+        if let Some(next_hop) = &self.next_hop {
+            write!(f, " via {next_hop}")?;
+        }
+        if let Some(metric) = self.metric {
+            write!(f, " metric {metric}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Profile properties NetworkManager rewrites on its own while the profile is active.
+const VOLATILE_PROPERTIES: &[&str] = &["connection.timestamp"];
+
+fn profile_changes(before: &ProfileSnapshot, after: &ProfileSnapshot) -> Vec<Change> {
+    let mut changes = Changes::default();
+    changes.value("filename", &before.filename, &after.filename);
+    changes.value("unsaved", before.unsaved, after.unsaved);
+
+    let properties = |profile: &ProfileSnapshot| -> BTreeMap<String, String> {
+        profile
+            .settings
+            .iter()
+            .flat_map(|(setting, properties)| {
+                properties.iter().map(move |(property, value)| {
+                    (format!("{setting}.{property}"), value.to_string())
+                })
+            })
+            .filter(|(path, _)| !VOLATILE_PROPERTIES.contains(&path.as_str()))
+            .collect()
+    };
+    let before = properties(before);
+    let after = properties(after);
+    let paths: BTreeSet<&String> = before.keys().chain(after.keys()).collect();
+    for path in paths {
+        changes.option(path, before.get(path).cloned(), after.get(path).cloned());
+    }
+
+    changes.0
+}
+
+impl fmt::Display for PropertyValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PropertyValue::Bool(value) => write!(f, "{value}"),
+            PropertyValue::Int(value) => write!(f, "{value}"),
+            PropertyValue::UInt(value) => write!(f, "{value}"),
+            PropertyValue::Double(value) => write!(f, "{value}"),
+            PropertyValue::String(value) => write!(f, "{value}"),
+            PropertyValue::List(values) => {
+                write!(f, "[")?;
+                for (idx, value) in values.iter().enumerate() {
+                    if idx > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{value}")?;
+                }
+                write!(f, "]")
+            }
+            PropertyValue::Map(values) => {
+                write!(f, "{{")?;
+                for (idx, (key, value)) in values.iter().enumerate() {
+                    if idx > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{key}: {value}")?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.before, &self.after) {
+            (Some(before), Some(after)) => write!(f, "{}: {before} -> {after}", self.path),
+            (None, Some(after)) => write!(f, "{}: + {after}", self.path),
+            (Some(before), None) => write!(f, "{}: - {before}", self.path),
+            (None, None) => write!(f, "{}", self.path),
+        }
+    }
+}
+
+impl fmt::Display for SnapshotDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "No changes");
+        }
+
+        if !self.general.is_empty() {
+            writeln!(f, "NetworkManager")?;
+            for change in &self.general {
+                writeln!(f, "  {change}")?;
+            }
+        }
+        write_section(
+            f,
+            "Devices",
+            &self.devices_added,
+            &self.devices_removed,
+            &self.devices_changed,
+        )?;
+        write_section(
+            f,
+            "Active connections",
+            &self.connections_activated,
+            &self.connections_deactivated,
+            &self.connections_changed,
+        )?;
+        write_section(
+            f,
+            "Profiles",
+            &self.profiles_added,
+            &self.profiles_removed,
+            &self.profiles_changed,
+        )
+    }
+}
+
+fn write_section(
+    f: &mut fmt::Formatter<'_>,
+    title: &str,
+    added: &[String],
+    removed: &[String],
+    changed: &[ObjectChanges],
+) -> fmt::Result {
+    if added.is_empty() && removed.is_empty() && changed.is_empty() {
+        return Ok(());
+    }
+
+    writeln!(f, "{title}")?;
+    for name in added {
+        writeln!(f, "  + {name}")?;
+    }
+    for name in removed {
+        writeln!(f, "  - {name}")?;
+    }
+    for object in changed {
+        writeln!(f, "  {}", object.name)?;
+        for change in &object.changes {
+            writeln!(f, "    {change}")?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network_manager::snapshot::{Logging, Radios};
+
+    fn profile(id: &str, uuid: &str, settings: &[(&str, &str, PropertyValue)]) -> ProfileSnapshot {
+        let mut profile = ProfileSnapshot {
+            id: id.to_owned(),
+            uuid: uuid.to_owned(),
+            connection_type: "802-3-ethernet".to_owned(),
+            filename: format!("/etc/NetworkManager/system-connections/{id}.nmconnection"),
+            unsaved: false,
+            settings: BTreeMap::new(),
+        };
+        for (setting, property, value) in settings {
+            profile
+                .settings
+                .entry(setting.to_string())
+                .or_default()
+                .insert(property.to_string(), value.clone());
+        }
+        profile
+    }
+
+    fn device(interface: &str, state: &str) -> DeviceSnapshot {
+        DeviceSnapshot {
+            interface: interface.to_owned(),
+            ip_interface: interface.to_owned(),
+            device_type: "ethernet".to_owned(),
+            state: state.to_owned(),
+            driver: "e1000e".to_owned(),
+            hw_address: "52:54:00:12:34:56".to_owned(),
+            managed: true,
+            mtu: 1500,
+            details: None,
+        }
+    }
+
+    fn active(addresses: &[&str], dhcp4: &[(&str, &str)]) -> ActiveConnectionSnapshot {
+        ActiveConnectionSnapshot {
+            id: "wired".to_owned(),
+            uuid: "6c1b1e3a-0000-4000-8000-000000000001".to_owned(),
+            connection_type: "802-3-ethernet".to_owned(),
+            state: "activated".to_owned(),
+            vpn: false,
+            default: true,
+            default6: false,
+            devices: vec!["eth0".to_owned()],
+            ip4: Some(IpConfigSnapshot {
+                addresses: addresses
+                    .iter()
+                    .map(|address| address.to_string())
+                    .collect(),
+                ..Default::default()
+            }),
+            ip6: None,
+            dhcp4: dhcp4
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+            dhcp6: BTreeMap::new(),
+        }
+    }
+
+    fn snapshot() -> Snapshot {
+        Snapshot {
+            version: "1.46.0".to_owned(),
+            state: "connected-global".to_owned(),
+            connectivity: "full".to_owned(),
+            networking_enabled: true,
+            radios: Radios::default(),
+            logging: Logging {
+                level: "INFO".to_owned(),
+                domains: vec!["PLATFORM".to_owned(), "DHCP".to_owned()],
+            },
+            devices: vec![device("eth0", "activated")],
+            active_connections: vec![active(&["192.168.1.10/24"], &[("expiry", "1760000000")])],
+            profiles: vec![profile(
+                "wired",
+                "6c1b1e3a-0000-4000-8000-000000000001",
+                &[
+                    ("connection", "timestamp", PropertyValue::UInt(1760000000)),
+                    ("ipv4", "method", PropertyValue::String("auto".to_owned())),
+                ],
+            )],
+        }
+    }
+
+    #[test]
+    fn identical_snapshots_have_no_changes() {
+        let diff = snapshot().diff(&snapshot());
+        assert!(diff.is_empty());
+        assert_eq!(diff.to_string(), "No changes\n");
+    }
+
+    #[test]
+    fn ignores_timestamps_and_dhcp_options() {
+        let before = snapshot();
+        let mut after = snapshot();
+        after.profiles[0]
+            .settings
+            .get_mut("connection")
+            .unwrap()
+            .insert("timestamp".to_owned(), PropertyValue::UInt(1760000300));
+        after.active_connections[0]
+            .dhcp4
+            .insert("expiry".to_owned(), "1760000300".to_owned());
+
+        assert!(before.diff(&after).is_empty());
+    }
+
+    #[test]
+    fn reports_profile_properties() {
+        let before = snapshot();
+        let mut after = snapshot();
+        let ipv4 = after.profiles[0].settings.get_mut("ipv4").unwrap();
+        ipv4.insert(
+            "method".to_owned(),
+            PropertyValue::String("manual".to_owned()),
+        );
+        ipv4.insert(
+            "dns".to_owned(),
+            PropertyValue::List(vec![PropertyValue::String("9.9.9.9".to_owned())]),
+        );
+        after.profiles.push(profile(
+            "guest",
+            "6c1b1e3a-0000-4000-8000-000000000002",
+            &[],
+        ));
+
+        let diff = before.diff(&after);
+        assert_eq!(diff.profiles_added, ["guest"]);
+        assert_eq!(
+            diff.profiles_changed,
+            [ObjectChanges {
+                name: "wired".to_owned(),
+                changes: vec![
+                    Change {
+                        path: "ipv4.dns".to_owned(),
+                        before: None,
+                        after: Some("[9.9.9.9]".to_owned()),
+                    },
+                    Change {
+                        path: "ipv4.method".to_owned(),
+                        before: Some("auto".to_owned()),
+                        after: Some("manual".to_owned()),
+                    },
+                ],
+            }]
+        );
+    }
+
+    #[test]
+    fn reports_set_entries_and_devices() {
+        let before = snapshot();
+        let mut after = snapshot();
+        after.devices[0].state = "disconnected".to_owned();
+        after.devices.push(device("eth1", "unavailable"));
+        after.active_connections[0] = active(&["192.168.1.10/24", "10.0.0.2/8"], &[]);
+        after.logging.domains.reverse();
+
+        let diff = before.diff(&after);
+        assert!(diff.general.is_empty());
+        assert_eq!(diff.devices_added, ["eth1"]);
+        assert_eq!(
+            diff.to_string(),
+            "Devices\n  + eth1\n  eth0\n    state: activated -> disconnected\n\
+             Active connections\n  wired\n    ip4.addresses: + 10.0.0.2/8\n"
+        );
+    }
+}
@@ -0,0 +1,461 @@
+//! Wi-Fi site survey recorder.
+//!
+//! Repeatedly scans with a wireless device and records every access point observation, the
+//! resulting [`Survey`] can be exported as CSV or JSON and summarized per BSSID and channel.
+
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use async_io::Timer;
+use futures_lite::{StreamExt, future};
+use serde_json::json;
+use zbus::zvariant::OwnedObjectPath;
+use zbus::{Connection, Result};
+
+use super::access_point::AccessPointProxy;
+use super::channel::{Band, Channel};
+use super::wireless::WirelessProxy;
+
+/// How long to wait for a requested scan to finish before recording anyway.
+const SCAN_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// A single access point seen during a scan.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Observation {
+    pub timestamp: SystemTime,
+    pub bssid: String,
+    pub ssid: String,
+    pub frequency: u32,
+    pub strength: u8,
+    pub max_bitrate: u32,
+    pub bandwidth: u32,
+}
+
+impl Observation {
+    pub fn channel(&self) -> Option<Channel> {
+        Channel::from_frequency(self.frequency)
+    }
+
+    pub fn band(&self) -> Option<Band> {
+        Band::from_frequency(self.frequency)
+    }
+}
+
+/// Strength statistics of one BSSID over a survey.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BssidSummary {
+    pub bssid: String,
+    pub ssid: String,
+    pub frequency: u32,
+    pub observations: usize,
+    pub min_strength: u8,
+    pub max_strength: u8,
+    pub avg_strength: f64,
+}
+
+/// Usage of a channel over a survey.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChannelUsage {
+    pub band: Band,
+    pub channel: u32,
+    /// BSSIDs seen on this channel, the co-channel count.
+    pub bssids: usize,
+    /// BSSIDs seen on other channels of the band whose spectrum overlaps the one used on this
+    /// channel, taking the bandwidth of the BSSs into account.
+    pub overlapping: usize,
+}
+
+/// The observations recorded by a [`SurveyRecorder`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Survey {
+    pub observations: Vec<Observation>,
+}
+
+impl Survey {
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from(
+            "timestamp,bssid,ssid,frequency,channel,band,strength,max_bitrate,bandwidth\n",
+        );
+        for observation in &self.observations {
+            let fields = [
+                unix_timestamp(observation.timestamp),
+                observation.bssid.clone(),
+                observation.ssid.clone(),
+                observation.frequency.to_string(),
+                observation
+                    .channel()
+                    .map(|channel| channel.channel.to_string())
+                    .unwrap_or_default(),
+                observation
+                    .band()
+                    .map(|band| band.as_str().to_owned())
+                    .unwrap_or_default(),
+                observation.strength.to_string(),
+                observation.max_bitrate.to_string(),
+                observation.bandwidth.to_string(),
+            ];
+            let fields: Vec<Cow<str>> = fields.iter().map(|field| csv_field(field)).collect();
+            csv.push_str(&fields.join(","));
+            csv.push('\n');
+        }
+        csv
+    }
+
+    pub fn to_json(&self) -> String {
+        let observations: Vec<serde_json::Value> = self
+            .observations
+            .iter()
+            .map(|observation| {
+                let since_epoch = observation
+                    .timestamp
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default();
+                json!({
+                    "timestamp": since_epoch.as_millis() as f64 / 1000.0,
+                    "bssid": observation.bssid,
+                    "ssid": observation.ssid,
+                    "frequency": observation.frequency,
+                    "channel": observation.channel().map(|channel| channel.channel),
+                    "band": observation.band().map(|band| band.as_str()),
+                    "strength": observation.strength,
+                    "max_bitrate": observation.max_bitrate,
+                    "bandwidth": observation.bandwidth,
+                })
+            })
+            .collect();
+        serde_json::Value::Array(observations).to_string()
+    }
+
+    /// Min/avg/max strength per BSSID, sorted by BSSID.
+    pub fn bssid_summaries(&self) -> Vec<BssidSummary> {
+        let mut by_bssid: BTreeMap<&str, Vec<&Observation>> = BTreeMap::new();
+        for observation in &self.observations {
+            by_bssid
+                .entry(&observation.bssid)
+                .or_default()
+                .push(observation);
+        }
+
+        by_bssid
+            .into_iter()
+            .map(|(bssid, observations)| {
+                let strengths = observations.iter().map(|observation| observation.strength);
+                let total: u64 = strengths.clone().map(u64::from).sum();
+                let last = observations[observations.len() - 1];
+                BssidSummary {
+                    bssid: bssid.to_owned(),
+                    ssid: last.ssid.clone(),
+                    frequency: last.frequency,
+                    observations: observations.len(),
+                    min_strength: strengths.clone().min().unwrap_or_default(),
+                    max_strength: strengths.max().unwrap_or_default(),
+                    avg_strength: total as f64 / observations.len() as f64,
+                }
+            })
+            .collect()
+    }
+
+    /// Co-channel and overlapping BSSID counts for every channel in use, sorted by band and
+    /// channel.
+    pub fn channel_usage(&self) -> Vec<ChannelUsage> {
+        // The BSSIDs per channel and the spectrum the widest of them occupies
+        let mut per_channel: BTreeMap<(Band, u32), (HashSet<&str>, Spectrum)> = BTreeMap::new();
+        for observation in &self.observations {
+            if let (Some(band), Some(channel)) = (observation.band(), observation.channel()) {
+                let (low, high) = occupied(band, observation.frequency, observation.bandwidth);
+                let (bssids, spectrum) = per_channel
+                    .entry((band, channel.channel))
+                    .or_insert_with(|| (HashSet::new(), (low, high)));
+                bssids.insert(&observation.bssid);
+                *spectrum = (spectrum.0.min(low), spectrum.1.max(high));
+            }
+        }
+
+        per_channel
+            .iter()
+            .map(|(&(band, channel), (bssids, spectrum))| ChannelUsage {
+                band,
+                channel,
+                bssids: bssids.len(),
+                overlapping: per_channel
+                    .iter()
+                    .filter(|&(&(other_band, other_channel), (_, other_spectrum))| {
+                        other_band == band
+                            && other_channel != channel
+                            && spectrum.0 < other_spectrum.1
+                            && other_spectrum.0 < spectrum.1
+                    })
+                    .map(|(_, (other_bssids, _))| other_bssids.len())
+                    .sum(),
+            })
+            .collect()
+    }
+
+    /// Distinct BSSIDs seen per channel, grouped by band.
+    pub fn channel_occupancy(&self) -> HashMap<Band, BTreeMap<u32, usize>> {
+        let mut occupancy: HashMap<Band, BTreeMap<u32, usize>> = HashMap::new();
+        for usage in self.channel_usage() {
+            occupancy
+                .entry(usage.band)
+                .or_default()
+                .insert(usage.channel, usage.bssids);
+        }
+        occupancy
+    }
+}
+
+/// The lowest and highest frequency in MHz in use.
+type Spectrum = (u32, u32);
+
+/// The spectrum a BSS with its primary channel at `frequency` and `bandwidth` MHz wide
+/// occupies, a bandwidth of `0` meaning unknown and taken as 20MHz.
+///
+/// 2.4GHz channels are 22MHz wide, so only channels five apart (1, 6, 11) don't overlap. The
+/// secondary channel of a 40MHz BSS may be above or below, so both are counted. 5GHz channels
+/// are bonded in blocks aligned on channel 36, or 149 from there on.
+fn occupied(band: Band, frequency: u32, bandwidth: u32) -> Spectrum {
+    match band {
+        Band::Bg => {
+            let secondary = if bandwidth > 20 { 20 } else { 0 };
+            (frequency - 11 - secondary, frequency + 11 + secondary)
+        }
+        Band::A => {
+            let width = bandwidth.max(20);
+            let base = if frequency < 5745 { 5170 } else { 5735 };
+            match (frequency - 10).checked_sub(base) {
+                Some(offset) if width > 20 => {
+                    let low = base + offset / width * width;
+                    (low, low + width)
+                }
+                _ => (frequency - 10, frequency + 10),
+            }
+        }
+    }
+}
+
+/// Records access point observations from a wireless device.
+pub struct SurveyRecorder<'a> {
+    connection: Connection,
+    wireless: WirelessProxy<'a>,
+    survey: Survey,
+}
+
+impl<'a> SurveyRecorder<'a> {
+    pub fn new(wireless: WirelessProxy<'a>) -> SurveyRecorder<'a> {
+        SurveyRecorder {
+            connection: wireless.inner().connection().clone(),
+            wireless,
+            survey: Survey::default(),
+        }
+    }
+
+    pub fn survey(&self) -> &Survey {
+        &self.survey
+    }
+
+    pub fn into_survey(self) -> Survey {
+        self.survey
+    }
+
+    /// Requests a scan, waits for it to complete and records the visible access points.
+    ///
+    /// When NetworkManager refuses the scan, e.g. because the device is busy or scanned
+    /// moments ago, the current scan results are recorded instead.
+    ///
+    /// Returns the number of observations recorded.
+    pub async fn scan(&mut self) -> Result<usize> {
+        let mut last_scan_changes = self.wireless.receive_last_scan_changed().await;
+        // The stream yields the current value first
+        last_scan_changes.next().await;
+
+        match self.wireless.request_scan(HashMap::new()).await {
+            Ok(()) => {
+                future::or(
+                    async {
+                        last_scan_changes.next().await;
+                    },
+                    async {
+                        Timer::after(SCAN_TIMEOUT).await;
+                    },
+                )
+                .await;
+            }
+            Err(zbus::Error::MethodError(..) | zbus::Error::FDO(_)) => {}
+            Err(err) => return Err(err),
+        }
+
+        self.record().await
+    }
+
+    /// Records the access points currently known to the device without scanning.
+    ///
+    /// Access points that disappear while they are read are skipped. Returns the number of
+    /// observations recorded.
+    pub async fn record(&mut self) -> Result<usize> {
+        let timestamp = SystemTime::now();
+        let mut count = 0;
+
+        for path in self.wireless.get_all_access_points().await? {
+            match self.observe(path, timestamp).await {
+                Ok(observation) => {
+                    self.survey.observations.push(observation);
+                    count += 1;
+                }
+                // The access point went away
+                Err(zbus::Error::MethodError(..) | zbus::Error::FDO(_)) => {}
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(count)
+    }
+
+    async fn observe(&self, path: OwnedObjectPath, timestamp: SystemTime) -> Result<Observation> {
+        let access_point = AccessPointProxy::new_from_path(path, &self.connection).await?;
+        Ok(Observation {
+            timestamp,
+            bssid: access_point.hw_address().await?,
+            ssid: String::from_utf8_lossy(&access_point.ssid().await?).into_owned(),
+            frequency: access_point.frequency().await?,
+            strength: access_point.strength().await?,
+            max_bitrate: access_point.max_bitrate().await?,
+            bandwidth: access_point.bandwidth().await?,
+        })
+    }
+
+    /// Scans `rounds` times, starting a new scan every `interval`.
+    pub async fn run(&mut self, interval: Duration, rounds: usize) -> Result<()> {
+        for round in 0..rounds {
+            let started = std::time::Instant::now();
+            self.scan().await?;
+            if round + 1 != rounds {
+                Timer::after(interval.saturating_sub(started.elapsed())).await;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn unix_timestamp(timestamp: SystemTime) -> String {
+    let since_epoch = timestamp.duration_since(UNIX_EPOCH).unwrap_or_default();
+    format!(
+        "{}.{:03}",
+        since_epoch.as_secs(),
+        since_epoch.subsec_millis()
+    )
+}
+
+/// Quotes a CSV field when it contains a separator, quote or line break.
+fn csv_field(value: &str) -> Cow<'_, str> {
+    if value.contains([',', '"', '\n', '\r']) {
+        Cow::Owned(format!("\"{}\"", value.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn observation(
+        seconds: u64,
+        bssid: &str,
+        ssid: &str,
+        frequency: u32,
+        strength: u8,
+        bandwidth: u32,
+    ) -> Observation {
+        Observation {
+            timestamp: UNIX_EPOCH + Duration::from_millis(seconds * 1000 + 250),
+            bssid: bssid.to_owned(),
+            ssid: ssid.to_owned(),
+            frequency,
+            strength,
+            max_bitrate: 54000,
+            bandwidth,
+        }
+    }
+
+    fn survey() -> Survey {
+        Survey {
+            observations: vec![
+                // 2.4GHz channels 1, 3 and 6
+                observation(1, "AA:00:00:00:00:01", "home", 2412, 70, 20),
+                observation(2, "AA:00:00:00:00:01", "home", 2412, 50, 20),
+                observation(1, "AA:00:00:00:00:02", "cafe", 2422, 40, 20),
+                observation(1, "AA:00:00:00:00:03", "office", 2437, 60, 20),
+                // 5GHz channel 36 at 80MHz spans 36-48, channel 52 starts the next block
+                observation(1, "BB:00:00:00:00:01", "home", 5180, 80, 80),
+                observation(1, "BB:00:00:00:00:02", "cafe", 5220, 30, 20),
+                observation(1, "BB:00:00:00:00:03", "office", 5260, 20, 20),
+            ],
+        }
+    }
+
+    fn usage(survey: &Survey) -> Vec<(u32, usize, usize)> {
+        survey
+            .channel_usage()
+            .iter()
+            .map(|usage| (usage.channel, usage.bssids, usage.overlapping))
+            .collect()
+    }
+
+    #[test]
+    fn summarizes_bssids() {
+        let summaries = survey().bssid_summaries();
+        assert_eq!(summaries.len(), 6);
+
+        let home = &summaries[0];
+        assert_eq!(home.bssid, "AA:00:00:00:00:01");
+        assert_eq!(home.observations, 2);
+        assert_eq!((home.min_strength, home.max_strength), (50, 70));
+        assert_eq!(home.avg_strength, 60.0);
+    }
+
+    #[test]
+    fn counts_overlapping_bssids_by_bandwidth() {
+        assert_eq!(
+            usage(&survey()),
+            [
+                (36, 1, 1),
+                (44, 1, 1),
+                (52, 1, 0),
+                (1, 1, 1),
+                (3, 1, 2),
+                (6, 1, 1)
+            ]
+        );
+
+        // At 20MHz channels 36 and 44 are apart
+        let mut narrow = survey();
+        narrow.observations[4].bandwidth = 20;
+        assert_eq!(usage(&narrow)[..3], [(36, 1, 0), (44, 1, 0), (52, 1, 0)]);
+
+        // At 160MHz channel 36 spans up to channel 64
+        let mut wide = survey();
+        wide.observations[4].bandwidth = 160;
+        assert_eq!(usage(&wide)[..3], [(36, 1, 2), (44, 1, 1), (52, 1, 1)]);
+    }
+
+    #[test]
+    fn blocks_start_again_at_channel_149() {
+        assert_eq!(occupied(Band::A, 5745, 80), (5735, 5815));
+        assert_eq!(occupied(Band::A, 5805, 80), (5735, 5815));
+        assert_eq!(occupied(Band::A, 5500, 40), (5490, 5530));
+        assert_eq!(occupied(Band::A, 5170, 40), (5160, 5180));
+    }
+
+    #[test]
+    fn exports_csv() {
+        let mut survey = survey();
+        survey.observations.truncate(1);
+        survey.observations[0].ssid = "say \"hi\", friend".to_owned();
+        assert_eq!(
+            survey.to_csv(),
+            "timestamp,bssid,ssid,frequency,channel,band,strength,max_bitrate,bandwidth\n\
+             1.250,AA:00:00:00:00:01,\"say \"\"hi\"\", friend\",2412,1,bg,70,54000,20\n"
+        );
+    }
+}
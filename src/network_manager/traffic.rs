@@ -0,0 +1,300 @@
+//! Per-device throughput from the traffic counters of `org.freedesktop.NetworkManager.Device.Statistics`.
+//!
+//! NetworkManager only updates `RxBytes` and `TxBytes` while `RefreshRateMs` is non-zero, and
+//! it is zero by default. [`TrafficMonitor`] raises the refresh rate of the devices it watches,
+//! turns the `PropertiesChanged` signals of the counters into [`TrafficSample`]s and puts the
+//! previous refresh rate back when the device is unwatched or the monitor stopped or dropped.
+
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+use futures_lite::{Stream, StreamExt, stream};
+use zbus::fdo::PropertiesChanged;
+use zbus::message::Type;
+use zbus::zvariant::{OwnedObjectPath, Value};
+use zbus::{Connection, MatchRule, MessageStream, Result};
+
+use super::statistics::StatisticsProxy;
+
+const STATISTICS_INTERFACE: &str = "org.freedesktop.NetworkManager.Device.Statistics";
+
+/// Number of rates averaged by default.
+const DEFAULT_WINDOW: usize = 5;
+
+/// The traffic of a device since its previous sample.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrafficSample {
+    pub device: OwnedObjectPath,
+    /// Time since the previous sample.
+    pub interval: Duration,
+    /// Counter values.
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+    /// Bytes per second over the interval.
+    pub rx_rate: f64,
+    pub tx_rate: f64,
+    /// Mean of the last rates, see [`TrafficMonitor::with_window`].
+    pub rx_average: f64,
+    pub tx_average: f64,
+    /// A counter went backwards, e.g. because the driver was reloaded. The rates then assume
+    /// it restarted from zero during the interval.
+    pub counter_reset: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Counters {
+    at: Instant,
+    rx_bytes: u64,
+    tx_bytes: u64,
+}
+
+struct WatchedDevice {
+    statistics: StatisticsProxy<'static>,
+    /// The refresh rate to put back, when the monitor changed it.
+    previous_refresh_rate_ms: Option<u32>,
+    /// The latest counter values, read by `watch` and then updated from the signals, which
+    /// only carry the counters that changed.
+    rx_bytes: u64,
+    tx_bytes: u64,
+    last: Option<Counters>,
+    rates: VecDeque<(f64, f64)>,
+}
+
+impl WatchedDevice {
+    fn update(
+        &mut self,
+        device: &OwnedObjectPath,
+        changed: Counters,
+        window: usize,
+    ) -> Option<TrafficSample> {
+        let Some(last) = self.last.replace(changed) else {
+            // The counters read before the refresh rate was raised may be stale
+            return None;
+        };
+        let interval = changed.at.duration_since(last.at);
+        let seconds = interval.as_secs_f64();
+        if seconds <= 0.0 {
+            return None;
+        }
+
+        let counter_reset = changed.rx_bytes < last.rx_bytes || changed.tx_bytes < last.tx_bytes;
+        let delta = |current: u64, previous: u64| match current.checked_sub(previous) {
+            Some(delta) => delta,
+            None => current,
+        };
+        let rx_rate = delta(changed.rx_bytes, last.rx_bytes) as f64 / seconds;
+        let tx_rate = delta(changed.tx_bytes, last.tx_bytes) as f64 / seconds;
+
+        if self.rates.len() == window {
+            self.rates.pop_front();
+        }
+        self.rates.push_back((rx_rate, tx_rate));
+        let count = self.rates.len() as f64;
+        let (rx_total, tx_total) = self
+            .rates
+            .iter()
+            .fold((0.0, 0.0), |(rx, tx), (rx_rate, tx_rate)| {
+                (rx + rx_rate, tx + tx_rate)
+            });
+
+        Some(TrafficSample {
+            device: device.clone(),
+            interval,
+            rx_bytes: changed.rx_bytes,
+            tx_bytes: changed.tx_bytes,
+            rx_rate,
+            tx_rate,
+            rx_average: rx_total / count,
+            tx_average: tx_total / count,
+            counter_reset,
+        })
+    }
+}
+
+/// Reports the throughput of a set of devices.
+///
+/// The first counter update of a device after [`watch`](Self::watch) only sets the baseline,
+/// samples start with the second one. NetworkManager doesn't send updates while the counters
+/// don't change, so an idle device produces no samples and the interval of the next one
+/// covers the idle time.
+///
+/// Dropping the monitor puts the refresh rates back in the background, ignoring failures;
+/// [`stop`](Self::stop) does it right away and reports them.
+pub struct TrafficMonitor {
+    connection: Connection,
+    refresh_rate_ms: u32,
+    window: usize,
+    devices: HashMap<OwnedObjectPath, WatchedDevice>,
+    changes: MessageStream,
+}
+
+impl TrafficMonitor {
+    /// Creates a monitor that has the counters of its devices updated every `refresh_rate`.
+    pub async fn new(connection: &Connection, refresh_rate: Duration) -> Result<TrafficMonitor> {
+        let rule = MatchRule::builder()
+            .msg_type(Type::Signal)
+            .sender("org.freedesktop.NetworkManager")?
+            .interface("org.freedesktop.DBus.Properties")?
+            .member("PropertiesChanged")?
+            .arg(0, STATISTICS_INTERFACE)?
+            .build();
+
+        Ok(TrafficMonitor {
+            changes: MessageStream::for_match_rule(rule, connection, None).await?,
+            connection: connection.clone(),
+            refresh_rate_ms: u32::try_from(refresh_rate.as_millis())
+                .unwrap_or(u32::MAX)
+                .max(1),
+            window: DEFAULT_WINDOW,
+            devices: HashMap::new(),
+        })
+    }
+
+    /// Averages the last `samples` rates, 5 by default.
+    pub fn with_window(mut self, samples: usize) -> Self {
+        self.window = samples.max(1);
+        self
+    }
+
+    /// Starts reporting the traffic of a device, raising its refresh rate if needed.
+    ///
+    /// Changing the refresh rate needs the `org.freedesktop.NetworkManager.network-control`
+    /// permission.
+    pub async fn watch(&mut self, device: OwnedObjectPath) -> Result<()> {
+        if self.devices.contains_key(&device) {
+            return Ok(());
+        }
+        let statistics: StatisticsProxy<'static> = StatisticsProxy::builder(&self.connection)
+            .path(device.clone())?
+            .build()
+            .await?;
+
+        let current = statistics.refresh_rate_ms().await?;
+        let previous_refresh_rate_ms = if current == 0 || current > self.refresh_rate_ms {
+            statistics.set_refresh_rate_ms(self.refresh_rate_ms).await?;
+            Some(current)
+        } else {
+            None
+        };
+        let rx_bytes = statistics.rx_bytes().await?;
+        let tx_bytes = statistics.tx_bytes().await?;
+
+        self.devices.insert(
+            device,
+            WatchedDevice {
+                statistics,
+                previous_refresh_rate_ms,
+                rx_bytes,
+                tx_bytes,
+                last: None,
+                rates: VecDeque::with_capacity(self.window),
+            },
+        );
+        Ok(())
+    }
+
+    /// Stops reporting the traffic of a device and puts its refresh rate back.
+    pub async fn unwatch(&mut self, device: &OwnedObjectPath) -> Result<()> {
+        match self.devices.remove(device) {
+            Some(mut watched) => restore(&mut watched).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Unwatches every device, reporting the first failure to put a refresh rate back.
+    pub async fn stop(mut self) -> Result<()> {
+        let mut result = Ok(());
+        for (_, mut watched) in self.devices.drain() {
+            let restored = restore(&mut watched).await;
+            if result.is_ok() {
+                result = restored;
+            }
+        }
+        result
+    }
+
+    /// Waits for the next sample of a watched device.
+    pub async fn next(&mut self) -> Option<Result<TrafficSample>> {
+        loop {
+            let message = match self.changes.next().await? {
+                Ok(message) => message,
+                Err(err) => return Some(Err(err)),
+            };
+            let at = Instant::now();
+            let Some(device) = message.header().path().cloned().map(OwnedObjectPath::from) else {
+                continue;
+            };
+            let Some(watched) = self.devices.get_mut(&device) else {
+                continue;
+            };
+            let Some(signal) = PropertiesChanged::from_message(message) else {
+                continue;
+            };
+            let args = match signal.args() {
+                Ok(args) => args,
+                Err(err) => return Some(Err(err)),
+            };
+
+            // Only the counters that changed are included, an idle direction keeps its value
+            let counter = |name: &str| match args.changed_properties.get(name) {
+                Some(Value::U64(value)) => Some(*value),
+                _ => None,
+            };
+            let (rx_bytes, tx_bytes) = (counter("RxBytes"), counter("TxBytes"));
+            if rx_bytes.is_none() && tx_bytes.is_none() {
+                continue;
+            }
+            watched.rx_bytes = rx_bytes.unwrap_or(watched.rx_bytes);
+            watched.tx_bytes = tx_bytes.unwrap_or(watched.tx_bytes);
+
+            let changed = Counters {
+                at,
+                rx_bytes: watched.rx_bytes,
+                tx_bytes: watched.tx_bytes,
+            };
+            if let Some(sample) = watched.update(&device, changed, self.window) {
+                return Some(Ok(sample));
+            }
+        }
+    }
+
+    /// Turns the monitor into a [`Stream`] of samples. Dropping the stream drops the monitor.
+    pub fn into_stream(self) -> impl Stream<Item = Result<TrafficSample>> {
+        stream::unfold(self, |mut monitor| async move {
+            monitor.next().await.map(|sample| (sample, monitor))
+        })
+    }
+}
+
+impl Drop for TrafficMonitor {
+    /// Puts back the refresh rates of the devices still watched on the connection's executor,
+    /// so dropping never blocks. The calls are lost if the process exits right away.
+    fn drop(&mut self) {
+        for (_, mut watched) in self.devices.drain() {
+            if watched.previous_refresh_rate_ms.is_none() {
+                continue;
+            }
+            self.connection
+                .executor()
+                .spawn(
+                    async move {
+                        let _ = restore(&mut watched).await;
+                    },
+                    "restore statistics refresh rate",
+                )
+                .detach();
+        }
+    }
+}
+
+async fn restore(watched: &mut WatchedDevice) -> Result<()> {
+    match watched.previous_refresh_rate_ms.take() {
+        Some(refresh_rate_ms) => {
+            watched
+                .statistics
+                .set_refresh_rate_ms(refresh_rate_ms)
+                .await
+        }
+        None => Ok(()),
+    }
+}
@@ -0,0 +1,217 @@
+//! Profile updates through `Update2`.
+//!
+//! [`UpdateOptions`] describes the flags and arguments of
+//! [`SettingsConnectionProxy::update2`], [`update_connection`] applies them and
+//! [`preview_update`] shows what an update would change without making it.
+//!
+//! An update replaces the whole profile, secrets included, but `GetSettings` leaves the secrets
+//! out. Reading a profile with [`get_settings_with_secrets`] or updating it with
+//! [`update_preserving_secrets`] keeps a read-modify-update cycle from dropping them.
+
+use std::collections::HashMap;
+
+use zbus::Result;
+use zbus::zvariant::{OwnedValue, Value};
+
+use super::connection_settings::{ConnectionSettings, PropertyChange, SECRET_SETTINGS};
+use super::dbus_interface_types::NMSettingsUpdate2Flags;
+use super::settings_connection::SettingsConnectionProxy;
+
+/// The errors of `GetSecrets` that mean the setting has no secrets NetworkManager can return.
+const NO_SECRETS_ERRORS: &[&str] = &[
+    "org.freedesktop.NetworkManager.AgentManager.NoSecrets",
+    "org.freedesktop.NetworkManager.SecretAgent.NoSecrets",
+    "org.freedesktop.NetworkManager.Settings.Connection.SettingNotFound",
+];
+
+/// Where an updated profile is stored. The modes exclude each other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Persistence {
+    /// Written to disk.
+    ToDisk,
+    /// Kept in memory, the profile on disk stays as it is.
+    InMemory,
+    /// Like [`InMemory`](Self::InMemory), but the profile on disk is forgotten: deleting the
+    /// profile later leaves the file in place.
+    InMemoryDetached,
+    /// Kept in memory only, the profile on disk is deleted.
+    InMemoryOnly,
+}
+
+impl Persistence {
+    fn flag(self) -> NMSettingsUpdate2Flags {
+        match self {
+            Persistence::ToDisk => NMSettingsUpdate2Flags::TO_DISK,
+            Persistence::InMemory => NMSettingsUpdate2Flags::IN_MEMORY,
+            Persistence::InMemoryDetached => NMSettingsUpdate2Flags::IN_MEMORY_DETACHED,
+            Persistence::InMemoryOnly => NMSettingsUpdate2Flags::IN_MEMORY_ONLY,
+        }
+    }
+}
+
+/// The flags and arguments of an `Update2` call.
+///
+/// The default changes the settings and leaves the storage as it is.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UpdateOptions {
+    pub persistence: Option<Persistence>,
+    /// Delete the profile once it is deactivated.
+    pub volatile: bool,
+    /// Don't autoconnect the profile until it is activated manually.
+    pub block_autoconnect: bool,
+    /// Don't reapply the changes to devices the profile is active on.
+    pub no_reapply: bool,
+    /// The settings plugin to store the profile with, e.g. `keyfile` to move it away from
+    /// `ifcfg-rh`.
+    pub plugin: Option<String>,
+    /// Refuse the update unless the profile still has this
+    /// [`version_id`](SettingsConnectionProxy::version_id), so changes made in between aren't
+    /// overwritten.
+    pub version_id: Option<u64>,
+}
+
+impl UpdateOptions {
+    pub fn new() -> UpdateOptions {
+        UpdateOptions::default()
+    }
+
+    pub fn with_persistence(mut self, persistence: Persistence) -> Self {
+        self.persistence = Some(persistence);
+        self
+    }
+
+    pub fn volatile(mut self) -> Self {
+        self.volatile = true;
+        self
+    }
+
+    pub fn block_autoconnect(mut self) -> Self {
+        self.block_autoconnect = true;
+        self
+    }
+
+    pub fn no_reapply(mut self) -> Self {
+        self.no_reapply = true;
+        self
+    }
+
+    pub fn with_plugin(mut self, plugin: impl Into<String>) -> Self {
+        self.plugin = Some(plugin.into());
+        self
+    }
+
+    pub fn with_version_id(mut self, version_id: u64) -> Self {
+        self.version_id = Some(version_id);
+        self
+    }
+
+    /// The `flags` argument of `Update2`.
+    pub fn flags(&self) -> u32 {
+        let mut flags = NMSettingsUpdate2Flags::NONE as u32;
+        if let Some(persistence) = self.persistence {
+            flags |= persistence.flag() as u32;
+        }
+        for (set, flag) in [
+            (self.volatile, NMSettingsUpdate2Flags::VOLATILE),
+            (
+                self.block_autoconnect,
+                NMSettingsUpdate2Flags::BLOCK_AUTOCONNECT,
+            ),
+            (self.no_reapply, NMSettingsUpdate2Flags::NO_REAPPLY),
+        ] {
+            if set {
+                flags |= flag as u32;
+            }
+        }
+        flags
+    }
+
+    /// The `args` argument of `Update2`.
+    pub fn args(&self) -> HashMap<&str, Value<'_>> {
+        let mut args = HashMap::new();
+        if let Some(plugin) = &self.plugin {
+            args.insert("plugin", Value::from(plugin.as_str()));
+        }
+        if let Some(version_id) = self.version_id {
+            args.insert("version-id", Value::from(version_id));
+        }
+        args
+    }
+}
+
+/// Replaces the settings of a profile, returning the result dictionary of `Update2`.
+pub async fn update_connection(
+    connection: &SettingsConnectionProxy<'_>,
+    settings: &ConnectionSettings,
+    options: &UpdateOptions,
+) -> Result<HashMap<String, OwnedValue>> {
+    connection
+        .update2(settings.to_dbus(), options.flags(), options.args())
+        .await
+}
+
+/// Like [`update_connection`], but the secrets of the profile that `settings` leaves out are
+/// sent along unchanged, see [`ConnectionSettings::fill_secrets`]. Removing a secret this way
+/// isn't possible: read the profile with [`get_settings_with_secrets`], remove it and use
+/// [`update_connection`].
+pub async fn update_preserving_secrets(
+    connection: &SettingsConnectionProxy<'_>,
+    settings: &ConnectionSettings,
+    options: &UpdateOptions,
+) -> Result<HashMap<String, OwnedValue>> {
+    let mut settings = settings.clone();
+    settings.fill_secrets(get_connection_secrets(connection, &settings).await?);
+    update_connection(connection, &settings, options).await
+}
+
+/// The settings of a profile with its secrets.
+///
+/// Secrets that can't be read are left out, e.g. those owned by an agent, which NetworkManager
+/// doesn't keep, or all of them without the permission to read them.
+pub async fn get_settings_with_secrets(
+    connection: &SettingsConnectionProxy<'_>,
+) -> Result<ConnectionSettings> {
+    let mut settings = ConnectionSettings::from(connection.get_settings().await?);
+    let secrets = get_connection_secrets(connection, &settings).await?;
+    settings.merge_secrets(secrets);
+    Ok(settings)
+}
+
+/// The secrets of the secret-bearing settings of a profile that `settings` has, as returned
+/// by [`get_secrets`](SettingsConnectionProxy::get_secrets).
+///
+/// Settings without saved secrets, e.g. because an agent owns them, are skipped. Any other
+/// error, like not being allowed to read secrets, is returned: updating a profile without
+/// the secrets it has would delete them.
+pub async fn get_connection_secrets(
+    connection: &SettingsConnectionProxy<'_>,
+    settings: &ConnectionSettings,
+) -> Result<ConnectionSettings> {
+    let mut secrets = ConnectionSettings::new();
+    for setting in SECRET_SETTINGS {
+        if !settings.contains_setting(setting) {
+            continue;
+        }
+        match connection.get_secrets(setting).await {
+            Ok(setting_secrets) => secrets.merge(ConnectionSettings::from(setting_secrets)),
+            Err(zbus::Error::MethodError(name, ..))
+                if NO_SECRETS_ERRORS.contains(&name.as_str()) => {}
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(secrets)
+}
+
+/// What updating a profile with `settings` would change, compared with
+/// [`get_settings`](SettingsConnectionProxy::get_settings).
+///
+/// `GetSettings` leaves out secrets, so secrets in `settings` show up as added, and most
+/// properties that have their default value, so setting one of those to its default shows up
+/// as a change even though NetworkManager ends up with the same profile.
+pub async fn preview_update(
+    connection: &SettingsConnectionProxy<'_>,
+    settings: &ConnectionSettings,
+) -> Result<Vec<PropertyChange>> {
+    let current = ConnectionSettings::from(connection.get_settings().await?);
+    Ok(current.diff(settings))
+}
@@ -0,0 +1,559 @@
+//! Import and export of wg-quick `.conf` files.
+//!
+//! NetworkManager keeps WireGuard profiles in the `wireguard` setting, with the peers as an
+//! `aa{sv}` list in `wireguard.peers`, and the interface addresses and DNS servers in the
+//! `ipv4`/`ipv6` settings. [`WireGuardConfig`] is the typed form of a wg-quick file and
+//! converts from and to that layout.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::net::IpAddr;
+
+use zbus::zvariant::{OwnedValue, Value};
+
+use super::connection_settings::ConnectionSettings;
+use super::import::{ImportReport, ParseError, Untranslated, add_address, new_uuid, parse_address};
+use super::settings_connection::SettingsConnectionProxy;
+use super::update::get_connection_secrets;
+
+/// The `[Interface]` section.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WireGuardInterface {
+    pub private_key: Option<String>,
+    /// Addresses in CIDR notation.
+    pub addresses: Vec<String>,
+    /// DNS servers and search domains.
+    pub dns: Vec<String>,
+    pub mtu: Option<u32>,
+    pub listen_port: Option<u16>,
+    pub fw_mark: Option<u32>,
+    /// `off`, `auto` or a routing table number.
+    pub table: Option<String>,
+}
+
+/// A `[Peer]` section.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WireGuardPeer {
+    pub public_key: String,
+    pub preshared_key: Option<String>,
+    pub endpoint: Option<String>,
+    pub allowed_ips: Vec<String>,
+    pub persistent_keepalive: Option<u32>,
+}
+
+/// A wg-quick configuration for the interface `name`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WireGuardConfig {
+    pub name: String,
+    pub interface: WireGuardInterface,
+    pub peers: Vec<WireGuardPeer>,
+}
+
+enum Section {
+    None,
+    Interface,
+    Peer,
+}
+
+impl WireGuardConfig {
+    /// Parses a wg-quick file, `name` is the interface name, usually the file name without
+    /// `.conf`. Keys without a NetworkManager equivalent, like `PostUp`, are returned as
+    /// untranslated.
+    pub fn parse(
+        name: &str,
+        input: &str,
+    ) -> Result<(WireGuardConfig, Vec<Untranslated>), ParseError> {
+        let mut config = WireGuardConfig {
+            name: name.to_owned(),
+            ..WireGuardConfig::default()
+        };
+        let mut report = ImportReport::default();
+        let mut section = Section::None;
+        let mut peer_lines = Vec::new();
+
+        for (idx, line) in input.lines().enumerate() {
+            let line_number = idx + 1;
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            if line.starts_with('[') {
+                section = match line.to_ascii_lowercase().as_str() {
+                    "[interface]" => Section::Interface,
+                    "[peer]" => {
+                        config.peers.push(WireGuardPeer::default());
+                        peer_lines.push(line_number);
+                        Section::Peer
+                    }
+                    _ => return Err(ParseError::new(line_number, "unknown section")),
+                };
+                continue;
+            }
+
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(ParseError::new(line_number, "expected key = value"));
+            };
+            let key = key.trim();
+            let value = value.trim();
+            let invalid = || ParseError::new(line_number, format!("invalid value for {key}"));
+
+            match (&section, key.to_ascii_lowercase().as_str()) {
+                (Section::None, _) => {
+                    return Err(ParseError::new(line_number, "key outside of a section"));
+                }
+                (Section::Interface, "privatekey") => {
+                    config.interface.private_key = Some(value.to_owned())
+                }
+                (Section::Interface, "address") => {
+                    for address in list(value) {
+                        match parse_address(&address) {
+                            Some(_) => config.interface.addresses.push(address),
+                            None => report.skip(line_number, key, "invalid address"),
+                        }
+                    }
+                }
+                (Section::Interface, "dns") => config.interface.dns.extend(list(value)),
+                (Section::Interface, "mtu") => {
+                    config.interface.mtu = Some(value.parse().map_err(|_| invalid())?)
+                }
+                (Section::Interface, "listenport") => {
+                    config.interface.listen_port = Some(value.parse().map_err(|_| invalid())?)
+                }
+                (Section::Interface, "fwmark") => {
+                    config.interface.fw_mark = match value {
+                        "off" => None,
+                        _ => Some(parse_number(value).ok_or_else(invalid)?),
+                    }
+                }
+                (Section::Interface, "table") => match value {
+                    "off" | "auto" => config.interface.table = Some(value.to_owned()),
+                    _ if parse_number(value).is_some() => {
+                        config.interface.table = Some(value.to_owned())
+                    }
+                    _ => report.skip(line_number, key, "named routing tables are not supported"),
+                },
+                (Section::Peer, _) => {
+                    let peer = config.peers.last_mut().ok_or_else(invalid)?;
+                    match key.to_ascii_lowercase().as_str() {
+                        "publickey" => peer.public_key = value.to_owned(),
+                        "presharedkey" => peer.preshared_key = Some(value.to_owned()),
+                        "endpoint" => peer.endpoint = Some(value.to_owned()),
+                        "allowedips" => peer.allowed_ips.extend(list(value)),
+                        "persistentkeepalive" => {
+                            peer.persistent_keepalive = match value {
+                                "off" => None,
+                                _ => Some(value.parse().map_err(|_| invalid())?),
+                            }
+                        }
+                        _ => report.skip(line_number, key, "no NetworkManager equivalent"),
+                    }
+                }
+                (Section::Interface, _) => {
+                    report.skip(line_number, key, "no NetworkManager equivalent")
+                }
+            }
+        }
+
+        if let Some(idx) = config
+            .peers
+            .iter()
+            .position(|peer| peer.public_key.is_empty())
+        {
+            return Err(ParseError::new(
+                peer_lines[idx],
+                format!("peer {} has no PublicKey", idx + 1),
+            ));
+        }
+
+        Ok((config, report.untranslated))
+    }
+
+    /// Builds the NetworkManager profile, including the private and preshared keys.
+    pub fn to_settings(&self) -> ConnectionSettings {
+        let mut settings = ConnectionSettings::new()
+            .with("connection", "id", self.name.clone())
+            .with("connection", "uuid", new_uuid())
+            .with("connection", "type", "wireguard")
+            .with("connection", "interface-name", self.name.clone());
+        settings.add_setting("wireguard");
+
+        let interface = &self.interface;
+        if let Some(private_key) = &interface.private_key {
+            settings.set("wireguard", "private-key", private_key.clone());
+        }
+        if let Some(listen_port) = interface.listen_port {
+            settings.set("wireguard", "listen-port", u32::from(listen_port));
+        }
+        if let Some(fw_mark) = interface.fw_mark {
+            settings.set("wireguard", "fwmark", fw_mark);
+        }
+        if let Some(mtu) = interface.mtu {
+            settings.set("wireguard", "mtu", mtu);
+        }
+
+        let peers: Vec<HashMap<String, Value<'static>>> =
+            self.peers.iter().map(peer_to_dbus).collect();
+        settings.set("wireguard", "peers", peers);
+
+        // Invalid addresses are reported by `parse`
+        for address in &interface.addresses {
+            add_address(&mut settings, address);
+        }
+
+        let mut ipv4_dns = Vec::new();
+        let mut ipv6_dns = Vec::new();
+        let mut dns_search = Vec::new();
+        for dns in &interface.dns {
+            match dns.parse::<IpAddr>() {
+                Ok(IpAddr::V4(ip)) => ipv4_dns.push(u32::from_ne_bytes(ip.octets())),
+                Ok(IpAddr::V6(ip)) => ipv6_dns.push(ip.octets().to_vec()),
+                Err(_) => dns_search.push(dns.clone()),
+            }
+        }
+
+        for family in ["ipv4", "ipv6"] {
+            let method = match settings.get(family, "address-data") {
+                Some(_) => "manual",
+                None => "disabled",
+            };
+            settings.set(family, "method", method);
+        }
+        if !ipv4_dns.is_empty() {
+            settings.set("ipv4", "dns", ipv4_dns);
+        }
+        if !ipv6_dns.is_empty() {
+            settings.set("ipv6", "dns", ipv6_dns);
+        }
+        if !dns_search.is_empty() {
+            settings.set("ipv4", "dns-search", dns_search);
+        }
+
+        match interface.table.as_deref() {
+            None | Some("auto") => {}
+            Some("off") => settings.set("wireguard", "peer-routes", false),
+            Some(table) => {
+                if let Some(table) = parse_number(table) {
+                    settings.set("ipv4", "route-table", table);
+                    settings.set("ipv6", "route-table", table);
+                }
+            }
+        }
+
+        settings
+    }
+
+    /// Reads a configuration back from NetworkManager settings.
+    ///
+    /// `settings` has to include the `wireguard` secrets for the keys to be exported, see
+    /// [`WireGuardConfig::from_connection`].
+    pub fn from_settings(settings: &ConnectionSettings) -> Option<WireGuardConfig> {
+        if settings.connection_type() != Some("wireguard") {
+            return None;
+        }
+
+        let mut interface = WireGuardInterface {
+            private_key: settings
+                .get_str("wireguard", "private-key")
+                .map(ToOwned::to_owned),
+            mtu: settings.get_u32("wireguard", "mtu").filter(|&mtu| mtu != 0),
+            listen_port: settings
+                .get_u32("wireguard", "listen-port")
+                .filter(|&port| port != 0)
+                .and_then(|port| u16::try_from(port).ok()),
+            fw_mark: settings
+                .get_u32("wireguard", "fwmark")
+                .filter(|&fw_mark| fw_mark != 0),
+            ..WireGuardInterface::default()
+        };
+
+        for family in ["ipv4", "ipv6"] {
+            for address in settings
+                .get_dicts(family, "address-data")
+                .unwrap_or_default()
+            {
+                let Some(ip) = dict_str(&address, "address") else {
+                    continue;
+                };
+                let prefix = dict_u32(&address, "prefix").unwrap_or_default();
+                interface.addresses.push(format!("{ip}/{prefix}"));
+            }
+        }
+
+        if let Some(servers) = settings
+            .get("ipv4", "dns")
+            .and_then(|dns| dns.try_clone().ok()?.downcast::<Vec<u32>>().ok())
+        {
+            interface.dns.extend(
+                servers
+                    .into_iter()
+                    .map(|server| std::net::Ipv4Addr::from(server.to_ne_bytes()).to_string()),
+            );
+        }
+        if let Some(servers) = settings
+            .get("ipv6", "dns")
+            .and_then(|dns| dns.try_clone().ok()?.downcast::<Vec<Vec<u8>>>().ok())
+        {
+            interface
+                .dns
+                .extend(servers.into_iter().filter_map(|server| {
+                    let octets: [u8; 16] = server.try_into().ok()?;
+                    Some(std::net::Ipv6Addr::from(octets).to_string())
+                }));
+        }
+        interface.dns.extend(
+            settings
+                .get_strings("ipv4", "dns-search")
+                .unwrap_or_default(),
+        );
+
+        if settings.get_bool("wireguard", "peer-routes") == Some(false) {
+            interface.table = Some("off".to_owned());
+        } else if let Some(table) = settings
+            .get_u32("ipv4", "route-table")
+            .filter(|&table| table != 0)
+        {
+            interface.table = Some(table.to_string());
+        }
+
+        let peers = settings
+            .get_dicts("wireguard", "peers")
+            .unwrap_or_default()
+            .iter()
+            .map(|peer| WireGuardPeer {
+                public_key: dict_str(peer, "public-key").unwrap_or_default(),
+                preshared_key: dict_str(peer, "preshared-key"),
+                endpoint: dict_str(peer, "endpoint"),
+                allowed_ips: peer
+                    .get("allowed-ips")
+                    .and_then(|ips| Vec::<String>::try_from(ips.try_clone().ok()?).ok())
+                    .unwrap_or_default(),
+                persistent_keepalive: dict_u32(peer, "persistent-keepalive")
+                    .filter(|&keepalive| keepalive != 0),
+            })
+            .collect();
+
+        Some(WireGuardConfig {
+            name: settings
+                .get_str("connection", "interface-name")
+                .or(settings.id())
+                .unwrap_or_default()
+                .to_owned(),
+            interface,
+            peers,
+        })
+    }
+
+    /// Reads the configuration of a saved WireGuard profile, including its secrets.
+    pub async fn from_connection(
+        connection: &SettingsConnectionProxy<'_>,
+    ) -> zbus::Result<WireGuardConfig> {
+        let mut settings = ConnectionSettings::from(connection.get_settings().await?);
+        let secrets = get_connection_secrets(connection, &settings).await?;
+        settings.merge_secrets(secrets);
+
+        WireGuardConfig::from_settings(&settings)
+            .ok_or_else(|| zbus::Error::Failure("not a WireGuard connection".to_owned()))
+    }
+}
+
+impl fmt::Display for WireGuardConfig {
+    /// Formats the configuration as a wg-quick file.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let interface = &self.interface;
+        writeln!(f, "[Interface]")?;
+        if let Some(private_key) = &interface.private_key {
+            writeln!(f, "PrivateKey = {private_key}")?;
+        }
+        if !interface.addresses.is_empty() {
+            writeln!(f, "Address = {}", interface.addresses.join(", "))?;
+        }
+        if !interface.dns.is_empty() {
+            writeln!(f, "DNS = {}", interface.dns.join(", "))?;
+        }
+        if let Some(mtu) = interface.mtu {
+            writeln!(f, "MTU = {mtu}")?;
+        }
+        if let Some(listen_port) = interface.listen_port {
+            writeln!(f, "ListenPort = {listen_port}")?;
+        }
+        if let Some(fw_mark) = interface.fw_mark {
+            writeln!(f, "FwMark = {fw_mark:#x}")?;
+        }
+        if let Some(table) = &interface.table {
+            writeln!(f, "Table = {table}")?;
+        }
+
+        for peer in &self.peers {
+            writeln!(f)?;
+            writeln!(f, "[Peer]")?;
+            writeln!(f, "PublicKey = {}", peer.public_key)?;
+            if let Some(preshared_key) = &peer.preshared_key {
+                writeln!(f, "PresharedKey = {preshared_key}")?;
+            }
+            if let Some(endpoint) = &peer.endpoint {
+                writeln!(f, "Endpoint = {endpoint}")?;
+            }
+            if !peer.allowed_ips.is_empty() {
+                writeln!(f, "AllowedIPs = {}", peer.allowed_ips.join(", "))?;
+            }
+            if let Some(persistent_keepalive) = peer.persistent_keepalive {
+                writeln!(f, "PersistentKeepalive = {persistent_keepalive}")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Parses a wg-quick file into a WireGuard profile.
+pub fn import_wg_quick(name: &str, input: &str) -> Result<ImportReport, ParseError> {
+    let (config, untranslated) = WireGuardConfig::parse(name, input)?;
+    Ok(ImportReport {
+        connections: vec![config.to_settings()],
+        untranslated,
+    })
+}
+
+fn peer_to_dbus(peer: &WireGuardPeer) -> HashMap<String, Value<'static>> {
+    let mut dict = HashMap::new();
+    dict.insert(
+        "public-key".to_owned(),
+        Value::from(peer.public_key.clone()),
+    );
+    if let Some(preshared_key) = &peer.preshared_key {
+        dict.insert(
+            "preshared-key".to_owned(),
+            Value::from(preshared_key.clone()),
+        );
+        // Stored with the profile rather than asked from a secret agent
+        dict.insert("preshared-key-flags".to_owned(), Value::from(0u32));
+    }
+    if let Some(endpoint) = &peer.endpoint {
+        dict.insert("endpoint".to_owned(), Value::from(endpoint.clone()));
+    }
+    dict.insert(
+        "allowed-ips".to_owned(),
+        Value::from(peer.allowed_ips.clone()),
+    );
+    if let Some(persistent_keepalive) = peer.persistent_keepalive {
+        dict.insert(
+            "persistent-keepalive".to_owned(),
+            Value::from(persistent_keepalive),
+        );
+    }
+    dict
+}
+
+/// A string entry of a dictionary such as a `wireguard.peers` element.
+pub(crate) fn dict_str(dict: &HashMap<String, OwnedValue>, key: &str) -> Option<String> {
+    dict.get(key)?
+        .downcast_ref::<&str>()
+        .ok()
+        .map(ToOwned::to_owned)
+}
+
+/// A `u32` entry of a dictionary such as an `ipv4.address-data` element.
+pub(crate) fn dict_u32(dict: &HashMap<String, OwnedValue>, key: &str) -> Option<u32> {
+    dict.get(key)?.downcast_ref().ok()
+}
+
+fn list(value: &str) -> impl Iterator<Item = String> + '_ {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(ToOwned::to_owned)
+}
+
+fn parse_number(value: &str) -> Option<u32> {
+    match value.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => value.parse().ok(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = "\
+[Interface]
+PrivateKey = yAnz5TF+lXXJte14tji3zlMNq+hd2rYUIgJBgB3fBmk=
+Address = 10.0.0.2/24, fd00::2/64, 10.0.0.300/24, 10.0.1.2/40
+DNS = 10.0.0.1, example.com
+ListenPort = 51820
+PostUp = iptables -A FORWARD -i wg0 -j ACCEPT
+
+[Peer]
+PublicKey = xTIBA5rboUvnH4htodjb6e697QjLERt1NAB4mZqp8Dg=
+Endpoint = vpn.example.com:51820
+AllowedIPs = 0.0.0.0/0, ::/0
+PersistentKeepalive = 25
+";
+
+    #[test]
+    fn parses_config() {
+        let (config, untranslated) = WireGuardConfig::parse("wg0", CONFIG).unwrap();
+        assert_eq!(config.name, "wg0");
+        assert_eq!(config.interface.addresses, ["10.0.0.2/24", "fd00::2/64"]);
+        assert_eq!(config.interface.dns, ["10.0.0.1", "example.com"]);
+        assert_eq!(config.interface.listen_port, Some(51820));
+        assert_eq!(config.peers.len(), 1);
+        assert_eq!(config.peers[0].allowed_ips, ["0.0.0.0/0", "::/0"]);
+        assert_eq!(config.peers[0].persistent_keepalive, Some(25));
+
+        let skipped: Vec<(usize, &str, &str)> = untranslated
+            .iter()
+            .map(|untranslated| {
+                (
+                    untranslated.line,
+                    untranslated.directive.as_str(),
+                    untranslated.reason.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            skipped,
+            [
+                (3, "Address", "invalid address"),
+                (3, "Address", "invalid address"),
+                (6, "PostUp", "no NetworkManager equivalent"),
+            ]
+        );
+    }
+
+    #[test]
+    fn translates_to_settings() {
+        let (config, _) = WireGuardConfig::parse("wg0", CONFIG).unwrap();
+        let settings = config.to_settings();
+        assert_eq!(settings.connection_type(), Some("wireguard"));
+        assert_eq!(settings.get_str("ipv4", "method"), Some("manual"));
+        assert_eq!(settings.get_str("ipv6", "method"), Some("manual"));
+        assert_eq!(settings.get_u32("wireguard", "listen-port"), Some(51820));
+        assert_eq!(
+            settings.get_strings("ipv4", "dns-search"),
+            Some(vec!["example.com".to_owned()])
+        );
+    }
+
+    #[test]
+    fn round_trips_through_settings() {
+        let (config, _) = WireGuardConfig::parse("wg0", CONFIG).unwrap();
+        let exported = WireGuardConfig::from_settings(&config.to_settings()).unwrap();
+        assert_eq!(exported, config);
+
+        let (reparsed, untranslated) =
+            WireGuardConfig::parse("wg0", &exported.to_string()).unwrap();
+        assert_eq!(reparsed, config);
+        assert!(untranslated.is_empty());
+    }
+
+    #[test]
+    fn rejects_peer_without_public_key() {
+        let err =
+            WireGuardConfig::parse("wg0", "[Interface]\n[Peer]\nEndpoint = a:1\n").unwrap_err();
+        assert_eq!(err.line, 2);
+        assert_eq!(err.message, "peer 1 has no PublicKey");
+    }
+}
@@ -0,0 +1,423 @@
+//! `WIFI:` URIs as used by Wi-Fi QR codes.
+//!
+//! The format is `WIFI:T:<type>;S:<ssid>;P:<password>;H:<hidden>;R:<transition disable>;;`
+//! where `;`, `,`, `:`, `\` and `"` inside values are escaped with a backslash.
+
+use std::fmt;
+use std::str::FromStr;
+
+use super::connection_settings::ConnectionSettings;
+use super::settings_connection::SettingsConnectionProxy;
+use super::update::get_connection_secrets;
+
+const SCHEME: &str = "WIFI:";
+const SPECIAL_CHARACTERS: &[char] = &['\\', ';', ',', ':', '"'];
+
+/// Authentication type of a [`WifiUri`], the `T:` field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WifiUriSecurity {
+    /// `T:nopass` or no `T:` field.
+    Open,
+    /// `T:WEP`
+    Wep,
+    /// `T:WPA`, WPA/WPA2 personal.
+    Wpa,
+    /// `T:SAE`, WPA3 personal.
+    Sae,
+}
+
+impl WifiUriSecurity {
+    fn as_str(&self) -> &'static str {
+        match self {
+            WifiUriSecurity::Open => "nopass",
+            WifiUriSecurity::Wep => "WEP",
+            WifiUriSecurity::Wpa => "WPA",
+            WifiUriSecurity::Sae => "SAE",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WifiUriError {
+    /// The string doesn't start with `WIFI:`.
+    MissingScheme,
+    /// There is no `S:` field.
+    MissingSsid,
+    /// A field isn't of the form `<key>:<value>`.
+    InvalidField(String),
+    /// The `T:` field has an unknown value.
+    UnknownSecurity(String),
+    /// The `H:` or `R:` field has an invalid value.
+    InvalidValue { field: &'static str, value: String },
+    /// The settings aren't those of a Wi-Fi connection.
+    NotWireless,
+    /// The SSID isn't valid UTF-8 and can't be put in a URI.
+    InvalidSsid,
+    /// The key management can't be expressed as a `WIFI:` URI, e.g. `wpa-eap`.
+    UnsupportedKeyManagement(String),
+}
+
+impl fmt::Display for WifiUriError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WifiUriError::MissingScheme => write!(f, "missing WIFI: scheme"),
+            WifiUriError::MissingSsid => write!(f, "missing S: field"),
+            WifiUriError::InvalidField(field) => write!(f, "invalid field '{field}'"),
+            WifiUriError::UnknownSecurity(security) => {
+                write!(f, "unknown authentication type '{security}'")
+            }
+            WifiUriError::InvalidValue { field, value } => {
+                write!(f, "invalid value '{value}' for field {field}")
+            }
+            WifiUriError::NotWireless => write!(f, "not a Wi-Fi connection"),
+            WifiUriError::InvalidSsid => write!(f, "SSID is not valid UTF-8"),
+            WifiUriError::UnsupportedKeyManagement(key_mgmt) => {
+                write!(f, "key management '{key_mgmt}' is not supported")
+            }
+        }
+    }
+}
+
+impl std::error::Error for WifiUriError {}
+
+/// A parsed `WIFI:` URI.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WifiUri {
+    pub ssid: String,
+    pub security: WifiUriSecurity,
+    pub password: Option<String>,
+    /// `H:true`, the network doesn't broadcast its SSID.
+    pub hidden: bool,
+    /// `R:` with bit 0 set, the network has disabled WPA2 transition mode.
+    pub transition_disable: bool,
+}
+
+impl WifiUri {
+    /// Builds connection settings suitable for
+    /// [`SettingsProxy::add_connection`](crate::SettingsProxy::add_connection).
+    pub fn to_settings(&self) -> ConnectionSettings {
+        let mut settings = ConnectionSettings::new()
+            .with("connection", "id", self.ssid.clone())
+            .with("connection", "type", "802-11-wireless")
+            .with("802-11-wireless", "ssid", self.ssid.as_bytes().to_vec())
+            .with("802-11-wireless", "mode", "infrastructure")
+            .with("ipv4", "method", "auto")
+            .with("ipv6", "method", "auto");
+
+        if self.hidden {
+            settings.set("802-11-wireless", "hidden", true);
+        }
+
+        let password = self.password.clone().unwrap_or_default();
+        match self.security {
+            WifiUriSecurity::Open => return settings,
+            WifiUriSecurity::Wep => {
+                settings.set("802-11-wireless-security", "key-mgmt", "none");
+                settings.set("802-11-wireless-security", "auth-alg", "open");
+                settings.set("802-11-wireless-security", "wep-key-type", 1u32);
+                settings.set("802-11-wireless-security", "wep-key0", password);
+            }
+            WifiUriSecurity::Wpa if !self.transition_disable => {
+                settings.set("802-11-wireless-security", "key-mgmt", "wpa-psk");
+                settings.set("802-11-wireless-security", "psk", password);
+            }
+            WifiUriSecurity::Wpa | WifiUriSecurity::Sae => {
+                settings.set("802-11-wireless-security", "key-mgmt", "sae");
+                settings.set("802-11-wireless-security", "psk", password);
+            }
+        }
+        settings.set("802-11-wireless", "security", "802-11-wireless-security");
+
+        settings
+    }
+
+    /// Builds a URI from connection settings that include the `802-11-wireless-security`
+    /// secrets.
+    pub fn from_settings(settings: &ConnectionSettings) -> Result<WifiUri, WifiUriError> {
+        let ssid = settings
+            .get_bytes("802-11-wireless", "ssid")
+            .ok_or(WifiUriError::NotWireless)?;
+        let ssid = String::from_utf8(ssid).map_err(|_| WifiUriError::InvalidSsid)?;
+        let hidden = settings
+            .get_bool("802-11-wireless", "hidden")
+            .unwrap_or(false);
+
+        let key_mgmt = settings.get_str("802-11-wireless-security", "key-mgmt");
+        let (security, password, transition_disable) = match key_mgmt {
+            None | Some("owe") => (WifiUriSecurity::Open, None, false),
+            Some("none") => {
+                let index = settings
+                    .get_u32("802-11-wireless-security", "wep-tx-keyidx")
+                    .unwrap_or(0);
+                let key = settings.get_str("802-11-wireless-security", &format!("wep-key{index}"));
+                (WifiUriSecurity::Wep, key, false)
+            }
+            Some("wpa-psk") => (
+                WifiUriSecurity::Wpa,
+                settings.get_str("802-11-wireless-security", "psk"),
+                false,
+            ),
+            Some("sae") => (
+                WifiUriSecurity::Sae,
+                settings.get_str("802-11-wireless-security", "psk"),
+                false,
+            ),
+            Some(key_mgmt) => {
+                return Err(WifiUriError::UnsupportedKeyManagement(key_mgmt.to_owned()));
+            }
+        };
+
+        Ok(WifiUri {
+            ssid,
+            security,
+            password: password.map(ToOwned::to_owned),
+            hidden,
+            transition_disable,
+        })
+    }
+
+    /// Builds a URI for a saved connection, fetching its secrets.
+    pub async fn from_connection(
+        connection: &SettingsConnectionProxy<'_>,
+    ) -> zbus::Result<WifiUri> {
+        let mut settings = ConnectionSettings::from(connection.get_settings().await?);
+        let secrets = get_connection_secrets(connection, &settings).await?;
+        settings.merge_secrets(secrets);
+
+        WifiUri::from_settings(&settings).map_err(|err| zbus::Error::Failure(err.to_string()))
+    }
+}
+
+impl FromStr for WifiUri {
+    type Err = WifiUriError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some(fields) = s
+            .get(..SCHEME.len())
+            .filter(|scheme| scheme.eq_ignore_ascii_case(SCHEME))
+            .map(|_| &s[SCHEME.len()..])
+        else {
+            return Err(WifiUriError::MissingScheme);
+        };
+
+        let mut ssid = None;
+        let mut security = WifiUriSecurity::Open;
+        let mut password = None;
+        let mut hidden = false;
+        let mut transition_disable = false;
+
+        for field in split_fields(fields) {
+            let Some((key, value)) = field.split_once(':') else {
+                return Err(WifiUriError::InvalidField(field));
+            };
+            let value = unescape(value);
+            match key {
+                "S" => ssid = Some(value),
+                "T" => {
+                    security = match value.to_ascii_uppercase().as_str() {
+                        "" | "NOPASS" => WifiUriSecurity::Open,
+                        "WEP" => WifiUriSecurity::Wep,
+                        "WPA" | "WPA2" => WifiUriSecurity::Wpa,
+                        "SAE" | "WPA3" => WifiUriSecurity::Sae,
+                        _ => return Err(WifiUriError::UnknownSecurity(value)),
+                    }
+                }
+                "P" => password = Some(value),
+                "H" => {
+                    hidden = match value.to_ascii_lowercase().as_str() {
+                        "true" => true,
+                        "false" | "" => false,
+                        _ => return Err(WifiUriError::InvalidValue { field: "H", value }),
+                    }
+                }
+                "R" => {
+                    let flags = u32::from_str_radix(&value, 16)
+                        .map_err(|_| WifiUriError::InvalidValue { field: "R", value })?;
+                    transition_disable = flags & 0x1 != 0;
+                }
+                // Unknown fields, e.g. the enterprise E:/I: fields, are ignored
+                _ => {}
+            }
+        }
+
+        Ok(WifiUri {
+            ssid: ssid.ok_or(WifiUriError::MissingSsid)?,
+            security,
+            password: password.filter(|_| security != WifiUriSecurity::Open),
+            hidden,
+            transition_disable,
+        })
+    }
+}
+
+impl fmt::Display for WifiUri {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{SCHEME}T:{};S:{};",
+            self.security.as_str(),
+            escape(&self.ssid)
+        )?;
+        if let Some(password) = &self.password {
+            write!(f, "P:{};", escape(password))?;
+        }
+        if self.hidden {
+            write!(f, "H:true;")?;
+        }
+        if self.transition_disable {
+            write!(f, "R:1;")?;
+        }
+        write!(f, ";")
+    }
+}
+
+/// Splits on unescaped `;`, keeping the escapes in the returned fields.
+fn split_fields(fields: &str) -> Vec<String> {
+    let mut result = Vec::new();
+    let mut current = String::new();
+    let mut chars = fields.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                current.push(c);
+                if let Some(escaped) = chars.next() {
+                    current.push(escaped);
+                }
+            }
+            ';' => {
+                if !current.is_empty() {
+                    result.push(std::mem::take(&mut current));
+                }
+            }
+            _ => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        result.push(current);
+    }
+    result
+}
+
+fn unescape(value: &str) -> String {
+    // Some generators quote values instead of escaping them
+    let value = value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .unwrap_or(value);
+
+    let mut result = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => result.extend(chars.next()),
+            _ => result.push(c),
+        }
+    }
+    result
+}
+
+fn escape(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    for c in value.chars() {
+        if SPECIAL_CHARACTERS.contains(&c) {
+            result.push('\\');
+        }
+        result.push(c);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unescapes_special_characters() {
+        let uri: WifiUri = r#"WIFI:T:WPA;S:my\;net\:work;P:back\\slash\,quote\";;"#
+            .parse()
+            .unwrap();
+        assert_eq!(uri.ssid, "my;net:work");
+        assert_eq!(uri.password.as_deref(), Some(r#"back\slash,quote""#));
+        assert_eq!(uri.security, WifiUriSecurity::Wpa);
+    }
+
+    #[test]
+    fn parses_hidden_sae_and_transition_disable() {
+        let uri: WifiUri = "wifi:T:SAE;S:home;P:secret;H:true;R:1;;".parse().unwrap();
+        assert_eq!(
+            uri,
+            WifiUri {
+                ssid: "home".to_owned(),
+                security: WifiUriSecurity::Sae,
+                password: Some("secret".to_owned()),
+                hidden: true,
+                transition_disable: true,
+            }
+        );
+
+        // Only bit 0 of R: is transition disable
+        let uri: WifiUri = "WIFI:T:WPA;S:home;P:secret;R:2;;".parse().unwrap();
+        assert!(!uri.transition_disable);
+
+        assert_eq!(
+            "WIFI:S:home;H:maybe;;".parse::<WifiUri>(),
+            Err(WifiUriError::InvalidValue {
+                field: "H",
+                value: "maybe".to_owned()
+            })
+        );
+        assert_eq!(
+            "WIFI:S:home;R:x;;".parse::<WifiUri>(),
+            Err(WifiUriError::InvalidValue {
+                field: "R",
+                value: "x".to_owned()
+            })
+        );
+    }
+
+    #[test]
+    fn transition_disable_selects_sae() {
+        let uri: WifiUri = "WIFI:T:WPA;S:home;P:secret;R:1;;".parse().unwrap();
+        let settings = uri.to_settings();
+        assert_eq!(
+            settings.get_str("802-11-wireless-security", "key-mgmt"),
+            Some("sae")
+        );
+    }
+
+    #[test]
+    fn round_trips() {
+        let uri = WifiUri {
+            ssid: r#"a;b:c\d,e"f"#.to_owned(),
+            security: WifiUriSecurity::Wpa,
+            password: Some(r#"p;a:s\s"#.to_owned()),
+            hidden: true,
+            transition_disable: false,
+        };
+        let formatted = uri.to_string();
+        assert_eq!(
+            formatted,
+            r#"WIFI:T:WPA;S:a\;b\:c\\d\,e\"f;P:p\;a\:s\\s;H:true;;"#
+        );
+        assert_eq!(formatted.parse::<WifiUri>(), Ok(uri.clone()));
+
+        let settings = uri.to_settings();
+        assert_eq!(WifiUri::from_settings(&settings), Ok(uri));
+    }
+
+    #[test]
+    fn rejects_invalid_uris() {
+        assert_eq!(
+            "S:home;;".parse::<WifiUri>(),
+            Err(WifiUriError::MissingScheme)
+        );
+        assert_eq!(
+            "WIFI:T:WPA;;".parse::<WifiUri>(),
+            Err(WifiUriError::MissingSsid)
+        );
+        assert_eq!(
+            "WIFI:T:EAP;S:home;;".parse::<WifiUri>(),
+            Err(WifiUriError::UnknownSecurity("EAP".to_owned()))
+        );
+    }
+}
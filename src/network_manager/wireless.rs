@@ -12,6 +12,9 @@
 //! [Writing a client proxy]: https://dbus2.github.io/zbus/client.html
 //! [D-Bus standard interfaces]: https://dbus.freedesktop.org/doc/dbus-specification.html#standard-interfaces,
 
+use std::collections::HashMap;
+
+use zbus::zvariant::Value;
 use zbus::{Connection, Result, proxy};
 
 impl WirelessProxy<'_> {
@@ -24,6 +27,28 @@ impl WirelessProxy<'_> {
             .build()
             .await
     }
+
+    /// RequestScan with typed options
+    pub async fn request_scan_with(&self, options: &ScanOptions) -> Result<()> {
+        self.request_scan(options.to_dbus()).await
+    }
+}
+
+/// Options for [`WirelessProxy::request_scan`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ScanOptions {
+    /// SSIDs to actively probe for, needed to find hidden networks.
+    pub ssids: Vec<Vec<u8>>,
+}
+
+impl ScanOptions {
+    pub fn to_dbus(&self) -> HashMap<&str, Value<'_>> {
+        let mut options = HashMap::new();
+        if !self.ssids.is_empty() {
+            options.insert("ssids", Value::from(self.ssids.clone()));
+        }
+        options
+    }
 }
 
 #[proxy(
@@ -0,0 +1,515 @@
+//! Import of `wpa_supplicant.conf` `network={...}` blocks.
+//!
+//! Each block becomes an `802-11-wireless` profile. Global directives and network directives
+//! without a NetworkManager equivalent are listed in [`ImportReport::untranslated`].
+
+use super::connection_settings::ConnectionSettings;
+use super::import::{ImportReport, ParseError, decode_hex, file_blob, new_uuid, parse_mac};
+
+const WIRELESS: &str = "802-11-wireless";
+const SECURITY: &str = "802-11-wireless-security";
+const IEEE8021X: &str = "802-1x";
+
+/// Parses a `wpa_supplicant.conf` file.
+pub fn import_wpa_supplicant(input: &str) -> Result<ImportReport, ParseError> {
+    let mut report = ImportReport::default();
+    let mut network: Option<Vec<Directive>> = None;
+    let mut network_start = 0;
+
+    for (idx, line) in input.lines().enumerate() {
+        let line_number = idx + 1;
+        let line = strip_comment(line).trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        if line == "}" {
+            let Some(directives) = network.take() else {
+                return Err(ParseError::new(line_number, "unexpected '}'"));
+            };
+            import_network(network_start, &directives, &mut report);
+        } else if let Some(directives) = &mut network {
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(ParseError::new(line_number, "expected key=value"));
+            };
+            directives.push(Directive {
+                line: line_number,
+                key: key.trim().to_owned(),
+                value: value.trim().to_owned(),
+            });
+        } else if line.replace(' ', "") == "network={" {
+            network = Some(Vec::new());
+            network_start = line_number;
+        } else {
+            let key = line.split_once('=').map_or(line, |(key, _)| key).trim();
+            report.skip(line_number, key, "global directive");
+        }
+    }
+
+    if network.is_some() {
+        return Err(ParseError::new(network_start, "unterminated network block"));
+    }
+
+    Ok(report)
+}
+
+struct Directive {
+    line: usize,
+    key: String,
+    value: String,
+}
+
+impl Directive {
+    /// The value without quotes if it was quoted.
+    fn quoted(&self) -> Option<&str> {
+        self.value
+            .strip_prefix('"')
+            .and_then(|value| value.strip_suffix('"'))
+    }
+
+    /// The value as a string, quoted or not.
+    fn string(&self) -> &str {
+        self.quoted().unwrap_or(&self.value)
+    }
+
+    /// A quoted string or hex encoded bytes, as used for `ssid`.
+    fn bytes(&self) -> Option<Vec<u8>> {
+        match self.quoted() {
+            Some(value) => Some(value.as_bytes().to_vec()),
+            None => decode_hex(&self.value),
+        }
+    }
+
+    fn flag(&self) -> bool {
+        self.value == "1"
+    }
+
+    fn list(&self) -> Vec<String> {
+        self.string()
+            .split_whitespace()
+            .map(str::to_lowercase)
+            .collect()
+    }
+}
+
+/// NetworkManager's range for `connection.autoconnect-priority`.
+const PRIORITY_RANGE: std::ops::RangeInclusive<i32> = -999..=999;
+
+/// Translates a network block starting at line `start`, reporting it if it has no SSID.
+fn import_network(start: usize, directives: &[Directive], report: &mut ImportReport) {
+    let mut settings = ConnectionSettings::new()
+        .with("connection", "uuid", new_uuid())
+        .with("connection", "type", WIRELESS)
+        .with(WIRELESS, "mode", "infrastructure")
+        .with("ipv4", "method", "auto")
+        .with("ipv6", "method", "auto");
+
+    let mut ssid = None;
+    let mut id = None;
+    let mut key_mgmt = None;
+    let mut has_psk = false;
+    let mut has_wep = false;
+    let mut has_eap = false;
+
+    for directive in directives {
+        let key = directive.key.as_str();
+        match key {
+            "ssid" => match directive.bytes() {
+                Some(bytes) => {
+                    settings.set(WIRELESS, "ssid", bytes.clone());
+                    ssid = Some(bytes);
+                }
+                None => report.skip(directive.line, key, "invalid SSID"),
+            },
+            "id_str" => id = Some(directive.string().to_owned()),
+            "bssid" => match parse_mac(&directive.value) {
+                Some(bssid) => settings.set(WIRELESS, "bssid", bssid),
+                None => report.skip(directive.line, key, "invalid BSSID"),
+            },
+            "scan_ssid" => settings.set(WIRELESS, "hidden", directive.flag()),
+            "disabled" => settings.set("connection", "autoconnect", !directive.flag()),
+            "priority" => match directive.value.parse::<i32>() {
+                Ok(priority) => settings.set(
+                    "connection",
+                    "autoconnect-priority",
+                    priority.clamp(*PRIORITY_RANGE.start(), *PRIORITY_RANGE.end()),
+                ),
+                Err(_) => report.skip(directive.line, key, "invalid priority"),
+            },
+            "mode" => match directive.value.as_str() {
+                "0" => settings.set(WIRELESS, "mode", "infrastructure"),
+                "1" => settings.set(WIRELESS, "mode", "adhoc"),
+                "2" => settings.set(WIRELESS, "mode", "ap"),
+                _ => report.skip(directive.line, key, "unsupported mode"),
+            },
+            "key_mgmt" => key_mgmt = Some((directive.line, directive.list())),
+            "psk" => {
+                // A quoted passphrase, or the raw 256-bit key as 64 hex digits
+                let psk = match directive.quoted() {
+                    Some(passphrase) => Some(passphrase.to_owned()),
+                    None if directive.value.len() == 64
+                        && decode_hex(&directive.value).is_some() =>
+                    {
+                        Some(directive.value.clone())
+                    }
+                    None => None,
+                };
+                match psk {
+                    Some(psk) => {
+                        settings.set(SECURITY, "psk", psk);
+                        has_psk = true;
+                    }
+                    None => report.skip(directive.line, key, "invalid PSK"),
+                }
+            }
+            "sae_password" => {
+                settings.set(SECURITY, "psk", directive.string().to_owned());
+                has_psk = true;
+            }
+            "wep_key0" | "wep_key1" | "wep_key2" | "wep_key3" => {
+                let property = key.replace('_', "-");
+                settings.set(SECURITY, &property, directive.string().to_owned());
+                // 1 is a hex or ASCII key, 2 a passphrase; wpa_supplicant only takes keys
+                settings.set(SECURITY, "wep-key-type", 1u32);
+                has_wep = true;
+            }
+            "wep_tx_keyidx" => match directive.value.parse::<u32>() {
+                Ok(index) => settings.set(SECURITY, "wep-tx-keyidx", index),
+                Err(_) => report.skip(directive.line, key, "invalid key index"),
+            },
+            "auth_alg" => match directive.list().as_slice() {
+                [alg] if alg == "open" || alg == "shared" || alg == "leap" => {
+                    settings.set(SECURITY, "auth-alg", alg.clone())
+                }
+                _ => report.skip(directive.line, key, "only a single algorithm is supported"),
+            },
+            "proto" => settings.set(
+                SECURITY,
+                "proto",
+                directive
+                    .list()
+                    .into_iter()
+                    .map(|proto| {
+                        if proto == "wpa2" {
+                            "rsn".to_owned()
+                        } else {
+                            proto
+                        }
+                    })
+                    .collect::<Vec<_>>(),
+            ),
+            "pairwise" => settings.set(SECURITY, "pairwise", directive.list()),
+            "group" => settings.set(SECURITY, "group", directive.list()),
+            "ieee80211w" => match directive.value.as_str() {
+                "0" => settings.set(SECURITY, "pmf", 1i32),
+                "1" => settings.set(SECURITY, "pmf", 2i32),
+                "2" => settings.set(SECURITY, "pmf", 3i32),
+                _ => report.skip(directive.line, key, "invalid value"),
+            },
+            "eap" => {
+                settings.set(IEEE8021X, "eap", directive.list());
+                has_eap = true;
+            }
+            "identity" | "anonymous_identity" | "domain_suffix_match" | "altsubject_match" => {
+                settings.set(
+                    IEEE8021X,
+                    &key.replace('_', "-"),
+                    directive.string().to_owned(),
+                );
+                has_eap = true;
+            }
+            "password" if directive.value.starts_with("hash:") => {
+                report.skip(directive.line, key, "NtPasswordHash is not supported")
+            }
+            "password" => settings.set(IEEE8021X, "password", directive.string().to_owned()),
+            "private_key_passwd" => settings.set(
+                IEEE8021X,
+                "private-key-password",
+                directive.string().to_owned(),
+            ),
+            "ca_cert" | "client_cert" | "private_key" | "ca_cert2" | "client_cert2"
+            | "private_key2" => {
+                let path = directive.string();
+                if path.starts_with("blob://") {
+                    report.skip(directive.line, key, "certificate blobs are not supported");
+                    continue;
+                }
+                let property = match key {
+                    "ca_cert2" => "phase2-ca-cert".to_owned(),
+                    "client_cert2" => "phase2-client-cert".to_owned(),
+                    "private_key2" => "phase2-private-key".to_owned(),
+                    _ => key.replace('_', "-"),
+                };
+                settings.set(IEEE8021X, &property, file_blob(path));
+            }
+            "ca_path" => settings.set(IEEE8021X, "ca-path", directive.string().to_owned()),
+            "phase1" => {
+                for option in directive.string().split_whitespace() {
+                    match option.split_once('=') {
+                        Some(("peapver", version)) => {
+                            settings.set(IEEE8021X, "phase1-peapver", version.to_owned())
+                        }
+                        Some(("peaplabel", label)) => {
+                            settings.set(IEEE8021X, "phase1-peaplabel", label.to_owned())
+                        }
+                        Some(("fast_provisioning", provisioning)) => settings.set(
+                            IEEE8021X,
+                            "phase1-fast-provisioning",
+                            provisioning.to_owned(),
+                        ),
+                        _ => report.skip(directive.line, key, "unsupported phase1 option"),
+                    }
+                }
+            }
+            "phase2" => {
+                for option in directive.string().split_whitespace() {
+                    match option.split_once('=') {
+                        Some(("auth", method)) => {
+                            settings.set(IEEE8021X, "phase2-auth", method.to_lowercase())
+                        }
+                        Some(("autheap", method)) => {
+                            settings.set(IEEE8021X, "phase2-autheap", method.to_lowercase())
+                        }
+                        _ => report.skip(directive.line, key, "unsupported phase2 option"),
+                    }
+                }
+            }
+            _ => report.skip(directive.line, key, "no NetworkManager equivalent"),
+        }
+    }
+
+    // wpa_supplicant defaults to "WPA-PSK WPA-EAP" when key_mgmt isn't given
+    let (key_mgmt_line, key_mgmt) = key_mgmt.unwrap_or_else(|| {
+        let default = if has_psk {
+            "wpa-psk"
+        } else if has_eap {
+            "wpa-eap"
+        } else {
+            "none"
+        };
+        (start, vec![default.to_owned()])
+    });
+    let has = |values: &[&str]| {
+        key_mgmt
+            .iter()
+            .any(|key_mgmt| values.contains(&key_mgmt.as_str()))
+    };
+    let key_mgmt = if has(&["wpa-eap-suite-b-192"]) {
+        Some("wpa-eap-suite-b-192")
+    } else if has(&["wpa-eap", "wpa-eap-sha256", "ft-eap", "ft-eap-sha384"]) {
+        Some("wpa-eap")
+    } else if has(&["wpa-psk", "wpa-psk-sha256", "ft-psk"]) {
+        Some("wpa-psk")
+    } else if has(&["sae", "ft-sae"]) {
+        Some("sae")
+    } else if has(&["owe"]) {
+        Some("owe")
+    } else if has(&["ieee8021x"]) {
+        Some("ieee8021x")
+    } else if has(&["none"]) {
+        has_wep.then_some("none")
+    } else {
+        // Importing it as an open network would be worse than not importing it
+        report.skip(
+            key_mgmt_line,
+            "key_mgmt",
+            "unsupported key management, block skipped",
+        );
+        return;
+    };
+
+    match key_mgmt {
+        Some(key_mgmt) => {
+            settings.set(SECURITY, "key-mgmt", key_mgmt);
+            settings.set(WIRELESS, "security", SECURITY);
+        }
+        None => {
+            settings.remove_setting(SECURITY);
+            settings.remove_setting(IEEE8021X);
+        }
+    }
+    if !matches!(
+        key_mgmt,
+        Some("wpa-eap" | "wpa-eap-suite-b-192" | "ieee8021x")
+    ) {
+        settings.remove_setting(IEEE8021X);
+    }
+
+    let Some(ssid) = ssid else {
+        report.skip(start, "network", "no valid SSID, block skipped");
+        return;
+    };
+    let id = id.unwrap_or_else(|| String::from_utf8_lossy(&ssid).into_owned());
+    settings.set("connection", "id", id);
+
+    report.connections.push(settings);
+}
+
+/// Strips a `#` comment that isn't inside a quoted string.
+fn strip_comment(line: &str) -> &str {
+    let mut in_quotes = false;
+    for (idx, c) in line.char_indices() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            '#' if !in_quotes => return &line[..idx],
+            _ => {}
+        }
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+ctrl_interface=/run/wpa_supplicant
+update_config=1
+
+network={
+    ssid="home # net"
+    psk="correct horse"
+    priority=5000
+}
+
+# WPA2 enterprise
+network={
+    ssid=6f6666696365
+    id_str="office"
+    key_mgmt=WPA-EAP
+    eap=PEAP
+    identity="alice"
+    password="secret"
+    ca_cert="/etc/ssl/ca.pem"
+    phase2="auth=MSCHAPV2"
+    engine=1
+}
+
+network={
+    key_mgmt=NONE
+}
+"#;
+
+    #[test]
+    fn imports_network_blocks() {
+        let report = import_wpa_supplicant(CONFIG).unwrap();
+        assert_eq!(report.connections.len(), 2);
+
+        let home = &report.connections[0];
+        assert_eq!(home.id(), Some("home # net"));
+        assert_eq!(
+            home.get_bytes(WIRELESS, "ssid"),
+            Some(b"home # net".to_vec())
+        );
+        assert_eq!(home.get_str(SECURITY, "key-mgmt"), Some("wpa-psk"));
+        assert_eq!(home.get_str(SECURITY, "psk"), Some("correct horse"));
+        assert!(!home.contains_setting(IEEE8021X));
+
+        let office = &report.connections[1];
+        assert_eq!(office.id(), Some("office"));
+        assert_eq!(office.get_bytes(WIRELESS, "ssid"), Some(b"office".to_vec()));
+        assert_eq!(office.get_str(SECURITY, "key-mgmt"), Some("wpa-eap"));
+        assert_eq!(
+            office.get_strings(IEEE8021X, "eap"),
+            Some(vec!["peap".to_owned()])
+        );
+        assert_eq!(office.get_str(IEEE8021X, "phase2-auth"), Some("mschapv2"));
+        assert_eq!(
+            office.get_bytes(IEEE8021X, "ca-cert"),
+            Some(b"file:///etc/ssl/ca.pem\0".to_vec())
+        );
+    }
+
+    #[test]
+    fn clamps_priority() {
+        let report = import_wpa_supplicant(CONFIG).unwrap();
+        assert_eq!(
+            report.connections[0].get_i32("connection", "autoconnect-priority"),
+            Some(999)
+        );
+    }
+
+    #[test]
+    fn reports_skipped_directives_and_blocks() {
+        let report = import_wpa_supplicant(CONFIG).unwrap();
+        let skipped: Vec<(usize, &str)> = report
+            .untranslated
+            .iter()
+            .map(|untranslated| (untranslated.line, untranslated.directive.as_str()))
+            .collect();
+        assert_eq!(
+            skipped,
+            [
+                (2, "ctrl_interface"),
+                (3, "update_config"),
+                (21, "engine"),
+                (24, "network"),
+            ]
+        );
+    }
+
+    #[test]
+    fn maps_key_management_and_bssid() {
+        let config = r#"
+network={
+    ssid="roaming"
+    bssid=00:11:22:33:44:55
+    key_mgmt=FT-PSK
+    psk="correct horse"
+}
+network={
+    ssid="suite-b"
+    bssid=00:11:22
+    key_mgmt=WPA-EAP-SUITE-B-192
+    eap=TLS
+}
+network={
+    ssid="future"
+    key_mgmt=DPP
+    psk="correct horse"
+}
+"#;
+        let report = import_wpa_supplicant(config).unwrap();
+        assert_eq!(report.connections.len(), 2);
+
+        let roaming = &report.connections[0];
+        assert_eq!(roaming.get_str(SECURITY, "key-mgmt"), Some("wpa-psk"));
+        assert_eq!(
+            roaming.get_bytes(WIRELESS, "bssid"),
+            Some(vec![0x00, 0x11, 0x22, 0x33, 0x44, 0x55])
+        );
+
+        let suite_b = &report.connections[1];
+        assert_eq!(
+            suite_b.get_str(SECURITY, "key-mgmt"),
+            Some("wpa-eap-suite-b-192")
+        );
+        assert!(suite_b.contains_setting(IEEE8021X));
+        assert!(suite_b.get(WIRELESS, "bssid").is_none());
+
+        let skipped: Vec<(usize, &str, &str)> = report
+            .untranslated
+            .iter()
+            .map(|untranslated| {
+                (
+                    untranslated.line,
+                    untranslated.directive.as_str(),
+                    untranslated.reason.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            skipped,
+            [
+                (10, "bssid", "invalid BSSID"),
+                (16, "key_mgmt", "unsupported key management, block skipped"),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_unterminated_block() {
+        let err = import_wpa_supplicant("network={\n    ssid=\"x\"\n").unwrap_err();
+        assert_eq!(err.line, 1);
+    }
+}